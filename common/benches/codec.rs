@@ -0,0 +1,55 @@
+//! Encode and decode cost of one frame body per wire format. Runs once per
+//! benchmark as a smoke test under `cargo test`.
+
+use common::codec::{decode_body, encode_frame_with_format};
+use common::framing::{Endianness, WireFormat};
+use common::proto::ImuData;
+use criterion::{Criterion, criterion_group, criterion_main};
+use std::hint::black_box;
+
+fn create_sample() -> ImuData {
+    ImuData {
+        x_acc: 12.5,
+        y_acc: -20.25,
+        z_acc: 1003.0,
+        timestamp_acc: 1_700_000_000,
+        x_gyro: 5000,
+        y_gyro: -3000,
+        z_gyro: 1500,
+        timestamp_gyro: 1_700_000_000,
+        x_mag: 200.0,
+        y_mag: 0.5,
+        z_mag: -400.0,
+        timestamp_mag: 1_700_000_000,
+        ..Default::default()
+    }
+}
+
+fn bench_codec(c: &mut Criterion) {
+    let sample = create_sample();
+
+    for (name, format) in [
+        ("protobuf", WireFormat::Protobuf),
+        ("packed", WireFormat::Packed),
+    ] {
+        let mut group = c.benchmark_group(name);
+        let mut buf = Vec::new();
+        group.bench_function("encode", |b| {
+            b.iter(|| {
+                buf.clear();
+                encode_frame_with_format(black_box(&sample), &mut buf, Endianness::Big, format);
+            })
+        });
+
+        buf.clear();
+        encode_frame_with_format(&sample, &mut buf, Endianness::Big, format);
+        let body = &buf[4..];
+        group.bench_function("decode", |b| {
+            b.iter(|| decode_body(black_box(body), format).unwrap())
+        });
+        group.finish();
+    }
+}
+
+criterion_group!(benches, bench_codec);
+criterion_main!(benches);
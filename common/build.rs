@@ -1,5 +1,11 @@
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("cargo:rerun-if-changed=proto/");
-    prost_build::Config::new().compile_protos(&["proto/imu.proto"], &["proto/"])?;
+    prost_build::Config::new()
+        // JSON-lines recordings hold samples with the proto field names
+        .type_attribute(
+            ".imu.IMUData",
+            "#[derive(serde::Serialize, serde::Deserialize)] #[serde(default)]",
+        )
+        .compile_protos(&["proto/imu.proto"], &["proto/"])?;
     Ok(())
 }
@@ -0,0 +1,76 @@
+/// Latest version of the wire format. Version 1 is a u32 length prefix
+/// followed by a protobuf `ImuData` body, or the packed struct if both sides
+/// select it. Version 2 puts `FRAME_MAGIC` in front of every length prefix,
+/// so a reader can find the next frame after corrupt data. Either version
+/// marks compressed bodies with `COMPRESSED_FLAG` in the length prefix, but
+/// only with compression enabled on both sides. Frames do not
+/// carry the version, so both sides must agree out of band; bump it on any
+/// change an older peer could not read.
+pub const PROTOCOL_VERSION: u32 = 2;
+
+/// What a build of either binary supports, printed as a JSON banner by
+/// `--print-capabilities` so tooling does not have to parse `--help`.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct Capabilities {
+    pub binary: &'static str,
+    pub version: &'static str,
+    pub protocol_version: u32,
+    /// Transports compiled into this build
+    pub transports: Vec<&'static str>,
+    /// Byte orders accepted for the frame length prefix
+    pub length_endianness: Vec<&'static str>,
+    /// Encodings accepted for the frame bodies
+    pub wire_formats: Vec<&'static str>,
+    /// Compressions of the frame bodies compiled into this build
+    pub compression: Vec<&'static str>,
+    /// Optional processing stages and modes, by CLI-facing name
+    pub features: Vec<&'static str>,
+}
+
+impl Capabilities {
+    pub fn new(binary: &'static str, version: &'static str, features: Vec<&'static str>) -> Self {
+        Self {
+            binary,
+            version,
+            protocol_version: PROTOCOL_VERSION,
+            transports: transports(),
+            length_endianness: vec!["big", "little"],
+            wire_formats: vec!["protobuf", "packed"],
+            compression: compression(),
+            features,
+        }
+    }
+}
+
+fn compression() -> Vec<&'static str> {
+    let mut compression = vec!["none"];
+    if cfg!(feature = "compression") {
+        compression.push("lz4");
+    }
+    compression
+}
+
+fn transports() -> Vec<&'static str> {
+    let mut transports = Vec::new();
+    if cfg!(unix) {
+        transports.push("unix-socket");
+    }
+    if cfg!(windows) {
+        transports.push("named-pipe");
+    }
+    transports.push("memory");
+    transports
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lists_a_platform_transport() {
+        let capabilities = Capabilities::new("test", "0.0.0", vec![]);
+        assert_eq!(capabilities.protocol_version, PROTOCOL_VERSION);
+        assert!(capabilities.transports.len() >= 2);
+        assert_eq!(capabilities.transports.last(), Some(&"memory"));
+    }
+}
@@ -1,6 +1,38 @@
+use crate::framing::{Compression, Endianness, WireFormat};
 use crate::logging::LogLevel;
+use crate::units::{AccelUnits, AngleUnits};
 
 pub const DEFAULT_LOG_LEVEL: LogLevel = LogLevel::Info;
+pub const DEFAULT_LENGTH_ENDIANNESS: Endianness = Endianness::Big;
+pub const DEFAULT_WIRE_FORMAT: WireFormat = WireFormat::Protobuf;
+pub const DEFAULT_PROTOCOL_VERSION: &str = "1";
+pub const DEFAULT_COMPRESSION: Compression = Compression::None;
+pub const DEFAULT_COMPRESS_THRESHOLD: &str = "256"; // bytes
+pub const DEFAULT_ACCEL_UNITS: AccelUnits = AccelUnits::Mg;
+pub const DEFAULT_ANGLE_UNITS: AngleUnits = AngleUnits::Rad;
 pub const DEFAULT_SOCKET_PATH: &str = "/tmp/imu-ipc.sock";
 pub const DEFAULT_FREQUENCY: &str = "500"; // Hz
+pub const DEFAULT_DECIMATE: &str = "1"; // samples
+pub const DEFAULT_ACC_RATE: u32 = 1000; // Hz
+pub const DEFAULT_GYRO_RATE: u32 = 800; // Hz
+pub const DEFAULT_MAG_RATE: u32 = 500; // Hz
 pub const DEFAULT_TIMEOUT: &str = "1000"; // ms
+pub const DEFAULT_WARMUP_SAMPLES: &str = "0"; // samples
+pub const DEFAULT_MIN_STEP_INTERVAL: &str = "250"; // ms
+pub const DEFAULT_ACC_DEADBAND: &str = "0.01"; // m/s^2
+pub const DEFAULT_GYRO_SMOOTHING_ORDER: &str = "2";
+pub const DEFAULT_KALMAN_PROCESS_NOISE: &str = "0.1"; // (m/s^2)^2
+pub const DEFAULT_ALTITUDE_PRIOR_VARIANCE: &str = "1.0"; // m^2
+pub const DEFAULT_GRAVITY_WINDOW_MIN: &str = "950"; // mg
+pub const DEFAULT_GRAVITY_WINDOW_MAX: &str = "1050"; // mg
+pub const DEFAULT_TIMING_ANOMALY_FACTOR: f32 = 3.0;
+pub const DEFAULT_REPLAY_SPEED: &str = "1.0";
+pub const DEFAULT_BAUD: &str = "115200"; // bit/s
+pub const DEFAULT_QUEUE_CAPACITY: u32 = 64; // samples
+pub const DEFAULT_FLUSH_EVERY: u32 = 1; // messages
+pub const DEFAULT_MAX_FLUSH_LATENCY: u32 = 10; // ms
+pub const DEFAULT_WRITE_TIMEOUT: u32 = 1000; // ms
+pub const DEFAULT_ACCEPT_RETRIES: &str = "0"; // 0 = infinite
+pub const DEFAULT_READ_BUFFER_HINT: &str = "128"; // bytes
+pub const DEFAULT_WARN_RATE_LIMIT: u32 = 10; // warnings/s
+pub const DEFAULT_STATE_HISTORY: u32 = 256; // states
@@ -0,0 +1,629 @@
+use crate::framing::{COMPRESSED_FLAG, Compression, Endianness, FRAME_MAGIC, WireFormat};
+use crate::proto::ImuData;
+use prost::Message;
+use std::fmt;
+use std::io;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+pub const DEFAULT_MAX_FRAME_SIZE: usize = 4096; // bytes
+pub const PACKED_LEN: usize = 48; // bytes
+
+/// A frame body that is not a valid `ImuData` in the expected wire format.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DecodeError {
+    Protobuf(prost::DecodeError),
+    /// Packed bodies have a fixed length
+    PackedLength {
+        len: usize,
+    },
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::Protobuf(e) => write!(f, "{}", e),
+            DecodeError::PackedLength { len } => write!(
+                f,
+                "packed body of {} bytes, expected {} bytes",
+                len, PACKED_LEN
+            ),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DecodeError::Protobuf(e) => Some(e),
+            DecodeError::PackedLength { .. } => None,
+        }
+    }
+}
+
+impl From<prost::DecodeError> for DecodeError {
+    fn from(e: prost::DecodeError) -> Self {
+        DecodeError::Protobuf(e)
+    }
+}
+
+/// Inner error of the `InvalidData` returned for frames above the maximum size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameTooLarge {
+    pub len: usize,
+    pub max: usize,
+}
+
+impl fmt::Display for FrameTooLarge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "frame of {} bytes exceeds maximum of {} bytes",
+            self.len, self.max
+        )
+    }
+}
+
+impl std::error::Error for FrameTooLarge {}
+
+/// Appends `data` framed with a big-endian u32 length prefix to `buf`.
+pub fn encode_frame(data: &ImuData, buf: &mut Vec<u8>) {
+    encode_frame_with_endianness(data, buf, Endianness::Big);
+}
+
+/// Appends `data` framed with a u32 length prefix in the given byte order.
+pub fn encode_frame_with_endianness(data: &ImuData, buf: &mut Vec<u8>, endianness: Endianness) {
+    encode_frame_with_format(data, buf, endianness, WireFormat::Protobuf);
+}
+
+/// Appends `data` encoded in `format`, framed with a u32 length prefix in
+/// the given byte order.
+pub fn encode_frame_with_format(
+    data: &ImuData,
+    buf: &mut Vec<u8>,
+    endianness: Endianness,
+    format: WireFormat,
+) {
+    match format {
+        WireFormat::Protobuf => {
+            buf.reserve(4 + data.encoded_len());
+            buf.extend_from_slice(&endianness.encode_len(data.encoded_len() as u32));
+            // Vec<u8> grows on demand, encoding into it cannot run out of space
+            data.encode(buf).unwrap();
+        }
+        WireFormat::Packed => {
+            buf.reserve(4 + PACKED_LEN);
+            buf.extend_from_slice(&endianness.encode_len(PACKED_LEN as u32));
+            buf.extend_from_slice(&encode_packed(data));
+        }
+    }
+}
+
+/// Compresses the body of the frame at the end of `buf`, whose length prefix
+/// starts at `prefix_at`, and marks the prefix with `COMPRESSED_FLAG`. Bodies
+/// below `threshold` bytes, and those that would not shrink, stay plain.
+#[cfg_attr(not(feature = "compression"), allow(unused_variables, clippy::ptr_arg))]
+pub fn compress_frame(
+    buf: &mut Vec<u8>,
+    prefix_at: usize,
+    endianness: Endianness,
+    compression: Compression,
+    threshold: usize,
+) {
+    match compression {
+        Compression::None => {}
+        #[cfg(feature = "compression")]
+        Compression::Lz4 => {
+            let body_at = prefix_at + 4;
+            let body = &buf[body_at..];
+            if body.len() < threshold {
+                return;
+            }
+            let compressed = lz4_flex::block::compress_prepend_size(body);
+            if compressed.len() >= body.len() {
+                return;
+            }
+            buf.truncate(body_at);
+            buf.extend_from_slice(&compressed);
+            buf[prefix_at..body_at]
+                .copy_from_slice(&endianness.encode_len(compressed.len() as u32 | COMPRESSED_FLAG));
+        }
+    }
+}
+
+/// Sensor readings and millisecond timestamps of `data` as little-endian
+/// fields in proto field order.
+pub fn encode_packed(data: &ImuData) -> [u8; PACKED_LEN] {
+    let words = [
+        data.x_acc.to_bits(),
+        data.y_acc.to_bits(),
+        data.z_acc.to_bits(),
+        data.x_gyro as u32,
+        data.y_gyro as u32,
+        data.z_gyro as u32,
+        data.x_mag.to_bits(),
+        data.y_mag.to_bits(),
+        data.z_mag.to_bits(),
+        data.timestamp_acc,
+        data.timestamp_gyro,
+        data.timestamp_mag,
+    ];
+    let mut packed = [0u8; PACKED_LEN];
+    for (chunk, word) in packed.chunks_exact_mut(4).zip(words) {
+        chunk.copy_from_slice(&word.to_le_bytes());
+    }
+    packed
+}
+
+/// Inverse of `encode_packed`, the optional fields stay unset.
+pub fn decode_packed(body: &[u8]) -> Result<ImuData, DecodeError> {
+    if body.len() != PACKED_LEN {
+        return Err(DecodeError::PackedLength { len: body.len() });
+    }
+    let mut words = body
+        .chunks_exact(4)
+        .map(|chunk| u32::from_le_bytes(chunk.try_into().unwrap()));
+    let mut next = || words.next().unwrap();
+    Ok(ImuData {
+        x_acc: f32::from_bits(next()),
+        y_acc: f32::from_bits(next()),
+        z_acc: f32::from_bits(next()),
+        x_gyro: next() as i32,
+        y_gyro: next() as i32,
+        z_gyro: next() as i32,
+        x_mag: f32::from_bits(next()),
+        y_mag: f32::from_bits(next()),
+        z_mag: f32::from_bits(next()),
+        timestamp_acc: next(),
+        timestamp_gyro: next(),
+        timestamp_mag: next(),
+        ..Default::default()
+    })
+}
+
+/// Decodes a frame body in `format`.
+pub fn decode_body(body: &[u8], format: WireFormat) -> Result<ImuData, DecodeError> {
+    match format {
+        WireFormat::Protobuf => Ok(ImuData::decode(body)?),
+        WireFormat::Packed => decode_packed(body),
+    }
+}
+
+/// Reads the next sample with the default framing, `None` on a clean EOF
+/// before a frame starts. Zero-length frames are skipped.
+pub async fn read_frame<R: AsyncRead + Unpin>(reader: &mut R) -> io::Result<Option<ImuData>> {
+    FrameReader::default().read(reader).await
+}
+
+/// Reads length-prefixed frames into a reused buffer that only grows.
+#[derive(Debug, Clone)]
+pub struct FrameReader {
+    endianness: Endianness,
+    wire_format: WireFormat,
+    frame_magic: bool,
+    compression: Compression,
+    max_frame_size: usize,
+    buffer: Vec<u8>,
+    #[cfg(feature = "compression")]
+    decompressed: Vec<u8>,
+    skipped_bytes: u64,
+}
+
+impl Default for FrameReader {
+    fn default() -> Self {
+        Self {
+            endianness: Endianness::default(),
+            wire_format: WireFormat::default(),
+            frame_magic: false,
+            compression: Compression::None,
+            max_frame_size: DEFAULT_MAX_FRAME_SIZE,
+            buffer: Vec::new(),
+            #[cfg(feature = "compression")]
+            decompressed: Vec::new(),
+            skipped_bytes: 0,
+        }
+    }
+}
+
+impl FrameReader {
+    pub fn set_endianness(&mut self, endianness: Endianness) {
+        self.endianness = endianness;
+    }
+
+    pub fn set_wire_format(&mut self, format: WireFormat) {
+        self.wire_format = format;
+    }
+
+    pub fn wire_format(&self) -> WireFormat {
+        self.wire_format
+    }
+
+    /// Expects `FRAME_MAGIC` in front of every frame, as from protocol
+    /// version 2 on. Data that does not start with it, and frames announcing
+    /// more than the maximum size, are skipped up to the next marker instead
+    /// of ending the stream.
+    pub fn set_frame_magic(&mut self, frame_magic: bool) {
+        self.frame_magic = frame_magic;
+    }
+
+    /// Decompresses bodies flagged with `COMPRESSED_FLAG`. Without it the
+    /// flag reads as an oversized length, peers only compress after agreeing
+    /// on it in the handshake, see `handshake::answer_offer`.
+    pub fn set_compression(&mut self, compression: Compression) {
+        self.compression = compression;
+    }
+
+    pub fn compression(&self) -> Compression {
+        self.compression
+    }
+
+    /// Bytes skipped to resynchronize since the last call.
+    pub fn take_skipped_bytes(&mut self) -> u64 {
+        std::mem::take(&mut self.skipped_bytes)
+    }
+
+    /// Frames announcing a larger length are treated as a corrupt stream.
+    pub fn set_max_frame_size(&mut self, bytes: usize) {
+        self.max_frame_size = bytes;
+    }
+
+    /// Pre-sizes the frame buffer.
+    pub fn reserve(&mut self, bytes: usize) {
+        if self.buffer.len() < bytes {
+            self.buffer.resize(bytes, 0);
+        }
+    }
+
+    pub fn buffer_capacity(&self) -> usize {
+        self.buffer.capacity()
+    }
+
+    /// Reads the next frame body, decompressed and empty for zero-length
+    /// frames. Returns `None` on a clean EOF before a frame starts and
+    /// `InvalidData` for frames above the maximum size, after which the
+    /// stream is unusable unless frames carry the magic marker.
+    pub async fn read_body<R: AsyncRead + Unpin>(
+        &mut self,
+        reader: &mut R,
+    ) -> io::Result<Option<&[u8]>> {
+        let (len, compressed) = loop {
+            if self.frame_magic && !self.find_magic(reader).await? {
+                return Ok(None);
+            }
+
+            let mut len_bytes = [0u8; 4];
+            match reader.read_exact(&mut len_bytes).await {
+                Ok(_) => {}
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+                Err(e) => return Err(e),
+            }
+
+            let mut len = self.endianness.decode_len(len_bytes);
+            let compressed = self.compression != Compression::None && len & COMPRESSED_FLAG != 0;
+            if compressed {
+                len &= !COMPRESSED_FLAG;
+            }
+            let len = len as usize;
+            if len <= self.max_frame_size {
+                break (len, compressed);
+            }
+            if !self.frame_magic {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    FrameTooLarge {
+                        len,
+                        max: self.max_frame_size,
+                    },
+                ));
+            }
+            // The marker was a coincidence inside corrupt data
+            self.skipped_bytes += (FRAME_MAGIC.len() + len_bytes.len()) as u64;
+        };
+
+        self.reserve(len);
+        let body = &mut self.buffer[..len];
+        reader.read_exact(body).await?;
+        if compressed {
+            return self.decompress(len).map(Some);
+        }
+        Ok(Some(&self.buffer[..len]))
+    }
+
+    /// Decompresses the first `len` bytes of the frame buffer, refusing
+    /// bodies that would decompress beyond the maximum frame size.
+    fn decompress(&mut self, len: usize) -> io::Result<&[u8]> {
+        match self.compression {
+            Compression::None => Ok(&self.buffer[..len]),
+            #[cfg(feature = "compression")]
+            Compression::Lz4 => {
+                let invalid = |e| io::Error::new(io::ErrorKind::InvalidData, e);
+                let (size, block) =
+                    lz4_flex::block::uncompressed_size(&self.buffer[..len]).map_err(invalid)?;
+                if size > self.max_frame_size {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        FrameTooLarge {
+                            len: size,
+                            max: self.max_frame_size,
+                        },
+                    ));
+                }
+                if self.decompressed.len() < size {
+                    self.decompressed.resize(size, 0);
+                }
+                let written =
+                    lz4_flex::block::decompress_into(block, &mut self.decompressed[..size])
+                        .map_err(invalid)?;
+                Ok(&self.decompressed[..written])
+            }
+        }
+    }
+
+    /// Consumes bytes up to and including the next `FRAME_MAGIC`, counting
+    /// any in front of it as skipped. `false` on EOF before one.
+    async fn find_magic<R: AsyncRead + Unpin>(&mut self, reader: &mut R) -> io::Result<bool> {
+        let mut window = [0u8; 2];
+        let mut read = 0u64;
+        loop {
+            let byte = match reader.read_u8().await {
+                Ok(byte) => byte,
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                    self.skipped_bytes += read;
+                    return Ok(false);
+                }
+                Err(e) => return Err(e),
+            };
+            window = [window[1], byte];
+            read += 1;
+            if read >= 2 && window == FRAME_MAGIC {
+                self.skipped_bytes += read - 2;
+                return Ok(true);
+            }
+        }
+    }
+
+    /// Reads and decodes the next sample, skipping zero-length frames.
+    /// Undecodable bodies are reported as `InvalidData`.
+    pub async fn read<R: AsyncRead + Unpin>(
+        &mut self,
+        reader: &mut R,
+    ) -> io::Result<Option<ImuData>> {
+        let format = self.wire_format;
+        loop {
+            match self.read_body(reader).await? {
+                None => return Ok(None),
+                Some([]) => continue,
+                Some(body) => {
+                    return decode_body(body, format)
+                        .map(Some)
+                        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(timestamp: u32) -> ImuData {
+        ImuData {
+            z_acc: 1000.0,
+            timestamp_acc: timestamp,
+            z_gyro: -500,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_round_trip_until_eof() {
+        let mut bytes = Vec::new();
+        encode_frame(&sample(1), &mut bytes);
+        encode_frame(&sample(2), &mut bytes);
+
+        let mut reader = &bytes[..];
+        assert_eq!(read_frame(&mut reader).await.unwrap(), Some(sample(1)));
+        assert_eq!(read_frame(&mut reader).await.unwrap(), Some(sample(2)));
+        assert_eq!(read_frame(&mut reader).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_skips_zero_length_frames() {
+        let mut bytes = vec![0, 0, 0, 0];
+        encode_frame(&sample(3), &mut bytes);
+
+        let mut reader = &bytes[..];
+        assert_eq!(read_frame(&mut reader).await.unwrap(), Some(sample(3)));
+    }
+
+    #[tokio::test]
+    async fn test_truncated_body_is_an_error() {
+        let mut bytes = Vec::new();
+        encode_frame(&sample(4), &mut bytes);
+        bytes.pop();
+
+        let mut reader = &bytes[..];
+        let err = read_frame(&mut reader).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[tokio::test]
+    async fn test_rejects_oversized_frame() {
+        let bytes = 0xFFFF_FFF0u32.to_be_bytes();
+
+        let mut frame_reader = FrameReader::default();
+        let err = frame_reader.read(&mut &bytes[..]).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(
+            err.get_ref()
+                .and_then(|e| e.downcast_ref::<FrameTooLarge>()),
+            Some(&FrameTooLarge {
+                len: 0xFFFF_FFF0,
+                max: DEFAULT_MAX_FRAME_SIZE
+            })
+        );
+        assert!(frame_reader.buffer_capacity() <= DEFAULT_MAX_FRAME_SIZE);
+    }
+
+    #[tokio::test]
+    async fn test_little_endian_round_trip() {
+        let mut bytes = Vec::new();
+        encode_frame_with_endianness(&sample(5), &mut bytes, Endianness::Little);
+
+        let mut frame_reader = FrameReader::default();
+        frame_reader.set_endianness(Endianness::Little);
+        let decoded = frame_reader.read(&mut &bytes[..]).await.unwrap();
+        assert_eq!(decoded, Some(sample(5)));
+    }
+
+    #[tokio::test]
+    async fn test_packed_round_trip() {
+        let data = ImuData {
+            x_acc: -12.5,
+            y_acc: f32::MIN_POSITIVE,
+            z_acc: 1000.25,
+            x_gyro: i32::MIN,
+            y_gyro: -1,
+            z_gyro: i32::MAX,
+            x_mag: 200.0,
+            y_mag: -0.0,
+            z_mag: -400.5,
+            timestamp_acc: 1,
+            timestamp_gyro: u32::MAX,
+            timestamp_mag: 123_456,
+            ..Default::default()
+        };
+        let mut bytes = Vec::new();
+        encode_frame_with_format(&data, &mut bytes, Endianness::Big, WireFormat::Packed);
+        assert_eq!(bytes.len(), 4 + PACKED_LEN);
+
+        let mut frame_reader = FrameReader::default();
+        frame_reader.set_wire_format(WireFormat::Packed);
+        let decoded = frame_reader.read(&mut &bytes[..]).await.unwrap().unwrap();
+        assert_eq!(decoded, data);
+        assert_eq!(decoded.y_mag.to_bits(), (-0.0f32).to_bits());
+
+        // The optional fields do not survive the packed encoding
+        let stamped = ImuData {
+            timestamp_acc_us: Some(1_000),
+            send_time_us: Some(2_000),
+            ..data
+        };
+        assert_eq!(decode_packed(&encode_packed(&stamped)), Ok(data));
+        assert_eq!(
+            decode_packed(&[0; 47]),
+            Err(DecodeError::PackedLength { len: 47 })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_magic_marker_resyncs_after_garbage() {
+        let frame = |timestamp, bytes: &mut Vec<u8>| {
+            bytes.extend_from_slice(&FRAME_MAGIC);
+            encode_frame(&sample(timestamp), bytes);
+        };
+        let mut bytes = Vec::new();
+        frame(1, &mut bytes);
+        // Garbage, then a stray marker announcing an impossible length
+        bytes.extend_from_slice(&[0x12, 0xA5, 0x00, 0xFF]);
+        bytes.extend_from_slice(&FRAME_MAGIC);
+        bytes.extend_from_slice(&0xFFFF_FFF0u32.to_be_bytes());
+        frame(2, &mut bytes);
+        // Half a frame cut off by a reconnect
+        frame(3, &mut bytes);
+        bytes.truncate(bytes.len() - 5);
+        frame(4, &mut bytes);
+        frame(5, &mut bytes);
+
+        let mut frame_reader = FrameReader::default();
+        frame_reader.set_frame_magic(true);
+        let mut reader = &bytes[..];
+        assert_eq!(
+            frame_reader.read(&mut reader).await.unwrap(),
+            Some(sample(1))
+        );
+        assert_eq!(frame_reader.take_skipped_bytes(), 0);
+        assert_eq!(
+            frame_reader.read(&mut reader).await.unwrap(),
+            Some(sample(2))
+        );
+        assert_eq!(frame_reader.take_skipped_bytes(), 10);
+
+        // The cut-off frame swallows the start of the next one, the reader
+        // then finds the marker of the one after
+        let mut next = Vec::new();
+        while let Some(result) = frame_reader.read(&mut reader).await.transpose() {
+            next.push(result.ok());
+        }
+        assert_eq!(next.last(), Some(&Some(sample(5))), "{:?}", next);
+    }
+
+    #[tokio::test]
+    async fn test_buffer_only_grows() {
+        let mut bytes = Vec::new();
+        encode_frame(&ImuData::default(), &mut bytes);
+        encode_frame(&sample(6), &mut bytes);
+
+        let mut frame_reader = FrameReader::default();
+        frame_reader.reserve(100);
+        let mut reader = &bytes[..];
+        while frame_reader.read(&mut reader).await.unwrap().is_some() {}
+
+        assert!(frame_reader.buffer.len() >= 100);
+    }
+
+    /// A frame around a repetitive body that LZ4 shrinks well.
+    #[cfg(feature = "compression")]
+    fn compressible_frame() -> (Vec<u8>, Vec<u8>) {
+        let body: Vec<u8> = (0..600).map(|i| (i % 12) as u8).collect();
+        let mut frame = Endianness::Big.encode_len(body.len() as u32).to_vec();
+        frame.extend_from_slice(&body);
+        (frame, body)
+    }
+
+    #[cfg(feature = "compression")]
+    #[tokio::test]
+    async fn test_compressed_round_trip() {
+        let (mut frame, body) = compressible_frame();
+        compress_frame(&mut frame, 0, Endianness::Big, Compression::Lz4, 256);
+        let len = Endianness::Big.decode_len(frame[..4].try_into().unwrap());
+        assert_ne!(len & COMPRESSED_FLAG, 0);
+        assert!(frame.len() < 4 + body.len());
+
+        // Small samples may not shrink, those stay plain and read the same
+        let mut bytes = frame.clone();
+        let prefix_at = bytes.len();
+        encode_frame(&sample(6), &mut bytes);
+        compress_frame(&mut bytes, prefix_at, Endianness::Big, Compression::Lz4, 0);
+
+        let mut frame_reader = FrameReader::default();
+        frame_reader.set_compression(Compression::Lz4);
+        let mut reader = &bytes[..];
+        assert_eq!(
+            frame_reader.read_body(&mut reader).await.unwrap(),
+            Some(&body[..])
+        );
+        assert_eq!(
+            frame_reader.read(&mut reader).await.unwrap(),
+            Some(sample(6))
+        );
+    }
+
+    #[cfg(feature = "compression")]
+    #[tokio::test]
+    async fn test_compression_keeps_small_bodies_plain() {
+        // Below the threshold the frame stays exactly what a peer without
+        // compression sends and expects
+        let (mut frame, _) = compressible_frame();
+        let plain = frame.clone();
+        compress_frame(&mut frame, 0, Endianness::Big, Compression::Lz4, 1024);
+        assert_eq!(frame, plain);
+
+        let mut bytes = Vec::new();
+        encode_frame(&sample(7), &mut bytes);
+        let mut frame_reader = FrameReader::default();
+        frame_reader.set_compression(Compression::Lz4);
+        let decoded = frame_reader.read(&mut &bytes[..]).await.unwrap();
+        assert_eq!(decoded, Some(sample(7)));
+    }
+}
@@ -0,0 +1,100 @@
+use clap::ValueEnum;
+
+/// Marker in front of every frame from protocol version 2 on, for finding
+/// frame boundaries again after corrupt data.
+pub const FRAME_MAGIC: [u8; 2] = [0xA5, 0x5A];
+
+/// Set in the length prefix of a frame whose body is compressed. Only
+/// written with compression enabled, frame lengths never reach it otherwise.
+pub const COMPRESSED_FLAG: u32 = 1 << 31;
+
+/// Byte order of the u32 length prefix in front of every encoded frame.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Endianness {
+    /// Network byte order, the default on both sides
+    #[default]
+    Big,
+    Little,
+}
+
+impl Endianness {
+    pub fn encode_len(self, len: u32) -> [u8; 4] {
+        match self {
+            Endianness::Big => len.to_be_bytes(),
+            Endianness::Little => len.to_le_bytes(),
+        }
+    }
+
+    pub fn decode_len(self, bytes: [u8; 4]) -> u32 {
+        match self {
+            Endianness::Big => u32::from_be_bytes(bytes),
+            Endianness::Little => u32::from_le_bytes(bytes),
+        }
+    }
+}
+
+/// Encoding of the frame bodies, both sides must agree on it.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WireFormat {
+    /// Protocol Buffers `ImuData`, the default on both sides
+    #[default]
+    Protobuf,
+    /// Fixed 48-byte little-endian struct of the sensor readings and their
+    /// millisecond timestamps, cheaper to encode and decode. Drops the
+    /// optional fields: microsecond timestamps, ground truth and send time
+    Packed,
+}
+
+/// Compression of frame bodies, negotiated per connection in the handshake.
+/// A side without it, or without support for it, falls back to plain.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    /// Plain bodies, the default on both sides
+    #[default]
+    None,
+    /// LZ4 block with the uncompressed size prepended, for bodies above a
+    /// size threshold that shrink. Others are sent plain. A single sample
+    /// stays below the default threshold, so without batching this is a
+    /// no-op unless the threshold is lowered
+    #[cfg(feature = "compression")]
+    Lz4,
+}
+
+impl Compression {
+    /// Number of the compression in the handshake, see `handshake::OFFER_MAGIC`.
+    pub fn id(self) -> u8 {
+        match self {
+            Compression::None => 0,
+            #[cfg(feature = "compression")]
+            Compression::Lz4 => 1,
+        }
+    }
+
+    /// Inverse of `id`, `None` for compressions this build doesn't support.
+    pub fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(Compression::None),
+            #[cfg(feature = "compression")]
+            1 => Some(Compression::Lz4),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_length_prefix_byte_order() {
+        assert_eq!(Endianness::Big.encode_len(0x0102), [0, 0, 1, 2]);
+        assert_eq!(Endianness::Little.encode_len(0x0102), [2, 1, 0, 0]);
+
+        for endianness in [Endianness::Big, Endianness::Little] {
+            assert_eq!(endianness.decode_len(endianness.encode_len(1234)), 1234);
+        }
+    }
+}
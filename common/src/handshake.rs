@@ -0,0 +1,183 @@
+use crate::framing::Compression;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf};
+
+/// Sent by a publisher with compression enabled right after a consumer
+/// connects, followed by one byte with bit `Compression::id` set for every
+/// compression it supports. Never the start of a frame: as a length prefix
+/// it is far above any maximum frame size, and protocol version 2 frames
+/// start with `FRAME_MAGIC`.
+pub const OFFER_MAGIC: [u8; 4] = *b"IMUc";
+
+/// A stream with the bytes read while looking for an offer put back in front.
+#[derive(Debug)]
+pub struct Negotiated<S> {
+    prefix: Vec<u8>,
+    consumed: usize,
+    stream: S,
+}
+
+impl<S> Negotiated<S> {
+    fn new(prefix: Vec<u8>, stream: S) -> Self {
+        Self {
+            prefix,
+            consumed: 0,
+            stream,
+        }
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for Negotiated<S> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = &mut *self;
+        let rest = &this.prefix[this.consumed..];
+        if rest.is_empty() {
+            return Pin::new(&mut this.stream).poll_read(cx, buf);
+        }
+        let n = rest.len().min(buf.remaining());
+        buf.put_slice(&rest[..n]);
+        this.consumed += n;
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Publisher side: offers `compression`, plain frames always being an
+/// option, and returns what the consumer chose. Falls back to
+/// `Compression::None` if the consumer doesn't support the offer or doesn't
+/// answer within `reply_timeout`. Without compression nothing is sent.
+pub async fn offer_compression<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut S,
+    compression: Compression,
+    reply_timeout: Duration,
+) -> io::Result<Compression> {
+    if compression == Compression::None {
+        return Ok(Compression::None);
+    }
+
+    let supported = 1 << Compression::None.id() | 1 << compression.id();
+    let mut offer = OFFER_MAGIC.to_vec();
+    offer.push(supported);
+    stream.write_all(&offer).await?;
+    stream.flush().await?;
+
+    let mut reply = [0u8; 1];
+    match tokio::time::timeout(reply_timeout, stream.read_exact(&mut reply)).await {
+        Ok(Ok(_)) => Ok(Compression::from_id(reply[0])
+            .filter(|&chosen| chosen == compression)
+            .unwrap_or(Compression::None)),
+        Ok(Err(e)) => Err(e),
+        Err(_) => Ok(Compression::None),
+    }
+}
+
+/// Consumer side: answers an offer at the start of `stream`, choosing
+/// `supported` if the publisher offered it and `Compression::None`
+/// otherwise. A stream without an offer comes back unchanged, its frames
+/// are plain.
+pub async fn answer_offer<S: AsyncRead + AsyncWrite + Unpin>(
+    mut stream: S,
+    supported: Compression,
+) -> io::Result<(Compression, Negotiated<S>)> {
+    // Read no further than the magic, a plain stream starts with a frame
+    let mut prefix = Vec::with_capacity(OFFER_MAGIC.len());
+    while prefix.len() < OFFER_MAGIC.len() && OFFER_MAGIC.starts_with(&prefix) {
+        let mut chunk = [0u8; OFFER_MAGIC.len()];
+        let n = stream
+            .read(&mut chunk[..OFFER_MAGIC.len() - prefix.len()])
+            .await?;
+        if n == 0 {
+            break;
+        }
+        prefix.extend_from_slice(&chunk[..n]);
+    }
+    if prefix != OFFER_MAGIC {
+        return Ok((Compression::None, Negotiated::new(prefix, stream)));
+    }
+
+    let offered = stream.read_u8().await?;
+    let chosen = if offered & 1 << supported.id() != 0 {
+        supported
+    } else {
+        Compression::None
+    };
+    stream.write_all(&[chosen.id()]).await?;
+    stream.flush().await?;
+    Ok((chosen, Negotiated::new(Vec::new(), stream)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::{FrameReader, encode_frame};
+    use crate::proto::ImuData;
+    use crate::transport::memory;
+
+    const REPLY_TIMEOUT: Duration = Duration::from_secs(5);
+
+    #[tokio::test]
+    async fn test_plain_stream_passes_through() {
+        let (mut publisher_end, consumer_end) = memory::pair();
+        let chosen = offer_compression(&mut publisher_end, Compression::None, REPLY_TIMEOUT)
+            .await
+            .unwrap();
+        assert_eq!(chosen, Compression::None);
+
+        let sample = ImuData {
+            z_acc: 1000.0,
+            timestamp_acc: 10,
+            ..Default::default()
+        };
+        let mut bytes = Vec::new();
+        encode_frame(&sample, &mut bytes);
+        publisher_end.write_all(&bytes).await.unwrap();
+        drop(publisher_end);
+
+        let (chosen, mut stream) = answer_offer(consumer_end, Compression::None).await.unwrap();
+        assert_eq!(chosen, Compression::None);
+        let mut frame_reader = FrameReader::default();
+        assert_eq!(frame_reader.read(&mut stream).await.unwrap(), Some(sample));
+        assert_eq!(frame_reader.read(&mut stream).await.unwrap(), None);
+    }
+
+    #[cfg(feature = "compression")]
+    #[tokio::test]
+    async fn test_negotiates_lz4_when_both_support_it() {
+        let (mut publisher_end, consumer_end) = memory::pair();
+        let (offered, answered) = tokio::join!(
+            offer_compression(&mut publisher_end, Compression::Lz4, REPLY_TIMEOUT),
+            answer_offer(consumer_end, Compression::Lz4)
+        );
+        assert_eq!(offered.unwrap(), Compression::Lz4);
+        assert_eq!(answered.unwrap().0, Compression::Lz4);
+    }
+
+    #[cfg(feature = "compression")]
+    #[tokio::test]
+    async fn test_falls_back_to_plain_without_consumer_support() {
+        let (mut publisher_end, consumer_end) = memory::pair();
+        let (offered, answered) = tokio::join!(
+            offer_compression(&mut publisher_end, Compression::Lz4, REPLY_TIMEOUT),
+            answer_offer(consumer_end, Compression::None)
+        );
+        assert_eq!(offered.unwrap(), Compression::None);
+        assert_eq!(answered.unwrap().0, Compression::None);
+
+        // Without an answer the offer lapses the same way
+        let (mut publisher_end, _consumer_end) = memory::pair();
+        let chosen = offer_compression(
+            &mut publisher_end,
+            Compression::Lz4,
+            Duration::from_millis(10),
+        )
+        .await
+        .unwrap();
+        assert_eq!(chosen, Compression::None);
+    }
+}
@@ -1,7 +1,16 @@
+pub mod capabilities;
 pub mod cli_defaults;
+pub mod codec;
+pub mod framing;
+pub mod handshake;
 pub mod logging;
 pub mod proto;
+pub mod trajectory;
+pub mod transport;
+pub mod units;
+pub mod wallclock;
 
 pub use clap;
+pub use nalgebra;
 pub use prost;
 pub use slog;
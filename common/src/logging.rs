@@ -3,7 +3,8 @@ use slog::{Drain, Level, Logger, o};
 use slog_async::Async;
 use slog_term::{FullFormat, TermDecorator};
 
-#[derive(ValueEnum, Clone, Debug)]
+#[derive(ValueEnum, Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum LogLevel {
     Debug,
     Info,
@@ -0,0 +1,178 @@
+use crate::proto::ImuData;
+use crate::units::STANDARD_GRAVITY;
+use nalgebra::{Unit, UnitQuaternion, Vector3};
+use std::f32::consts::TAU;
+
+/// Earth field in the world frame, pointing north and down.
+pub const MAG_FIELD_MGAUSS: (f32, f32, f32) = (200.0, 0.0, -400.0);
+
+/// Closed-form translation along X without rotation, for validating
+/// position integration against a known answer.
+///
+/// The body frame stays aligned with the world frame and the position is
+/// `x(t) = amplitude * (1 - cos(2 * PI * frequency_hz * t))`, which starts
+/// at rest at the origin. Emitted samples are noise-free.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SinusoidalTranslation {
+    pub amplitude: f32,    // m
+    pub frequency_hz: f32, // Hz
+}
+
+impl SinusoidalTranslation {
+    pub fn new(amplitude: f32, frequency_hz: f32) -> Self {
+        Self {
+            amplitude,
+            frequency_hz,
+        }
+    }
+
+    /// World-frame position [m] at `t` seconds since the start.
+    pub fn position(&self, t: f32) -> Vector3<f32> {
+        let phase = TAU * self.frequency_hz * t;
+        Vector3::new(self.amplitude * (1.0 - phase.cos()), 0.0, 0.0)
+    }
+
+    /// World-frame acceleration without gravity [m/s^2], the second
+    /// derivative of `position`.
+    pub fn acceleration(&self, t: f32) -> Vector3<f32> {
+        let omega = TAU * self.frequency_hz;
+        Vector3::new(self.amplitude * omega * omega * (omega * t).cos(), 0.0, 0.0)
+    }
+
+    /// Sample at `t` seconds since the start with every sensor stamped
+    /// `timestamp` and the ground truth filled in.
+    pub fn sample(&self, t: f32, timestamp: u32) -> ImuData {
+        // The accel measures specific force: motion plus the reaction to gravity
+        let specific_force = self.acceleration(t) + Vector3::new(0.0, 0.0, STANDARD_GRAVITY);
+        let acc_mg = specific_force * 1000.0 / STANDARD_GRAVITY;
+        let position = self.position(t);
+
+        ImuData {
+            x_acc: acc_mg.x,
+            y_acc: acc_mg.y,
+            z_acc: acc_mg.z,
+            timestamp_acc: timestamp,
+            timestamp_gyro: timestamp,
+            x_mag: MAG_FIELD_MGAUSS.0,
+            y_mag: MAG_FIELD_MGAUSS.1,
+            z_mag: MAG_FIELD_MGAUSS.2,
+            timestamp_mag: timestamp,
+            true_x_pos: Some(position.x),
+            true_y_pos: Some(position.y),
+            true_z_pos: Some(position.z),
+            true_qw: Some(1.0),
+            true_qx: Some(0.0),
+            true_qy: Some(0.0),
+            true_qz: Some(0.0),
+            ..Default::default()
+        }
+    }
+}
+
+/// Rotation at a constant rate about a fixed body axis, in place.
+///
+/// The orientation is tracked exactly, so the gravity reaction and the
+/// earth field are rotated into the body frame and move across its axes as
+/// the device turns. Emitted samples are noise-free.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ConstantRotation {
+    pub rate: Vector3<f32>, // deg/s
+}
+
+impl ConstantRotation {
+    pub fn new(rate: Vector3<f32>) -> Self {
+        Self { rate }
+    }
+
+    /// Body-to-world orientation at `t` seconds since the start.
+    pub fn orientation(&self, t: f32) -> UnitQuaternion<f32> {
+        let rate = self.rate.map(f32::to_radians);
+        match Unit::try_new(rate, f32::EPSILON) {
+            Some(axis) => UnitQuaternion::from_axis_angle(&axis, rate.norm() * t),
+            None => UnitQuaternion::identity(),
+        }
+    }
+
+    /// Sample at `t` seconds since the start with every sensor stamped
+    /// `timestamp` and the ground truth filled in.
+    pub fn sample(&self, t: f32, timestamp: u32) -> ImuData {
+        let orientation = self.orientation(t);
+        // The accel measures the reaction to gravity, up in the world frame
+        let acc_mg = orientation.inverse_transform_vector(&Vector3::new(0.0, 0.0, 1000.0));
+        let mag = orientation.inverse_transform_vector(&Vector3::new(
+            MAG_FIELD_MGAUSS.0,
+            MAG_FIELD_MGAUSS.1,
+            MAG_FIELD_MGAUSS.2,
+        ));
+        let gyro_mdps = self.rate * 1000.0;
+
+        ImuData {
+            x_acc: acc_mg.x,
+            y_acc: acc_mg.y,
+            z_acc: acc_mg.z,
+            timestamp_acc: timestamp,
+            x_gyro: gyro_mdps.x.round() as i32,
+            y_gyro: gyro_mdps.y.round() as i32,
+            z_gyro: gyro_mdps.z.round() as i32,
+            timestamp_gyro: timestamp,
+            x_mag: mag.x,
+            y_mag: mag.y,
+            z_mag: mag.z,
+            timestamp_mag: timestamp,
+            true_x_pos: Some(0.0),
+            true_y_pos: Some(0.0),
+            true_z_pos: Some(0.0),
+            true_qw: Some(orientation.w),
+            true_qx: Some(orientation.i),
+            true_qy: Some(orientation.j),
+            true_qz: Some(orientation.k),
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::units::mg_to_mps2;
+
+    #[test]
+    fn test_accel_is_second_derivative_of_position() {
+        let trajectory = SinusoidalTranslation::new(0.5, 0.5);
+        let h = 1e-2;
+
+        for t in [0.0f32, 0.3, 0.7, 1.2] {
+            let t = t + h;
+            let numeric = (trajectory.position(t + h) - 2.0 * trajectory.position(t)
+                + trajectory.position(t - h))
+                / (h * h);
+            let sample = trajectory.sample(t, 0);
+
+            assert!((numeric.x - mg_to_mps2(sample.x_acc)).abs() < 1e-2);
+            assert!((mg_to_mps2(sample.z_acc) - STANDARD_GRAVITY).abs() < 1e-4);
+            assert_eq!(sample.true_x_pos, Some(trajectory.position(t).x));
+        }
+    }
+
+    #[test]
+    fn test_gravity_moves_across_body_axes_while_rotating() {
+        // Pitching at 90deg/s about Y
+        let rotation = ConstantRotation::new(Vector3::new(0.0, 90.0, 0.0));
+
+        let level = rotation.sample(0.0, 0);
+        assert_eq!((level.x_acc, level.z_acc), (0.0, 1000.0));
+        assert_eq!(level.y_gyro, 90_000);
+
+        // A quarter turn later gravity reads on X instead of Z, with body -X
+        // now pointing up
+        let turned = rotation.sample(1.0, 1000);
+        assert!(
+            (turned.x_acc + 1000.0).abs() < 1e-2,
+            "x_acc {}",
+            turned.x_acc
+        );
+        assert!(turned.z_acc.abs() < 1e-2, "z_acc {}", turned.z_acc);
+        assert!(turned.y_acc.abs() < 1e-2);
+        assert!((turned.true_qw.unwrap() - 0.5f32.sqrt()).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,196 @@
+//! Local IPC transport between publisher and consumer.
+//!
+//! Unix domain sockets on Unix. On Windows the socket path names a named
+//! pipe instead: paths already under `\\.\pipe\` are used as is, any other
+//! path maps to the pipe named after its file name, so the default
+//! `/tmp/imu-ipc.sock` becomes `\\.\pipe\imu-ipc.sock`.
+
+#[cfg(unix)]
+mod imp {
+    use std::io;
+    use std::path::Path;
+    use tokio::net::{UnixListener, UnixStream};
+
+    /// Publisher side of an accepted connection.
+    pub type ServerStream = UnixStream;
+    /// Consumer side of a connection.
+    pub type ClientStream = UnixStream;
+
+    #[derive(Debug)]
+    pub struct Listener(UnixListener);
+
+    impl Listener {
+        pub fn bind(path: &Path) -> io::Result<Self> {
+            UnixListener::bind(path).map(Listener)
+        }
+
+        pub async fn accept(&mut self) -> io::Result<ServerStream> {
+            self.0.accept().await.map(|(stream, _addr)| stream)
+        }
+    }
+
+    pub async fn connect(path: &Path) -> io::Result<ClientStream> {
+        UnixStream::connect(path).await
+    }
+
+    /// Whether a listener is accepting connections at `path`, as opposed to
+    /// a stale socket file left behind by a crashed publisher.
+    pub fn is_live(path: &Path) -> bool {
+        std::os::unix::net::UnixStream::connect(path).is_ok()
+    }
+}
+
+#[cfg(windows)]
+mod imp {
+    use std::ffi::OsString;
+    use std::io;
+    use std::path::Path;
+    use std::time::Duration;
+    use tokio::net::windows::named_pipe::{
+        ClientOptions, NamedPipeClient, NamedPipeServer, ServerOptions,
+    };
+
+    const PIPE_PREFIX: &str = r"\\.\pipe\";
+    const ERROR_PIPE_BUSY: i32 = 231;
+    const PIPE_BUSY_RETRY: Duration = Duration::from_millis(50);
+
+    /// Publisher side of an accepted connection.
+    pub type ServerStream = NamedPipeServer;
+    /// Consumer side of a connection.
+    pub type ClientStream = NamedPipeClient;
+
+    /// Keeps one unconnected pipe instance ready for the next client.
+    #[derive(Debug)]
+    pub struct Listener {
+        name: OsString,
+        next: NamedPipeServer,
+    }
+
+    impl Listener {
+        pub fn bind(path: &Path) -> io::Result<Self> {
+            let name = pipe_name(path);
+            let next = ServerOptions::new()
+                .first_pipe_instance(true)
+                .create(&name)?;
+            Ok(Listener { name, next })
+        }
+
+        pub async fn accept(&mut self) -> io::Result<ServerStream> {
+            self.next.connect().await?;
+            let next = ServerOptions::new().create(&self.name)?;
+            Ok(std::mem::replace(&mut self.next, next))
+        }
+    }
+
+    pub async fn connect(path: &Path) -> io::Result<ClientStream> {
+        let name = pipe_name(path);
+        loop {
+            match ClientOptions::new().open(&name) {
+                Err(e) if e.raw_os_error() == Some(ERROR_PIPE_BUSY) => {
+                    tokio::time::sleep(PIPE_BUSY_RETRY).await;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    pub fn pipe_name(path: &Path) -> OsString {
+        if path.to_string_lossy().starts_with(PIPE_PREFIX) {
+            return path.as_os_str().to_owned();
+        }
+        let mut name = OsString::from(PIPE_PREFIX);
+        name.push(path.file_name().unwrap_or(path.as_os_str()));
+        name
+    }
+}
+
+pub use imp::*;
+
+/// In-process transport for wiring publisher and consumer together without
+/// touching the filesystem, mainly for tests.
+pub mod memory {
+    use tokio::io::DuplexStream;
+
+    /// Bytes buffered in each direction before writes wait for the reader.
+    pub const BUFFER_SIZE: usize = 64 * 1024;
+
+    pub type MemoryStream = DuplexStream;
+
+    /// Both ends of a connection, writes on one are read from the other.
+    pub fn pair() -> (MemoryStream, MemoryStream) {
+        tokio::io::duplex(BUFFER_SIZE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::{encode_frame, read_frame};
+    use crate::proto::ImuData;
+    use std::path::PathBuf;
+    use tokio::io::AsyncWriteExt;
+
+    async fn exchange_frame(path: PathBuf) {
+        let mut listener = Listener::bind(&path).expect("Failed to bind");
+        let sample = ImuData {
+            z_acc: 1000.0,
+            timestamp_acc: 42,
+            ..Default::default()
+        };
+
+        let server = tokio::spawn(async move {
+            let mut stream = listener.accept().await.expect("Failed to accept");
+            let mut buf = Vec::new();
+            encode_frame(&sample, &mut buf);
+            stream.write_all(&buf).await.expect("Failed to write");
+        });
+
+        let mut client = connect(&path).await.expect("Failed to connect");
+        assert_eq!(read_frame(&mut client).await.unwrap(), Some(sample));
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_memory_round_trip() {
+        let (mut server, mut client) = memory::pair();
+        let sample = ImuData {
+            timestamp_gyro: 7,
+            ..Default::default()
+        };
+
+        let mut buf = Vec::new();
+        encode_frame(&sample, &mut buf);
+        server.write_all(&buf).await.unwrap();
+        drop(server);
+
+        assert_eq!(read_frame(&mut client).await.unwrap(), Some(sample));
+        assert_eq!(read_frame(&mut client).await.unwrap(), None);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_unix_socket_round_trip() {
+        let path = PathBuf::from("/tmp/test_imu_transport.sock");
+        let _ = std::fs::remove_file(&path);
+
+        exchange_frame(path.clone()).await;
+        assert!(!is_live(&path), "Listener is gone, only the file is left");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(windows)]
+    #[tokio::test]
+    async fn test_named_pipe_round_trip() {
+        exchange_frame(PathBuf::from(r"\\.\pipe\test_imu_transport")).await;
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_pipe_name_mapping() {
+        assert_eq!(
+            pipe_name(&PathBuf::from("/tmp/imu-ipc.sock")),
+            r"\\.\pipe\imu-ipc.sock"
+        );
+        assert_eq!(pipe_name(&PathBuf::from(r"\\.\pipe\imu")), r"\\.\pipe\imu");
+    }
+}
@@ -0,0 +1,166 @@
+use crate::proto::ImuData;
+use nalgebra::Vector3;
+
+pub const STANDARD_GRAVITY: f32 = 9.81; // m/s^2
+
+/// Acceleration [mg] -> [m/s^2]
+pub fn mg_to_mps2(mg: f32) -> f32 {
+    mg * STANDARD_GRAVITY / 1000.0
+}
+
+/// Unit of the raw accelerometer fields sent by the publisher.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AccelUnits {
+    /// Milli-g, what the bundled publisher sends
+    #[default]
+    Mg,
+    /// Meters per second squared
+    #[value(name = "mps2")]
+    Mps2,
+    /// Multiples of standard gravity
+    G,
+}
+
+impl AccelUnits {
+    /// Raw reading in these units -> [mg]
+    pub fn to_mg(self, value: f32) -> f32 {
+        match self {
+            AccelUnits::Mg => value,
+            AccelUnits::Mps2 => value * 1000.0 / STANDARD_GRAVITY,
+            AccelUnits::G => value * 1000.0,
+        }
+    }
+}
+
+/// Unit angular quantities are logged in. The math always uses radians.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AngleUnits {
+    #[default]
+    Rad,
+    Deg,
+}
+
+impl AngleUnits {
+    /// Angle [rad] or angular rate [rad/s] -> these units (per second)
+    pub fn from_radians(self, value: f32) -> f32 {
+        match self {
+            AngleUnits::Rad => value,
+            AngleUnits::Deg => value.to_degrees(),
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            AngleUnits::Rad => "rad",
+            AngleUnits::Deg => "deg",
+        }
+    }
+}
+
+/// Rate of rotation [mDeg/s] -> [rad/s]
+pub fn mdps_to_radps(mdps: f32) -> f32 {
+    mdps * 0.001 * std::f32::consts::PI / 180.0
+}
+
+/// Magnetic induction [mGauss] -> [T]
+pub fn mgauss_to_tesla(mgauss: f32) -> f32 {
+    mgauss * 1e-7
+}
+
+/// Typed accessors converting the raw proto fields to SI units.
+pub trait ImuDataExt {
+    fn accel_mps2(&self) -> Vector3<f32>;
+    fn gyro_radps(&self) -> Vector3<f32>;
+    fn mag_tesla(&self) -> Vector3<f32>;
+}
+
+impl ImuDataExt for ImuData {
+    fn accel_mps2(&self) -> Vector3<f32> {
+        Vector3::new(self.x_acc, self.y_acc, self.z_acc).map(mg_to_mps2)
+    }
+
+    fn gyro_radps(&self) -> Vector3<f32> {
+        Vector3::new(self.x_gyro as f32, self.y_gyro as f32, self.z_gyro as f32).map(mdps_to_radps)
+    }
+
+    fn mag_tesla(&self) -> Vector3<f32> {
+        Vector3::new(self.x_mag, self.y_mag, self.z_mag).map(mgauss_to_tesla)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() <= 1e-6 * b.abs().max(1.0), "{} != {}", a, b);
+    }
+
+    #[test]
+    fn test_scalar_conversions_match_factors() {
+        assert_close(mg_to_mps2(1000.0), 9.81);
+        assert_close(mg_to_mps2(-250.0), -250.0 * 9.81 / 1000.0);
+
+        assert_close(mdps_to_radps(180_000.0), std::f32::consts::PI);
+        assert_close(
+            mdps_to_radps(1234.0),
+            1234.0 * std::f32::consts::PI / 180.0 / 1000.0,
+        );
+
+        // 1 Gauss = 1e-4 T
+        assert_close(mgauss_to_tesla(1000.0), 1e-4);
+    }
+
+    #[test]
+    fn test_imu_data_extension() {
+        let data = ImuData {
+            x_acc: 1000.0,
+            y_acc: -500.0,
+            z_acc: 0.0,
+            x_gyro: 90_000,
+            y_gyro: 0,
+            z_gyro: -180_000,
+            x_mag: 500.0,
+            y_mag: 0.0,
+            z_mag: -250.0,
+            ..Default::default()
+        };
+
+        let acc = data.accel_mps2();
+        assert_close(acc.x, 9.81);
+        assert_close(acc.y, -4.905);
+        assert_close(acc.z, 0.0);
+
+        let gyro = data.gyro_radps();
+        assert_close(gyro.x, std::f32::consts::FRAC_PI_2);
+        assert_close(gyro.y, 0.0);
+        assert_close(gyro.z, -std::f32::consts::PI);
+
+        let mag = data.mag_tesla();
+        assert_close(mag.x, 5e-5);
+        assert_close(mag.z, -2.5e-5);
+    }
+
+    #[test]
+    fn test_accel_units_agree_on_same_acceleration() {
+        let readings = [
+            (AccelUnits::Mg, -500.0),
+            (AccelUnits::Mps2, -4.905),
+            (AccelUnits::G, -0.5),
+        ];
+
+        for (units, value) in readings {
+            assert_close(mg_to_mps2(units.to_mg(value)), -4.905);
+        }
+    }
+
+    #[test]
+    fn test_angle_units_display_quarter_turn() {
+        let quarter_turn = std::f32::consts::FRAC_PI_2;
+        assert_close(AngleUnits::Deg.from_radians(quarter_turn), 90.0);
+        assert_close(AngleUnits::Rad.from_radians(quarter_turn), quarter_turn);
+        assert_eq!(AngleUnits::Deg.label(), "deg");
+    }
+}
@@ -0,0 +1,12 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Wall-clock time [us since the Unix epoch], 0 if the clock is set before it.
+///
+/// Unlike `Instant` this is comparable across processes, but only on the
+/// same host, or hosts whose clocks are kept in sync.
+pub fn unix_time_us() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_micros() as u64)
+        .unwrap_or(0)
+}
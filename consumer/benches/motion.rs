@@ -0,0 +1,58 @@
+//! Throughput of `MotionProcessor::process` on a synthetic walking-like
+//! stream. Runs once per benchmark as a smoke test under `cargo test`.
+
+use common::proto::ImuData;
+use common::slog::{Discard, Logger, o};
+use consumer::motion::MotionProcessor;
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+use std::hint::black_box;
+
+const SAMPLE_COUNT: u32 = 1000;
+const SAMPLE_INTERVAL_MS: u32 = 2; // 500 Hz
+
+fn create_samples() -> Vec<ImuData> {
+    (0..SAMPLE_COUNT)
+        .map(|i| {
+            let phase = i as f32 * 0.05;
+            let timestamp = 1000 + i * SAMPLE_INTERVAL_MS;
+            ImuData {
+                x_acc: 50.0 * phase.sin(),
+                y_acc: 20.0 * phase.cos(),
+                z_acc: 1000.0 + 150.0 * (2.0 * phase).sin(),
+                timestamp_acc: timestamp,
+                x_gyro: (5000.0 * phase.cos()) as i32,
+                y_gyro: (3000.0 * phase.sin()) as i32,
+                z_gyro: 1500,
+                timestamp_gyro: timestamp,
+                x_mag: 200.0,
+                y_mag: 0.0,
+                z_mag: -400.0,
+                timestamp_mag: timestamp,
+                ..Default::default()
+            }
+        })
+        .collect()
+}
+
+fn bench_process(c: &mut Criterion) {
+    let samples = create_samples();
+    let mut group = c.benchmark_group("process");
+    group.throughput(Throughput::Elements(samples.len() as u64));
+
+    for (name, gyro_only) in [("complementary", false), ("gyro_only", true)] {
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                let mut processor = MotionProcessor::new(Logger::root(Discard, o!()));
+                processor.set_gyro_only(gyro_only);
+                for sample in &samples {
+                    black_box(processor.process(black_box(sample)));
+                }
+            })
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_process);
+criterion_main!(benches);
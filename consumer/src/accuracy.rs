@@ -0,0 +1,114 @@
+use crate::motion::MotionState;
+use common::proto::ImuData;
+use nalgebra::{Quaternion, UnitQuaternion, Vector3};
+
+/// True position [m] carried by synthetic samples, if complete.
+pub fn true_position(imu_data: &ImuData) -> Option<Vector3<f32>> {
+    Some(Vector3::new(
+        imu_data.true_x_pos?,
+        imu_data.true_y_pos?,
+        imu_data.true_z_pos?,
+    ))
+}
+
+/// True body-to-world orientation carried by synthetic samples, if complete.
+pub fn true_orientation(imu_data: &ImuData) -> Option<UnitQuaternion<f32>> {
+    Some(UnitQuaternion::from_quaternion(Quaternion::new(
+        imu_data.true_qw?,
+        imu_data.true_qx?,
+        imu_data.true_qy?,
+        imu_data.true_qz?,
+    )))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AccuracySummary {
+    /// Samples that carried ground truth
+    pub samples: u32,
+    /// RMS of the angle between estimated and true orientation [rad]
+    pub orientation_rms: Option<f32>,
+    /// RMS of the distance between estimated and true position [m]
+    pub position_rms: Option<f32>,
+}
+
+/// Running RMS error of the motion estimate against the ground truth of
+/// synthetic streams. Samples without ground truth are ignored.
+#[derive(Debug, Clone, Default)]
+pub struct GroundTruthTracker {
+    samples: u32,
+    orientation_samples: u32,
+    orientation_sum_sq: f64,
+    position_samples: u32,
+    position_sum_sq: f64,
+}
+
+impl GroundTruthTracker {
+    /// Compares the state after processing `imu_data` with its ground truth,
+    /// returns whether the sample carried any.
+    pub fn update(&mut self, imu_data: &ImuData, state: &MotionState) -> bool {
+        let orientation = true_orientation(imu_data);
+        let position = true_position(imu_data);
+
+        if let Some(orientation) = orientation {
+            let error = state.orientation.angle_to(&orientation) as f64;
+            self.orientation_sum_sq += error * error;
+            self.orientation_samples += 1;
+        }
+        if let Some(position) = position {
+            let error = (state.position - position).norm() as f64;
+            self.position_sum_sq += error * error;
+            self.position_samples += 1;
+        }
+
+        let has_truth = orientation.is_some() || position.is_some();
+        self.samples += has_truth as u32;
+        has_truth
+    }
+
+    pub fn summary(&self) -> AccuracySummary {
+        let rms = |sum_sq: f64, n: u32| (n > 0).then(|| (sum_sq / n as f64).sqrt() as f32);
+        AccuracySummary {
+            samples: self.samples,
+            orientation_rms: rms(self.orientation_sum_sq, self.orientation_samples),
+            position_rms: rms(self.position_sum_sq, self.position_samples),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_rms_over_samples_with_truth() {
+        let mut tracker = GroundTruthTracker::default();
+        let mut state = MotionState::default();
+        let truth = ImuData {
+            true_x_pos: Some(0.0),
+            true_y_pos: Some(0.0),
+            true_z_pos: Some(0.0),
+            true_qw: Some(1.0),
+            true_qx: Some(0.0),
+            true_qy: Some(0.0),
+            true_qz: Some(0.0),
+            ..Default::default()
+        };
+
+        state.position = Vector3::new(3.0, 0.0, 0.0);
+        assert!(tracker.update(&truth, &state));
+        state.position = Vector3::new(0.0, 4.0, 0.0);
+        state.orientation = UnitQuaternion::from_euler_angles(0.2, 0.0, 0.0);
+        assert!(tracker.update(&truth, &state));
+        assert!(!tracker.update(&ImuData::default(), &state));
+
+        let summary = tracker.summary();
+        assert_eq!(summary.samples, 2);
+        assert_relative_eq!(summary.position_rms.unwrap(), 12.5f32.sqrt());
+        assert_relative_eq!(
+            summary.orientation_rms.unwrap(),
+            0.02f32.sqrt(),
+            epsilon = 1e-5
+        );
+    }
+}
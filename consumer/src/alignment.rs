@@ -0,0 +1,113 @@
+use common::proto::ImuData;
+
+/// Timestamp [us] of one sensor, from the millis if it carries no micros.
+fn time_us(ms: u32, us: Option<u64>) -> u64 {
+    us.unwrap_or(u64::from(ms) * 1000)
+}
+
+/// Position of `t` between `t0` and `t1`, `None` unless bracketed.
+fn fraction(t0: u64, t1: u64, t: u64) -> Option<f32> {
+    if t1 <= t0 || t < t0 || t > t1 {
+        return None;
+    }
+    Some((t - t0) as f32 / (t1 - t0) as f32)
+}
+
+fn lerp(a: f32, b: f32, fraction: f32) -> f32 {
+    a + (b - a) * fraction
+}
+
+/// Resamples the gyro and mag readings of a sample to its accel timestamp,
+/// so fusion sees all three sensors at the same instant.
+///
+/// Each reading is interpolated linearly between the previous and the
+/// current one of that sensor, if the accel timestamp falls between them.
+/// A reading that does not bracket the accel timestamp is passed on as is,
+/// there is nothing later to interpolate against yet.
+#[derive(Debug, Clone, Default)]
+pub struct SensorAligner {
+    previous: Option<ImuData>,
+}
+
+impl SensorAligner {
+    pub fn align(&mut self, imu_data: &ImuData) -> ImuData {
+        let Some(previous) = self.previous.replace(*imu_data) else {
+            return *imu_data;
+        };
+        let mut aligned = *imu_data;
+        let t_acc = time_us(imu_data.timestamp_acc, imu_data.timestamp_acc_us);
+
+        if let Some(f) = fraction(
+            time_us(previous.timestamp_gyro, previous.timestamp_gyro_us),
+            time_us(imu_data.timestamp_gyro, imu_data.timestamp_gyro_us),
+            t_acc,
+        ) {
+            let gyro = |a: i32, b: i32| lerp(a as f32, b as f32, f).round() as i32;
+            aligned.x_gyro = gyro(previous.x_gyro, imu_data.x_gyro);
+            aligned.y_gyro = gyro(previous.y_gyro, imu_data.y_gyro);
+            aligned.z_gyro = gyro(previous.z_gyro, imu_data.z_gyro);
+            aligned.timestamp_gyro = imu_data.timestamp_acc;
+            aligned.timestamp_gyro_us = imu_data.timestamp_acc_us;
+        }
+
+        if let Some(f) = fraction(
+            time_us(previous.timestamp_mag, previous.timestamp_mag_us),
+            time_us(imu_data.timestamp_mag, imu_data.timestamp_mag_us),
+            t_acc,
+        ) {
+            aligned.x_mag = lerp(previous.x_mag, imu_data.x_mag, f);
+            aligned.y_mag = lerp(previous.y_mag, imu_data.y_mag, f);
+            aligned.z_mag = lerp(previous.z_mag, imu_data.z_mag, f);
+            aligned.timestamp_mag = imu_data.timestamp_acc;
+            aligned.timestamp_mag_us = imu_data.timestamp_acc_us;
+        }
+
+        aligned
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_sample(t_acc: u32, t_gyro: u32, t_mag: u32, value: i32) -> ImuData {
+        ImuData {
+            z_acc: 1000.0,
+            timestamp_acc: t_acc,
+            z_gyro: value,
+            timestamp_gyro: t_gyro,
+            x_mag: value as f32,
+            timestamp_mag: t_mag,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_interpolates_offset_sensors_to_accel_time() {
+        let mut aligner = SensorAligner::default();
+        // Gyro 4ms and mag 8ms ahead of the accel, 10ms apart
+        assert_eq!(
+            aligner.align(&create_sample(1000, 1004, 1008, 100)),
+            create_sample(1000, 1004, 1008, 100),
+            "Nothing to interpolate against yet"
+        );
+        let aligned = aligner.align(&create_sample(1010, 1014, 1018, 200));
+
+        // The accel time lies 60% past the previous gyro reading, 20% past the mag
+        assert_eq!(aligned.z_gyro, 160);
+        assert!((aligned.x_mag - 120.0).abs() < 1e-3, "{}", aligned.x_mag);
+        assert!((100..=200).contains(&aligned.z_gyro));
+        assert_eq!(aligned.timestamp_gyro, 1010);
+        assert_eq!(aligned.timestamp_mag, 1010);
+        assert_eq!(aligned.timestamp_acc, 1010);
+    }
+
+    #[test]
+    fn test_leaves_unbracketed_readings_alone() {
+        let mut aligner = SensorAligner::default();
+        aligner.align(&create_sample(1000, 996, 992, 100));
+        // Gyro and mag behind the accel, no reading later than it yet
+        let sample = create_sample(1010, 1006, 1002, 200);
+        assert_eq!(aligner.align(&sample), sample);
+    }
+}
@@ -1,19 +1,375 @@
+use common::capabilities::Capabilities;
 use common::clap;
 use common::cli_defaults::*;
+use common::codec::DEFAULT_MAX_FRAME_SIZE;
+use common::framing::{Compression, Endianness, WireFormat};
 use common::logging::LogLevel;
 use common::slog;
+use common::units::{AccelUnits, AngleUnits};
 
-#[derive(clap::Parser, Debug)]
+use crate::motion::{Axis, PositionBoundPolicy, SampleGapPolicy};
+use crate::orientation::{AccWeightCurve, QuaternionConvention};
+
+#[derive(clap::Parser, Debug, serde::Serialize)]
 #[command(version, about, long_about = None)]
 pub struct ConsumerArgs {
+    /// Print the effective configuration as JSON and exit
+    #[arg(long)]
+    #[serde(skip)]
+    pub show_config: bool,
+
+    /// Print a JSON banner of the supported features before starting
+    #[arg(long)]
+    #[serde(skip)]
+    pub print_capabilities: bool,
+
+    /// Socket of the publisher, repeat for hot-standby publishers tried in order when the connection drops (not with --socket-path-2)
     #[arg(short, long, default_value = DEFAULT_SOCKET_PATH, value_parser = clap::value_parser!(std::path::PathBuf))]
-    pub socket_path: std::path::PathBuf,
+    pub socket_path: Vec<std::path::PathBuf>,
+
+    /// Carry the motion state over to the next --socket-path on failover instead of starting over
+    #[arg(long)]
+    pub keep_state_on_failover: bool,
+
+    /// Read recorded frames from this file instead of a socket, stopping at its end
+    #[arg(long, conflicts_with_all = ["socket_path", "socket_path_2"], value_parser = clap::value_parser!(std::path::PathBuf))]
+    pub input_file: Option<std::path::PathBuf>,
+
+    /// Read samples from a JSON-lines file, one ImuData object with the proto field names per line, instead of a socket
+    #[arg(long, conflicts_with_all = ["socket_path", "socket_path_2", "input_file"], value_parser = clap::value_parser!(std::path::PathBuf))]
+    pub replay_from_jsonl: Option<std::path::PathBuf>,
+
+    /// Write the estimated trajectory to this file in TUM format, one `timestamp tx ty tz qx qy qz qw` line per sample
+    #[arg(long, value_parser = clap::value_parser!(std::path::PathBuf))]
+    pub traj_out: Option<std::path::PathBuf>,
+
+    /// Socket of a second, redundant IMU whose samples are fused with the first
+    #[arg(long, value_parser = clap::value_parser!(std::path::PathBuf))]
+    pub socket_path_2: Option<std::path::PathBuf>,
+
+    /// Bind this socket and forward every received frame unchanged to the consumers connecting to it
+    #[arg(long, value_parser = clap::value_parser!(std::path::PathBuf))]
+    pub tee: Option<std::path::PathBuf>,
+
+    /// Load accel/gyro/mag biases and scales from a JSON file written by --save-bias-file
+    #[arg(long, value_parser = clap::value_parser!(std::path::PathBuf))]
+    pub bias_file: Option<std::path::PathBuf>,
+
+    /// Write the calibration in use to this JSON file once the stream ends
+    #[arg(long, value_parser = clap::value_parser!(std::path::PathBuf))]
+    pub save_bias_file: Option<std::path::PathBuf>,
+
+    /// Resume from a motion state and calibration written by --save-state, --bias-file still overrides the calibration
+    #[arg(long, value_parser = clap::value_parser!(std::path::PathBuf))]
+    pub load_state: Option<std::path::PathBuf>,
+
+    /// Write the motion state and calibration to this JSON file once the stream ends, to resume with --load-state
+    #[arg(long, value_parser = clap::value_parser!(std::path::PathBuf))]
+    pub save_state: Option<std::path::PathBuf>,
+
+    /// Number of recent motion states kept for --save-recent-states, 0 keeps none
+    #[arg(long, default_value_t = DEFAULT_STATE_HISTORY)]
+    pub state_history: u32,
+
+    /// Write the last --state-history motion states to this JSON file once the stream ends, e.g. to inspect what led up to an error
+    #[arg(long, value_parser = clap::value_parser!(std::path::PathBuf))]
+    pub save_recent_states: Option<std::path::PathBuf>,
+
+    /// Estimate the magnetometer hard-iron offset and soft-iron scale from the whole run, turn the device through every orientation. Applied once the input ends, save it with --save-bias-file
+    #[arg(long)]
+    pub calibrate_mag: bool,
+
+    /// Estimate the accelerometer bias and scale from the whole run, rest the device with each axis pointing up and down in turn. Applied once the input ends, save it with --save-bias-file
+    #[arg(long)]
+    pub calibrate_acc_scale: bool,
+
+    /// Estimate the gyro scale of one axis from the whole run as <axis>:<degrees>, e.g. z:360 for one full turn about Z. Applied once the input ends, save it with --save-bias-file
+    #[arg(long, value_parser = parse_gyro_scale_run)]
+    pub calibrate_gyro_scale: Option<(Axis, f32)>,
 
     #[arg(short, long, value_enum, default_value_t = DEFAULT_LOG_LEVEL, value_parser = clap::value_parser!(LogLevel))]
     pub log_level: LogLevel,
 
     #[arg(short, long, default_value = DEFAULT_TIMEOUT, value_parser = clap::value_parser!(u32).range(1..=60*1000))]
     pub timeout: u32,
+
+    /// Process this many samples before reporting the motion state
+    #[arg(long, default_value = DEFAULT_WARMUP_SAMPLES, value_parser = clap::value_parser!(u32))]
+    pub warmup_samples: u32,
+
+    /// Enable step counting with the given peak threshold above baseline [mg]
+    #[arg(long, value_parser = parse_positive_f32)]
+    pub step_threshold: Option<f32>,
+
+    /// Minimum interval between two counted steps [ms]
+    #[arg(long, default_value = DEFAULT_MIN_STEP_INTERVAL, value_parser = clap::value_parser!(u32))]
+    pub min_step_interval: u32,
+
+    /// Warn when a sensor timestamp lags the others by more than this, disabled if not set [ms]
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub stale_sensor_timeout: Option<u32>,
+
+    /// Warn when the velocity exceeds this while the device is at rest, disabled if not set [m/s]
+    #[arg(long, value_parser = parse_positive_f32)]
+    pub warn_on_drift: Option<f32>,
+
+    /// Log the orientation [deg/s] and position [m/s] drift rate observed at rest once per this much sample time, disabled if not set [ms]
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub drift_rate_interval: Option<u32>,
+
+    /// Hold velocity and position at the origin until the accel or gyro first leaves the stationary detector's still band
+    #[arg(long, conflicts_with = "orientation_only")]
+    pub motion_gate: bool,
+
+    /// Linearly interpolate the gyro and mag readings to the accel timestamp before fusion, for sensors sampled out of phase
+    #[arg(long)]
+    pub align_sensors: bool,
+
+    /// Orientation of the sensor in the vehicle as roll,pitch,yaw [deg], so orientation and position are reported for the vehicle
+    #[arg(long, value_parser = parse_mount_rotation, allow_hyphen_values = true)]
+    pub mount_rotation: Option<[f32; 3]>,
+
+    /// World-frame acceleration below this value is not integrated [m/s^2]
+    #[arg(long, default_value = DEFAULT_ACC_DEADBAND, value_parser = parse_non_negative_f32)]
+    pub acc_deadband: f32,
+
+    /// Cutoff of the high-pass filter on world-frame acceleration, disabled if not set [Hz]
+    #[arg(long, value_parser = parse_positive_f32)]
+    pub acc_high_pass: Option<f32>,
+
+    /// Complementary filter crossover frequency, fixed 0.98/0.02 weights if not set [Hz]
+    #[arg(long, value_parser = parse_positive_f32)]
+    pub filter_cutoff: Option<f32>,
+
+    /// Lowest accel magnitude the complementary filter trusts as gravity [mg]
+    #[arg(long, default_value = DEFAULT_GRAVITY_WINDOW_MIN, value_parser = clap::value_parser!(f32))]
+    pub gravity_window_min: f32,
+
+    /// Highest accel magnitude the complementary filter trusts as gravity [mg]
+    #[arg(long, default_value = DEFAULT_GRAVITY_WINDOW_MAX, value_parser = clap::value_parser!(f32))]
+    pub gravity_window_max: f32,
+
+    /// Trust accel magnitudes within 1g +- this as gravity whatever the --accel-units, instead of the mg window [g]
+    #[arg(long, conflicts_with_all = ["gravity_window_min", "gravity_window_max"], value_parser = parse_gravity_tolerance)]
+    pub gravity_tolerance: Option<f32>,
+
+    /// Accel weight of the complementary filter within the gravity window: fixed, or falling off away from 1g so linear acceleration disturbs the orientation less
+    #[arg(long, value_enum, default_value_t = AccWeightCurve::Fixed)]
+    pub acc_weight_curve: AccWeightCurve,
+
+    /// Expected publisher sample rate used to derive filter weights [Hz]
+    #[arg(long, default_value = DEFAULT_FREQUENCY, value_parser = clap::value_parser!(u32).range(1..=1000))]
+    pub sample_rate: u32,
+
+    /// Savitzky-Golay smoothing window over gyro samples, disabled if not set [samples]
+    #[arg(long, value_parser = clap::value_parser!(u32).range(2..=255))]
+    pub gyro_smoothing_window: Option<u32>,
+
+    /// Polynomial order of the gyro smoothing, must be below the window length
+    #[arg(long, default_value = DEFAULT_GYRO_SMOOTHING_ORDER, value_parser = clap::value_parser!(u32).range(0..=6))]
+    pub gyro_smoothing_order: u32,
+
+    /// Integrate the gyro only, without any accel correction of the orientation
+    #[arg(long)]
+    pub gyro_only: bool,
+
+    /// Remove gravity along the accel direction, independent of the estimated orientation
+    #[arg(long)]
+    pub accel_gravity: bool,
+
+    /// Log dt, accel magnitude, correction angle and filter weights of every sample at debug level
+    #[arg(long)]
+    pub verbose_motion: bool,
+
+    /// Log the RMS error against the ground truth of synthetic streams, e.g. `publisher --profile translation`
+    #[arg(long)]
+    pub compare_ground_truth: bool,
+
+    /// Log a recap once the input ends cleanly: samples, distance traveled, max speed, final orientation and error counts
+    #[arg(long)]
+    pub summary: bool,
+
+    /// Also log the estimated direction of gravity in the body frame
+    #[arg(long)]
+    pub log_gravity: bool,
+
+    /// Also log the fused heading and, for samples with a mag reading, the tilt-compensated magnetic heading, both counterclockwise from +X [deg]
+    #[arg(long)]
+    pub log_heading: bool,
+
+    /// Unit of the accel fields sent by the publisher
+    #[arg(long, value_enum, default_value_t = DEFAULT_ACCEL_UNITS)]
+    pub accel_units: AccelUnits,
+
+    /// Unit of logged angles and angular rates, the math always uses radians
+    #[arg(long, value_enum, default_value_t = DEFAULT_ANGLE_UNITS)]
+    pub angle_units: AngleUnits,
+
+    /// Layout of the logged orientation: hamilton [w,x,y,z] or jpl [x,y,z,w] with the vector part negated
+    #[arg(long, value_enum, default_value_t = QuaternionConvention::Hamilton)]
+    pub quaternion_convention: QuaternionConvention,
+
+    /// Compute dt from sample arrival times instead of the embedded timestamps
+    #[arg(long)]
+    pub use_receive_clock: bool,
+
+    /// Track orientation only and skip velocity/position integration
+    #[arg(long)]
+    pub orientation_only: bool,
+
+    /// Smooth the logged orientation by blending toward each new estimate with this weight, integration stays raw
+    #[arg(long, value_parser = parse_smoothing_factor)]
+    pub orientation_smoothing: Option<f32>,
+
+    /// Hold the altitude near zero with a 1D Kalman filter instead of integrating accel Z. There is no altitude sensor, zero is a prior for a device staying at one height
+    #[arg(long, alias = "altitude-kalman")]
+    pub zero_altitude_prior: bool,
+
+    /// Acceleration noise variance of the --zero-altitude-prior Kalman filter [(m/s^2)^2]
+    #[arg(long, default_value = DEFAULT_KALMAN_PROCESS_NOISE, value_parser = parse_positive_f32)]
+    pub kalman_process_noise: f32,
+
+    /// Variance of the --zero-altitude-prior, larger follows real vertical motion longer at the cost of more drift [m^2]
+    #[arg(long, alias = "kalman-measurement-noise", default_value = DEFAULT_ALTITUDE_PRIOR_VARIANCE, value_parser = parse_positive_f32)]
+    pub altitude_prior_variance: f32,
+
+    /// Sanity bound on the integrated position magnitude, unbounded if not set [m]
+    #[arg(long, value_parser = parse_positive_f32)]
+    pub max_position: Option<f32>,
+
+    /// What to do once the position exceeds --max-position
+    #[arg(long, value_enum, default_value_t = PositionBoundPolicy::Clamp)]
+    pub position_bound_policy: PositionBoundPolicy,
+
+    /// Restart the estimate when the sensor timestamps jump by more than this instead of skipping one sample [ms]
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub max_sample_gap: Option<u32>,
+
+    /// How the estimate restarts after a gap longer than --max-sample-gap
+    #[arg(long, value_enum, default_value_t = SampleGapPolicy::Reset)]
+    pub sample_gap_policy: SampleGapPolicy,
+
+    /// Warn when a sample arrives this many times earlier or later than the average spacing
+    #[arg(long, default_value_t = DEFAULT_TIMING_ANOMALY_FACTOR, value_parser = parse_anomaly_factor)]
+    pub timing_anomaly_factor: f32,
+
+    /// Byte order of the frame length prefix, must match the publisher
+    #[arg(long, value_enum, default_value_t = DEFAULT_LENGTH_ENDIANNESS)]
+    pub length_endianness: Endianness,
+
+    /// Encoding of the frame bodies, must match the publisher. packed is a fixed 48-byte struct without the optional fields
+    #[arg(long, value_enum, default_value_t = DEFAULT_WIRE_FORMAT)]
+    pub format: WireFormat,
+
+    /// Wire protocol version, must match the publisher. 2 puts a marker in front of every frame so the consumer can find the next one after corrupt data
+    #[arg(long, default_value = DEFAULT_PROTOCOL_VERSION, value_parser = clap::value_parser!(u32).range(1..=i64::from(common::capabilities::PROTOCOL_VERSION)))]
+    pub protocol_version: u32,
+
+    /// Compression of frame bodies accepted from a publisher offering it, plain frames otherwise. lz4 needs the compression feature
+    #[arg(long, value_enum, default_value_t = DEFAULT_COMPRESSION)]
+    pub compression: Compression,
+
+    /// Initial size of the frame read buffer, reused and only grown afterwards [bytes]
+    #[arg(long, default_value = DEFAULT_READ_BUFFER_HINT, value_parser = clap::value_parser!(u32))]
+    pub read_buffer_hint: u32,
+
+    /// Larger frames are treated as a corrupt stream and end the connection [bytes]
+    #[arg(long, default_value_t = DEFAULT_MAX_FRAME_SIZE as u32, value_parser = clap::value_parser!(u32).range(1..))]
+    pub max_frame_size: u32,
+
+    /// Identical warnings logged per second before further ones are only counted, 0 logs all
+    #[arg(long, default_value_t = DEFAULT_WARN_RATE_LIMIT, value_parser = clap::value_parser!(u32))]
+    pub warn_rate_limit: u32,
+
+    /// Exit once this many frames in a row fail to decode, e.g. an incompatible publisher; skip them forever if not set
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub max_decode_errors: Option<u32>,
+
+    /// Log a status line with connection state, sample count and motion summary this often, also on SIGUSR1 [s]
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub status_interval: Option<u32>,
+
+    /// Report the motion state at most this often in sample time, processing still runs on every sample [Hz]
+    #[arg(long, value_parser = parse_output_rate)]
+    pub output_rate: Option<f32>,
+
+    /// Run on a single-threaded runtime, less scheduling jitter at moderate rates but no parallelism
+    #[arg(long)]
+    pub single_thread: bool,
+}
+
+fn parse_positive_f32(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(value) if value.is_finite() && value > 0.0 => Ok(value),
+        Ok(_) => Err("must be a positive number".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn parse_non_negative_f32(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(value) if value.is_finite() && value >= 0.0 => Ok(value),
+        Ok(_) => Err("must be a non-negative number".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn parse_anomaly_factor(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(factor) if factor.is_finite() && factor > 1.0 => Ok(factor),
+        Ok(_) => Err("timing anomaly factor must be greater than 1".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn parse_gravity_tolerance(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(tolerance) if tolerance > 0.0 && tolerance < 1.0 => Ok(tolerance),
+        Ok(_) => Err("gravity tolerance must be in (0, 1)".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn parse_output_rate(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(rate) if rate.is_finite() && rate > 0.0 => Ok(rate),
+        Ok(_) => Err("output rate must be a positive number".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn parse_mount_rotation(s: &str) -> Result<[f32; 3], String> {
+    let angles = s
+        .split(',')
+        .map(|angle| angle.trim().parse::<f32>().map_err(|e| e.to_string()))
+        .collect::<Result<Vec<_>, _>>()?;
+    match angles[..] {
+        [roll, pitch, yaw] if angles.iter().all(|angle| angle.is_finite()) => {
+            Ok([roll, pitch, yaw])
+        }
+        _ => Err("mount rotation must be three angles roll,pitch,yaw in degrees".to_string()),
+    }
+}
+
+/// Parses `<axis>:<degrees>`, e.g. `z:360`.
+fn parse_gyro_scale_run(s: &str) -> Result<(Axis, f32), String> {
+    let (axis, angle) = s
+        .split_once(':')
+        .ok_or_else(|| format!("{} is not <axis>:<degrees>", s))?;
+    let axis = <Axis as clap::ValueEnum>::from_str(axis, true)?;
+    match angle.parse::<f32>() {
+        Ok(angle) if angle.is_finite() && angle != 0.0 => Ok((axis, angle)),
+        Ok(_) => Err("turn angle must be a non-zero number of degrees".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn parse_smoothing_factor(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(factor) if factor > 0.0 && factor <= 1.0 => Ok(factor),
+        Ok(_) => Err("orientation smoothing must be in (0, 1]".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
 }
 
 impl ConsumerArgs {
@@ -21,9 +377,322 @@ impl ConsumerArgs {
         <Self as clap::Parser>::parse()
     }
 
+    pub fn to_json(&self) -> String {
+        // Plain data with string keys, serialization cannot fail
+        serde_json::to_string_pretty(self).unwrap()
+    }
+
+    pub fn capabilities() -> Capabilities {
+        Capabilities::new(
+            "consumer",
+            env!("CARGO_PKG_VERSION"),
+            vec![
+                "input-file",
+                "jsonl-input",
+                "fused-input",
+                "complementary-filter",
+                "acc-high-pass",
+                "gyro-smoothing",
+                "zero-altitude-prior",
+                "step-detection",
+                "sensor-watchdog",
+                "drift-monitor",
+                "drift-rate",
+                "position-bound",
+                "orientation-smoothing",
+                "ground-truth",
+                "warn-rate-limit",
+                "bias-file",
+                "status-report",
+                "angle-units",
+                "sample-gap-reset",
+                "max-decode-errors",
+                "tee",
+                "quaternion-convention",
+                "latency",
+                "output-rate",
+                "failover",
+                "single-thread",
+                "gravity-tolerance",
+                "summary",
+                "motion-gate",
+                "sensor-alignment",
+                "frame-magic",
+                "tum-export",
+                "mount-rotation",
+                "adaptive-acc-weight",
+            ],
+        )
+    }
+
+    pub fn capabilities_json() -> String {
+        serde_json::to_string(&Self::capabilities()).unwrap()
+    }
+
     pub fn print(&self, logger: &slog::Logger) {
         slog::info!(logger, "Log level: {:?}", self.log_level);
         slog::info!(logger, "Socket path: {:?}", self.socket_path);
+        slog::info!(
+            logger,
+            "Keep state on failover: {:?}",
+            self.keep_state_on_failover
+        );
+        slog::info!(logger, "Input file: {:?}", self.input_file);
+        slog::info!(logger, "JSON-lines input: {:?}", self.replay_from_jsonl);
+        slog::info!(logger, "Trajectory output: {:?}", self.traj_out);
+        slog::info!(logger, "Second socket path: {:?}", self.socket_path_2);
+        slog::info!(logger, "Tee socket path: {:?}", self.tee);
+        slog::info!(logger, "Bias file: {:?}", self.bias_file);
+        slog::info!(logger, "Save bias file: {:?}", self.save_bias_file);
+        slog::info!(logger, "Load state: {:?}", self.load_state);
+        slog::info!(logger, "Save state: {:?}", self.save_state);
+        slog::info!(logger, "State history: {}", self.state_history);
+        slog::info!(logger, "Save recent states: {:?}", self.save_recent_states);
+        slog::info!(logger, "Calibrate mag: {:?}", self.calibrate_mag);
+        slog::info!(
+            logger,
+            "Calibrate acc scale: {:?}",
+            self.calibrate_acc_scale
+        );
+        slog::info!(
+            logger,
+            "Calibrate gyro scale: {:?}",
+            self.calibrate_gyro_scale
+        );
         slog::info!(logger, "Timeout: {:?}ms", self.timeout);
+        slog::info!(logger, "Warmup samples: {:?}", self.warmup_samples);
+        slog::info!(logger, "Step threshold: {:?}mg", self.step_threshold);
+        slog::info!(logger, "Min step interval: {:?}ms", self.min_step_interval);
+        slog::info!(
+            logger,
+            "Stale sensor timeout: {:?}ms",
+            self.stale_sensor_timeout
+        );
+        slog::info!(logger, "Warn on drift: {:?}m/s", self.warn_on_drift);
+        slog::info!(
+            logger,
+            "Drift rate interval: {:?}ms",
+            self.drift_rate_interval
+        );
+        slog::info!(logger, "Motion gate: {:?}", self.motion_gate);
+        slog::info!(logger, "Align sensors: {:?}", self.align_sensors);
+        slog::info!(logger, "Mount rotation: {:?}deg", self.mount_rotation);
+        slog::info!(logger, "Accel deadband: {:?}m/s^2", self.acc_deadband);
+        slog::info!(logger, "Accel high-pass cutoff: {:?}Hz", self.acc_high_pass);
+        slog::info!(logger, "Filter cutoff: {:?}Hz", self.filter_cutoff);
+        slog::info!(
+            logger,
+            "Gravity window min: {:?}mg",
+            self.gravity_window_min
+        );
+        slog::info!(
+            logger,
+            "Gravity window max: {:?}mg",
+            self.gravity_window_max
+        );
+        slog::info!(logger, "Gravity tolerance: {:?}g", self.gravity_tolerance);
+        slog::info!(logger, "Accel weight curve: {:?}", self.acc_weight_curve);
+        slog::info!(logger, "Sample rate: {:?}Hz", self.sample_rate);
+        slog::info!(logger, "Gyro only: {:?}", self.gyro_only);
+        slog::info!(logger, "Accel gravity: {:?}", self.accel_gravity);
+        slog::info!(logger, "Accel units: {:?}", self.accel_units);
+        slog::info!(logger, "Angle units: {:?}", self.angle_units);
+        slog::info!(
+            logger,
+            "Quaternion convention: {:?}",
+            self.quaternion_convention
+        );
+        slog::info!(logger, "Verbose motion: {:?}", self.verbose_motion);
+        slog::info!(logger, "Log gravity: {:?}", self.log_gravity);
+        slog::info!(logger, "Log heading: {:?}", self.log_heading);
+        slog::info!(
+            logger,
+            "Compare ground truth: {:?}",
+            self.compare_ground_truth
+        );
+        slog::info!(logger, "Summary: {:?}", self.summary);
+        slog::info!(
+            logger,
+            "Gyro smoothing window: {:?}",
+            self.gyro_smoothing_window
+        );
+        slog::info!(
+            logger,
+            "Gyro smoothing order: {:?}",
+            self.gyro_smoothing_order
+        );
+        slog::info!(logger, "Use receive clock: {:?}", self.use_receive_clock);
+        slog::info!(logger, "Orientation only: {:?}", self.orientation_only);
+        slog::info!(
+            logger,
+            "Orientation smoothing: {:?}",
+            self.orientation_smoothing
+        );
+        slog::info!(
+            logger,
+            "Zero altitude prior: {:?}",
+            self.zero_altitude_prior
+        );
+        slog::info!(
+            logger,
+            "Kalman process noise: {:?}",
+            self.kalman_process_noise
+        );
+        slog::info!(
+            logger,
+            "Altitude prior variance: {:?}",
+            self.altitude_prior_variance
+        );
+        slog::info!(logger, "Max position: {:?}", self.max_position);
+        slog::info!(
+            logger,
+            "Position bound policy: {:?}",
+            self.position_bound_policy
+        );
+        slog::info!(logger, "Max sample gap: {:?}ms", self.max_sample_gap);
+        slog::info!(logger, "Sample gap policy: {:?}", self.sample_gap_policy);
+        slog::info!(
+            logger,
+            "Timing anomaly factor: {:?}",
+            self.timing_anomaly_factor
+        );
+        slog::info!(logger, "Length endianness: {:?}", self.length_endianness);
+        slog::info!(logger, "Format: {:?}", self.format);
+        slog::info!(logger, "Protocol version: {:?}", self.protocol_version);
+        slog::info!(logger, "Compression: {:?}", self.compression);
+        slog::info!(logger, "Read buffer hint: {:?}B", self.read_buffer_hint);
+        slog::info!(logger, "Max frame size: {:?}B", self.max_frame_size);
+        slog::info!(logger, "Warn rate limit: {:?}/s", self.warn_rate_limit);
+        slog::info!(logger, "Max decode errors: {:?}", self.max_decode_errors);
+        slog::info!(logger, "Status interval: {:?}s", self.status_interval);
+        slog::info!(logger, "Output rate: {:?}Hz", self.output_rate);
+        slog::info!(logger, "Single thread: {:?}", self.single_thread);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn test_show_config_json_contains_every_field() {
+        let args =
+            <ConsumerArgs as clap::Parser>::try_parse_from(["consumer", "--show-config"]).unwrap();
+        assert!(args.show_config);
+
+        let json: serde_json::Value = serde_json::from_str(&args.to_json()).unwrap();
+        let object = json
+            .as_object()
+            .expect("Config should serialize to a JSON object");
+
+        for arg in ConsumerArgs::command().get_arguments() {
+            let id = arg.get_id().as_str();
+            if matches!(
+                id,
+                "help" | "version" | "show_config" | "print_capabilities"
+            ) {
+                continue;
+            }
+            assert!(
+                object.contains_key(id),
+                "Missing field '{}' in {}",
+                id,
+                json
+            );
+        }
+
+        assert_eq!(
+            object["socket_path"],
+            serde_json::json!([DEFAULT_SOCKET_PATH])
+        );
+        assert_eq!(object["log_level"], "info");
+    }
+
+    #[test]
+    fn test_rejects_non_positive_values() {
+        for flag in [
+            "--filter-cutoff",
+            "--acc-high-pass",
+            "--max-position",
+            "--warn-on-drift",
+            "--kalman-process-noise",
+            "--altitude-prior-variance",
+            "--step-threshold",
+        ] {
+            for value in ["0", "-1", "NaN", "inf"] {
+                let result =
+                    <ConsumerArgs as clap::Parser>::try_parse_from(["consumer", flag, value]);
+                assert!(result.is_err(), "{} {} should be rejected", flag, value);
+            }
+            let args =
+                <ConsumerArgs as clap::Parser>::try_parse_from(["consumer", flag, "0.5"]).unwrap();
+            assert!(args.to_json().contains("0.5"));
+        }
+    }
+
+    #[test]
+    fn test_rejects_negative_acc_deadband() {
+        for value in ["-0.1", "NaN", "inf"] {
+            let result = <ConsumerArgs as clap::Parser>::try_parse_from([
+                "consumer",
+                "--acc-deadband",
+                value,
+            ]);
+            assert!(
+                result.is_err(),
+                "--acc-deadband {} should be rejected",
+                value
+            );
+        }
+        let args =
+            <ConsumerArgs as clap::Parser>::try_parse_from(["consumer", "--acc-deadband", "0"])
+                .unwrap();
+        assert_eq!(args.acc_deadband, 0.0);
+    }
+
+    #[test]
+    fn test_parses_gyro_scale_run() {
+        let args = <ConsumerArgs as clap::Parser>::try_parse_from([
+            "consumer",
+            "--calibrate-gyro-scale",
+            "Z:-360",
+        ])
+        .unwrap();
+        assert_eq!(args.calibrate_gyro_scale, Some((Axis::Z, -360.0)));
+
+        for value in ["z", "w:360", "3:360", "x:0", "y:NaN"] {
+            let result = <ConsumerArgs as clap::Parser>::try_parse_from([
+                "consumer",
+                "--calibrate-gyro-scale",
+                value,
+            ]);
+            assert!(result.is_err(), "{} should be rejected", value);
+        }
+    }
+
+    #[test]
+    fn test_capabilities_banner_lists_expected_keys() {
+        let json: serde_json::Value =
+            serde_json::from_str(&ConsumerArgs::capabilities_json()).unwrap();
+        for key in [
+            "binary",
+            "version",
+            "protocol_version",
+            "transports",
+            "length_endianness",
+            "wire_formats",
+            "compression",
+            "features",
+        ] {
+            assert!(json.get(key).is_some(), "Missing key '{}' in {}", key, json);
+        }
+
+        assert_eq!(json["binary"], "consumer");
+        assert_eq!(
+            json["protocol_version"],
+            common::capabilities::PROTOCOL_VERSION
+        );
+        assert!(!json["features"].as_array().unwrap().is_empty());
     }
 }
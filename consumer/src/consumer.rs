@@ -1,19 +1,103 @@
-use crate::motion::MotionProcessor;
-use common::prost::Message;
+use crate::accuracy::{AccuracySummary, GroundTruthTracker};
+use crate::error::ConsumerError;
+use crate::export::TumWriter;
+use crate::fusion::StreamAligner;
+use crate::health::{ConnectionState, HealthReport, HealthTracker, StatusTrigger};
+use crate::latency::{LatencySummary, LatencyTracker};
+use crate::motion::{Axis, MotionProcessor, SkipReason};
+use crate::orientation::QuaternionConvention;
+use crate::ratelimit::WarnLimiter;
+use crate::summary::{TrajectorySummary, TrajectoryTracker};
+use crate::tee::Tee;
+use common::cli_defaults::{DEFAULT_TIMING_ANOMALY_FACTOR, DEFAULT_WARN_RATE_LIMIT};
+use common::codec::{FrameReader, FrameTooLarge, decode_body};
+use common::framing::{Compression, Endianness, WireFormat};
+use common::handshake::{self, Negotiated};
 use common::proto::ImuData;
-use common::slog::{Logger, error, info, warn};
-use std::path::PathBuf;
-use std::time::Duration;
-use tokio::io::{AsyncReadExt, BufReader};
-use tokio::net::UnixStream;
+use common::slog::{Logger, debug, error, info, o, warn};
+use common::transport::{self, ClientStream};
+use common::wallclock::unix_time_us;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, BufReader};
+use tokio::sync::mpsc;
 use tokio::time::timeout;
 
+const FUSED_QUEUE_CAPACITY: usize = 64; // samples
+const ARRIVAL_EMA_ALPHA: f32 = 0.1;
+const ARRIVAL_EMA_WARMUP: u32 = 10; // samples
+const GROUND_TRUTH_LOG_INTERVAL: u32 = 100; // samples
+const LATENCY_LOG_INTERVAL: u64 = 1000; // samples
+const FAILOVER_BACKOFF_INITIAL: Duration = Duration::from_millis(50);
+const FAILOVER_BACKOFF_MAX: Duration = Duration::from_secs(2);
+// Connections ending sooner count as a flapping publisher
+const FAILOVER_STABLE_SESSION: Duration = Duration::from_secs(1);
+const FAILOVER_MAX_SHORT_SESSIONS: u32 = 5;
+
+/// `warn!` through a `WarnLimiter`, first summarizing what it suppressed.
+macro_rules! limited_warn {
+    ($limiter:expr, $logger:expr, $msg:literal $(; $($kv:tt)*)?) => {
+        if let Some(suppressed) = $limiter.admit($msg) {
+            if suppressed > 0 {
+                warn!($logger, "Suppressed repeated warnings"; "message" => $msg, "occurrences" => suppressed);
+            }
+            warn!($logger, $msg $(; $($kv)*)?);
+        }
+    };
+}
+
+/// A calibration estimated from all samples of a run, applied to the motion
+/// processor once the input ends cleanly, e.g. for `--save-bias-file`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CalibrationRun {
+    /// Magnetometer hard-iron offset and soft-iron scale, while the device is
+    /// turned through every orientation
+    Mag,
+    /// Accelerometer bias and scale, while the device rests with each axis
+    /// pointing up and down in turn
+    AccScale,
+    /// Gyro scale of one axis, while the device is turned by a known angle
+    /// [deg] about it
+    GyroScale { axis: Axis, angle: f32 },
+}
+
 #[derive(Debug)]
 pub struct Consumer {
     socket_path: PathBuf,
+    standby_socket_paths: Vec<PathBuf>,
+    keep_state_on_failover: bool,
+    secondary_socket_path: Option<PathBuf>,
+    input_file: Option<PathBuf>,
+    input_jsonl: Option<PathBuf>,
     timeout: Duration,
     logger: Logger,
     motion_processor: MotionProcessor,
+    frame_reader: FrameReader,
+    warn_limiter: WarnLimiter,
+    timing_anomaly_factor: f32,
+    last_arrival: Option<Instant>,
+    arrival_dt_ema: Option<f32>,
+    arrival_count: u32,
+    warmup_samples: u32,
+    log_gravity: bool,
+    log_heading: bool,
+    processed_samples: u32,
+    ground_truth: Option<GroundTruthTracker>,
+    health: Arc<Mutex<HealthTracker>>,
+    status_interval: Option<Duration>,
+    max_decode_errors: Option<u32>,
+    tee: Option<Tee>,
+    quaternion_convention: QuaternionConvention,
+    latency: LatencyTracker,
+    output_interval_us: Option<u64>,
+    next_output_us: Option<u64>,
+    trajectory: Option<TrajectoryTracker>,
+    trajectory_output: Option<TumWriter>,
+    decode_errors: Arc<AtomicU64>,
+    calibration_runs: Vec<CalibrationRun>,
+    calibration_samples: Vec<ImuData>,
 }
 
 impl Consumer {
@@ -22,97 +106,917 @@ impl Consumer {
         let timeout = Duration::from_secs(timeout.into());
         Self {
             socket_path,
+            standby_socket_paths: Vec::new(),
+            keep_state_on_failover: false,
+            secondary_socket_path: None,
+            input_file: None,
+            input_jsonl: None,
             timeout,
             logger,
             motion_processor,
+            frame_reader: FrameReader::default(),
+            warn_limiter: WarnLimiter::new(DEFAULT_WARN_RATE_LIMIT),
+            timing_anomaly_factor: DEFAULT_TIMING_ANOMALY_FACTOR,
+            last_arrival: None,
+            arrival_dt_ema: None,
+            arrival_count: 0,
+            warmup_samples: 0,
+            processed_samples: 0,
+            log_gravity: false,
+            log_heading: false,
+            ground_truth: None,
+            health: Arc::default(),
+            status_interval: None,
+            max_decode_errors: None,
+            tee: None,
+            quaternion_convention: QuaternionConvention::default(),
+            latency: LatencyTracker::default(),
+            output_interval_us: None,
+            next_output_us: None,
+            trajectory: None,
+            trajectory_output: None,
+            decode_errors: Arc::default(),
+            calibration_runs: Vec::new(),
+            calibration_samples: Vec::new(),
+        }
+    }
+
+    /// Publishers to fail over to, in order, when the connection drops or the
+    /// one before does not accept it. The list is cycled, starting over at
+    /// the primary socket, until none of them accepts a connection. Not used
+    /// when fusing a second stream.
+    pub fn set_standby_socket_paths(&mut self, socket_paths: Vec<PathBuf>) {
+        self.standby_socket_paths = socket_paths;
+    }
+
+    /// Carries the motion state over to the next publisher on failover,
+    /// instead of starting the estimate over. Only sensible if the
+    /// publishers observe the same sensor.
+    pub fn set_keep_state_on_failover(&mut self, keep_state: bool) {
+        self.keep_state_on_failover = keep_state;
+    }
+
+    /// Connects to a second, redundant IMU stream whose samples are averaged
+    /// with the primary ones before processing.
+    pub fn set_secondary_socket_path(&mut self, socket_path: Option<PathBuf>) {
+        self.secondary_socket_path = socket_path;
+    }
+
+    /// Reads frames recorded in `path` instead of connecting to the socket,
+    /// finishing at the end of the file.
+    pub fn set_input_file(&mut self, path: Option<PathBuf>) {
+        self.input_file = path;
+    }
+
+    /// Reads samples from JSON lines in `path` instead of connecting to the
+    /// socket, one `ImuData` object with the proto field names per line.
+    pub fn set_input_jsonl(&mut self, path: Option<PathBuf>) {
+        self.input_jsonl = path;
+    }
+
+    /// Arrival intervals longer or shorter than the running average by more
+    /// than this factor are logged as timing anomalies.
+    pub fn set_timing_anomaly_factor(&mut self, factor: f32) {
+        self.timing_anomaly_factor = factor;
+    }
+
+    /// Identical warnings beyond this many per second are counted instead of
+    /// logged and summarized later, 0 logs every one.
+    pub fn set_warn_rate_limit(&mut self, per_second: u32) {
+        self.warn_limiter = WarnLimiter::new(per_second);
+    }
+
+    /// Byte order of the frame length prefix, must match the publisher.
+    pub fn set_length_endianness(&mut self, endianness: Endianness) {
+        self.frame_reader.set_endianness(endianness);
+    }
+
+    /// Encoding of the frame bodies, must match the publisher.
+    pub fn set_wire_format(&mut self, format: WireFormat) {
+        self.frame_reader.set_wire_format(format);
+    }
+
+    /// Expects a marker in front of every frame, protocol version 2. After
+    /// corrupt data the stream picks up again at the next marker.
+    pub fn set_frame_magic(&mut self, frame_magic: bool) {
+        self.frame_reader.set_frame_magic(frame_magic);
+    }
+
+    /// Compression accepted when the publisher offers it in the handshake,
+    /// see `negotiate`. Plain frames are read either way.
+    pub fn set_compression(&mut self, compression: Compression) {
+        self.frame_reader.set_compression(compression);
+    }
+
+    /// Processes the first `samples` samples without reporting the motion
+    /// state, letting the filters settle from their initial guesses.
+    pub fn set_warmup_samples(&mut self, samples: u32) {
+        self.warmup_samples = samples;
+    }
+
+    /// Also reports where the estimate thinks down is in the body frame,
+    /// e.g. for leveling.
+    pub fn set_log_gravity(&mut self, log_gravity: bool) {
+        self.log_gravity = log_gravity;
+    }
+
+    /// Also reports the fused heading and, for samples with a mag reading,
+    /// the tilt-compensated magnetic heading to compare it against.
+    pub fn set_log_heading(&mut self, log_heading: bool) {
+        self.log_heading = log_heading;
+    }
+
+    /// Tracks the RMS error of the estimate against the ground truth of
+    /// synthetic streams, logged periodically and summarized at the end.
+    pub fn set_compare_ground_truth(&mut self, compare: bool) {
+        self.ground_truth = compare.then(GroundTruthTracker::default);
+    }
+
+    /// Accuracy against the ground truth so far, `None` unless comparing.
+    pub fn accuracy_summary(&self) -> Option<AccuracySummary> {
+        self.ground_truth.as_ref().map(GroundTruthTracker::summary)
+    }
+
+    /// Logs a recap of the run once the input ends cleanly: samples, distance
+    /// traveled, max speed, final orientation and error counts.
+    pub fn set_print_summary(&mut self, print: bool) {
+        self.trajectory = print.then(TrajectoryTracker::default);
+    }
+
+    /// Writes the state after every processed sample to `writer`, flushed
+    /// when the run ends. A failed write stops the export, not the run.
+    pub fn set_trajectory_output(&mut self, writer: Option<TumWriter>) {
+        self.trajectory_output = writer;
+    }
+
+    /// Recap of the run so far, `None` unless printing it.
+    pub fn trajectory_summary(&self) -> Option<TrajectorySummary> {
+        let decode_errors = self.decode_errors.load(Ordering::Relaxed);
+        self.trajectory
+            .as_ref()
+            .map(|tracker| tracker.summary(decode_errors))
+    }
+
+    /// Logs a status line with the connection state, sample count and
+    /// motion summary this often while running. On Unix it is also logged
+    /// on SIGUSR1, regardless of this setting.
+    pub fn set_status_interval(&mut self, interval: Option<Duration>) {
+        self.status_interval = interval;
+    }
+
+    /// Current connection state, sample count and motion summary.
+    pub fn status_report(&self) -> HealthReport {
+        self.health.lock().unwrap().report()
+    }
+
+    /// Gives up on a stream once this many frames in a row failed to decode,
+    /// as it is likely incompatible. None skips undecodable frames forever.
+    pub fn set_max_decode_errors(&mut self, max_errors: Option<u32>) {
+        self.max_decode_errors = max_errors;
+    }
+
+    /// Forwards every received frame unchanged to the consumers connected to
+    /// `tee`, before processing it. With a secondary stream only the
+    /// primary one is forwarded.
+    pub fn set_tee(&mut self, tee: Option<Tee>) {
+        self.tee = tee;
+    }
+
+    /// Layout of the logged orientation quaternion, for toolchains expecting
+    /// JPL instead of Hamilton quaternions.
+    pub fn set_quaternion_convention(&mut self, convention: QuaternionConvention) {
+        self.quaternion_convention = convention;
+    }
+
+    /// Transport latency of the frames stamped with their send time so far,
+    /// `None` if the publisher does not stamp them. Only meaningful with
+    /// publisher and consumer on the same host.
+    pub fn latency_summary(&self) -> Option<LatencySummary> {
+        self.latency.summary()
+    }
+
+    /// Reports the motion state at most `hz` times per second of sample time,
+    /// using the latest estimate, while every sample is still processed.
+    /// None reports after every sample.
+    pub fn set_output_rate(&mut self, hz: Option<f32>) {
+        self.output_interval_us = hz.map(|hz| (1e6 / hz).round() as u64);
+        self.next_output_us = None;
+    }
+
+    /// Frames announcing a larger length are treated as a corrupt stream.
+    pub fn set_max_frame_size(&mut self, bytes: usize) {
+        self.frame_reader.set_max_frame_size(bytes);
+    }
+
+    /// Pre-sizes the frame buffer, which is reused across frames and only grows.
+    pub fn set_read_buffer_hint(&mut self, bytes: usize) {
+        self.frame_reader.reserve(bytes);
+    }
+
+    /// Keeps every sample of the run in memory and estimates these
+    /// calibrations from them once the input ends cleanly, replacing the
+    /// corresponding part of the motion processor's calibration.
+    pub fn set_calibration_runs(&mut self, runs: Vec<CalibrationRun>) {
+        self.calibration_runs = runs;
+        self.calibration_samples.clear();
+    }
+
+    pub fn motion_processor_mut(&mut self) -> &mut MotionProcessor {
+        &mut self.motion_processor
+    }
+
+    pub async fn run(&mut self) -> Result<(), ConsumerError> {
+        let reporter = self.spawn_status_reporter();
+        let result = self.connect_and_process().await;
+        reporter.abort();
+        if let Some(writer) = self.trajectory_output.as_mut()
+            && let Err(e) = writer.flush()
+        {
+            error!(self.logger, "Failed to write trajectory"; "error" => %e);
+        }
+        if result.is_ok() {
+            self.log_trajectory_summary();
+            self.apply_calibration_runs();
+        }
+        result
+    }
+
+    async fn connect_and_process(&mut self) -> Result<(), ConsumerError> {
+        if let Some(input_file) = self.input_file.clone() {
+            info!(self.logger, "Reading recorded frames"; "path" => %input_file.display());
+            let file = tokio::fs::File::open(&input_file).await.map_err(|e| {
+                error!(self.logger, "Failed to open input file"; "path" => %input_file.display(), "error" => %e);
+                ConsumerError::Connect(e)
+            })?;
+            return self.run_single(file).await;
+        }
+        if let Some(input_jsonl) = self.input_jsonl.clone() {
+            info!(self.logger, "Reading JSON-lines samples"; "path" => %input_jsonl.display());
+            let file = tokio::fs::File::open(&input_jsonl).await.map_err(|e| {
+                error!(self.logger, "Failed to open JSON-lines input"; "path" => %input_jsonl.display(), "error" => %e);
+                ConsumerError::Connect(e)
+            })?;
+            return self.run_jsonl(file).await;
+        }
+
+        if self.secondary_socket_path.is_none() && !self.standby_socket_paths.is_empty() {
+            return self.run_with_failover().await;
+        }
+
+        let stream = self.connect(&self.socket_path).await?;
+
+        match self.secondary_socket_path.clone() {
+            Some(secondary_path) => {
+                // Connect both before negotiating, a publisher may hold its
+                // offer until the other one is connected too
+                let secondary = self.connect(&secondary_path).await?;
+                let (stream, secondary) =
+                    tokio::join!(self.negotiate(stream), self.negotiate(secondary));
+                self.run_fused([stream?, secondary?]).await
+            }
+            None => {
+                let stream = self.negotiate(stream).await?;
+                self.run_single(stream).await
+            }
         }
     }
 
-    pub async fn run(&mut self) -> std::io::Result<()> {
-        info!(self.logger, "Attempting to connect to socket"; "path" => %self.socket_path.display(), "timeout" => ?self.timeout);
+    /// Moves on to the next publisher whenever the current connection ends
+    /// or fails, until a whole round through the list found none accepting.
+    /// Connections that end within a second back off exponentially before
+    /// the next attempt, and after `FAILOVER_MAX_SHORT_SESSIONS` of them in a
+    /// row the publishers are given up on.
+    async fn run_with_failover(&mut self) -> Result<(), ConsumerError> {
+        let socket_paths: Vec<PathBuf> = std::iter::once(self.socket_path.clone())
+            .chain(self.standby_socket_paths.iter().cloned())
+            .collect();
+        let mut failed_connects = 0;
+        let mut connected_before = false;
+        let mut short_sessions = 0;
+        let mut backoff = FAILOVER_BACKOFF_INITIAL;
+
+        for socket_path in socket_paths.iter().cycle() {
+            let stream = match self.connect(socket_path).await {
+                Ok(stream) => stream,
+                Err(e) => {
+                    failed_connects += 1;
+                    if failed_connects >= socket_paths.len() {
+                        error!(self.logger, "No publisher left to fail over to"; "paths" => socket_paths.len());
+                        return Err(e);
+                    }
+                    continue;
+                }
+            };
+            failed_connects = 0;
+
+            if connected_before {
+                if self.keep_state_on_failover {
+                    self.motion_processor.restart_stream();
+                } else {
+                    self.motion_processor.reset_state();
+                }
+                info!(self.logger, "Failed over to standby publisher"; "path" => %socket_path.display(), "kept_state" => self.keep_state_on_failover);
+            }
+            connected_before = true;
+
+            let session_start = Instant::now();
+            let result = match self.negotiate(stream).await {
+                Ok(stream) => self.run_single(stream).await,
+                Err(e) => Err(e),
+            };
+            if let Err(e) = result {
+                warn!(self.logger, "Lost publisher, failing over"; "path" => %socket_path.display(), "error" => %e);
+            } else {
+                warn!(self.logger, "Publisher closed the connection, failing over"; "path" => %socket_path.display());
+            }
 
-        let stream = match timeout(self.timeout, UnixStream::connect(&self.socket_path)).await {
+            if session_start.elapsed() >= FAILOVER_STABLE_SESSION {
+                short_sessions = 0;
+                backoff = FAILOVER_BACKOFF_INITIAL;
+                continue;
+            }
+            short_sessions += 1;
+            if short_sessions >= FAILOVER_MAX_SHORT_SESSIONS {
+                error!(self.logger, "Publishers keep dropping the connection, giving up"; "sessions" => short_sessions);
+                return Err(ConsumerError::UnstablePublishers {
+                    sessions: short_sessions,
+                });
+            }
+            debug!(self.logger, "Backing off before failing over"; "backoff" => ?backoff);
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(FAILOVER_BACKOFF_MAX);
+        }
+        unreachable!("cycling a non-empty list never ends")
+    }
+
+    /// Logs the status report whenever the `StatusTrigger` fires, until
+    /// aborted.
+    fn spawn_status_reporter(&self) -> tokio::task::JoinHandle<()> {
+        let health = self.health.clone();
+        let logger = self.logger.clone();
+        let mut trigger = StatusTrigger::new(self.status_interval);
+
+        tokio::spawn(async move {
+            loop {
+                trigger.wait().await;
+                let report = health.lock().unwrap().report();
+                info!(logger, "{}", report);
+            }
+        })
+    }
+
+    fn set_connection(&self, connection: ConnectionState) {
+        self.health.lock().unwrap().set_connection(connection);
+    }
+
+    async fn connect(&self, socket_path: &Path) -> Result<ClientStream, ConsumerError> {
+        info!(self.logger, "Attempting to connect to socket"; "path" => %socket_path.display(), "timeout" => ?self.timeout);
+
+        match timeout(self.timeout, transport::connect(socket_path)).await {
             Ok(Ok(stream)) => {
-                info!(self.logger, "Successfully connected to socket"; "path" => %self.socket_path.display());
-                stream
+                info!(self.logger, "Successfully connected to socket"; "path" => %socket_path.display());
+                Ok(stream)
             }
             Ok(Err(e)) => {
-                error!(self.logger, "Failed to connect to socket"; "path" => %self.socket_path.display(), "error" => %e);
-                return Err(e);
+                error!(self.logger, "Failed to connect to socket"; "path" => %socket_path.display(), "error" => %e);
+                Err(ConsumerError::Connect(e))
             }
             Err(_) => {
-                error!(self.logger, "Connection attempt timed out"; "path" => %self.socket_path.display(), "timeout" => ?self.timeout);
-                return Err(std::io::Error::new(
-                    std::io::ErrorKind::TimedOut,
-                    "connection timed out",
-                ));
+                error!(self.logger, "Connection attempt timed out"; "path" => %socket_path.display(), "timeout" => ?self.timeout);
+                Err(ConsumerError::ConnectTimeout)
             }
-        };
+        }
+    }
+
+    /// Answers the compression offer a publisher sends right after accepting,
+    /// if any, with the compression set by `set_compression`. Returns the
+    /// stream to pass on to `run_single`, in-process setups call this on
+    /// their end of the connection first.
+    pub async fn negotiate<S: AsyncRead + AsyncWrite + Unpin>(
+        &self,
+        stream: S,
+    ) -> Result<Negotiated<S>, ConsumerError> {
+        let (compression, stream) =
+            handshake::answer_offer(stream, self.frame_reader.compression())
+                .await
+                .map_err(|e| {
+                    error!(self.logger, "Compression handshake failed"; "error" => %e);
+                    ConsumerError::Read(e)
+                })?;
+        if compression != Compression::None {
+            info!(self.logger, "Negotiated compression"; "compression" => ?compression);
+        }
+        Ok(stream)
+    }
 
+    /// Processes samples from an already connected stream until it ends.
+    /// `run` calls this for its connection or input file, in-process setups
+    /// can pass any reader, e.g. one end of `transport::memory::pair`.
+    pub async fn run_single<R: AsyncRead + Unpin>(
+        &mut self,
+        stream: R,
+    ) -> Result<(), ConsumerError> {
         let mut reader = BufReader::new(stream);
-        let mut buffer = Vec::new();
+        self.set_connection(ConnectionState::Connected);
+
+        let result = loop {
+            let sample = Self::next_sample(
+                &mut reader,
+                &mut self.frame_reader,
+                &mut self.warn_limiter,
+                self.max_decode_errors,
+                &self.decode_errors,
+                self.tee.as_ref(),
+                &self.logger,
+            )
+            .await;
+            match sample {
+                Ok(Some(imu_data)) => self.handle_sample(&imu_data),
+                Ok(None) => break Ok(()),
+                Err(e) => break Err(e),
+            }
+        };
+        self.set_connection(ConnectionState::Disconnected);
+        log_suppressed(&mut self.warn_limiter, &self.logger);
+        self.log_accuracy_summary();
+        self.log_latency_summary();
+        result
+    }
+
+    /// Processes one JSON-encoded `ImuData` per line until the input ends.
+    /// Fields missing from a line default to zero, malformed lines are
+    /// skipped with a warning.
+    pub async fn run_jsonl<R: AsyncRead + Unpin>(&mut self, input: R) -> Result<(), ConsumerError> {
+        let mut lines = BufReader::new(input).lines();
+        self.set_connection(ConnectionState::Connected);
+
+        let result = loop {
+            match lines.next_line().await {
+                Ok(Some(line)) if line.trim().is_empty() => {}
+                Ok(Some(line)) => match serde_json::from_str::<ImuData>(&line) {
+                    Ok(imu_data) => self.handle_sample(&imu_data),
+                    Err(e) => {
+                        limited_warn!(self.warn_limiter, self.logger, "Skipping malformed JSON line"; "error" => %e);
+                    }
+                },
+                Ok(None) => break Ok(()),
+                Err(e) => {
+                    error!(self.logger, "Failed to read JSON-lines input"; "error" => %e);
+                    break Err(ConsumerError::Read(e));
+                }
+            }
+        };
+        self.set_connection(ConnectionState::Disconnected);
+        log_suppressed(&mut self.warn_limiter, &self.logger);
+        self.log_accuracy_summary();
+        self.log_latency_summary();
+        result
+    }
+
+    /// Fuses two redundant streams until both ended. A stream that fails
+    /// only ends its half, the run fails only if no stream ended cleanly.
+    async fn run_fused<S: AsyncRead + Unpin + Send + 'static>(
+        &mut self,
+        streams: [S; 2],
+    ) -> Result<(), ConsumerError> {
+        let (sender, mut receiver) = mpsc::channel(FUSED_QUEUE_CAPACITY);
+        self.set_connection(ConnectionState::Connected);
+
+        let readers: Vec<_> = streams
+            .into_iter()
+            .enumerate()
+            .map(|(index, stream)| {
+                let sender = sender.clone();
+                let logger = self.logger.new(o!("stream" => index));
+                let mut frame_reader = self.frame_reader.clone();
+                let mut warn_limiter = self.warn_limiter.clone();
+                let max_decode_errors = self.max_decode_errors;
+                let decode_errors = self.decode_errors.clone();
+                let tee = self.tee.clone().filter(|_| index == 0);
+
+                tokio::spawn(async move {
+                    let mut reader = BufReader::new(stream);
+                    let result = loop {
+                        let sample = Self::next_sample(
+                            &mut reader,
+                            &mut frame_reader,
+                            &mut warn_limiter,
+                            max_decode_errors,
+                            &decode_errors,
+                            tee.as_ref(),
+                            &logger,
+                        )
+                        .await;
+                        match sample {
+                            Ok(Some(sample)) => {
+                                if sender.send((index, Some(sample))).await.is_err() {
+                                    break Ok(());
+                                }
+                            }
+                            Ok(None) => break Ok(()),
+                            Err(e) => break Err(e),
+                        }
+                    };
+                    log_suppressed(&mut warn_limiter, &logger);
+                    let _ = sender.send((index, None)).await;
+                    result
+                })
+            })
+            .collect();
+        drop(sender);
+
+        let mut aligner = StreamAligner::new();
+        while let Some((index, sample)) = receiver.recv().await {
+            let ready = match sample {
+                Some(sample) => aligner.push(index, sample),
+                None => {
+                    warn!(self.logger, "IMU stream ended"; "stream" => index);
+                    aligner.close(index)
+                }
+            };
+            if let Some(imu_data) = ready {
+                self.handle_sample(&imu_data);
+            }
+        }
+        self.set_connection(ConnectionState::Disconnected);
+        log_suppressed(&mut self.warn_limiter, &self.logger);
+        self.log_accuracy_summary();
+        self.log_latency_summary();
+
+        let mut ended_cleanly = false;
+        let mut first_error = None;
+        for (index, reader) in readers.into_iter().enumerate() {
+            let result = reader
+                .await
+                .map_err(|e| ConsumerError::Read(std::io::Error::other(e)))
+                .and_then(|result| result);
+            match result {
+                Ok(()) => ended_cleanly = true,
+                Err(e) => {
+                    warn!(self.logger, "IMU stream failed"; "stream" => index, "error" => %e);
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+        match first_error {
+            Some(e) if !ended_cleanly => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns the next decodable sample, or `None` once the stream ends cleanly.
+    /// Undecodable frames are skipped, the framing itself is still intact,
+    /// unless `max_decode_errors` of them follow each other.
+    async fn next_sample<R: AsyncRead + Unpin>(
+        reader: &mut BufReader<R>,
+        frame_reader: &mut FrameReader,
+        warn_limiter: &mut WarnLimiter,
+        max_decode_errors: Option<u32>,
+        total_decode_errors: &AtomicU64,
+        tee: Option<&Tee>,
+        logger: &Logger,
+    ) -> Result<Option<ImuData>, ConsumerError> {
+        let mut decode_errors = 0;
+        loop {
+            match Self::read_sample(reader, frame_reader, warn_limiter, tee, logger).await {
+                Err(ConsumerError::Decode(e)) => {
+                    total_decode_errors.fetch_add(1, Ordering::Relaxed);
+                    decode_errors += 1;
+                    if max_decode_errors.is_some_and(|max| decode_errors >= max) {
+                        error!(logger, "Too many consecutive decode failures, the stream is incompatible or corrupt"; "count" => decode_errors, "error" => %e);
+                        return Err(ConsumerError::TooManyDecodeErrors {
+                            count: decode_errors,
+                        });
+                    }
+                    limited_warn!(warn_limiter, logger, "Failed to decode ImuData"; "error" => %e);
+                }
+                result => return result,
+            }
+        }
+    }
 
+    /// Reads the next non-empty frame and decodes it, `None` on a clean EOF.
+    /// Every complete frame goes to `tee` first, even if it fails to decode.
+    async fn read_sample<R: AsyncRead + Unpin>(
+        reader: &mut BufReader<R>,
+        frame_reader: &mut FrameReader,
+        warn_limiter: &mut WarnLimiter,
+        tee: Option<&Tee>,
+        logger: &Logger,
+    ) -> Result<Option<ImuData>, ConsumerError> {
+        let format = frame_reader.wire_format();
         loop {
-            let message_len = match reader.read_u32().await {
-                Ok(len) => len as usize,
-                Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
-                    info!(self.logger, "Connection closed cleanly (EOF)");
-                    break Ok(());
+            let frame = match frame_reader.read_body(reader).await {
+                Ok(Some(frame)) => frame,
+                Ok(None) => {
+                    info!(logger, "Connection closed cleanly (EOF)");
+                    return Ok(None);
                 }
                 Err(e) => {
-                    error!(self.logger, "Failed to read message length"; "error" => %e);
-                    break Err(e);
+                    error!(logger, "Failed to read frame"; "error" => %e);
+                    let too_large = e.get_ref().and_then(|e| e.downcast_ref::<FrameTooLarge>());
+                    return Err(match too_large {
+                        Some(&FrameTooLarge { len, max }) => {
+                            ConsumerError::OversizedFrame { len, max }
+                        }
+                        None => ConsumerError::Read(e),
+                    });
                 }
             };
 
-            if message_len == 0 {
-                warn!(self.logger, "Received message with length 0, skipping.");
+            if let Some(tee) = tee {
+                tee.forward(frame);
+            }
+
+            let decoded = (!frame.is_empty()).then(|| decode_body(frame, format));
+            let skipped = frame_reader.take_skipped_bytes();
+            if skipped > 0 {
+                limited_warn!(warn_limiter, logger, "Resynchronized on the next frame marker"; "skipped_bytes" => skipped);
+            }
+
+            let Some(decoded) = decoded else {
+                limited_warn!(
+                    warn_limiter,
+                    logger,
+                    "Received message with length 0, skipping."
+                );
                 continue;
+            };
+            return decoded.map(Some).map_err(ConsumerError::Decode);
+        }
+    }
+
+    /// Tracks an EMA of the inter-arrival time and warns about hiccups and
+    /// bursts once it has settled. Recordings carry no arrival timing.
+    fn track_arrival(&mut self) {
+        if self.is_recording() {
+            return;
+        }
+
+        let now = Instant::now();
+        let Some(last_arrival) = self.last_arrival.replace(now) else {
+            return;
+        };
+        let dt = now.duration_since(last_arrival).as_secs_f32();
+        self.arrival_count = self.arrival_count.saturating_add(1);
+
+        let Some(ema) = self.arrival_dt_ema else {
+            self.arrival_dt_ema = Some(dt);
+            return;
+        };
+        if self.arrival_count > ARRIVAL_EMA_WARMUP
+            && (dt > ema * self.timing_anomaly_factor || dt * self.timing_anomaly_factor < ema)
+        {
+            limited_warn!(self.warn_limiter, self.logger, "Sample arrival timing anomaly"; "dt_ms" => dt * 1000.0, "average_dt_ms" => ema * 1000.0);
+        }
+        self.arrival_dt_ema = Some(ema + ARRIVAL_EMA_ALPHA * (dt - ema));
+    }
+
+    /// Measures how long a frame stamped with its send time took to get
+    /// here, including the wait for its partner when fusing two streams.
+    /// Recorded send times say nothing about the current transport.
+    fn track_latency(&mut self, imu_data: &ImuData) {
+        let Some(send_time_us) = imu_data.send_time_us.filter(|_| !self.is_recording()) else {
+            return;
+        };
+
+        if self.latency.record(send_time_us, unix_time_us()).is_none() {
+            limited_warn!(self.warn_limiter, self.logger, "Frame stamped after it was received, publisher and consumer clocks disagree"; "skewed_frames" => self.latency.clock_skewed());
+            return;
+        }
+        if let Some(summary) = self.latency.summary()
+            && summary.samples.is_multiple_of(LATENCY_LOG_INTERVAL)
+        {
+            log_latency(&self.logger, "Running transport latency", &summary);
+        }
+    }
+
+    /// Whether the state at this sample should be reported under the output
+    /// rate. Reports follow a fixed grid so the rate holds whatever the
+    /// input rate, the grid restarts after gaps and timestamps jumping back.
+    fn output_due(&mut self, imu_data: &ImuData) -> bool {
+        let Some(interval) = self.output_interval_us else {
+            return true;
+        };
+
+        let time_us = sample_time_us(imu_data);
+        match self.next_output_us {
+            Some(next) if time_us < next && next - time_us <= interval => false,
+            Some(next) if time_us < next + interval && time_us >= next => {
+                self.next_output_us = Some(next + interval);
+                true
+            }
+            _ => {
+                self.next_output_us = Some(time_us + interval);
+                true
             }
+        }
+    }
 
-            buffer.resize(message_len, 0);
-            if let Err(e) = reader.read_exact(&mut buffer).await {
-                error!(self.logger, "Failed to read message body"; "expected_len" => message_len, "error" => %e);
-                break Err(e);
+    fn is_recording(&self) -> bool {
+        self.input_file.is_some() || self.input_jsonl.is_some()
+    }
+
+    fn log_latency_summary(&self) {
+        if let Some(summary) = self.latency_summary() {
+            log_latency(&self.logger, "Transport latency", &summary);
+        }
+    }
+
+    fn apply_calibration_runs(&mut self) {
+        let samples = std::mem::take(&mut self.calibration_samples);
+        for run in self.calibration_runs.clone() {
+            match run {
+                CalibrationRun::Mag => {
+                    self.motion_processor.calibrate_mag_hard_iron(&samples);
+                    self.motion_processor.calibrate_mag_soft_iron(&samples);
+                }
+                CalibrationRun::AccScale => {
+                    self.motion_processor.calibrate_acc_scale_from_run(&samples)
+                }
+                CalibrationRun::GyroScale { axis, angle } => self
+                    .motion_processor
+                    .calibrate_gyro_scale(&samples, axis, angle),
             }
+            info!(self.logger, "Calibrated from the recorded run"; "run" => ?run, "samples" => samples.len(), "calibration" => ?self.motion_processor.calibration());
+        }
+    }
+
+    fn log_trajectory_summary(&self) {
+        let Some(summary) = self.trajectory_summary() else {
+            return;
+        };
+        let angle_units = self.motion_processor.angle_units();
+        let (roll, pitch, yaw) = summary.final_euler;
+        info!(self.logger, "Run summary"; "samples" => summary.samples, "distance_m" => summary.distance, "max_speed_mps" => summary.max_speed, "final_roll" => angle_units.from_radians(roll), "final_pitch" => angle_units.from_radians(pitch), "final_yaw" => angle_units.from_radians(yaw), "angle_units" => angle_units.label(), "decode_errors" => summary.decode_errors, "skipped_updates" => summary.skipped_updates);
+    }
+
+    fn log_accuracy_summary(&self) {
+        let Some(summary) = self.accuracy_summary() else {
+            return;
+        };
+        if summary.samples == 0 {
+            warn!(
+                self.logger,
+                "No samples carried ground truth to compare against"
+            );
+            return;
+        }
+        info!(self.logger, "Ground truth comparison"; "samples" => summary.samples, "orientation_rms_deg" => ?summary.orientation_rms.map(f32::to_degrees), "position_rms_m" => ?summary.position_rms);
+    }
+
+    fn handle_sample(&mut self, imu_data: &ImuData) {
+        if !self.calibration_runs.is_empty() {
+            self.calibration_samples.push(*imu_data);
+        }
+        self.track_arrival();
+        self.track_latency(imu_data);
+        let verbose = self.motion_processor.verbose_motion();
+        let angle_units = self.motion_processor.angle_units();
+        let output_due = self.output_due(imu_data);
+        let outcome = self.motion_processor.process(imu_data);
+
+        match outcome.skipped_reason {
+            Some(SkipReason::ExcessiveGyroDelta { dt }) => {
+                limited_warn!(self.warn_limiter, self.logger, "Skipped orientation update due to excessive time delta"; "dt_gyro" => dt);
+            }
+            Some(SkipReason::ExcessiveAccDelta { dt }) => {
+                limited_warn!(self.warn_limiter, self.logger, "Skipped velocity/position update due to excessive time delta"; "dt_acc" => dt);
+            }
+            Some(SkipReason::SmallAngle { angle }) if verbose => {
+                debug!(self.logger, "Skipped orientation update due to small angle"; "angle" => angle_units.from_radians(angle), "angle_units" => angle_units.label());
+            }
+            Some(SkipReason::SmallAngle { .. } | SkipReason::AwaitingMotion) | None => {}
+        }
 
-            match ImuData::decode(buffer.as_slice()) {
-                Ok(imu_data) => {
-                    let state = self.motion_processor.process(&imu_data);
+        if verbose && !outcome.orientation_updated && !outcome.position_updated {
+            debug!(self.logger, "Motion state unchanged by sample"; "timestamp_acc" => imu_data.timestamp_acc);
+        }
+
+        let state = outcome.state;
+        self.health.lock().unwrap().record_sample(state);
+        if let Some(tracker) = self.trajectory.as_mut() {
+            let skipped_update = matches!(
+                outcome.skipped_reason,
+                Some(SkipReason::ExcessiveGyroDelta { .. } | SkipReason::ExcessiveAccDelta { .. })
+            );
+            tracker.record(sample_time_us(imu_data), state, skipped_update);
+        }
+        if let Some(writer) = self.trajectory_output.as_mut()
+            && let Err(e) = writer.write(sample_time_us(imu_data), state)
+        {
+            error!(self.logger, "Failed to write trajectory, stopping the export"; "error" => %e);
+            self.trajectory_output = None;
+        }
+        if let Some(tracker) = self.ground_truth.as_mut()
+            && tracker.update(imu_data, state)
+        {
+            let summary = tracker.summary();
+            if summary.samples.is_multiple_of(GROUND_TRUTH_LOG_INTERVAL) {
+                info!(self.logger, "Running error against ground truth"; "samples" => summary.samples, "orientation_rms_deg" => ?summary.orientation_rms.map(f32::to_degrees), "position_rms_m" => ?summary.position_rms);
+            }
+        }
 
+        self.processed_samples = self.processed_samples.saturating_add(1);
+        if self.processed_samples <= self.warmup_samples {
+            if self.processed_samples == self.warmup_samples {
+                info!(self.logger, "Warmup complete, reporting motion state"; "samples" => self.warmup_samples);
+            }
+        } else if output_due {
+            let orientation = self
+                .quaternion_convention
+                .components(&state.smoothed_orientation());
+            let angular_velocity = state.angular_velocity.map(|w| angle_units.from_radians(w));
+            info!(
+                self.logger,
+                "Pos: [{:+.3},{:+.3},{:+.3}]m | Vel: [{:+.3},{:+.3},{:+.3}]m/s | Orient: [{:+.3},{:+.3},{:+.3},{:+.3}]{} | AngVel: [{:+.3},{:+.3},{:+.3}]{}/s | LinAcc: [{:+.3},{:+.3},{:+.3}]m/s^2 | Jerk: {:.3}m/s^3",
+                state.position.x,
+                state.position.y,
+                state.position.z,
+                state.velocity.x,
+                state.velocity.y,
+                state.velocity.z,
+                orientation[0],
+                orientation[1],
+                orientation[2],
+                orientation[3],
+                self.quaternion_convention.label(),
+                angular_velocity.x,
+                angular_velocity.y,
+                angular_velocity.z,
+                angle_units.label(),
+                state.linear_acceleration.x,
+                state.linear_acceleration.y,
+                state.linear_acceleration.z,
+                state.jerk.norm()
+            );
+            if self.log_gravity {
+                let gravity = state.gravity_direction_body();
+                info!(
+                    self.logger,
+                    "Gravity (body): [{:+.3},{:+.3},{:+.3}]", gravity.x, gravity.y, gravity.z
+                );
+            }
+            if self.log_heading {
+                let heading = self.motion_processor.heading();
+                if imu_data.timestamp_mag > 0 {
+                    let mag_heading = self
+                        .motion_processor
+                        .tilt_compensated_heading_from_mag(imu_data);
                     info!(
                         self.logger,
-                        "Pos: [{:+.3},{:+.3},{:+.3}]m | Vel: [{:+.3},{:+.3},{:+.3}]m/s | Orient: [{:+.3},{:+.3},{:+.3},{:+.3}]quat",
-                        state.position.x,
-                        state.position.y,
-                        state.position.z,
-                        state.velocity.x,
-                        state.velocity.y,
-                        state.velocity.z,
-                        state.orientation.scalar(),
-                        state.orientation.vector().x,
-                        state.orientation.vector().y,
-                        state.orientation.vector().z
+                        "Heading: {:.1}deg | Mag heading: {:.1}deg", heading, mag_heading
                     );
-                }
-                Err(e) => {
-                    warn!(self.logger, "Failed to decode ImuData"; "error" => %e, "bytes_read" => message_len);
+                } else {
+                    info!(self.logger, "Heading: {:.1}deg", heading);
                 }
             }
         }
+
+        if self.motion_processor.step_detected() {
+            info!(self.logger, "Step detected"; "steps" => ?self.motion_processor.step_count());
+        }
+
+        for event in self.motion_processor.take_events() {
+            warn!(self.logger, "Motion event detected"; "event" => ?event);
+        }
+    }
+}
+
+/// Acc timestamp of a sample [us], from the millis if it carries no micros.
+fn sample_time_us(imu_data: &ImuData) -> u64 {
+    imu_data
+        .timestamp_acc_us
+        .unwrap_or(u64::from(imu_data.timestamp_acc) * 1000)
+}
+
+fn log_latency(logger: &Logger, message: &'static str, summary: &LatencySummary) {
+    let ms = |us: u64| us as f32 / 1000.0;
+    info!(logger, "{}", message; "samples" => summary.samples, "min_ms" => ms(summary.min_us), "mean_ms" => ms(summary.mean_us), "max_ms" => ms(summary.max_us), "histogram" => %summary, "clock_skewed" => summary.clock_skewed);
+}
+
+/// Summarizes the warnings suppressed since they were last logged.
+fn log_suppressed(warn_limiter: &mut WarnLimiter, logger: &Logger) {
+    for (message, occurrences) in warn_limiter.take_suppressed() {
+        warn!(logger, "Suppressed repeated warnings"; "message" => message, "occurrences" => occurrences);
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, unix))]
 mod tests {
     use super::*;
+    use crate::test_support::capturing_logger;
+    use common::codec::{DEFAULT_MAX_FRAME_SIZE, encode_frame, encode_frame_with_endianness};
     use common::prost::Message;
     use common::proto::ImuData;
     use common::slog::o;
+    use common::units::AngleUnits;
+    use common::wallclock::unix_time_us;
+    use nalgebra::Vector3;
     use std::fs;
     use std::io;
     use tokio::io::AsyncWriteExt;
-    use tokio::net::UnixListener;
+    use tokio::net::{UnixListener, UnixStream};
 
     fn setup_socket_path(test_name: &str) -> PathBuf {
         let socket_dir = PathBuf::from("/tmp");
@@ -150,7 +1054,7 @@ mod tests {
         socket_path: PathBuf,
         timeout_secs: u32,
         logger: common::slog::Logger,
-    ) -> tokio::task::JoinHandle<std::io::Result<()>> {
+    ) -> tokio::task::JoinHandle<Result<(), ConsumerError>> {
         tokio::spawn(async move {
             let mut consumer = Consumer::new(socket_path, timeout_secs, logger);
             consumer.run().await
@@ -171,16 +1075,14 @@ mod tests {
             y_mag: 0.02,
             z_mag: 0.03,
             timestamp_mag: timestamp,
+            ..Default::default()
         }
     }
 
     async fn send_message(stream: &mut UnixStream, msg: &ImuData) -> io::Result<()> {
         let mut buf = Vec::new();
-        msg.encode(&mut buf)
-            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
-        let len = buf.len() as u32;
+        encode_frame(msg, &mut buf);
 
-        stream.write_u32(len).await?;
         stream.write_all(&buf).await?;
         stream.flush().await?;
         Ok(())
@@ -289,6 +1191,376 @@ mod tests {
         cleanup_socket(&socket_path);
     }
 
+    #[tokio::test]
+    async fn test_gives_up_after_consecutive_decode_errors() {
+        fn garbage_frame(buf: &mut Vec<u8>) {
+            buf.extend_from_slice(&25u32.to_be_bytes());
+            buf.extend_from_slice(b"this is not protobuf data");
+        }
+        async fn run(frames: &[u8]) -> (Result<(), ConsumerError>, u64) {
+            let (mut publisher_end, consumer_end) = common::transport::memory::pair();
+            publisher_end.write_all(frames).await.unwrap();
+            drop(publisher_end);
+
+            let mut consumer = Consumer::new(PathBuf::from("/tmp/unused"), 5, create_logger());
+            consumer.set_max_decode_errors(Some(3));
+            let result = consumer.run_single(consumer_end).await;
+            (result, consumer.status_report().samples)
+        }
+
+        // Two bad frames at a time are tolerated, a good one resets the count
+        let mut tolerated = Vec::new();
+        for i in 0..2 {
+            garbage_frame(&mut tolerated);
+            garbage_frame(&mut tolerated);
+            encode_frame(&create_test_imu_data(100 + i * 10), &mut tolerated);
+        }
+        let (result, samples) = run(&tolerated).await;
+        assert!(result.is_ok(), "Got {:?}", result);
+        assert_eq!(samples, 2);
+
+        let mut corrupt = Vec::new();
+        encode_frame(&create_test_imu_data(100), &mut corrupt);
+        for _ in 0..4 {
+            garbage_frame(&mut corrupt);
+        }
+        encode_frame(&create_test_imu_data(110), &mut corrupt);
+        let (result, samples) = run(&corrupt).await;
+        assert!(
+            matches!(result, Err(ConsumerError::TooManyDecodeErrors { count: 3 })),
+            "Got {:?}",
+            result
+        );
+        assert_eq!(samples, 1, "Nothing after the third bad frame is processed");
+    }
+
+    #[tokio::test]
+    async fn test_jsonl_input_matches_framed_input() {
+        let samples: Vec<ImuData> = (0..20u32)
+            .map(|i| ImuData {
+                x_acc: 100.0 * (i as f32 * 0.3).sin(),
+                z_acc: 1000.0,
+                x_gyro: 5000 * (i % 4) as i32 - 7500,
+                timestamp_acc_us: Some(100_000 + i as u64 * 10_000),
+                true_x_pos: (i % 2 == 0).then_some(0.5),
+                ..create_test_imu_data(100 + i * 10)
+            })
+            .collect();
+
+        let mut framed = Consumer::new(PathBuf::from("/tmp/unused"), 5, create_logger());
+        let (mut publisher_end, consumer_end) = common::transport::memory::pair();
+        let mut buf = Vec::new();
+        for sample in &samples {
+            encode_frame(sample, &mut buf);
+        }
+        publisher_end.write_all(&buf).await.unwrap();
+        drop(publisher_end);
+        framed.run_single(consumer_end).await.unwrap();
+
+        // What a JSON-lines log of the stream holds, plus lines to skip
+        let mut jsonl = String::new();
+        for (i, sample) in samples.iter().enumerate() {
+            jsonl.push_str(&serde_json::to_string(sample).unwrap());
+            jsonl.push('\n');
+            if i == 10 {
+                jsonl.push_str("{\"x_acc\": \"not a number\"}\n\n");
+            }
+        }
+        let mut replayed = Consumer::new(PathBuf::from("/tmp/unused"), 5, create_logger());
+        replayed.run_jsonl(jsonl.as_bytes()).await.unwrap();
+
+        assert_eq!(replayed.status_report().samples, samples.len() as u64);
+        assert_eq!(
+            replayed.motion_processor.recent_states().last(),
+            framed.motion_processor.recent_states().last()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_latency_of_stamped_frames_is_small_and_positive() {
+        let (mut publisher_end, consumer_end) = common::transport::memory::pair();
+        let consumer_handle = tokio::spawn(async move {
+            let mut consumer = Consumer::new(PathBuf::from("/tmp/unused"), 5, create_logger());
+            consumer.run_single(consumer_end).await.map(|_| consumer)
+        });
+
+        let mut buf = Vec::new();
+        for i in 0..20u32 {
+            let sample = ImuData {
+                send_time_us: Some(unix_time_us()),
+                ..create_test_imu_data(100 + i * 10)
+            };
+            buf.clear();
+            encode_frame(&sample, &mut buf);
+            publisher_end.write_all(&buf).await.unwrap();
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+        // Unstamped frames are processed but not measured
+        buf.clear();
+        encode_frame(&create_test_imu_data(300), &mut buf);
+        publisher_end.write_all(&buf).await.unwrap();
+        drop(publisher_end);
+
+        let consumer = consumer_handle.await.unwrap().unwrap();
+        assert_eq!(consumer.status_report().samples, 21);
+        let summary = consumer.latency_summary().unwrap();
+        assert_eq!(summary.samples, 20);
+        assert_eq!(summary.clock_skewed, 0);
+        assert!(summary.min_us <= summary.mean_us && summary.mean_us <= summary.max_us);
+        assert!(summary.max_us < 1_000_000, "{:?}", summary);
+    }
+
+    #[tokio::test]
+    async fn test_fails_over_to_standby_publisher() {
+        let primary_path = setup_socket_path("failover_primary");
+        let standby_path = setup_socket_path("failover_standby");
+        let primary = UnixListener::bind(&primary_path).expect("Failed to bind primary");
+        let standby = UnixListener::bind(&standby_path).expect("Failed to bind standby");
+
+        let (logger, captured) = capturing_logger();
+        let mut consumer = Consumer::new(primary_path.clone(), 5, logger);
+        consumer.set_standby_socket_paths(vec![standby_path.clone()]);
+        let consumer_handle = tokio::spawn(async move {
+            let result = consumer.run().await;
+            (result, consumer)
+        });
+
+        // The primary dies after a few samples, listener and all
+        let (mut stream, _) = primary.accept().await.expect("Failed to accept");
+        for i in 0..5 {
+            send_message(&mut stream, &create_test_imu_data(100 + i * 10))
+                .await
+                .unwrap();
+        }
+        drop(stream);
+        drop(primary);
+        fs::remove_file(&primary_path).unwrap();
+
+        let (mut stream, _) = tokio::time::timeout(Duration::from_secs(5), standby.accept())
+            .await
+            .expect("Consumer did not fail over")
+            .expect("Failed to accept");
+        for i in 0..3 {
+            send_message(&mut stream, &create_test_imu_data(5000 + i * 10))
+                .await
+                .unwrap();
+        }
+        drop(stream);
+        drop(standby);
+
+        // With both gone there is nothing left to fail over to
+        let (result, consumer) = tokio::time::timeout(Duration::from_secs(5), consumer_handle)
+            .await
+            .expect("Consumer did not give up")
+            .unwrap();
+        assert!(
+            matches!(result, Err(ConsumerError::Connect(_))),
+            "{:?}",
+            result
+        );
+        assert_eq!(consumer.status_report().samples, 8);
+        let messages = captured.messages();
+        let failovers = messages
+            .iter()
+            .filter(|m| *m == "Failed over to standby publisher")
+            .count();
+        assert_eq!(failovers, 1);
+
+        cleanup_socket(&standby_path);
+    }
+
+    #[tokio::test]
+    async fn test_backs_off_and_gives_up_on_flapping_publishers() {
+        let paths = [
+            setup_socket_path("flapping_primary"),
+            setup_socket_path("flapping_standby"),
+        ];
+        // Both publishers accept and drop every connection right away
+        let listeners: Vec<_> = paths
+            .iter()
+            .map(|path| {
+                let listener = UnixListener::bind(path).expect("Failed to bind");
+                tokio::spawn(async move {
+                    while let Ok((stream, _)) = listener.accept().await {
+                        drop(stream);
+                    }
+                })
+            })
+            .collect();
+
+        let mut consumer = Consumer::new(paths[0].clone(), 5, create_logger());
+        consumer.set_standby_socket_paths(vec![paths[1].clone()]);
+        let start = Instant::now();
+        let result = tokio::time::timeout(Duration::from_secs(5), consumer.run())
+            .await
+            .expect("Consumer did not give up");
+
+        assert!(
+            matches!(
+                result,
+                Err(ConsumerError::UnstablePublishers {
+                    sessions: FAILOVER_MAX_SHORT_SESSIONS
+                })
+            ),
+            "{:?}",
+            result
+        );
+        // 50 + 100 + 200 + 400ms between the five sessions
+        assert!(
+            start.elapsed() >= Duration::from_millis(750),
+            "{:?}",
+            start.elapsed()
+        );
+
+        for listener in listeners {
+            listener.abort();
+        }
+        for path in &paths {
+            cleanup_socket(path);
+        }
+    }
+
+    #[test]
+    fn test_output_rate_is_independent_of_input_rate() {
+        const OUTPUT_HZ: f32 = 20.0;
+        const DURATION_MS: u32 = 2000;
+
+        for input_hz in [50, 100, 250, 1000] {
+            let (logger, captured) = capturing_logger();
+            let mut consumer = Consumer::new(PathBuf::from("/tmp/unused"), 5, logger);
+            consumer.set_output_rate(Some(OUTPUT_HZ));
+
+            let spacing_ms = 1000 / input_hz;
+            for i in 0..DURATION_MS / spacing_ms {
+                consumer.handle_sample(&create_test_imu_data(1000 + i * spacing_ms));
+            }
+
+            let reports = captured
+                .messages()
+                .iter()
+                .filter(|m| m.starts_with("Pos: ["))
+                .count();
+            assert_eq!(
+                consumer.status_report().samples,
+                (DURATION_MS / spacing_ms) as u64
+            );
+            assert_eq!(reports, 40, "at {}Hz input", input_hz);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_read_failures_map_to_variants() {
+        let logger = create_logger();
+        let mut frame_reader = FrameReader::default();
+        let mut warn_limiter = WarnLimiter::new(0);
+
+        let mut garbage = 25u32.to_be_bytes().to_vec();
+        garbage.extend_from_slice(b"this is not protobuf data");
+        let mut reader = BufReader::new(&garbage[..]);
+        let err = Consumer::read_sample(
+            &mut reader,
+            &mut frame_reader,
+            &mut warn_limiter,
+            None,
+            &logger,
+        )
+        .await
+        .expect_err("Garbage should not decode");
+        assert!(matches!(err, ConsumerError::Decode(_)), "Got {:?}", err);
+
+        let mut truncated = Vec::new();
+        encode_frame(&create_test_imu_data(100), &mut truncated);
+        truncated.truncate(truncated.len() - 3);
+        let mut reader = BufReader::new(&truncated[..]);
+        let err = Consumer::read_sample(
+            &mut reader,
+            &mut frame_reader,
+            &mut warn_limiter,
+            None,
+            &logger,
+        )
+        .await
+        .expect_err("Truncated body should fail");
+        assert!(matches!(err, ConsumerError::Read(_)), "Got {:?}", err);
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[tokio::test]
+    async fn test_consumer_fails_to_open_missing_input_file() {
+        let mut consumer = Consumer::new(PathBuf::from("/tmp/unused"), 5, create_logger());
+        consumer.set_input_file(Some(PathBuf::from("/tmp/test_imu_missing.frames")));
+
+        let err = consumer.run().await.expect_err("Missing file should fail");
+        assert!(matches!(err, ConsumerError::Connect(_)), "Got {:?}", err);
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+
+    /// Records `samples` as length-prefixed frames in a file for `set_input_file`.
+    fn write_recording(test_name: &str, samples: &[ImuData]) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("test_imu_{}.frames", test_name));
+        let mut bytes = Vec::new();
+        for sample in samples {
+            encode_frame(sample, &mut bytes);
+        }
+        fs::write(&path, bytes).expect("Failed to write recording");
+        path
+    }
+
+    /// Level samples at 100Hz with the mag turning once around Z and once
+    /// around Y, scaled per axis by `mag_gain` and offset by `mag_bias`.
+    fn mag_turn_samples(mag_bias: Vector3<f32>, mag_gain: Vector3<f32>) -> Vec<ImuData> {
+        (0..720)
+            .map(|i| {
+                let angle = (i % 360) as f32 * std::f32::consts::PI / 180.0;
+                let field = if i < 360 {
+                    Vector3::new(angle.cos(), angle.sin(), 0.0)
+                } else {
+                    Vector3::new(angle.cos(), 0.0, angle.sin())
+                } * 400.0;
+                let mag = field.component_mul(&mag_gain) + mag_bias;
+                let timestamp = 1000 + i * 10;
+                ImuData {
+                    z_acc: 1000.0,
+                    timestamp_acc: timestamp,
+                    timestamp_gyro: timestamp,
+                    x_mag: mag.x,
+                    y_mag: mag.y,
+                    z_mag: mag.z,
+                    timestamp_mag: timestamp,
+                    ..Default::default()
+                }
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_calibrates_mag_from_recorded_run() {
+        let mag_bias = Vector3::new(120.0, -80.0, 35.0);
+        let mag_gain = Vector3::new(1.2, 0.8, 1.0);
+        let path = write_recording("calibrate_mag", &mag_turn_samples(mag_bias, mag_gain));
+
+        let mut consumer = Consumer::new(PathBuf::from("/tmp/unused"), 5, create_logger());
+        consumer.set_input_file(Some(path.clone()));
+        consumer.set_calibration_runs(vec![CalibrationRun::Mag]);
+        consumer.run().await.unwrap();
+
+        let calibration = consumer.motion_processor.calibration();
+        assert!(
+            (calibration.mag_bias - mag_bias).norm() < 1.0,
+            "{:?}",
+            calibration.mag_bias
+        );
+        let corrected_gain = calibration.mag_scale.component_mul(&mag_gain);
+        assert!(
+            corrected_gain.iter().all(|g| (g - 1.0).abs() < 0.01),
+            "{:?}",
+            calibration.mag_scale
+        );
+        assert!(consumer.calibration_samples.is_empty());
+
+        fs::remove_file(&path).ok();
+    }
+
     #[tokio::test]
     async fn test_consumer_connection_fails_before_timeout() {
         let socket_path = setup_socket_path("connection_fail_quick");
@@ -303,6 +1575,7 @@ mod tests {
         );
 
         if let Err(e) = result {
+            assert!(matches!(e, ConsumerError::Connect(_)), "Got {:?}", e);
             assert!(
                 matches!(
                     e.kind(),
@@ -476,4 +1749,780 @@ mod tests {
 
         cleanup_socket(&socket_path);
     }
+
+    #[tokio::test]
+    async fn test_consumer_reuses_buffer_for_varying_frames() {
+        let socket_path = setup_socket_path("varying_frames");
+        let logger = create_logger();
+
+        let listener = UnixListener::bind(&socket_path).expect("Failed to bind listener");
+        let consumer_handle = tokio::spawn({
+            let socket_path = socket_path.clone();
+            async move {
+                let mut consumer = Consumer::new(socket_path, 5, logger);
+                consumer.set_read_buffer_hint(16);
+                let result = consumer.run().await;
+                (result, consumer)
+            }
+        });
+
+        let (mut stream, _) = listener
+            .accept()
+            .await
+            .expect("Failed to accept connection");
+
+        // Sparse messages encode much shorter than fully populated ones
+        let sparse = ImuData {
+            z_acc: 1000.0,
+            timestamp_acc: 100,
+            ..Default::default()
+        };
+        let frames = [
+            sparse,
+            create_test_imu_data(110),
+            sparse,
+            create_test_imu_data(120),
+        ];
+        let max_len = frames.iter().map(|f| f.encoded_len()).max().unwrap();
+
+        for frame in &frames {
+            send_message(&mut stream, frame)
+                .await
+                .expect("Failed to send frame");
+        }
+        drop(stream);
+
+        let (result, consumer) = tokio::time::timeout(Duration::from_secs(1), consumer_handle)
+            .await
+            .expect("Consumer timed out")
+            .expect("Consumer task panicked");
+
+        result.expect("Consumer should process varying frames cleanly");
+        assert!(consumer.frame_reader.buffer_capacity() >= max_len);
+
+        cleanup_socket(&socket_path);
+    }
+
+    #[tokio::test]
+    async fn test_consumer_rejects_oversized_frame() {
+        let socket_path = setup_socket_path("oversized_frame");
+        let logger = create_logger();
+
+        let listener = UnixListener::bind(&socket_path).expect("Failed to bind listener");
+        let consumer_handle = tokio::spawn({
+            let socket_path = socket_path.clone();
+            async move {
+                let mut consumer = Consumer::new(socket_path, 5, logger);
+                let result = consumer.run().await;
+                (result, consumer)
+            }
+        });
+
+        let (mut stream, _) = listener
+            .accept()
+            .await
+            .expect("Failed to accept connection");
+
+        send_message(&mut stream, &create_test_imu_data(100))
+            .await
+            .expect("Failed to send valid message");
+        stream
+            .write_u32(0xFFFF_FFF0)
+            .await
+            .expect("Failed to send absurd length");
+
+        let (result, consumer) = tokio::time::timeout(Duration::from_secs(1), consumer_handle)
+            .await
+            .expect("Consumer should disconnect instead of waiting for the body")
+            .expect("Consumer task panicked");
+
+        let err = result.expect_err("Oversized frame should end the connection");
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert!(matches!(
+            err,
+            ConsumerError::OversizedFrame {
+                len: 0xFFFF_FFF0,
+                max: DEFAULT_MAX_FRAME_SIZE
+            }
+        ));
+        assert!(consumer.frame_reader.buffer_capacity() <= DEFAULT_MAX_FRAME_SIZE);
+
+        cleanup_socket(&socket_path);
+    }
+
+    #[tokio::test]
+    async fn test_consumer_fuses_two_streams_and_survives_drop() {
+        let primary_path = setup_socket_path("fused_primary");
+        let secondary_path = setup_socket_path("fused_secondary");
+        let logger = create_logger();
+
+        let primary_listener = UnixListener::bind(&primary_path).expect("Failed to bind primary");
+        let secondary_listener =
+            UnixListener::bind(&secondary_path).expect("Failed to bind secondary");
+        let consumer_handle = tokio::spawn({
+            let (primary_path, secondary_path) = (primary_path.clone(), secondary_path.clone());
+            async move {
+                let mut consumer = Consumer::new(primary_path, 5, logger);
+                consumer.set_secondary_socket_path(Some(secondary_path));
+                // Keep the accel correction from pulling the yaw back
+                consumer.motion_processor_mut().set_gyro_only(true);
+                let result = consumer.run().await;
+                (result, consumer)
+            }
+        });
+
+        let (mut primary, _) = primary_listener.accept().await.expect("Failed to accept");
+        let (mut secondary, _) = secondary_listener.accept().await.expect("Failed to accept");
+
+        let z_rate_mdps = 90_000;
+        let sample = |timestamp: u32| ImuData {
+            z_acc: 1000.0,
+            timestamp_acc: timestamp,
+            z_gyro: z_rate_mdps,
+            timestamp_gyro: timestamp,
+            timestamp_mag: timestamp,
+            ..Default::default()
+        };
+
+        let mut secondary = Some(&mut secondary);
+        for i in 0..20 {
+            let msg = sample(1000 + i * 10);
+            send_message(&mut primary, &msg)
+                .await
+                .expect("Failed to send primary");
+            if i < 5 {
+                if let Some(stream) = secondary.as_mut() {
+                    send_message(stream, &msg)
+                        .await
+                        .expect("Failed to send secondary");
+                }
+            } else if let Some(stream) = secondary.take() {
+                stream.shutdown().await.expect("Failed to close secondary");
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        drop(primary);
+
+        let (result, consumer) = tokio::time::timeout(Duration::from_secs(2), consumer_handle)
+            .await
+            .expect("Consumer timed out")
+            .expect("Consumer task panicked");
+
+        result.expect("Dropping one stream should not fail the consumer");
+
+        // Every sample, fused or passed through, advanced the orientation
+        let expected = z_rate_mdps as f32 / 1000.0 * (0.001 + 19.0 * 0.01);
+        let heading = consumer.motion_processor.heading();
+        assert!(
+            (heading - expected).abs() < 0.5,
+            "Expected heading {} but got {}",
+            expected,
+            heading
+        );
+
+        cleanup_socket(&primary_path);
+        cleanup_socket(&secondary_path);
+    }
+
+    #[tokio::test]
+    async fn test_fused_streams_outlive_a_failed_one_and_average_noise() {
+        let z_rate_mdps = 90_000;
+        // Opposite errors on the two sensors, averaging cancels them
+        let sample = |i: u32, sign: i32| ImuData {
+            z_acc: 1000.0,
+            timestamp_acc: 1000 + i * 10,
+            z_gyro: z_rate_mdps + sign * (10_000 + (i as i32 * 7919) % 20_000),
+            timestamp_gyro: 1000 + i * 10,
+            timestamp_mag: 1000 + i * 10,
+            ..Default::default()
+        };
+        let (fused_samples, total_samples) = (35, 40);
+
+        let (primary_end, mut primary) = common::transport::memory::pair();
+        let (secondary_end, mut secondary) = common::transport::memory::pair();
+        let consumer_handle = tokio::spawn(async move {
+            let mut consumer = Consumer::new(PathBuf::from("/tmp/unused"), 5, create_logger());
+            consumer.motion_processor_mut().set_gyro_only(true);
+            let result = consumer.run_fused([primary_end, secondary_end]).await;
+            (result, consumer)
+        });
+
+        let frame = |msg: ImuData| {
+            let mut buf = Vec::new();
+            encode_frame(&msg, &mut buf);
+            buf
+        };
+        for i in 0..total_samples {
+            primary.write_all(&frame(sample(i, 1))).await.unwrap();
+            if i < fused_samples {
+                secondary.write_all(&frame(sample(i, -1))).await.unwrap();
+            } else if i == fused_samples {
+                // An oversized length fails the secondary stream
+                secondary
+                    .write_all(&0xFFFF_FFF0u32.to_be_bytes())
+                    .await
+                    .unwrap();
+            }
+            tokio::time::sleep(Duration::from_millis(2)).await;
+        }
+        drop(primary);
+
+        let (result, fused) = tokio::time::timeout(Duration::from_secs(2), consumer_handle)
+            .await
+            .expect("Consumer timed out")
+            .unwrap();
+        result.expect("A failed secondary should not fail the fused run");
+
+        // The same primary samples on their own
+        let bytes: Vec<u8> = (0..total_samples)
+            .flat_map(|i| frame(sample(i, 1)))
+            .collect();
+        let mut single = Consumer::new(PathBuf::from("/tmp/unused"), 5, create_logger());
+        single.motion_processor_mut().set_gyro_only(true);
+        single.run_single(&bytes[..]).await.unwrap();
+
+        let expected = z_rate_mdps as f32 / 1000.0 * (0.001 + (total_samples - 1) as f32 * 0.01);
+        let fused_error = (fused.motion_processor.heading() - expected).abs();
+        let single_error = (single.motion_processor.heading() - expected).abs();
+        assert!(
+            fused_error < single_error / 2.0,
+            "Fused heading error {} should be well below the single stream's {}",
+            fused_error,
+            single_error
+        );
+    }
+
+    #[tokio::test]
+    async fn test_trajectory_output_has_a_tum_line_per_sample() {
+        let input_path = std::env::temp_dir().join("test_imu_consumer_traj_in.bin");
+        let output_path = std::env::temp_dir().join("test_imu_consumer_traj_out.tum");
+        let mut recording = Vec::new();
+        for i in 0..20 {
+            let frame = ImuData {
+                z_acc: 1000.0,
+                timestamp_acc: 1000 + i * 10,
+                z_gyro: 20_000,
+                timestamp_gyro: 1000 + i * 10,
+                timestamp_mag: 1000 + i * 10,
+                ..Default::default()
+            };
+            encode_frame(&frame, &mut recording);
+        }
+        fs::write(&input_path, recording).expect("Failed to write input file");
+
+        let mut consumer = Consumer::new(PathBuf::from("/tmp/unused"), 5, create_logger());
+        consumer.set_input_file(Some(input_path.clone()));
+        consumer.set_trajectory_output(Some(TumWriter::create(&output_path).unwrap()));
+        consumer.run().await.expect("Run should end cleanly at EOF");
+
+        let text = fs::read_to_string(&output_path).expect("Failed to read trajectory");
+        let lines: Vec<Vec<f64>> = text
+            .lines()
+            .map(|line| line.split(' ').map(|v| v.parse().unwrap()).collect())
+            .collect();
+        assert_eq!(lines.len(), 20);
+        for (i, columns) in lines.iter().enumerate() {
+            assert_eq!(columns.len(), 8, "{:?}", columns);
+            assert!((columns[0] - (1.0 + i as f64 * 0.01)).abs() < 1e-9);
+            let norm = columns[4..].iter().map(|q| q * q).sum::<f64>().sqrt();
+            assert!((norm - 1.0).abs() < 1e-4, "{:?}", columns);
+        }
+        // Turning about +Z only: qx, qy stay zero while qz grows and qw shrinks
+        let last = lines.last().unwrap();
+        assert!(last[4].abs() < 1e-6 && last[5].abs() < 1e-6);
+        assert!(last[6] > 0.0 && last[7] < 1.0, "{:?}", last);
+
+        let _ = fs::remove_file(&input_path);
+        let _ = fs::remove_file(&output_path);
+    }
+
+    #[tokio::test]
+    async fn test_consumer_input_file_matches_live_stream() {
+        let socket_path = setup_socket_path("input_file_live");
+        let input_path = std::env::temp_dir().join("test_imu_consumer_input.bin");
+        let frames: Vec<ImuData> = (0..20)
+            .map(|i| ImuData {
+                x_acc: 100.0,
+                z_acc: 1000.0,
+                timestamp_acc: 1000 + i * 10,
+                z_gyro: 20_000,
+                timestamp_gyro: 1000 + i * 10,
+                timestamp_mag: 1000 + i * 10,
+                ..Default::default()
+            })
+            .collect();
+
+        let mut recording = Vec::new();
+        for frame in &frames {
+            encode_frame(frame, &mut recording);
+        }
+        fs::write(&input_path, recording).expect("Failed to write input file");
+
+        let mut offline = Consumer::new(socket_path.clone(), 5, create_logger());
+        offline.set_input_file(Some(input_path.clone()));
+        offline
+            .run()
+            .await
+            .expect("Offline run should end cleanly at EOF");
+
+        let listener = UnixListener::bind(&socket_path).expect("Failed to bind listener");
+        let live_handle = tokio::spawn({
+            let socket_path = socket_path.clone();
+            async move {
+                let mut consumer = Consumer::new(socket_path, 5, create_logger());
+                let result = consumer.run().await;
+                (result, consumer)
+            }
+        });
+        let (mut stream, _) = listener.accept().await.expect("Failed to accept");
+        for frame in &frames {
+            send_message(&mut stream, frame)
+                .await
+                .expect("Failed to send frame");
+        }
+        drop(stream);
+
+        let (result, live) = tokio::time::timeout(Duration::from_secs(1), live_handle)
+            .await
+            .expect("Live consumer timed out")
+            .expect("Live consumer panicked");
+        result.expect("Live run should end cleanly");
+
+        let final_state =
+            |consumer: &Consumer| consumer.motion_processor.recent_states().last().cloned();
+        assert!(final_state(&offline).is_some());
+        assert_eq!(final_state(&offline), final_state(&live));
+
+        fs::remove_file(&input_path).ok();
+        cleanup_socket(&socket_path);
+    }
+
+    #[tokio::test]
+    async fn test_consumer_warns_on_delayed_frame() {
+        let socket_path = setup_socket_path("timing_anomaly");
+        let (logger, captured) = capturing_logger();
+
+        let listener = UnixListener::bind(&socket_path).expect("Failed to bind listener");
+        let consumer_handle = spawn_consumer_task(socket_path.clone(), 5, logger);
+        let (mut stream, _) = listener.accept().await.expect("Failed to accept");
+
+        let anomalies = || {
+            captured
+                .messages()
+                .iter()
+                .filter(|m| m.contains("timing anomaly"))
+                .count()
+        };
+
+        for i in 0..20 {
+            send_message(&mut stream, &create_test_imu_data(100 + i * 10))
+                .await
+                .expect("Failed to send frame");
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let before_delay = anomalies();
+
+        // A hiccup ten times longer than the usual spacing
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        send_message(&mut stream, &create_test_imu_data(400))
+            .await
+            .expect("Failed to send delayed frame");
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        drop(stream);
+
+        tokio::time::timeout(Duration::from_secs(1), consumer_handle)
+            .await
+            .expect("Consumer timed out")
+            .expect("Consumer task panicked")
+            .expect("Consumer should end cleanly");
+
+        assert!(
+            anomalies() > before_delay,
+            "Delayed frame should be reported as a timing anomaly"
+        );
+        cleanup_socket(&socket_path);
+    }
+
+    #[tokio::test]
+    async fn test_consumer_processes_stream_in_memory() {
+        let (mut publisher_end, consumer_end) = common::transport::memory::pair();
+        let publisher_handle = tokio::spawn(async move {
+            let mut buf = Vec::new();
+            for i in 0..100 {
+                buf.clear();
+                let msg = ImuData {
+                    z_acc: 1000.0,
+                    timestamp_acc: 1000 + i * 10,
+                    z_gyro: 90_000,
+                    timestamp_gyro: 1000 + i * 10,
+                    ..Default::default()
+                };
+                encode_frame(&msg, &mut buf);
+                publisher_end
+                    .write_all(&buf)
+                    .await
+                    .expect("Failed to send frame");
+            }
+        });
+
+        let mut consumer = Consumer::new(PathBuf::from("/tmp/unused"), 5, create_logger());
+        consumer.motion_processor_mut().set_gyro_only(true);
+        tokio::time::timeout(Duration::from_secs(1), consumer.run_single(consumer_end))
+            .await
+            .expect("Consumer timed out")
+            .expect("In-memory stream should be processed cleanly");
+        publisher_handle.await.unwrap();
+
+        let states: Vec<_> = consumer.motion_processor.recent_states().collect();
+        assert_eq!(states.len(), 100);
+        // 90 deg/s, integrated over 0.99s after the first sample
+        let (_, _, yaw) = states[99].orientation.euler_angles();
+        assert!(
+            (yaw.to_degrees() - 89.1).abs() < 1.0,
+            "yaw {}",
+            yaw.to_degrees()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_status_report_tracks_connection_and_samples() {
+        let (mut publisher_end, consumer_end) = common::transport::memory::pair();
+        let mut buf = Vec::new();
+        for i in 0..7 {
+            encode_frame(&create_test_imu_data(100 + i * 10), &mut buf);
+        }
+        publisher_end.write_all(&buf).await.unwrap();
+        drop(publisher_end);
+
+        let mut consumer = Consumer::new(PathBuf::from("/tmp/unused"), 5, create_logger());
+        let before = consumer.status_report();
+        assert_eq!(before.connection, ConnectionState::Connecting);
+        assert_eq!(before.samples, 0);
+
+        consumer.run_single(consumer_end).await.unwrap();
+
+        let report = consumer.status_report();
+        assert_eq!(report.connection, ConnectionState::Disconnected);
+        assert_eq!(report.samples, 7);
+        assert!(report.since_last_sample.is_some());
+        let line = report.to_string();
+        assert!(line.contains("Status: disconnected"), "Got {}", line);
+        assert!(line.contains("samples: 7"), "Got {}", line);
+    }
+
+    #[tokio::test]
+    async fn test_angular_velocity_logged_in_chosen_units() {
+        async fn logged_rate(units: AngleUnits) -> String {
+            let (mut publisher_end, consumer_end) = common::transport::memory::pair();
+            let mut buf = Vec::new();
+            for i in 0..3 {
+                let imu_data = ImuData {
+                    x_gyro: 0,
+                    y_gyro: 0,
+                    z_gyro: 90_000, // a quarter turn per second
+                    ..create_test_imu_data(100 + i * 10)
+                };
+                encode_frame(&imu_data, &mut buf);
+            }
+            publisher_end.write_all(&buf).await.unwrap();
+            drop(publisher_end);
+
+            let (logger, captured) = capturing_logger();
+            let mut consumer = Consumer::new(PathBuf::from("/tmp/unused"), 5, logger);
+            consumer.motion_processor_mut().set_angle_units(units);
+            consumer.run_single(consumer_end).await.unwrap();
+
+            let messages = captured.messages();
+            let report = messages.iter().rfind(|m| m.starts_with("Pos: [")).unwrap();
+            report.split(" | ").nth(3).unwrap().to_string()
+        }
+
+        assert_eq!(
+            logged_rate(AngleUnits::Deg).await,
+            "AngVel: [+0.000,+0.000,+90.000]deg/s"
+        );
+        assert_eq!(
+            logged_rate(AngleUnits::Rad).await,
+            "AngVel: [+0.000,+0.000,+1.571]rad/s"
+        );
+    }
+
+    #[test]
+    fn test_logs_heading_when_enabled() {
+        let (logger, captured) = capturing_logger();
+        let mut consumer = Consumer::new(PathBuf::from("/tmp/unused"), 5, logger);
+        consumer.set_log_heading(true);
+        consumer.motion_processor_mut().set_gyro_only(true);
+
+        // Level and still, magnetic north along +X
+        let mut sample = ImuData {
+            z_acc: 1000.0,
+            timestamp_acc: 100,
+            timestamp_gyro: 100,
+            x_mag: 300.0,
+            z_mag: -400.0,
+            timestamp_mag: 100,
+            ..Default::default()
+        };
+        consumer.handle_sample(&sample);
+        sample.timestamp_acc = 110;
+        sample.timestamp_gyro = 110;
+        sample.timestamp_mag = 0;
+        consumer.handle_sample(&sample);
+
+        let messages = captured.messages();
+        let headings: Vec<_> = messages
+            .iter()
+            .filter(|m| m.starts_with("Heading"))
+            .collect();
+        assert_eq!(
+            headings,
+            ["Heading: 0.0deg | Mag heading: 0.0deg", "Heading: 0.0deg"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_logs_gravity_direction_when_enabled() {
+        let (mut publisher_end, consumer_end) = common::transport::memory::pair();
+        let mut buf = Vec::new();
+        for i in 0..5 {
+            encode_frame(&create_test_imu_data(100 + i * 10), &mut buf);
+        }
+        publisher_end.write_all(&buf).await.unwrap();
+        drop(publisher_end);
+
+        let (logger, captured) = capturing_logger();
+        let mut consumer = Consumer::new(PathBuf::from("/tmp/unused"), 5, logger);
+        consumer.set_log_gravity(true);
+        // Nearly no rotation from the gyro, down stays along -Z
+        consumer.motion_processor_mut().set_gyro_only(true);
+        consumer.run_single(consumer_end).await.unwrap();
+
+        let messages = captured.messages();
+        let gravity: Vec<_> = messages
+            .iter()
+            .filter(|m| m.starts_with("Gravity (body)"))
+            .collect();
+        assert_eq!(gravity.len(), 5);
+        assert!(
+            gravity[0].ends_with("[+0.000,+0.000,-1.000]"),
+            "Got {}",
+            gravity[0]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_ground_truth_error_of_clean_stream_is_small() {
+        let trajectory = common::trajectory::SinusoidalTranslation::new(0.5, 0.5);
+        let (mut publisher_end, consumer_end) = common::transport::memory::pair();
+        let publisher_handle = tokio::spawn(async move {
+            let mut buf = Vec::new();
+            for i in 0..1000 {
+                buf.clear();
+                encode_frame(&trajectory.sample(i as f32 * 0.002, 1000 + i * 2), &mut buf);
+                publisher_end.write_all(&buf).await.unwrap();
+            }
+        });
+
+        let mut consumer = Consumer::new(PathBuf::from("/tmp/unused"), 5, create_logger());
+        consumer.set_compare_ground_truth(true);
+        let motion_processor = consumer.motion_processor_mut();
+        motion_processor.set_gyro_only(true);
+        motion_processor.set_velocity_decay(1.0);
+        motion_processor.set_acc_deadband(0.0);
+        consumer.run_single(consumer_end).await.unwrap();
+        publisher_handle.await.unwrap();
+
+        let summary = consumer.accuracy_summary().unwrap();
+        assert_eq!(summary.samples, 1000);
+        assert!(summary.orientation_rms.unwrap() < 1e-3, "{:?}", summary);
+        assert!(summary.position_rms.unwrap() < 0.02, "{:?}", summary);
+    }
+
+    #[tokio::test]
+    async fn test_trajectory_summary_of_known_stream() {
+        // One period of 0.5m amplitude: out 1m and back, peaking at 0.5*PI m/s
+        let trajectory = common::trajectory::SinusoidalTranslation::new(0.5, 0.5);
+        let (mut publisher_end, consumer_end) = common::transport::memory::pair();
+        let publisher_handle = tokio::spawn(async move {
+            let mut buf = Vec::new();
+            for i in 0..=1000 {
+                buf.clear();
+                encode_frame(&trajectory.sample(i as f32 * 0.002, 1000 + i * 2), &mut buf);
+                publisher_end.write_all(&buf).await.unwrap();
+                if i % 400 == 0 {
+                    // A field tag without its value never decodes
+                    publisher_end.write_all(&[0, 0, 0, 1, 0x08]).await.unwrap();
+                }
+            }
+        });
+
+        let mut consumer = Consumer::new(PathBuf::from("/tmp/unused"), 5, create_logger());
+        consumer.set_print_summary(true);
+        let motion_processor = consumer.motion_processor_mut();
+        motion_processor.set_gyro_only(true);
+        motion_processor.set_velocity_decay(1.0);
+        motion_processor.set_acc_deadband(0.0);
+        consumer.run_single(consumer_end).await.unwrap();
+        publisher_handle.await.unwrap();
+
+        let summary = consumer.trajectory_summary().unwrap();
+        assert_eq!(summary.samples, 1001);
+        assert_eq!(summary.decode_errors, 3);
+        assert_eq!(summary.skipped_updates, 0);
+        assert!((summary.distance - 2.0).abs() < 0.05, "{:?}", summary);
+        assert!(
+            (summary.max_speed - 0.5 * std::f32::consts::PI).abs() < 0.05,
+            "{:?}",
+            summary
+        );
+        let (roll, pitch, yaw) = summary.final_euler;
+        assert!(roll.abs() < 1e-3 && pitch.abs() < 1e-3 && yaw.abs() < 1e-3);
+    }
+
+    #[tokio::test]
+    async fn test_warning_storm_is_rate_limited() {
+        let (mut publisher_end, consumer_end) = common::transport::memory::pair();
+        // A field tag without its value never decodes
+        let bad_frame = [0, 0, 0, 1, 0x08];
+        for _ in 0..1000 {
+            publisher_end.write_all(&bad_frame).await.unwrap();
+        }
+        drop(publisher_end);
+
+        let (logger, captured) = capturing_logger();
+        let mut consumer = Consumer::new(PathBuf::from("/tmp/unused"), 5, logger);
+        consumer.set_warn_rate_limit(20);
+        tokio::time::timeout(Duration::from_secs(1), consumer.run_single(consumer_end))
+            .await
+            .expect("Consumer timed out")
+            .expect("Undecodable frames should be skipped");
+
+        let count = |message: &str| {
+            captured
+                .messages()
+                .iter()
+                .filter(|m| m.as_str() == message)
+                .count()
+        };
+        let warnings = count("Failed to decode ImuData");
+        assert!(
+            (20..=40).contains(&warnings),
+            "{} decode warnings got through",
+            warnings
+        );
+        assert!(count("Suppressed repeated warnings") >= 1);
+    }
+
+    #[tokio::test]
+    async fn test_consumer_decodes_little_endian_frames() {
+        let socket_path = setup_socket_path("little_endian");
+
+        let listener = UnixListener::bind(&socket_path).expect("Failed to bind listener");
+        let consumer_handle = tokio::spawn({
+            let socket_path = socket_path.clone();
+            async move {
+                let mut consumer = Consumer::new(socket_path, 5, create_logger());
+                consumer.set_length_endianness(Endianness::Little);
+                let result = consumer.run().await;
+                (result, consumer)
+            }
+        });
+        let (mut stream, _) = listener.accept().await.expect("Failed to accept");
+
+        for i in 0..5 {
+            let msg = create_test_imu_data(100 + i * 10);
+            let mut buf = Vec::new();
+            encode_frame_with_endianness(&msg, &mut buf, Endianness::Little);
+            stream.write_all(&buf).await.expect("Failed to send frame");
+        }
+        drop(stream);
+
+        let (result, consumer) = tokio::time::timeout(Duration::from_secs(1), consumer_handle)
+            .await
+            .expect("Consumer timed out")
+            .expect("Consumer task panicked");
+
+        result.expect("Little-endian frames should decode cleanly");
+        assert_eq!(consumer.motion_processor.recent_states().count(), 5);
+
+        cleanup_socket(&socket_path);
+    }
+
+    #[tokio::test]
+    async fn test_consumer_resyncs_on_frame_magic_after_garbage() {
+        let (mut publisher_end, consumer_end) = common::transport::memory::pair();
+        let mut buf = Vec::new();
+        for i in 0..10 {
+            if i == 5 {
+                // Garbage, then a stray marker announcing an impossible length
+                buf.extend_from_slice(&[0x13, 0x37]);
+                buf.extend_from_slice(&common::framing::FRAME_MAGIC);
+                buf.extend_from_slice(&[0xFF; 4]);
+            }
+            buf.extend_from_slice(&common::framing::FRAME_MAGIC);
+            encode_frame(&create_test_imu_data(100 + i * 10), &mut buf);
+        }
+        publisher_end.write_all(&buf).await.unwrap();
+        drop(publisher_end);
+
+        let (logger, captured) = capturing_logger();
+        let mut consumer = Consumer::new(PathBuf::from("/tmp/unused"), 5, logger);
+        consumer.set_frame_magic(true);
+        tokio::time::timeout(Duration::from_secs(1), consumer.run_single(consumer_end))
+            .await
+            .expect("Consumer timed out")
+            .expect("Garbage between marked frames should not end the stream");
+
+        assert_eq!(consumer.motion_processor.recent_states().count(), 10);
+        assert_eq!(consumer.decode_errors.load(Ordering::Relaxed), 0);
+        let messages = captured.messages();
+        assert_eq!(
+            messages
+                .iter()
+                .filter(|m| m.contains("Resynchronized on the next frame marker"))
+                .count(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn test_consumer_suppresses_state_during_warmup() {
+        let socket_path = setup_socket_path("warmup");
+        let (logger, captured) = capturing_logger();
+
+        let listener = UnixListener::bind(&socket_path).expect("Failed to bind listener");
+        let consumer_handle = tokio::spawn({
+            let socket_path = socket_path.clone();
+            async move {
+                let mut consumer = Consumer::new(socket_path, 5, logger);
+                consumer.set_warmup_samples(5);
+                consumer.run().await
+            }
+        });
+        let (mut stream, _) = listener.accept().await.expect("Failed to accept");
+
+        for i in 0..12 {
+            send_message(&mut stream, &create_test_imu_data(100 + i * 10))
+                .await
+                .expect("Failed to send frame");
+        }
+        drop(stream);
+
+        tokio::time::timeout(Duration::from_secs(1), consumer_handle)
+            .await
+            .expect("Consumer timed out")
+            .expect("Consumer task panicked")
+            .expect("Consumer should end cleanly");
+
+        let messages = captured.messages();
+        let warmup_end = messages
+            .iter()
+            .position(|m| m.starts_with("Warmup complete"))
+            .expect("Warmup completion should be logged");
+        assert!(!messages[..warmup_end].iter().any(|m| m.starts_with("Pos:")));
+        assert_eq!(messages.iter().filter(|m| m.starts_with("Pos:")).count(), 7);
+
+        cleanup_socket(&socket_path);
+    }
 }
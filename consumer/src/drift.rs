@@ -0,0 +1,224 @@
+use nalgebra::{UnitQuaternion, Vector3};
+
+const STATIONARY_ACC_TOLERANCE_MG: f32 = 30.0;
+const STATIONARY_MAX_GYRO_RATE: f32 = 0.05; // rad/s
+const STATIONARY_MIN_SAMPLES: u32 = 20;
+
+/// Whether a single sample looks still: accel magnitude [mg] close to 1g and
+/// gyro rate [rad/s] close to zero.
+fn is_still(acc_magnitude: f32, gyro_rate: f32) -> bool {
+    (acc_magnitude - 1000.0).abs() < STATIONARY_ACC_TOLERANCE_MG
+        && gyro_rate < STATIONARY_MAX_GYRO_RATE
+}
+
+/// Reports the device at rest once the accel magnitude stayed close to 1g
+/// and the gyro close to zero for `STATIONARY_MIN_SAMPLES` samples.
+#[derive(Debug, Clone, Default)]
+pub struct StationaryDetector {
+    still_samples: u32,
+}
+
+impl StationaryDetector {
+    /// Feeds the bias-corrected accel magnitude [mg] and gyro rate [rad/s]
+    /// of one sample, returns whether the device is at rest.
+    pub fn update(&mut self, acc_magnitude: f32, gyro_rate: f32) -> bool {
+        self.still_samples = if is_still(acc_magnitude, gyro_rate) {
+            self.still_samples.saturating_add(1)
+        } else {
+            0
+        };
+        self.still_samples >= STATIONARY_MIN_SAMPLES
+    }
+}
+
+/// Opens once the first sample leaves the still band of the stationary
+/// detector and stays open, so integration can wait for motion to begin.
+#[derive(Debug, Clone, Default)]
+pub struct MotionGate {
+    open: bool,
+}
+
+impl MotionGate {
+    /// Feeds the bias-corrected accel magnitude [mg] and gyro rate [rad/s]
+    /// of one sample, returns whether motion has begun.
+    pub fn update(&mut self, acc_magnitude: f32, gyro_rate: f32) -> bool {
+        self.open |= !is_still(acc_magnitude, gyro_rate);
+        self.open
+    }
+}
+
+/// Flags an integrated velocity that keeps growing while the device is at
+/// rest, the symptom of residual bias or a bad calibration.
+#[derive(Debug, Clone)]
+pub struct DriftMonitor {
+    max_velocity: f32, // m/s
+    stationary: StationaryDetector,
+    drifting: bool,
+}
+
+impl DriftMonitor {
+    pub fn new(max_velocity: f32) -> Self {
+        Self {
+            max_velocity,
+            stationary: StationaryDetector::default(),
+            drifting: false,
+        }
+    }
+
+    /// Feeds one sample, returns the velocity magnitude the first time it
+    /// exceeds the threshold at rest. Reports again only after the velocity
+    /// fell back below the threshold or the device moved.
+    pub fn update(
+        &mut self,
+        acc_magnitude: f32,
+        gyro_rate: f32,
+        velocity: &Vector3<f32>,
+    ) -> Option<f32> {
+        let speed = velocity.norm();
+        if !self.stationary.update(acc_magnitude, gyro_rate) || speed <= self.max_velocity {
+            self.drifting = false;
+            return None;
+        }
+        (!std::mem::replace(&mut self.drifting, true)).then_some(speed)
+    }
+}
+
+/// Orientation and position change per second while at rest.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DriftRate {
+    pub orientation: f32, // deg/s
+    pub position: f32,    // m/s
+}
+
+/// Rolling estimate of how fast the orientation and position leak away while
+/// the device is at rest, a measure of the residual sensor bias.
+///
+/// Every `interval_ms` of sample time spent at rest yields one estimate,
+/// from the change over that interval. Moving discards the partial interval.
+#[derive(Debug, Clone)]
+pub struct DriftRateEstimator {
+    interval_ms: u32,
+    stationary: StationaryDetector,
+    start: Option<(u32, UnitQuaternion<f32>, Vector3<f32>)>,
+}
+
+impl DriftRateEstimator {
+    pub fn new(interval_ms: u32) -> Self {
+        Self {
+            interval_ms,
+            stationary: StationaryDetector::default(),
+            start: None,
+        }
+    }
+
+    /// Feeds one sample and the state it produced, returns an estimate at
+    /// the end of each interval at rest.
+    pub fn update(
+        &mut self,
+        acc_magnitude: f32,
+        gyro_rate: f32,
+        timestamp_ms: u32,
+        orientation: &UnitQuaternion<f32>,
+        position: &Vector3<f32>,
+    ) -> Option<DriftRate> {
+        if !self.stationary.update(acc_magnitude, gyro_rate) {
+            self.start = None;
+            return None;
+        }
+
+        let Some((start_ms, start_orientation, start_position)) = self.start else {
+            self.start = Some((timestamp_ms, *orientation, *position));
+            return None;
+        };
+        let elapsed_ms = timestamp_ms.wrapping_sub(start_ms);
+        if elapsed_ms < self.interval_ms {
+            return None;
+        }
+
+        self.start = Some((timestamp_ms, *orientation, *position));
+        let elapsed = elapsed_ms as f32 / 1000.0;
+        Some(DriftRate {
+            orientation: start_orientation.angle_to(orientation).to_degrees() / elapsed,
+            position: (position - start_position).norm() / elapsed,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stationary_after_still_samples() {
+        let mut detector = StationaryDetector::default();
+        for _ in 1..STATIONARY_MIN_SAMPLES {
+            assert!(!detector.update(1005.0, 0.01));
+        }
+        assert!(detector.update(1005.0, 0.01));
+
+        assert!(!detector.update(1005.0, 0.5), "Rotating is not at rest");
+        assert!(!detector.update(1005.0, 0.01), "Needs a new still period");
+    }
+
+    #[test]
+    fn test_motion_gate_stays_open_after_first_motion() {
+        let mut gate = MotionGate::default();
+        assert!(!gate.update(1020.0, 0.01));
+        assert!(gate.update(1000.0, 0.2), "Rotating is motion");
+        assert!(gate.update(1000.0, 0.0));
+
+        let mut gate = MotionGate::default();
+        assert!(gate.update(1100.0, 0.0), "Accelerating is motion");
+    }
+
+    #[test]
+    fn test_reports_drift_once_while_at_rest() {
+        let mut monitor = DriftMonitor::new(0.1);
+        let drifting = Vector3::new(0.2, 0.0, 0.0);
+
+        for _ in 1..STATIONARY_MIN_SAMPLES {
+            assert_eq!(monitor.update(1000.0, 0.0, &drifting), None);
+        }
+        assert_eq!(monitor.update(1000.0, 0.0, &drifting), Some(0.2));
+        assert_eq!(monitor.update(1000.0, 0.0, &drifting), None);
+
+        // Moving rearms the report
+        assert_eq!(monitor.update(1500.0, 0.0, &drifting), None);
+        for _ in 1..STATIONARY_MIN_SAMPLES {
+            monitor.update(1000.0, 0.0, &drifting);
+        }
+        assert_eq!(monitor.update(1000.0, 0.0, &drifting), Some(0.2));
+    }
+
+    #[test]
+    fn test_drift_rate_per_interval_at_rest() {
+        let mut estimator = DriftRateEstimator::new(1000);
+        let update = |estimator: &mut DriftRateEstimator, t: u32, acc: f32| {
+            // Yawing at 2deg/s and creeping along X at 0.1m/s
+            let orientation =
+                UnitQuaternion::from_euler_angles(0.0, 0.0, (t as f32 * 0.002).to_radians());
+            let position = Vector3::new(t as f32 * 0.0001, 0.0, 0.0);
+            estimator.update(acc, 0.0, t, &orientation, &position)
+        };
+
+        let rates: Vec<(u32, DriftRate)> = (0..=250)
+            .map(|i| i * 10)
+            .filter_map(|t| update(&mut estimator, t, 1000.0).map(|rate| (t, rate)))
+            .collect();
+        // At rest from the 20th sample on, one estimate per second after it
+        assert_eq!(
+            rates.iter().map(|(t, _)| *t).collect::<Vec<_>>(),
+            [1190, 2190]
+        );
+        for (_, rate) in rates {
+            assert!((rate.orientation - 2.0).abs() < 1e-2, "{:?}", rate);
+            assert!((rate.position - 0.1).abs() < 1e-3, "{:?}", rate);
+        }
+
+        // Moving discards the interval in progress
+        assert_eq!(update(&mut estimator, 2600, 1500.0), None);
+        for t in (2610..3600).step_by(10) {
+            assert_eq!(update(&mut estimator, t, 1000.0), None);
+        }
+    }
+}
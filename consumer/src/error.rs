@@ -0,0 +1,113 @@
+use std::fmt;
+use std::io;
+
+/// Failure modes of `Consumer`, converted to `io::Error` at the binary's edge.
+#[derive(Debug)]
+pub enum ConsumerError {
+    /// The socket did not accept the connection within the timeout
+    ConnectTimeout,
+    /// Connecting to the socket or opening the input file failed
+    Connect(io::Error),
+    /// Reading from an established stream failed mid-frame
+    Read(io::Error),
+    /// A complete frame did not decode as `ImuData`
+    Decode(common::codec::DecodeError),
+    /// A frame announced a length above the maximum, the stream is unusable
+    OversizedFrame { len: usize, max: usize },
+    /// This many frames in a row did not decode, the stream is incompatible
+    /// or corrupt
+    TooManyDecodeErrors { count: u32 },
+    /// This many failover connections in a row ended right after connecting
+    UnstablePublishers { sessions: u32 },
+}
+
+impl ConsumerError {
+    pub fn kind(&self) -> io::ErrorKind {
+        match self {
+            ConsumerError::ConnectTimeout => io::ErrorKind::TimedOut,
+            ConsumerError::Connect(e) | ConsumerError::Read(e) => e.kind(),
+            ConsumerError::Decode(_)
+            | ConsumerError::OversizedFrame { .. }
+            | ConsumerError::TooManyDecodeErrors { .. } => io::ErrorKind::InvalidData,
+            ConsumerError::UnstablePublishers { .. } => io::ErrorKind::ConnectionAborted,
+        }
+    }
+}
+
+impl fmt::Display for ConsumerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConsumerError::ConnectTimeout => write!(f, "connection timed out"),
+            ConsumerError::Connect(e) => write!(f, "connection failed: {}", e),
+            ConsumerError::Read(e) => write!(f, "read failed: {}", e),
+            ConsumerError::Decode(e) => write!(f, "decoding failed: {}", e),
+            ConsumerError::OversizedFrame { len, max } => {
+                write!(f, "frame of {} bytes exceeds maximum of {} bytes", len, max)
+            }
+            ConsumerError::TooManyDecodeErrors { count } => {
+                write!(f, "{} consecutive frames failed to decode", count)
+            }
+            ConsumerError::UnstablePublishers { sessions } => {
+                write!(
+                    f,
+                    "{} consecutive connections dropped shortly after connecting",
+                    sessions
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConsumerError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConsumerError::Connect(e) | ConsumerError::Read(e) => Some(e),
+            ConsumerError::Decode(e) => Some(e),
+            ConsumerError::ConnectTimeout
+            | ConsumerError::OversizedFrame { .. }
+            | ConsumerError::TooManyDecodeErrors { .. }
+            | ConsumerError::UnstablePublishers { .. } => None,
+        }
+    }
+}
+
+impl From<ConsumerError> for io::Error {
+    fn from(e: ConsumerError) -> Self {
+        io::Error::new(e.kind(), e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_io_error_kinds() {
+        let cases = [
+            (ConsumerError::ConnectTimeout, io::ErrorKind::TimedOut),
+            (
+                ConsumerError::Connect(io::ErrorKind::ConnectionRefused.into()),
+                io::ErrorKind::ConnectionRefused,
+            ),
+            (
+                ConsumerError::OversizedFrame { len: 10, max: 5 },
+                io::ErrorKind::InvalidData,
+            ),
+            (
+                ConsumerError::TooManyDecodeErrors { count: 3 },
+                io::ErrorKind::InvalidData,
+            ),
+            (
+                ConsumerError::UnstablePublishers { sessions: 5 },
+                io::ErrorKind::ConnectionAborted,
+            ),
+        ];
+
+        for (err, kind) in cases {
+            let message = err.to_string();
+            let io_err = io::Error::from(err);
+            assert_eq!(io_err.kind(), kind);
+            assert_eq!(io_err.to_string(), message);
+        }
+    }
+}
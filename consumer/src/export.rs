@@ -0,0 +1,68 @@
+use crate::motion::MotionState;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+/// Writes the estimated trajectory in the TUM format read by tools such as
+/// evo, one `timestamp tx ty tz qx qy qz qw` line per sample with the time
+/// in seconds, the position in meters and the body-to-world orientation.
+#[derive(Debug)]
+pub struct TumWriter<W: Write = BufWriter<File>> {
+    writer: W,
+}
+
+impl TumWriter {
+    pub fn create(path: &Path) -> io::Result<Self> {
+        Ok(Self::new(BufWriter::new(File::create(path)?)))
+    }
+}
+
+impl<W: Write> TumWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+
+    pub fn write(&mut self, time_us: u64, state: &MotionState) -> io::Result<()> {
+        let p = state.position;
+        let q = state.orientation;
+        writeln!(
+            self.writer,
+            "{}.{:06} {:.6} {:.6} {:.6} {:.6} {:.6} {:.6} {:.6}",
+            time_us / 1_000_000,
+            time_us % 1_000_000,
+            p.x,
+            p.y,
+            p.z,
+            q.i,
+            q.j,
+            q.k,
+            q.w
+        )
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nalgebra::{UnitQuaternion, Vector3};
+
+    #[test]
+    fn test_line_has_tum_column_order() {
+        let mut writer = TumWriter::new(Vec::new());
+        let mut state = MotionState::default();
+        state.position = Vector3::new(1.0, -2.0, 0.5);
+        state.orientation =
+            UnitQuaternion::from_euler_angles(0.0, 0.0, std::f32::consts::FRAC_PI_2);
+        writer.write(12_000_250, &state).unwrap();
+
+        let text = String::from_utf8(writer.writer).unwrap();
+        assert_eq!(
+            text,
+            "12.000250 1.000000 -2.000000 0.500000 0.000000 0.000000 0.707107 0.707107\n"
+        );
+    }
+}
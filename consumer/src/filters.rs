@@ -0,0 +1,155 @@
+use nalgebra::{DMatrix, Vector3};
+use std::collections::VecDeque;
+
+/// First-order high-pass filter applied per axis.
+///
+/// Removes slowly varying offsets (e.g. gravity leaking through orientation
+/// error) while passing changes faster than the cutoff frequency.
+#[derive(Debug, Clone)]
+pub struct HighPassFilter {
+    time_constant: f32,
+    previous_input: Vector3<f32>,
+    previous_output: Vector3<f32>,
+}
+
+impl HighPassFilter {
+    pub fn new(cutoff_hz: f32) -> Self {
+        Self {
+            time_constant: 1.0 / (2.0 * std::f32::consts::PI * cutoff_hz),
+            previous_input: Vector3::zeros(),
+            previous_output: Vector3::zeros(),
+        }
+    }
+
+    pub fn apply(&mut self, input: Vector3<f32>, dt: f32) -> Vector3<f32> {
+        let alpha = self.time_constant / (self.time_constant + dt);
+        let output = alpha * (self.previous_output + input - self.previous_input);
+
+        self.previous_input = input;
+        self.previous_output = output;
+        output
+    }
+}
+
+/// Causal Savitzky-Golay smoothing applied per axis.
+///
+/// Fits a polynomial to the last `window_len` samples by least squares and
+/// evaluates it at the newest one. Unlike a low-pass filter this follows
+/// ramps and curves up to the polynomial order without lag. Samples pass
+/// through unchanged until the window is filled.
+#[derive(Debug, Clone)]
+pub struct SavitzkyGolayFilter {
+    coefficients: Vec<f32>,
+    window: VecDeque<Vector3<f32>>,
+}
+
+impl SavitzkyGolayFilter {
+    /// Returns `None` unless `poly_order < window_len`, as the fit would be
+    /// underdetermined.
+    pub fn new(window_len: usize, poly_order: usize) -> Option<Self> {
+        if poly_order >= window_len {
+            return None;
+        }
+
+        // Oldest sample at x = -(window_len - 1), newest at x = 0
+        let design = DMatrix::<f64>::from_fn(window_len, poly_order + 1, |row, power| {
+            (row as f64 - (window_len - 1) as f64).powi(power as i32)
+        });
+        let normal = (design.transpose() * &design).try_inverse()?;
+        // The fitted value at x = 0 is the constant term of the polynomial
+        let projection = normal * design.transpose();
+        let coefficients = projection.row(0).iter().map(|&c| c as f32).collect();
+
+        Some(Self {
+            coefficients,
+            window: VecDeque::with_capacity(window_len),
+        })
+    }
+
+    pub fn apply(&mut self, input: Vector3<f32>) -> Vector3<f32> {
+        if self.window.len() == self.coefficients.len() {
+            self.window.pop_front();
+        }
+        self.window.push_back(input);
+
+        if self.window.len() < self.coefficients.len() {
+            return input;
+        }
+
+        self.window
+            .iter()
+            .zip(&self.coefficients)
+            .map(|(sample, coefficient)| sample * *coefficient)
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_high_pass_removes_constant_offset() {
+        let mut filter = HighPassFilter::new(1.0);
+        let offset = Vector3::new(1.0, -2.0, 0.5);
+
+        let mut output = Vector3::zeros();
+        for _ in 0..1000 {
+            output = filter.apply(offset, 0.01);
+        }
+
+        assert_relative_eq!(output.norm(), 0.0, epsilon = 1e-3);
+    }
+
+    #[test]
+    fn test_high_pass_passes_step_change() {
+        let mut filter = HighPassFilter::new(0.1);
+
+        let output = filter.apply(Vector3::new(1.0, 0.0, 0.0), 0.01);
+
+        assert!(
+            output.x > 0.99,
+            "Fast change should pass almost unattenuated"
+        );
+    }
+
+    #[test]
+    fn test_savitzky_golay_rejects_underdetermined_fit() {
+        assert!(SavitzkyGolayFilter::new(3, 3).is_none());
+        assert!(SavitzkyGolayFilter::new(5, 2).is_some());
+    }
+
+    #[test]
+    fn test_savitzky_golay_smooths_noisy_ramp() {
+        let mut filter = SavitzkyGolayFilter::new(15, 2).unwrap();
+        let ramp = |i: usize| i as f32 * 0.1;
+        // Alternating noise is the highest frequency the samples can carry
+        let noise = |i: usize| if i.is_multiple_of(2) { 0.5 } else { -0.5 };
+
+        let (mut raw_error, mut smoothed_error) = (0.0, 0.0);
+        for i in 0..200 {
+            let input = ramp(i) + noise(i);
+            let output = filter.apply(Vector3::new(input, 0.0, 0.0));
+            if i >= 15 {
+                raw_error += (input - ramp(i)).powi(2);
+                smoothed_error += (output.x - ramp(i)).powi(2);
+            }
+        }
+
+        assert!(
+            smoothed_error < raw_error * 0.25,
+            "Smoothing should suppress noise: raw {} smoothed {}",
+            raw_error,
+            smoothed_error
+        );
+
+        // Without noise the ramp is reproduced exactly, i.e. without lag
+        let mut filter = SavitzkyGolayFilter::new(15, 2).unwrap();
+        let mut output = Vector3::zeros();
+        for i in 0..50 {
+            output = filter.apply(Vector3::new(ramp(i), 0.0, 0.0));
+        }
+        assert_relative_eq!(output.x, ramp(49), epsilon = 1e-3);
+    }
+}
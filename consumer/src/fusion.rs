@@ -0,0 +1,157 @@
+use common::proto::ImuData;
+
+/// Samples further apart than this are not paired [ms]
+pub const MAX_TIMESTAMP_SKEW: u32 = 10;
+
+/// Pairs samples of two redundant IMU streams by nearest timestamp and
+/// averages each pair into one sample. Once a stream closes, samples of the
+/// other one are passed through unchanged.
+#[derive(Debug, Default)]
+pub struct StreamAligner {
+    pending: [Option<ImuData>; 2],
+    closed: [bool; 2],
+}
+
+impl StreamAligner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the sample to process, if any, after `sample` arrived on `stream`.
+    pub fn push(&mut self, stream: usize, sample: ImuData) -> Option<ImuData> {
+        let other = 1 - stream;
+        if self.closed[other] {
+            return Some(sample);
+        }
+
+        let Some(pending) = self.pending[other].take() else {
+            // The other stream is behind, keep only the newest sample
+            self.pending[stream] = Some(sample);
+            return None;
+        };
+
+        if pending.timestamp_acc.abs_diff(sample.timestamp_acc) <= MAX_TIMESTAMP_SKEW {
+            return Some(fuse_samples(&pending, &sample));
+        }
+
+        // Too far apart to pair, the older one will never find a match
+        if pending.timestamp_acc > sample.timestamp_acc {
+            self.pending[other] = Some(pending);
+        } else {
+            self.pending[stream] = Some(sample);
+        }
+        None
+    }
+
+    /// Marks `stream` as ended. Returns the unmatched sample of the other
+    /// stream, which would otherwise wait forever.
+    pub fn close(&mut self, stream: usize) -> Option<ImuData> {
+        self.closed[stream] = true;
+        self.pending[stream] = None;
+        self.pending[1 - stream].take()
+    }
+}
+
+/// Averages two samples of the same instant from redundant sensors.
+pub fn fuse_samples(a: &ImuData, b: &ImuData) -> ImuData {
+    let mean_i32 = |a: i32, b: i32| ((a as i64 + b as i64) / 2) as i32;
+    ImuData {
+        x_acc: (a.x_acc + b.x_acc) / 2.0,
+        y_acc: (a.y_acc + b.y_acc) / 2.0,
+        z_acc: (a.z_acc + b.z_acc) / 2.0,
+        timestamp_acc: a.timestamp_acc.max(b.timestamp_acc),
+        x_gyro: mean_i32(a.x_gyro, b.x_gyro),
+        y_gyro: mean_i32(a.y_gyro, b.y_gyro),
+        z_gyro: mean_i32(a.z_gyro, b.z_gyro),
+        timestamp_gyro: a.timestamp_gyro.max(b.timestamp_gyro),
+        x_mag: (a.x_mag + b.x_mag) / 2.0,
+        y_mag: (a.y_mag + b.y_mag) / 2.0,
+        z_mag: (a.z_mag + b.z_mag) / 2.0,
+        timestamp_mag: a.timestamp_mag.max(b.timestamp_mag),
+        timestamp_acc_us: a.timestamp_acc_us.max(b.timestamp_acc_us),
+        timestamp_gyro_us: a.timestamp_gyro_us.max(b.timestamp_gyro_us),
+        timestamp_mag_us: a.timestamp_mag_us.max(b.timestamp_mag_us),
+        // Ground truth describes the instant, not the sensor
+        ..*a
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(z_acc: f32, z_gyro: i32, timestamp: u32) -> ImuData {
+        ImuData {
+            z_acc,
+            timestamp_acc: timestamp,
+            z_gyro,
+            timestamp_gyro: timestamp,
+            timestamp_mag: timestamp,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_pairs_nearest_timestamps() {
+        let mut aligner = StreamAligner::new();
+
+        assert!(aligner.push(0, sample(1000.0, 100, 100)).is_none());
+        let fused = aligner.push(1, sample(1010.0, 200, 102)).unwrap();
+        assert_eq!(fused.z_acc, 1005.0);
+        assert_eq!(fused.z_gyro, 150);
+        assert_eq!(fused.timestamp_acc, 102);
+
+        // Stream 1 lags behind, its stale sample is dropped
+        assert!(aligner.push(0, sample(1000.0, 100, 200)).is_none());
+        assert!(aligner.push(1, sample(1000.0, 100, 150)).is_none());
+        let fused = aligner.push(1, sample(1000.0, 100, 199)).unwrap();
+        assert_eq!(fused.timestamp_acc, 200);
+    }
+
+    #[test]
+    fn test_passes_through_after_close() {
+        let mut aligner = StreamAligner::new();
+
+        assert!(aligner.push(0, sample(1000.0, 0, 100)).is_none());
+        let flushed = aligner.close(1).unwrap();
+        assert_eq!(flushed.timestamp_acc, 100);
+
+        let passed = aligner.push(0, sample(1000.0, 0, 110)).unwrap();
+        assert_eq!(passed.timestamp_acc, 110);
+    }
+
+    #[test]
+    fn test_fused_stream_is_less_noisy() {
+        // Deterministic xorshift noise, independent per sensor
+        let noise = |state: &mut u32| {
+            *state ^= *state << 13;
+            *state ^= *state >> 17;
+            *state ^= *state << 5;
+            (*state % 2001) as i32 - 1000
+        };
+        let variance = |samples: &[ImuData]| {
+            samples
+                .iter()
+                .map(|s| (s.z_gyro as f64).powi(2))
+                .sum::<f64>()
+                / samples.len() as f64
+        };
+
+        let (mut seed_a, mut seed_b) = (0x1234_5678, 0x9abc_def1);
+        let mut aligner = StreamAligner::new();
+        let (mut stream_a, mut stream_b, mut fused) = (Vec::new(), Vec::new(), Vec::new());
+
+        for t in 0..1000 {
+            let a = sample(1000.0, noise(&mut seed_a), t * 2);
+            let b = sample(1000.0, noise(&mut seed_b), t * 2 + 1);
+            stream_a.push(a);
+            stream_b.push(b);
+            assert!(aligner.push(0, a).is_none());
+            fused.push(aligner.push(1, b).unwrap());
+        }
+
+        let fused_variance = variance(&fused);
+        assert!(fused_variance < variance(&stream_a) * 0.6);
+        assert!(fused_variance < variance(&stream_b) * 0.6);
+    }
+}
@@ -0,0 +1,192 @@
+use crate::motion::MotionState;
+use nalgebra::Vector3;
+use std::fmt;
+use std::time::{Duration, Instant};
+use tokio::time::Interval;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConnectionState {
+    Connecting,
+    Connected,
+    Disconnected,
+}
+
+impl fmt::Display for ConnectionState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ConnectionState::Connecting => "connecting",
+            ConnectionState::Connected => "connected",
+            ConnectionState::Disconnected => "disconnected",
+        })
+    }
+}
+
+/// Answers "is it still working?" in one line, without per-sample logging.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HealthReport {
+    pub connection: ConnectionState,
+    pub samples: u64,
+    /// Age of the last processed sample, `None` before the first one
+    pub since_last_sample: Option<Duration>,
+    pub position: Vector3<f32>, // m
+    pub speed: f32,             // m/s
+}
+
+impl fmt::Display for HealthReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Status: {} | samples: {} | last sample: ",
+            self.connection, self.samples
+        )?;
+        match self.since_last_sample {
+            Some(age) => write!(f, "{}ms ago", age.as_millis())?,
+            None => write!(f, "never")?,
+        }
+        write!(
+            f,
+            " | position: [{:+.3},{:+.3},{:+.3}]m | speed: {:.3}m/s",
+            self.position.x, self.position.y, self.position.z, self.speed
+        )
+    }
+}
+
+/// Liveness of the consumer, updated by the processing loop and read by the
+/// status reporter.
+#[derive(Debug, Clone)]
+pub struct HealthTracker {
+    connection: ConnectionState,
+    samples: u64,
+    last_sample: Option<Instant>,
+    position: Vector3<f32>,
+    speed: f32,
+}
+
+impl Default for HealthTracker {
+    fn default() -> Self {
+        Self {
+            connection: ConnectionState::Connecting,
+            samples: 0,
+            last_sample: None,
+            position: Vector3::zeros(),
+            speed: 0.0,
+        }
+    }
+}
+
+impl HealthTracker {
+    pub fn set_connection(&mut self, connection: ConnectionState) {
+        self.connection = connection;
+    }
+
+    /// Notes a processed sample and the state it produced.
+    pub fn record_sample(&mut self, state: &MotionState) {
+        self.samples += 1;
+        self.last_sample = Some(Instant::now());
+        self.position = state.position;
+        self.speed = state.velocity.norm();
+    }
+
+    pub fn report(&self) -> HealthReport {
+        HealthReport {
+            connection: self.connection,
+            samples: self.samples,
+            since_last_sample: self.last_sample.map(|last| last.elapsed()),
+            position: self.position,
+            speed: self.speed,
+        }
+    }
+}
+
+/// When to print a status report: every `interval` if set and, on Unix,
+/// whenever the process receives SIGUSR1.
+pub struct StatusTrigger {
+    ticker: Option<Interval>,
+    #[cfg(unix)]
+    signal: Option<tokio::signal::unix::Signal>,
+}
+
+impl StatusTrigger {
+    pub fn new(interval: Option<Duration>) -> Self {
+        Self {
+            ticker: interval.map(|period| {
+                tokio::time::interval_at(tokio::time::Instant::now() + period, period)
+            }),
+            #[cfg(unix)]
+            signal: tokio::signal::unix::signal(tokio::signal::unix::SignalKind::user_defined1())
+                .ok(),
+        }
+    }
+
+    /// Resolves when the next report is due.
+    pub async fn wait(&mut self) {
+        let tick = async {
+            match self.ticker.as_mut() {
+                Some(ticker) => {
+                    ticker.tick().await;
+                }
+                None => std::future::pending().await,
+            }
+        };
+
+        #[cfg(unix)]
+        let signal = async {
+            let received = match self.signal.as_mut() {
+                Some(signal) => signal.recv().await,
+                None => None,
+            };
+            if received.is_none() {
+                std::future::pending::<()>().await;
+            }
+        };
+        #[cfg(not(unix))]
+        let signal = std::future::pending::<()>();
+
+        tokio::select! {
+            _ = tick => {}
+            _ = signal => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_before_and_after_samples() {
+        let mut tracker = HealthTracker::default();
+        assert_eq!(
+            tracker.report().to_string(),
+            "Status: connecting | samples: 0 | last sample: never | position: [+0.000,+0.000,+0.000]m | speed: 0.000m/s"
+        );
+
+        tracker.set_connection(ConnectionState::Connected);
+        let mut state = MotionState::default();
+        state.position = Vector3::new(1.0, -2.0, 0.5);
+        state.velocity = Vector3::new(3.0, 4.0, 0.0);
+        tracker.record_sample(&state);
+        tracker.record_sample(&state);
+
+        let report = tracker.report();
+        assert_eq!(report.connection, ConnectionState::Connected);
+        assert_eq!(report.samples, 2);
+        assert!(report.since_last_sample.unwrap() < Duration::from_secs(1));
+        assert_eq!(report.speed, 5.0);
+        assert!(
+            report
+                .to_string()
+                .contains("position: [+1.000,-2.000,+0.500]m")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_trigger_fires_every_interval() {
+        let mut trigger = StatusTrigger::new(Some(Duration::from_millis(20)));
+        let start = Instant::now();
+
+        trigger.wait().await;
+        trigger.wait().await;
+        assert!(start.elapsed() >= Duration::from_millis(40));
+    }
+}
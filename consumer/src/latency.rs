@@ -0,0 +1,114 @@
+use std::fmt;
+
+/// Upper bounds of the histogram buckets [us], the last bucket is open.
+const BUCKET_BOUNDS_US: [u64; 4] = [100, 1_000, 10_000, 100_000];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LatencySummary {
+    pub samples: u64,
+    pub min_us: u64,
+    pub mean_us: u64,
+    pub max_us: u64,
+    /// Counts below 0.1ms, 1ms, 10ms, 100ms and above
+    pub histogram: [u64; BUCKET_BOUNDS_US.len() + 1],
+    /// Frames stamped after they were received, the clocks disagree
+    pub clock_skewed: u64,
+}
+
+impl fmt::Display for LatencySummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let labels = ["<0.1ms", "<1ms", "<10ms", "<100ms", ">=100ms"];
+        for (i, (label, count)) in labels.iter().zip(self.histogram).enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            write!(f, "{}:{}", label, count)?;
+        }
+        Ok(())
+    }
+}
+
+/// Transport latency of frames stamped with their send time, the receive
+/// time minus the send time.
+///
+/// Both are wall-clock times, so the numbers only mean something with
+/// publisher and consumer on the same host. Frames that seem to arrive
+/// before they were sent are counted as clock skew and left out.
+#[derive(Debug, Clone, Default)]
+pub struct LatencyTracker {
+    samples: u64,
+    min_us: u64,
+    max_us: u64,
+    sum_us: u64,
+    histogram: [u64; BUCKET_BOUNDS_US.len() + 1],
+    clock_skewed: u64,
+}
+
+impl LatencyTracker {
+    /// Records one frame, returns its latency [us] unless the clocks are skewed.
+    pub fn record(&mut self, send_time_us: u64, receive_time_us: u64) -> Option<u64> {
+        let Some(latency) = receive_time_us.checked_sub(send_time_us) else {
+            self.clock_skewed += 1;
+            return None;
+        };
+
+        self.min_us = if self.samples == 0 {
+            latency
+        } else {
+            self.min_us.min(latency)
+        };
+        self.max_us = self.max_us.max(latency);
+        self.sum_us = self.sum_us.saturating_add(latency);
+        self.samples += 1;
+        let bucket = BUCKET_BOUNDS_US
+            .iter()
+            .position(|&bound| latency < bound)
+            .unwrap_or(BUCKET_BOUNDS_US.len());
+        self.histogram[bucket] += 1;
+        Some(latency)
+    }
+
+    /// `None` until a frame with a usable send time was recorded.
+    pub fn summary(&self) -> Option<LatencySummary> {
+        (self.samples > 0).then(|| LatencySummary {
+            samples: self.samples,
+            min_us: self.min_us,
+            mean_us: self.sum_us / self.samples,
+            max_us: self.max_us,
+            histogram: self.histogram,
+            clock_skewed: self.clock_skewed,
+        })
+    }
+
+    pub fn clock_skewed(&self) -> u64 {
+        self.clock_skewed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary_and_histogram() {
+        let mut tracker = LatencyTracker::default();
+        assert_eq!(tracker.summary(), None);
+
+        assert_eq!(tracker.record(1_000, 1_050), Some(50));
+        assert_eq!(tracker.record(1_000, 1_500), Some(500));
+        assert_eq!(tracker.record(1_000, 251_000), Some(250_000));
+        // Sent after it arrived, the publisher clock runs ahead
+        assert_eq!(tracker.record(2_000, 1_000), None);
+
+        let summary = tracker.summary().unwrap();
+        assert_eq!(summary.samples, 3);
+        assert_eq!((summary.min_us, summary.max_us), (50, 250_000));
+        assert_eq!(summary.mean_us, 250_550 / 3);
+        assert_eq!(summary.histogram, [1, 1, 0, 0, 1]);
+        assert_eq!(summary.clock_skewed, 1);
+        assert_eq!(
+            summary.to_string(),
+            "<0.1ms:1 <1ms:1 <10ms:0 <100ms:0 >=100ms:1"
+        );
+    }
+}
@@ -0,0 +1,22 @@
+pub mod accuracy;
+pub mod alignment;
+pub mod cli;
+pub mod consumer;
+pub mod drift;
+pub mod error;
+pub mod export;
+pub mod filters;
+pub mod fusion;
+pub mod health;
+pub mod latency;
+pub mod motion;
+pub mod orientation;
+pub mod position;
+pub mod ratelimit;
+pub mod steps;
+pub mod summary;
+pub mod tee;
+pub mod watchdog;
+
+#[cfg(test)]
+mod test_support;
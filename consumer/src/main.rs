@@ -1,14 +1,172 @@
-mod cli;
-mod consumer;
-mod motion;
+use consumer::consumer::{CalibrationRun, Consumer};
+use consumer::{cli, drift, export, filters, steps, tee, watchdog};
 
-#[tokio::main]
-async fn main() -> std::io::Result<()> {
+fn main() -> std::io::Result<()> {
     let args = cli::ConsumerArgs::parse();
+    // One thread avoids cross-thread wakeups and their jitter, at the cost
+    // of parallelism and of stalling everything on a blocking task
+    let mut runtime = if args.single_thread {
+        tokio::runtime::Builder::new_current_thread()
+    } else {
+        tokio::runtime::Builder::new_multi_thread()
+    };
+    runtime.enable_all().build()?.block_on(run(args))
+}
+
+async fn run(args: cli::ConsumerArgs) -> std::io::Result<()> {
+    if args.show_config {
+        println!("{}", args.to_json());
+        return Ok(());
+    }
+    if args.print_capabilities {
+        println!("{}", cli::ConsumerArgs::capabilities_json());
+    }
+
     let logger = common::logging::setup_logger(args.log_level.clone());
     cli::ConsumerArgs::print(&args, &logger);
 
-    consumer::Consumer::new(args.socket_path, args.timeout, logger.clone())
-        .run()
-        .await
+    if args.socket_path.len() > 1 && args.socket_path_2.is_some() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "--socket-path-2 cannot be combined with standby --socket-path publishers",
+        ));
+    }
+    // clap falls back to the default path, there is always a first one
+    let mut socket_paths = args.socket_path.into_iter();
+    let mut consumer = Consumer::new(socket_paths.next().unwrap(), args.timeout, logger.clone());
+
+    consumer.set_standby_socket_paths(socket_paths.collect());
+    consumer.set_keep_state_on_failover(args.keep_state_on_failover);
+
+    consumer.set_secondary_socket_path(args.socket_path_2);
+    consumer.set_input_file(args.input_file);
+    consumer.set_input_jsonl(args.replay_from_jsonl);
+    consumer.set_warmup_samples(args.warmup_samples);
+    consumer.set_log_gravity(args.log_gravity);
+    consumer.set_log_heading(args.log_heading);
+    let mut calibration_runs = Vec::new();
+    if args.calibrate_mag {
+        calibration_runs.push(CalibrationRun::Mag);
+    }
+    if args.calibrate_acc_scale {
+        calibration_runs.push(CalibrationRun::AccScale);
+    }
+    if let Some((axis, angle)) = args.calibrate_gyro_scale {
+        calibration_runs.push(CalibrationRun::GyroScale { axis, angle });
+    }
+    consumer.set_calibration_runs(calibration_runs);
+    consumer.set_compare_ground_truth(args.compare_ground_truth);
+    consumer.set_print_summary(args.summary);
+    if let Some(path) = &args.traj_out {
+        consumer.set_trajectory_output(Some(export::TumWriter::create(path)?));
+    }
+    consumer.set_timing_anomaly_factor(args.timing_anomaly_factor);
+    consumer.set_length_endianness(args.length_endianness);
+    consumer.set_wire_format(args.format);
+    consumer.set_frame_magic(args.protocol_version >= 2);
+    consumer.set_compression(args.compression);
+    consumer.set_read_buffer_hint(args.read_buffer_hint as usize);
+    consumer.set_max_frame_size(args.max_frame_size as usize);
+    consumer.set_warn_rate_limit(args.warn_rate_limit);
+    consumer.set_max_decode_errors(args.max_decode_errors);
+    consumer.set_quaternion_convention(args.quaternion_convention);
+    consumer.set_output_rate(args.output_rate);
+    if let Some(path) = &args.tee {
+        let mut tee = tee::Tee::bind(path, args.length_endianness, logger.clone())?;
+        tee.set_frame_magic(args.protocol_version >= 2);
+        consumer.set_tee(Some(tee));
+    }
+    consumer.set_status_interval(
+        args.status_interval
+            .map(|seconds| std::time::Duration::from_secs(seconds.into())),
+    );
+
+    let motion_processor = consumer.motion_processor_mut();
+    motion_processor.set_acc_deadband(args.acc_deadband);
+    motion_processor.set_zero_altitude_prior(
+        args.zero_altitude_prior
+            .then_some((args.kalman_process_noise, args.altitude_prior_variance)),
+    );
+    motion_processor.set_acc_high_pass_cutoff(args.acc_high_pass);
+    motion_processor.set_gyro_only(args.gyro_only);
+    motion_processor.set_history_capacity(args.state_history as usize);
+    motion_processor.set_accel_gravity(args.accel_gravity);
+    motion_processor.set_accel_units(args.accel_units);
+    motion_processor.set_angle_units(args.angle_units);
+    motion_processor.set_verbose_motion(args.verbose_motion);
+    motion_processor.set_use_receive_clock(args.use_receive_clock);
+    motion_processor.set_orientation_only(args.orientation_only);
+    motion_processor.set_orientation_smoothing(args.orientation_smoothing);
+    motion_processor.set_position_bound(args.max_position, args.position_bound_policy);
+    motion_processor.set_max_sample_gap(
+        args.max_sample_gap.map(|ms| ms as f32 / 1000.0),
+        args.sample_gap_policy,
+    );
+    if let Some(tolerance) = args.gravity_tolerance {
+        motion_processor.set_gravity_tolerance(tolerance);
+    } else if args.gravity_window_min >= args.gravity_window_max {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "--gravity-window-min must be below --gravity-window-max",
+        ));
+    } else {
+        motion_processor.set_gravity_window(args.gravity_window_min, args.gravity_window_max);
+    }
+    motion_processor.set_acc_weight_curve(args.acc_weight_curve);
+    if let Some(cutoff) = args.filter_cutoff {
+        motion_processor.set_complementary_cutoff_hz(cutoff, args.sample_rate as f32);
+    }
+    if let Some(window) = args.gyro_smoothing_window {
+        let filter =
+            filters::SavitzkyGolayFilter::new(window as usize, args.gyro_smoothing_order as usize)
+                .ok_or_else(|| {
+                    std::io::Error::new(
+                        std::io::ErrorKind::InvalidInput,
+                        "--gyro-smoothing-order must be below --gyro-smoothing-window",
+                    )
+                })?;
+        motion_processor.set_gyro_smoothing(Some(filter));
+    }
+    motion_processor.set_step_detector(
+        args.step_threshold
+            .map(|threshold| steps::StepDetector::new(threshold, args.min_step_interval)),
+    );
+    motion_processor
+        .set_sensor_watchdog(args.stale_sensor_timeout.map(watchdog::SensorWatchdog::new));
+    motion_processor.set_drift_monitor(args.warn_on_drift.map(drift::DriftMonitor::new));
+    motion_processor
+        .set_drift_rate_estimator(args.drift_rate_interval.map(drift::DriftRateEstimator::new));
+    motion_processor.set_motion_gate(args.motion_gate);
+    motion_processor.set_align_sensors(args.align_sensors);
+    if let Some([roll, pitch, yaw]) = args.mount_rotation {
+        motion_processor.set_mount_rotation(nalgebra::UnitQuaternion::from_euler_angles(
+            roll.to_radians(),
+            pitch.to_radians(),
+            yaw.to_radians(),
+        ));
+    }
+    if let Some(path) = &args.load_state {
+        motion_processor.load_state(path)?;
+        common::slog::info!(logger, "Loaded state from {}", path.display());
+    }
+    if let Some(path) = &args.bias_file {
+        motion_processor.load_calibration(path)?;
+        common::slog::info!(logger, "Loaded calibration from {}", path.display());
+    }
+
+    let result = consumer.run().await;
+    common::slog::info!(logger, "{}", consumer.status_report());
+    if let Some(path) = &args.save_bias_file {
+        consumer.motion_processor_mut().save_calibration(path)?;
+        common::slog::info!(logger, "Saved calibration to {}", path.display());
+    }
+    if let Some(path) = &args.save_state {
+        consumer.motion_processor_mut().save_state(path)?;
+        common::slog::info!(logger, "Saved state to {}", path.display());
+    }
+    if let Some(path) = &args.save_recent_states {
+        consumer.motion_processor_mut().save_recent_states(path)?;
+        common::slog::info!(logger, "Saved recent states to {}", path.display());
+    }
+    Ok(result?)
 }
@@ -1,17 +1,183 @@
+use crate::alignment::SensorAligner;
+use crate::drift::{DriftMonitor, DriftRateEstimator, MotionGate};
+use crate::filters::{HighPassFilter, SavitzkyGolayFilter};
+use crate::orientation::{
+    AccWeightCurve, ComplementaryFilter, DEFAULT_GRAVITY_WINDOW, OrientationFilter,
+    gravity_consistency,
+};
+use crate::position::{DeadReckoning, PositionEstimator, ZeroAltitudePrior};
+use crate::steps::StepDetector;
+use crate::watchdog::{SensorStatus, SensorWatchdog};
+use common::clap;
+use common::cli_defaults::DEFAULT_STATE_HISTORY;
 use common::proto::ImuData;
-use common::slog::{Logger, debug, warn};
-use nalgebra::{Quaternion, UnitQuaternion, Vector3};
+use common::slog::{Logger, debug, info, warn};
+use common::units::{self, AccelUnits, AngleUnits, ImuDataExt, STANDARD_GRAVITY};
+use nalgebra::{UnitQuaternion, Vector3};
+use std::collections::VecDeque;
+use std::path::Path;
+use std::time::Instant;
 
+const STATE_SNAPSHOT_VERSION: u32 = 2;
+const CALIBRATION_VERSION: u32 = 1;
 const MIN_DELTA_TIME: f32 = 0.001;
 const MAX_DELTA_TIME: f32 = 0.1;
+const GRAVITY_TRACKING_TIME_CONSTANT: f32 = 1.0; // s
+const RENORMALIZE_EVERY: u32 = 100; // orientation updates
+const MAX_NORM_DEVIATION: f32 = 1e-5;
 
-#[derive(Debug, Clone)]
+const FREE_FALL_THRESHOLD_MG: f32 = 300.0;
+const FREE_FALL_MIN_DURATION_MS: u32 = 100;
+const HIGH_G_THRESHOLD_MG: f32 = 3000.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MotionEvent {
+    /// Accel magnitude stayed near 0g for at least `FREE_FALL_MIN_DURATION_MS`
+    FreeFall { timestamp: u32, duration_ms: u32 },
+    /// Accel magnitude crossed `HIGH_G_THRESHOLD_MG`
+    HighImpact { timestamp: u32, magnitude_mg: f32 },
+}
+
+/// What happens once the integrated position leaves the sanity bound.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PositionBoundPolicy {
+    /// Pull the position back onto the bound, keeping its direction
+    Clamp,
+    /// Restart dead reckoning from zero position and velocity
+    Reset,
+}
+
+/// A sensor axis in the body frame.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Axis {
+    X,
+    Y,
+    Z,
+}
+
+impl Axis {
+    fn index(self) -> usize {
+        match self {
+            Axis::X => 0,
+            Axis::Y => 1,
+            Axis::Z => 2,
+        }
+    }
+}
+
+/// How the estimate restarts after a gap in the sample stream.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SampleGapPolicy {
+    /// Restart dead reckoning from zero position and velocity
+    Reset,
+    /// Also re-level the orientation from the accel, keeping the heading
+    ResetAndLevel,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SkipReason {
+    /// Time since the previous gyro sample exceeded `MAX_DELTA_TIME`
+    ExcessiveGyroDelta { dt: f32 },
+    /// Rotation over the sample was too small to be worth integrating
+    SmallAngle { angle: f32 },
+    /// Time since the previous accel sample exceeded `MAX_DELTA_TIME`
+    ExcessiveAccDelta { dt: f32 },
+    /// Velocity and position are held at rest until motion first begins
+    AwaitingMotion,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct StateSnapshot {
+    version: u32,
+    state: MotionState,
+    calibration: Calibration,
+    gravity_body: Option<Vector3<f32>>,
+}
+
+/// Sensor corrections worth reusing across runs, see `save_calibration`.
+/// Vectors serialize as `[x, y, z]`.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Calibration {
+    /// Subtracted from every accel reading [mg]
+    pub acc_bias: Vector3<f32>,
+    /// Per-axis accel scale applied after the bias, absent in older files
+    #[serde(default = "unit_scale")]
+    pub acc_scale: Vector3<f32>,
+    /// Subtracted from every gyro reading [mdps]
+    pub gyro_bias: Vector3<f32>,
+    /// Per-axis gyro scale applied after the bias, absent in older files
+    #[serde(default = "unit_scale")]
+    pub gyro_scale: Vector3<f32>,
+    /// Hard-iron offset subtracted from every magnetometer reading [mGauss]
+    pub mag_bias: Vector3<f32>,
+    /// Soft-iron scale applied after the hard-iron offset, absent in older files
+    #[serde(default = "unit_scale")]
+    pub mag_scale: Vector3<f32>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct CalibrationFile {
+    version: u32,
+    calibration: Calibration,
+}
+
+#[derive(Debug)]
+pub struct ProcessOutcome<'a> {
+    pub state: &'a MotionState,
+    pub orientation_updated: bool,
+    pub position_updated: bool,
+    /// First reason an update was skipped, orientation takes precedence
+    pub skipped_reason: Option<SkipReason>,
+}
+
+/// Vectors serialize as `[x, y, z]` and the orientation as `[i, j, k, w]`.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
 pub struct MotionState {
     pub orientation: UnitQuaternion<f32>,
     pub velocity: Vector3<f32>,
     pub position: Vector3<f32>,
+    /// Bias-corrected gyro rate of the last orientation update [rad/s]
+    #[serde(default)]
+    pub angular_velocity: Vector3<f32>,
+    /// World-frame acceleration with gravity removed of the last position
+    /// update [m/s^2]
+    #[serde(default)]
+    pub linear_acceleration: Vector3<f32>,
+    /// Change of `linear_acceleration` since the previous position update
+    /// per second, e.g. for vibration analysis [m/s^3]
+    #[serde(default)]
+    pub jerk: Vector3<f32>,
+    /// How far the accel magnitude of the last sample is from 1g, as a
+    /// trust signal for the orientation: 1 at exactly 1g, falling to 0 at
+    /// the edges of the gravity window, where the accel stops correcting
+    /// the gyro. Always 0 in gyro-only mode
+    #[serde(default)]
+    pub orientation_confidence: f32,
     last_acc_timestamp: u32,
     last_gyro_timestamp: u32,
+    #[serde(default)]
+    last_acc_timestamp_us: Option<u64>,
+    #[serde(default)]
+    last_gyro_timestamp_us: Option<u64>,
+    #[serde(default)]
+    smoothed_orientation: Option<UnitQuaternion<f32>>,
+}
+
+impl MotionState {
+    /// Unit vector pointing down, towards the earth, in the body frame.
+    pub fn gravity_direction_body(&self) -> Vector3<f32> {
+        self.orientation.inverse_transform_vector(&-Vector3::z())
+    }
+
+    /// Orientation for display, trailing `orientation` without its
+    /// per-sample jitter. Equal to `orientation` unless smoothing is enabled
+    /// with `MotionProcessor::set_orientation_smoothing`.
+    pub fn smoothed_orientation(&self) -> UnitQuaternion<f32> {
+        self.smoothed_orientation.unwrap_or(self.orientation)
+    }
 }
 
 impl Default for MotionState {
@@ -20,8 +186,15 @@ impl Default for MotionState {
             orientation: UnitQuaternion::identity(),
             velocity: Vector3::zeros(),
             position: Vector3::zeros(),
+            angular_velocity: Vector3::zeros(),
+            linear_acceleration: Vector3::zeros(),
+            jerk: Vector3::zeros(),
+            orientation_confidence: 0.0,
             last_acc_timestamp: 0,
             last_gyro_timestamp: 0,
+            last_acc_timestamp_us: None,
+            last_gyro_timestamp_us: None,
+            smoothed_orientation: None,
         }
     }
 }
@@ -31,11 +204,51 @@ pub struct MotionProcessor {
     state: MotionState,
     logger: Logger,
     acc_bias: Vector3<f32>,
+    acc_scale: Vector3<f32>,
     gyro_bias: Vector3<f32>,
-    gyro_weight: f32,
-    acc_weight: f32,
+    gyro_scale: Vector3<f32>,
+    mag_bias: Vector3<f32>,
+    mag_scale: Vector3<f32>,
     velocity_decay: f32,
+    acc_deadband: f32,
+    position_estimator: Box<dyn PositionEstimator>,
+    custom_position_estimator: bool,
+    zero_altitude_prior: Option<(f32, f32)>,
+    acc_high_pass: Option<HighPassFilter>,
+    gyro_smoothing: Option<SavitzkyGolayFilter>,
+    position_bound: Option<(f32, PositionBoundPolicy)>,
+    position_bound_warned: bool,
+    max_sample_gap: Option<(f32, SampleGapPolicy)>,
     disable_complementary_filter: bool,
+    gravity_window: (f32, f32),                // g, after unit conversion
+    complementary_filter: ComplementaryFilter, // settings of the built-in filter
+    orientation_filter: Box<dyn OrientationFilter>,
+    custom_orientation_filter: bool,
+    accel_gravity: bool,
+    accel_units: AccelUnits,
+    angle_units: AngleUnits,
+    gravity_body: Option<Vector3<f32>>,
+    free_fall_start: Option<u32>,
+    free_fall_reported: bool,
+    high_g_active: bool,
+    events: Vec<MotionEvent>,
+    step_detector: Option<StepDetector>,
+    sensor_watchdog: Option<SensorWatchdog>,
+    drift_monitor: Option<DriftMonitor>,
+    drift_rate: Option<DriftRateEstimator>,
+    motion_gate: Option<MotionGate>,
+    sensor_aligner: Option<SensorAligner>,
+    mount_rotation: UnitQuaternion<f32>, // sensor to vehicle
+    step_detected: bool,
+    use_receive_clock: bool,
+    orientation_only: bool,
+    orientation_smoothing: Option<f32>,
+    verbose_motion: bool,
+    last_receive: Option<Instant>,
+    history: VecDeque<MotionState>,
+    history_capacity: usize,
+    updates_since_renormalize: u32,
+    max_norm_deviation: f32,
 }
 
 impl MotionProcessor {
@@ -44,251 +257,2941 @@ impl MotionProcessor {
             state: MotionState::default(),
             logger,
             acc_bias: Vector3::zeros(),
+            acc_scale: unit_scale(),
             gyro_bias: Vector3::zeros(),
-            gyro_weight: 0.98,
-            acc_weight: 0.02,
+            gyro_scale: unit_scale(),
+            mag_bias: Vector3::zeros(),
+            mag_scale: unit_scale(),
             velocity_decay: 0.98,
+            acc_deadband: 0.01,
+            position_estimator: Box::new(DeadReckoning::new(0.98, 0.01)),
+            custom_position_estimator: false,
+            zero_altitude_prior: None,
+            acc_high_pass: None,
+            gyro_smoothing: None,
+            position_bound: None,
+            position_bound_warned: false,
+            max_sample_gap: None,
             disable_complementary_filter: false,
+            gravity_window: DEFAULT_GRAVITY_WINDOW,
+            complementary_filter: ComplementaryFilter::new(0.98, 0.02),
+            orientation_filter: Box::new(ComplementaryFilter::new(0.98, 0.02)),
+            custom_orientation_filter: false,
+            accel_gravity: false,
+            accel_units: AccelUnits::default(),
+            angle_units: AngleUnits::default(),
+            gravity_body: None,
+            free_fall_start: None,
+            free_fall_reported: false,
+            high_g_active: false,
+            events: Vec::new(),
+            step_detector: None,
+            sensor_watchdog: None,
+            drift_monitor: None,
+            drift_rate: None,
+            motion_gate: None,
+            sensor_aligner: None,
+            mount_rotation: UnitQuaternion::identity(),
+            step_detected: false,
+            use_receive_clock: false,
+            orientation_only: false,
+            orientation_smoothing: None,
+            verbose_motion: false,
+            last_receive: None,
+            history: VecDeque::new(),
+            history_capacity: DEFAULT_STATE_HISTORY as usize,
+            updates_since_renormalize: 0,
+            max_norm_deviation: 0.0,
         }
     }
 
-    pub fn process(&mut self, imu_data: &ImuData) -> &MotionState {
-        self.update_orientation(imu_data);
-        self.update_velocity_and_position(imu_data);
-        &self.state
+    pub fn process(&mut self, imu_data: &ImuData) -> ProcessOutcome<'_> {
+        let aligned;
+        let imu_data = match self.sensor_aligner.as_mut() {
+            Some(aligner) => {
+                aligned = aligner.align(imu_data);
+                &aligned
+            }
+            None => imu_data,
+        };
+        let acc_magnitude = self.acc_vector(imu_data).norm();
+        self.detect_events(acc_magnitude, imu_data.timestamp_acc);
+        self.step_detected = self
+            .step_detector
+            .as_mut()
+            .is_some_and(|detector| detector.update(acc_magnitude, imu_data.timestamp_acc));
+        self.check_sensors(imu_data);
+        self.check_sample_gap(imu_data);
+        let receive_dt = self.use_receive_clock.then(|| self.receive_dt());
+        let orientation_result = self.update_orientation(imu_data, receive_dt);
+        self.smooth_orientation();
+        let position_result = (!self.orientation_only)
+            .then(|| self.update_velocity_and_position(imu_data, receive_dt));
+        self.check_drift(acc_magnitude);
+        self.report_drift_rate(acc_magnitude, imu_data.timestamp_acc);
+        self.record_history();
+
+        ProcessOutcome {
+            state: &self.state,
+            orientation_updated: orientation_result.is_ok(),
+            position_updated: matches!(position_result, Some(Ok(()))),
+            skipped_reason: orientation_result
+                .and(position_result.unwrap_or(Ok(())))
+                .err(),
+        }
     }
 
-    fn update_orientation(&mut self, imu_data: &ImuData) {
-        let dt_gyro = if self.state.last_gyro_timestamp != 0 {
-            imu_data.timestamp_gyro.saturating_sub(self.state.last_gyro_timestamp) as f32 / 1000.0
-        } else {
-            MIN_DELTA_TIME
+    /// Writes the full integration state to `path` as JSON: the motion state,
+    /// the calibration and the `set_accel_gravity` estimate. Filter internals
+    /// such as the high-pass, the gyro smoothing and the altitude prior
+    /// variance restart and settle again within a few samples.
+    pub fn save_state(&self, path: &Path) -> std::io::Result<()> {
+        let snapshot = StateSnapshot {
+            version: STATE_SNAPSHOT_VERSION,
+            state: self.state.clone(),
+            calibration: self.calibration(),
+            gravity_body: self.gravity_body,
         };
-        self.state.last_gyro_timestamp = imu_data.timestamp_gyro;
+        let json = serde_json::to_string_pretty(&snapshot).map_err(std::io::Error::other)?;
+        std::fs::write(path, json)
+    }
 
-        if dt_gyro > MAX_DELTA_TIME {
-            warn!(self.logger, "Skipping orientation update due to excesive time delta"; "dt_gyro" => dt_gyro);
-            return;
+    /// Restores a state written by `save_state`. The current state is kept if
+    /// the snapshot is malformed or from an incompatible version.
+    pub fn load_state(&mut self, path: &Path) -> std::io::Result<()> {
+        let snapshot: StateSnapshot =
+            read_versioned_json(path, STATE_SNAPSHOT_VERSION, "state snapshot")?;
+        self.state = snapshot.state;
+        self.set_calibration(&snapshot.calibration);
+        self.gravity_body = snapshot.gravity_body;
+        debug!(self.logger, "Loaded state snapshot"; "calibration" => ?snapshot.calibration);
+        Ok(())
+    }
+
+    pub fn calibration(&self) -> Calibration {
+        Calibration {
+            acc_bias: self.acc_bias,
+            acc_scale: self.acc_scale,
+            gyro_bias: self.gyro_bias,
+            gyro_scale: self.gyro_scale,
+            mag_bias: self.mag_bias,
+            mag_scale: self.mag_scale,
         }
+    }
 
-        let gyro_x =
-            (imu_data.x_gyro as f32 - self.gyro_bias.x) * 0.001 * std::f32::consts::PI / 180.0;
-        let gyro_y =
-            (imu_data.y_gyro as f32 - self.gyro_bias.y) * 0.001 * std::f32::consts::PI / 180.0;
-        let gyro_z =
-            (imu_data.z_gyro as f32 - self.gyro_bias.z) * 0.001 * std::f32::consts::PI / 180.0;
+    pub fn set_calibration(&mut self, calibration: &Calibration) {
+        self.acc_bias = calibration.acc_bias;
+        self.acc_scale = calibration.acc_scale;
+        self.gyro_bias = calibration.gyro_bias;
+        self.gyro_scale = calibration.gyro_scale;
+        self.mag_bias = calibration.mag_bias;
+        self.mag_scale = calibration.mag_scale;
+    }
 
-        let gyro_vec = Vector3::new(gyro_x, gyro_y, gyro_z);
+    /// Writes the current biases and scale to `path` as JSON.
+    pub fn save_calibration(&self, path: &Path) -> std::io::Result<()> {
+        let file = CalibrationFile {
+            version: CALIBRATION_VERSION,
+            calibration: self.calibration(),
+        };
+        let json = serde_json::to_string_pretty(&file).map_err(std::io::Error::other)?;
+        std::fs::write(path, json)
+    }
 
-        const EPSILON: f32 = 1e-6;
-        let angle = gyro_vec.norm() * dt_gyro;
+    /// Applies a calibration written by `save_calibration`. The current one
+    /// is kept if the file is malformed or from an incompatible version.
+    pub fn load_calibration(&mut self, path: &Path) -> std::io::Result<()> {
+        let file: CalibrationFile = read_versioned_json(path, CALIBRATION_VERSION, "calibration")?;
+        self.set_calibration(&file.calibration);
+        debug!(self.logger, "Loaded calibration"; "calibration" => ?file.calibration);
+        Ok(())
+    }
 
-        if angle < EPSILON {
-            debug!(self.logger, "Skipping orientation update due to small angle"; "angle" => angle);
+    /// States after the most recent `process` calls, oldest first.
+    pub fn recent_states(&self) -> impl Iterator<Item = &MotionState> {
+        self.history.iter()
+    }
+
+    /// Number of recent states kept for `recent_states`, 0 disables the history.
+    pub fn set_history_capacity(&mut self, capacity: usize) {
+        self.history_capacity = capacity;
+        let excess = self.history.len().saturating_sub(capacity);
+        self.history.drain(..excess);
+    }
+
+    /// Writes the `recent_states` to `path` as a JSON array, oldest first.
+    pub fn save_recent_states(&self, path: &Path) -> std::io::Result<()> {
+        let json = serde_json::to_string_pretty(&self.history).map_err(std::io::Error::other)?;
+        std::fs::write(path, json)
+    }
+
+    fn record_history(&mut self) {
+        if self.history_capacity == 0 {
             return;
         }
+        if self.history.len() == self.history_capacity {
+            self.history.pop_front();
+        }
+        self.history.push_back(self.state.clone());
+    }
 
-        let axis = if gyro_vec.norm() > EPSILON {
-            gyro_vec.normalize()
-        } else {
-            Vector3::x()
-        };
+    /// Current yaw of the fused orientation in degrees, wrapped to [0, 360).
+    ///
+    /// Like every angle here it turns counterclockwise about +Z (up), from
+    /// +X at 0. A compass bearing turns clockwise instead: it is
+    /// `360 - heading` for +X pointing north.
+    pub fn heading(&self) -> f32 {
+        let (_, _, yaw) = self.state.orientation.euler_angles();
+        wrap_degrees(yaw.to_degrees())
+    }
 
-        let axis_unit = nalgebra::Unit::new_normalize(axis);
-        let gyro_quat = UnitQuaternion::from_axis_angle(&axis_unit, angle);
+    /// Tilt-compensated magnetic heading in degrees, wrapped to [0, 360).
+    ///
+    /// Levels the magnetometer reading using the roll and pitch of the current
+    /// orientation and returns the yaw that points magnetic north along +X,
+    /// counterclockwise about +Z like `heading`, so both agree for a fused
+    /// yaw that started out facing north. Does not modify the processor state.
+    pub fn tilt_compensated_heading_from_mag(&self, imu_data: &ImuData) -> f32 {
+        let (roll, pitch, _) = self.state.orientation.euler_angles();
+        let tilt = UnitQuaternion::from_euler_angles(roll, pitch, 0.0);
 
-        let gyro_orientation = self.state.orientation * gyro_quat;
+        let mag_body = self.mag_vector(imu_data);
+        let mag_level = tilt * mag_body;
 
-        if self.disable_complementary_filter {
-            debug!(
+        wrap_degrees((-mag_level.y).atan2(mag_level.x).to_degrees())
+    }
+
+    /// Pitch and roll in radians derived directly from the gravity vector of
+    /// the calibrated accel reading, free of gyro drift but only valid while
+    /// static. Re-levels the orientation for `SampleGapPolicy::ResetAndLevel`.
+    /// Returns zeros when the accel magnitude is too small to define "down".
+    pub fn tilt_angles_from_accel(&self, imu_data: &ImuData) -> (f32, f32) {
+        const MIN_ACC_MAGNITUDE: f32 = 1e-3; // mg
+
+        let acc = self.acc_vector(imu_data);
+        if acc.norm() < MIN_ACC_MAGNITUDE {
+            return (0.0, 0.0);
+        }
+
+        let pitch = (-acc.x).atan2((acc.y * acc.y + acc.z * acc.z).sqrt());
+        let roll = acc.y.atan2(acc.z);
+        (pitch, roll)
+    }
+
+    /// Derives the complementary filter weights from a crossover frequency.
+    ///
+    /// The blend `gyro_weight * gyro + acc_weight * acc` applied once per
+    /// sample acts as a first-order high-pass on the gyro path and low-pass on
+    /// the accel path with time constant `tau = 1 / (2 * PI * cutoff_hz)`:
+    /// `gyro_weight = tau / (tau + dt)` with `dt = 1 / sample_rate_hz`.
+    /// Fixed weights shift the crossover with the sample rate, this keeps it put.
+    pub fn set_complementary_cutoff_hz(&mut self, cutoff_hz: f32, sample_rate_hz: f32) {
+        let tau = 1.0 / (2.0 * std::f32::consts::PI * cutoff_hz);
+        let dt = 1.0 / sample_rate_hz;
+
+        let gyro_weight = tau / (tau + dt);
+        self.complementary_filter
+            .set_weights(gyro_weight, 1.0 - gyro_weight);
+        self.install_complementary_filter();
+    }
+
+    /// Bounds [mg] of the accel magnitude the complementary filter trusts
+    /// as gravity, 950-1050 by default. Outside them the orientation follows
+    /// the gyro alone. The magnitude is compared after conversion from the
+    /// `set_accel_units` input units, so the bounds hold for all of them.
+    pub fn set_gravity_window(&mut self, lower_mg: f32, upper_mg: f32) {
+        self.gravity_window = (lower_mg / 1000.0, upper_mg / 1000.0);
+        self.complementary_filter
+            .set_gravity_window(self.gravity_window.0, self.gravity_window.1);
+        self.install_complementary_filter();
+    }
+
+    /// Symmetric gravity window of 1g +- `tolerance` [g], e.g. 0.05 for the
+    /// default 0.95-1.05g.
+    pub fn set_gravity_tolerance(&mut self, tolerance: f32) {
+        self.gravity_window = (1.0 - tolerance, 1.0 + tolerance);
+        self.complementary_filter
+            .set_gravity_window(self.gravity_window.0, self.gravity_window.1);
+        self.install_complementary_filter();
+    }
+
+    /// How the complementary filter weighs the accel within the gravity
+    /// window, full weight throughout by default.
+    pub fn set_acc_weight_curve(&mut self, curve: AccWeightCurve) {
+        self.complementary_filter.set_acc_weight_curve(curve);
+        self.install_complementary_filter();
+    }
+
+    /// Replaces the default complementary filter with a custom estimator.
+    /// The settings of the complementary filter, `set_complementary_cutoff_hz`,
+    /// `set_gravity_window`, `set_acc_weight_curve` and `set_gyro_only`, no
+    /// longer apply and are ignored with a warning.
+    pub fn set_orientation_filter(&mut self, filter: Box<dyn OrientationFilter>) {
+        self.orientation_filter = filter;
+        self.custom_orientation_filter = true;
+    }
+
+    fn install_complementary_filter(&mut self) {
+        if self.custom_orientation_filter {
+            warn!(
                 self.logger,
-                "Complementary filter disabled, using gyro orientation"; "gyro_orientation" => ?gyro_orientation
-            );
-            self.state.orientation = gyro_orientation;
-        } else {
-            let acc_vec = Vector3::new(
-                imu_data.x_acc - self.acc_bias.x,
-                imu_data.y_acc - self.acc_bias.y,
-                imu_data.z_acc - self.acc_bias.z,
+                "Custom orientation filter in use, ignoring the complementary filter settings"
             );
+            return;
+        }
+        let mut filter = self.complementary_filter.clone();
+        if self.disable_complementary_filter {
+            filter.set_weights(1.0, 0.0);
+        }
+        self.orientation_filter = Box::new(filter);
+    }
 
-            let acc_magnitude = acc_vec.norm();
-            if (acc_magnitude > 950.0) && (acc_magnitude < 1050.0) {
-                let acc_norm = acc_vec / acc_magnitude;
-
-                let gravity = Vector3::new(0.0, 0.0, 1.0);
-                let gravity_unit = nalgebra::Unit::new_normalize(gravity);
-                let acc_norm_unit = nalgebra::Unit::new_normalize(acc_norm);
-
-                let acc_quat = UnitQuaternion::rotation_between(&gravity_unit, &acc_norm_unit)
-                    .unwrap_or(UnitQuaternion::identity());
-
-                self.state.orientation = UnitQuaternion::from_quaternion(
-                    Quaternion::new(
-                        self.gyro_weight * gyro_orientation.scalar()
-                            + self.acc_weight * acc_quat.scalar(),
-                        self.gyro_weight * gyro_orientation.vector().x
-                            + self.acc_weight * acc_quat.vector().x,
-                        self.gyro_weight * gyro_orientation.vector().y
-                            + self.acc_weight * acc_quat.vector().y,
-                        self.gyro_weight * gyro_orientation.vector().z
-                            + self.acc_weight * acc_quat.vector().z,
-                    )
-                    .normalize(),
-                );
-            } else {
-                self.state.orientation = gyro_orientation;
+    /// Per-axis scale correcting accel gain errors, applied after the bias
+    /// is removed.
+    pub fn set_acc_scale(&mut self, acc_scale: Vector3<f32>) {
+        self.acc_scale = acc_scale;
+    }
+
+    /// Six-position accel calibration from samples taken at rest with each
+    /// axis pointing up and down, `samples_per_axis[axis] = [up, down]`.
+    ///
+    /// Each axis then measures +1g and -1g. The mean of the two readings is
+    /// its bias and their half-difference its gain, so both the bias and the
+    /// scale are replaced. Keeps the current calibration if any position has
+    /// no samples or an axis reads the same both ways.
+    pub fn calibrate_acc_scale(&mut self, samples_per_axis: [[&[ImuData]; 2]; 3]) {
+        const MIN_SPAN: f32 = 1.0; // mg
+
+        let mean_along = |samples: &[ImuData], axis: usize| {
+            let sum: f32 = samples
+                .iter()
+                .map(|s| self.accel_units.to_mg([s.x_acc, s.y_acc, s.z_acc][axis]))
+                .sum();
+            (!samples.is_empty()).then(|| sum / samples.len() as f32)
+        };
+
+        let mut bias = Vector3::zeros();
+        let mut scale = Vector3::zeros();
+        for (axis, [up, down]) in samples_per_axis.into_iter().enumerate() {
+            let (Some(up), Some(down)) = (mean_along(up, axis), mean_along(down, axis)) else {
+                warn!(self.logger, "Missing accel samples for an axis, skipping scale calibration"; "axis" => axis);
+                return;
+            };
+            if (up - down).abs() < MIN_SPAN {
+                warn!(self.logger, "Accel axis reads the same up and down, skipping scale calibration"; "axis" => axis);
+                return;
             }
+            bias[axis] = (up + down) / 2.0;
+            scale[axis] = 2000.0 / (up - down);
         }
+
+        self.acc_bias = bias;
+        self.acc_scale = scale;
+        debug!(self.logger, "Calibrated accelerometer"; "acc_bias" => ?self.acc_bias, "acc_scale" => ?self.acc_scale);
     }
 
-    fn update_velocity_and_position(&mut self, imu_data: &ImuData) {
-        let dt_acc = if self.state.last_acc_timestamp != 0 {
-            imu_data.timestamp_acc.saturating_sub(self.state.last_acc_timestamp) as f32 / 1000.0
-        } else {
-            MIN_DELTA_TIME
-        };
-        self.state.last_acc_timestamp = imu_data.timestamp_acc;
+    /// Six-position accel calibration from one recorded run, resting the
+    /// device with each axis pointing up and down in turn.
+    ///
+    /// Samples are sorted into the six positions by the axis and sign that
+    /// dominate their reading, those taken while turning between positions,
+    /// with no clearly dominant axis, are dropped. Run from the CLI with
+    /// `--calibrate-acc-scale`.
+    pub fn calibrate_acc_scale_from_run(&mut self, samples: &[ImuData]) {
+        const MIN_DOMINANCE: f32 = 0.95; // of the magnitude
 
-        if dt_acc > MAX_DELTA_TIME {
-            warn!(self.logger, "Skipping velocity/position update due to excessive time delta"; "dt_acc" => dt_acc);
-            return;
+        let mut positions: [[Vec<ImuData>; 2]; 3] = Default::default();
+        for sample in samples {
+            let acc = Vector3::new(sample.x_acc, sample.y_acc, sample.z_acc)
+                .map(|a| self.accel_units.to_mg(a));
+            let axis = acc.iamax();
+            if acc[axis].abs() < MIN_DOMINANCE * acc.norm() {
+                continue;
+            }
+            positions[axis][usize::from(acc[axis] < 0.0)].push(*sample);
         }
 
-        let acc_body = Vector3::new(
-            (imu_data.x_acc - self.acc_bias.x) * 9.81 / 1000.0,
-            (imu_data.y_acc - self.acc_bias.y) * 9.81 / 1000.0,
-            (imu_data.z_acc - self.acc_bias.z) * 9.81 / 1000.0,
+        self.calibrate_acc_scale(
+            positions
+                .each_ref()
+                .map(|axis| axis.each_ref().map(Vec::as_slice)),
         );
+    }
 
-        let gravity = Vector3::new(0.0, 0.0, 9.81);
+    /// Per-axis scale correcting gyro scale-factor errors, applied after the
+    /// bias is removed.
+    pub fn set_gyro_scale(&mut self, gyro_scale: Vector3<f32>) {
+        self.gyro_scale = gyro_scale;
+    }
 
-        let acc_world = self.state.orientation * acc_body;
-        let acc_world_no_gravity = acc_world - gravity;
+    /// Estimates the scale of one gyro `axis` from samples recorded while
+    /// turning the device by a known `angle` [deg] about it, e.g. one full
+    /// 360 turn on a turntable. Run from the CLI with `--calibrate-gyro-scale`.
+    ///
+    /// The bias-corrected rate is integrated over the sample timestamps and
+    /// the scale set to the ratio of the known to the measured angle. The
+    /// other axes are untouched. Keeps the current scale if the samples
+    /// measured next to no rotation.
+    pub fn calibrate_gyro_scale(&mut self, samples: &[ImuData], axis: Axis, angle: f32) {
+        const MIN_MEASURED_ANGLE: f32 = 1.0; // deg
 
-        let acc_threshold = 0.01;
-        let filtered_acc =
-            acc_world_no_gravity.map(|a| if a.abs() < acc_threshold { 0.0 } else { a });
+        let index = axis.index();
+        let measured: f32 = samples
+            .windows(2)
+            .map(|pair| {
+                let dt = sensor_delta_time(
+                    (pair[1].timestamp_gyro, pair[1].timestamp_gyro_us),
+                    (pair[0].timestamp_gyro, pair[0].timestamp_gyro_us),
+                );
+                let rate = [pair[1].x_gyro, pair[1].y_gyro, pair[1].z_gyro][index] as f32
+                    - self.gyro_bias[index];
+                rate / 1000.0 * dt
+            })
+            .sum();
+        if measured.abs() < MIN_MEASURED_ANGLE {
+            warn!(self.logger, "Gyro samples measured next to no rotation, skipping scale calibration"; "axis" => ?axis, "measured_deg" => measured);
+            return;
+        }
 
-        self.state.velocity += filtered_acc * dt_acc;
-        self.state.velocity *= self.velocity_decay;
-        self.state.position += self.state.velocity * dt_acc;
+        self.gyro_scale[index] = angle / measured;
+        debug!(self.logger, "Calibrated gyro scale"; "axis" => ?axis, "measured_deg" => measured, "gyro_scale" => ?self.gyro_scale);
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use approx::assert_relative_eq;
-    use common::slog::{Discard, Logger, o};
+    /// Hard-iron offset [mGauss] subtracted from every magnetometer reading.
+    ///
+    /// The built-in complementary filter doesn't fuse the magnetometer, so the
+    /// mag calibration only affects the `--log-heading` mag heading and custom
+    /// `OrientationFilter`s.
+    pub fn set_mag_bias(&mut self, mag_bias: Vector3<f32>) {
+        self.mag_bias = mag_bias;
+    }
 
-    fn create_test_logger() -> Logger {
-        Logger::root(Discard, o!())
+    /// Estimates the hard-iron offset as the per-axis midpoint of min/max.
+    ///
+    /// The samples should cover a full rotation of the device so every axis
+    /// sees both extremes of the local field. Empty input keeps the current
+    /// bias. Run from the CLI with `--calibrate-mag`.
+    pub fn calibrate_mag_hard_iron(&mut self, samples: &[ImuData]) {
+        let Some((min, max)) = Self::mag_extremes(samples) else {
+            return;
+        };
+
+        self.mag_bias = (min + max) / 2.0;
+        debug!(self.logger, "Calibrated magnetometer hard-iron offset"; "mag_bias" => ?self.mag_bias);
     }
 
-    fn create_test_imu_data(
-        x_acc: f32,
-        y_acc: f32,
-        z_acc: f32,
-        x_gyro: i32,
-        y_gyro: i32,
-        z_gyro: i32,
-        timestamp: u32,
-    ) -> ImuData {
-        ImuData {
-            x_acc,
-            y_acc,
-            z_acc,
-            timestamp_acc: timestamp,
-            x_gyro,
-            y_gyro,
-            z_gyro,
-            timestamp_gyro: timestamp,
-            x_mag: 0.0,
-            y_mag: 0.0,
-            z_mag: 0.0,
-            timestamp_mag: timestamp,
+    /// Per-axis soft-iron scale applied to magnetometer readings after the
+    /// hard-iron offset is removed.
+    pub fn set_mag_scale(&mut self, mag_scale: Vector3<f32>) {
+        self.mag_scale = mag_scale;
+    }
+
+    /// Fits an axis-aligned soft-iron correction from per-axis min/max.
+    ///
+    /// Each axis is scaled so its half-range matches the average half-range,
+    /// turning the measured ellipsoid back into a sphere. Like the hard-iron
+    /// calibration it needs samples covering a full rotation. Run from the
+    /// CLI with `--calibrate-mag`.
+    pub fn calibrate_mag_soft_iron(&mut self, samples: &[ImuData]) {
+        const MIN_RADIUS: f32 = 1e-3; // mGauss
+
+        let Some((min, max)) = Self::mag_extremes(samples) else {
+            return;
+        };
+
+        let radii = (max - min) / 2.0;
+        if radii.min() < MIN_RADIUS {
+            warn!(self.logger, "Magnetometer samples don't span every axis, skipping soft-iron calibration"; "radii" => ?radii);
+            return;
         }
+
+        let average_radius = radii.sum() / 3.0;
+        self.mag_scale = radii.map(|r| average_radius / r);
+        debug!(self.logger, "Calibrated magnetometer soft-iron scale"; "mag_scale" => ?self.mag_scale);
     }
 
-    #[test]
-    fn test_default_motion_state() {
-        let state = MotionState::default();
-        assert_eq!(state.position, Vector3::zeros());
-        assert_eq!(state.velocity, Vector3::zeros());
-        assert_eq!(state.orientation, UnitQuaternion::identity());
-        assert_eq!(state.last_acc_timestamp, 0);
-        assert_eq!(state.last_gyro_timestamp, 0);
+    fn mag_extremes(samples: &[ImuData]) -> Option<(Vector3<f32>, Vector3<f32>)> {
+        let mut readings = samples
+            .iter()
+            .map(|s| Vector3::new(s.x_mag, s.y_mag, s.z_mag));
+        let first = readings.next()?;
+
+        Some(readings.fold((first, first), |(min, max), m| (min.inf(&m), max.sup(&m))))
     }
 
-    #[test]
-    fn test_acceleration_integration() {
-        let logger = create_test_logger();
-        let mut processor = MotionProcessor::new(logger);
+    /// Derives dt from sample arrival on the local monotonic clock instead of
+    /// the embedded sensor timestamps, e.g. when those are unreliable.
+    pub fn set_use_receive_clock(&mut self, use_receive_clock: bool) {
+        self.use_receive_clock = use_receive_clock;
+        self.last_receive = None;
+    }
 
-        processor.velocity_decay = 1.0;
+    /// Logs the intermediate quantities of every orientation update at debug
+    /// level, for tuning the filter.
+    pub fn set_verbose_motion(&mut self, verbose_motion: bool) {
+        self.verbose_motion = verbose_motion;
+    }
 
-        let imu_data = create_test_imu_data(1000.0, 0.0, 1000.0, 0, 0, 0, 1000);
+    pub fn verbose_motion(&self) -> bool {
+        self.verbose_motion
+    }
 
-        let total_time_steps = 100;
-        let dt_ms = 10;
-        let dt_s = dt_ms as f32 / 1000.0;
+    /// Tracks attitude only: velocity and position are never integrated and
+    /// stay at zero, avoiding the drifting position estimate.
+    pub fn set_orientation_only(&mut self, orientation_only: bool) {
+        self.orientation_only = orientation_only;
+    }
 
-        for i in 0..total_time_steps {
-            let mut data = imu_data;
-            let current_timestamp = 1000 + i * dt_ms;
-            data.timestamp_acc = current_timestamp;
-            data.timestamp_gyro = current_timestamp;
-            processor.process(&data);
-        }
+    /// Integrates the gyro alone, bypassing the accel correction and its
+    /// gravity window entirely. Useful as a baseline for gyro drift.
+    pub fn set_gyro_only(&mut self, gyro_only: bool) {
+        self.disable_complementary_filter = gyro_only;
+        self.install_complementary_filter();
+    }
 
-        let expected_velocity_x = 9.81 * (total_time_steps as f32 * dt_s);
+    /// Removes gravity along the low-passed accel direction in the body frame
+    /// instead of relying on the estimated orientation. Combined with
+    /// `set_gyro_only` the attitude output stays pure gyro while its drift no
+    /// longer leaks gravity into velocity and position.
+    ///
+    /// Only samples inside the gravity window feed the low-pass, so sustained
+    /// acceleration beyond it is not absorbed into gravity. Acceleration
+    /// within the window that lasts longer than about a second still is.
+    pub fn set_accel_gravity(&mut self, accel_gravity: bool) {
+        self.accel_gravity = accel_gravity;
+        self.gravity_body = None;
+    }
 
-        let expected_position_x = 0.5 * 9.81 * (total_time_steps as f32 * dt_s).powi(2);
+    /// Unit of the raw accel fields, converted to mg on arrival so calibration
+    /// and event thresholds keep working unchanged.
+    pub fn set_accel_units(&mut self, accel_units: AccelUnits) {
+        self.accel_units = accel_units;
+    }
 
-        println!(
-            "Final Velocity: {:?}, Final Position: {:?}",
-            processor.state.velocity, processor.state.position
-        );
+    /// Unit angles and angular rates are logged in, the math stays in radians.
+    pub fn set_angle_units(&mut self, angle_units: AngleUnits) {
+        self.angle_units = angle_units;
+    }
 
-        assert_relative_eq!(
-            processor.state.velocity.x,
-            expected_velocity_x,
-            epsilon = 0.1
-        );
-        assert_relative_eq!(
-            processor.state.position.x,
-            expected_position_x,
-            epsilon = 0.1
-        );
+    pub fn angle_units(&self) -> AngleUnits {
+        self.angle_units
+    }
 
-        assert_relative_eq!(processor.state.velocity.y, 0.0, epsilon = 0.01);
-        assert_relative_eq!(processor.state.position.y, 0.0, epsilon = 0.01);
+    /// World-frame accelerations below this magnitude [m/s^2] are treated as
+    /// noise and not integrated. Too small lets noise drift the velocity,
+    /// too large swallows real motion.
+    pub fn set_acc_deadband(&mut self, acc_deadband: f32) {
+        self.acc_deadband = acc_deadband;
+        self.install_position_estimator();
+    }
 
-        assert_relative_eq!(processor.state.velocity.z, 0.0, epsilon = 0.1);
-        assert_relative_eq!(processor.state.position.z, 0.0, epsilon = 0.1);
+    /// Factor applied to the velocity after every sample, 1.0 disables the decay.
+    pub fn set_velocity_decay(&mut self, velocity_decay: f32) {
+        self.velocity_decay = velocity_decay;
+        self.install_position_estimator();
     }
 
-    #[test]
-    fn test_complementary_filter() {
-        let logger = create_test_logger();
-        let mut processor = MotionProcessor::new(logger);
+    /// Holds the altitude near zero with a 1D Kalman filter instead of
+    /// integrating the vertical axis, given the process noise and prior
+    /// variances. See `ZeroAltitudePrior`. `None` restores plain dead
+    /// reckoning.
+    pub fn set_zero_altitude_prior(&mut self, noise: Option<(f32, f32)>) {
+        self.zero_altitude_prior = noise;
+        self.install_position_estimator();
+    }
+
+    /// Replaces the default dead reckoning with a custom estimator. The
+    /// settings of the built-in one, `set_acc_deadband`,
+    /// `set_velocity_decay` and `set_zero_altitude_prior`, no longer apply and
+    /// are ignored with a warning.
+    pub fn set_position_estimator(&mut self, estimator: Box<dyn PositionEstimator>) {
+        self.position_estimator = estimator;
+        self.custom_position_estimator = true;
+    }
 
-        processor.gyro_weight = 0.5;
-        processor.acc_weight = 0.5;
+    fn install_position_estimator(&mut self) {
+        if self.custom_position_estimator {
+            warn!(
+                self.logger,
+                "Custom position estimator in use, ignoring the dead reckoning settings"
+            );
+            return;
+        }
+        let dead_reckoning = DeadReckoning::new(self.velocity_decay, self.acc_deadband);
+        self.position_estimator = match self.zero_altitude_prior {
+            Some((process_noise, prior_variance)) => Box::new(ZeroAltitudePrior::new(
+                dead_reckoning,
+                process_noise,
+                prior_variance,
+            )),
+            None => Box::new(dead_reckoning),
+        };
+    }
 
-        let imu_data = create_test_imu_data(0.0, 0.0, 1000.0, 10000, 0, 0, 1000);
+    /// Enables a high-pass filter with the given cutoff [Hz] on the
+    /// gravity-removed world acceleration before integration, `None` disables it.
+    pub fn set_acc_high_pass_cutoff(&mut self, cutoff_hz: Option<f32>) {
+        self.acc_high_pass = cutoff_hz.map(HighPassFilter::new);
+    }
 
-        for i in 0..10 {
-            let mut data = imu_data;
-            data.timestamp_acc = 1000 + i * 10;
-            data.timestamp_gyro = 1000 + i * 10;
-            processor.process(&data);
+    /// Smooths the bias-corrected gyro rate before integration.
+    pub fn set_gyro_smoothing(&mut self, filter: Option<SavitzkyGolayFilter>) {
+        self.gyro_smoothing = filter;
+    }
+
+    /// Sanity bound on the position magnitude [m]. Drift beyond it is logged
+    /// once and handled according to `policy`.
+    pub fn set_position_bound(&mut self, max_position: Option<f32>, policy: PositionBoundPolicy) {
+        self.position_bound = max_position.map(|max_position| (max_position, policy));
+        self.position_bound_warned = false;
+    }
+
+    /// Restarts the estimate according to `policy` when the sensor
+    /// timestamps jump by more than `max_gap` [s], e.g. after a stalled
+    /// connection, instead of continuing from a stale state. Without it such
+    /// samples are skipped once and integration resumes where it stopped.
+    pub fn set_max_sample_gap(&mut self, max_gap: Option<f32>, policy: SampleGapPolicy) {
+        self.max_sample_gap = max_gap.map(|max_gap| (max_gap, policy));
+    }
+
+    /// Starts the estimate over: level, at rest at the origin and with no
+    /// previous sample to integrate from.
+    pub fn reset_state(&mut self) {
+        self.state = MotionState::default();
+        if let Some(gate) = self.motion_gate.as_mut() {
+            *gate = MotionGate::default();
         }
+    }
 
-        let (roll, _, _) = processor.state.orientation.euler_angles();
+    /// Keeps the estimate but integrates the next sample as the first one
+    /// of a new stream, whose timestamps need not continue the old ones.
+    pub fn restart_stream(&mut self) {
+        self.state.last_acc_timestamp = 0;
+        self.state.last_acc_timestamp_us = None;
+        self.state.last_gyro_timestamp = 0;
+        self.state.last_gyro_timestamp_us = None;
+    }
 
-        assert!(roll > 0.0);
-        assert!(roll < 0.17);
+    /// Zeroes velocity and position, the orientation is kept.
+    pub fn reset_position(&mut self) {
+        self.state.velocity = Vector3::zeros();
+        self.state.position = Vector3::zeros();
+        self.state.linear_acceleration = Vector3::zeros();
+        self.state.jerk = Vector3::zeros();
+    }
+
+    pub fn set_step_detector(&mut self, step_detector: Option<StepDetector>) {
+        self.step_detector = step_detector;
+    }
+
+    /// Warns about sensors whose timestamp stopped advancing, disabled if `None`.
+    pub fn set_sensor_watchdog(&mut self, sensor_watchdog: Option<SensorWatchdog>) {
+        self.sensor_watchdog = sensor_watchdog;
+    }
+
+    /// Warns when the velocity exceeds the monitor's threshold while the
+    /// device is at rest, disabled if `None`.
+    pub fn set_drift_monitor(&mut self, drift_monitor: Option<DriftMonitor>) {
+        self.drift_monitor = drift_monitor;
+    }
+
+    /// Interpolates the gyro and mag readings to the accel timestamp before
+    /// fusing them, for sensors sampled out of phase.
+    pub fn set_align_sensors(&mut self, align: bool) {
+        self.sensor_aligner = align.then(SensorAligner::default);
+    }
+
+    /// Rotation from the sensor to the vehicle frame, for an IMU mounted at
+    /// an angle. Calibrated readings are rotated by it before use, so the
+    /// orientation, world-frame acceleration and position describe the
+    /// vehicle rather than the sensor.
+    pub fn set_mount_rotation(&mut self, mount_rotation: UnitQuaternion<f32>) {
+        self.mount_rotation = mount_rotation;
+    }
+
+    /// Keeps velocity and position at rest until the accel or gyro first
+    /// leaves the still band of the stationary detector, so no drift builds
+    /// up while the device idles after startup. The orientation is always
+    /// tracked.
+    pub fn set_motion_gate(&mut self, enabled: bool) {
+        self.motion_gate = enabled.then(MotionGate::default);
+    }
+
+    /// Logs the orientation and position drift rate observed at rest once
+    /// per interval of the estimator, disabled if `None`.
+    pub fn set_drift_rate_estimator(&mut self, estimator: Option<DriftRateEstimator>) {
+        self.drift_rate = estimator;
+    }
+
+    /// Steps counted so far, `None` if step detection is disabled.
+    pub fn step_count(&self) -> Option<u32> {
+        self.step_detector.as_ref().map(StepDetector::step_count)
+    }
+
+    /// Whether the last processed sample completed a step.
+    pub fn step_detected(&self) -> bool {
+        self.step_detected
+    }
+
+    /// Drains the events detected since the last call.
+    pub fn take_events(&mut self) -> Vec<MotionEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    /// Time since the previous `process` call on the local monotonic clock,
+    /// clamped to the accepted delta range.
+    fn receive_dt(&mut self) -> f32 {
+        let now = Instant::now();
+        self.last_receive
+            .replace(now)
+            .map_or(MIN_DELTA_TIME, |last| {
+                now.duration_since(last).as_secs_f32()
+            })
+            .clamp(MIN_DELTA_TIME, MAX_DELTA_TIME)
+    }
+
+    /// Bias- and scale-corrected accel reading [mg]
+    fn acc_vector(&self, imu_data: &ImuData) -> Vector3<f32> {
+        self.mount_rotation
+            * (Vector3::new(imu_data.x_acc, imu_data.y_acc, imu_data.z_acc)
+                .map(|a| self.accel_units.to_mg(a))
+                - self.acc_bias)
+                .component_mul(&self.acc_scale)
+    }
+
+    fn mag_vector(&self, imu_data: &ImuData) -> Vector3<f32> {
+        self.mount_rotation
+            * Vector3::new(
+                imu_data.x_mag - self.mag_bias.x,
+                imu_data.y_mag - self.mag_bias.y,
+                imu_data.z_mag - self.mag_bias.z,
+            )
+            .component_mul(&self.mag_scale)
+    }
+
+    fn check_sensors(&mut self, imu_data: &ImuData) {
+        let Some(watchdog) = self.sensor_watchdog.as_mut() else {
+            return;
+        };
+
+        for status in watchdog.update(imu_data) {
+            match status {
+                SensorStatus::Stale { sensor, stale_ms } => {
+                    warn!(self.logger, "{:?} timestamp stopped advancing", sensor; "stale_ms" => stale_ms)
+                }
+                SensorStatus::Recovered { sensor } => {
+                    info!(self.logger, "{:?} timestamp advancing again", sensor)
+                }
+            }
+        }
+    }
+
+    fn check_drift(&mut self, acc_magnitude: f32) {
+        let Some(monitor) = self.drift_monitor.as_mut() else {
+            return;
+        };
+
+        let gyro_rate = self.state.angular_velocity.norm();
+        if let Some(speed) = monitor.update(acc_magnitude, gyro_rate, &self.state.velocity) {
+            warn!(self.logger, "Velocity drifting while stationary, consider recalibrating"; "velocity" => speed, "acc_bias" => ?self.acc_bias, "gyro_bias" => ?self.gyro_bias);
+        }
+    }
+
+    fn report_drift_rate(&mut self, acc_magnitude: f32, timestamp_ms: u32) {
+        let Some(estimator) = self.drift_rate.as_mut() else {
+            return;
+        };
+
+        let gyro_rate = self.state.angular_velocity.norm();
+        if let Some(rate) = estimator.update(
+            acc_magnitude,
+            gyro_rate,
+            timestamp_ms,
+            &self.state.orientation,
+            &self.state.position,
+        ) {
+            info!(self.logger, "Drift rate while stationary"; "orientation_deg_per_s" => rate.orientation, "position_m_per_s" => rate.position);
+        }
+    }
+
+    fn check_sample_gap(&mut self, imu_data: &ImuData) {
+        let Some((max_gap, policy)) = self.max_sample_gap else {
+            return;
+        };
+
+        let gap = [
+            (
+                (imu_data.timestamp_acc, imu_data.timestamp_acc_us),
+                (
+                    self.state.last_acc_timestamp,
+                    self.state.last_acc_timestamp_us,
+                ),
+            ),
+            (
+                (imu_data.timestamp_gyro, imu_data.timestamp_gyro_us),
+                (
+                    self.state.last_gyro_timestamp,
+                    self.state.last_gyro_timestamp_us,
+                ),
+            ),
+        ]
+        .into_iter()
+        .filter(|(_, (last, _))| *last != 0)
+        .map(|(current, last)| sensor_delta_time(current, last))
+        .fold(0.0, f32::max);
+        if gap <= max_gap {
+            return;
+        }
+
+        warn!(self.logger, "Gap in sample stream, restarting estimate"; "gap_s" => gap, "max_gap_s" => max_gap, "policy" => ?policy);
+        self.reset_position();
+        if policy == SampleGapPolicy::ResetAndLevel {
+            let (pitch, roll) = self.tilt_angles_from_accel(imu_data);
+            let (_, _, yaw) = self.state.orientation.euler_angles();
+            self.state.orientation = UnitQuaternion::from_euler_angles(roll, pitch, yaw);
+            self.state.smoothed_orientation = None;
+        }
+        // Integrate this sample as the first one of a fresh stream
+        self.restart_stream();
+    }
+
+    fn detect_events(&mut self, acc_magnitude: f32, timestamp: u32) {
+        if acc_magnitude < FREE_FALL_THRESHOLD_MG {
+            let start = *self.free_fall_start.get_or_insert(timestamp);
+            let duration_ms = timestamp.saturating_sub(start);
+            if !self.free_fall_reported && duration_ms >= FREE_FALL_MIN_DURATION_MS {
+                self.free_fall_reported = true;
+                self.events.push(MotionEvent::FreeFall {
+                    timestamp: start,
+                    duration_ms,
+                });
+            }
+        } else {
+            self.free_fall_start = None;
+            self.free_fall_reported = false;
+        }
+
+        if acc_magnitude > HIGH_G_THRESHOLD_MG {
+            if !self.high_g_active {
+                self.high_g_active = true;
+                self.events.push(MotionEvent::HighImpact {
+                    timestamp,
+                    magnitude_mg: acc_magnitude,
+                });
+            }
+        } else {
+            self.high_g_active = false;
+        }
+    }
+
+    fn update_orientation(
+        &mut self,
+        imu_data: &ImuData,
+        receive_dt: Option<f32>,
+    ) -> Result<(), SkipReason> {
+        let dt_gyro = if let Some(dt) = receive_dt {
+            dt
+        } else if self.state.last_gyro_timestamp != 0 {
+            sensor_delta_time(
+                (imu_data.timestamp_gyro, imu_data.timestamp_gyro_us),
+                (
+                    self.state.last_gyro_timestamp,
+                    self.state.last_gyro_timestamp_us,
+                ),
+            )
+        } else {
+            MIN_DELTA_TIME
+        };
+        self.state.last_gyro_timestamp = imu_data.timestamp_gyro;
+        self.state.last_gyro_timestamp_us = imu_data.timestamp_gyro_us;
+
+        let accel = self.acc_vector(imu_data).map(units::mg_to_mps2);
+        self.state.orientation_confidence = if self.disable_complementary_filter {
+            0.0
+        } else {
+            gravity_consistency(accel.norm() / STANDARD_GRAVITY, self.gravity_window)
+        };
+
+        if dt_gyro > MAX_DELTA_TIME {
+            return Err(SkipReason::ExcessiveGyroDelta { dt: dt_gyro });
+        }
+
+        let mut gyro_vec = self.mount_rotation
+            * (imu_data.gyro_radps() - self.gyro_bias.map(units::mdps_to_radps))
+                .component_mul(&self.gyro_scale);
+        if let Some(filter) = self.gyro_smoothing.as_mut() {
+            gyro_vec = filter.apply(gyro_vec);
+        }
+        self.state.angular_velocity = gyro_vec;
+
+        const EPSILON: f32 = 1e-6;
+        let angle = gyro_vec.norm() * dt_gyro;
+
+        if angle < EPSILON {
+            return Err(SkipReason::SmallAngle { angle });
+        }
+
+        self.orientation_filter
+            .set_orientation(self.state.orientation);
+        let mag = (imu_data.timestamp_mag != 0)
+            .then(|| self.mag_vector(imu_data).map(units::mgauss_to_tesla));
+        self.state.orientation = self
+            .orientation_filter
+            .update(gyro_vec, accel, mag, dt_gyro);
+        self.renormalize_orientation();
+
+        if self.verbose_motion {
+            let diagnostics = self.orientation_filter.diagnostics().unwrap_or_default();
+            debug!(self.logger, "Orientation update";
+                "dt_gyro" => dt_gyro,
+                "gyro_rate" => self.angle_units.from_radians(gyro_vec.norm()),
+                "acc_magnitude" => accel.norm() / STANDARD_GRAVITY,
+                "correction_angle" => self.angle_units.from_radians(diagnostics.correction_angle),
+                "angle_units" => self.angle_units.label(),
+                "gyro_weight" => diagnostics.gyro_weight,
+                "acc_weight" => diagnostics.acc_weight,
+                "max_norm_deviation" => self.max_norm_deviation);
+        }
+
+        Ok(())
+    }
+
+    /// Blends the display orientation toward the latest estimate by
+    /// `factor` per sample, 1 follows it exactly. The raw estimate used for
+    /// integration is unaffected. None disables smoothing.
+    pub fn set_orientation_smoothing(&mut self, factor: Option<f32>) {
+        self.orientation_smoothing = factor;
+        self.state.smoothed_orientation = None;
+    }
+
+    fn smooth_orientation(&mut self) {
+        let Some(factor) = self.orientation_smoothing else {
+            return;
+        };
+
+        let raw = self.state.orientation;
+        let previous = self.state.smoothed_orientation.unwrap_or(raw);
+        // Opposite orientations have no unique arc, jump instead
+        self.state.smoothed_orientation =
+            Some(previous.try_slerp(&raw, factor, 1e-6).unwrap_or(raw));
+    }
+
+    /// Repeated quaternion products slowly denormalize the orientation, which
+    /// `UnitQuaternion` assumes away. Renormalizes every `RENORMALIZE_EVERY`
+    /// updates, or at once if the norm strays beyond `MAX_NORM_DEVIATION`.
+    /// The largest deviation seen is logged with `--verbose-motion`.
+    fn renormalize_orientation(&mut self) {
+        let deviation = (self.state.orientation.norm() - 1.0).abs();
+        self.max_norm_deviation = self.max_norm_deviation.max(deviation);
+        self.updates_since_renormalize += 1;
+
+        if self.updates_since_renormalize >= RENORMALIZE_EVERY || deviation > MAX_NORM_DEVIATION {
+            self.state.orientation.renormalize();
+            self.updates_since_renormalize = 0;
+        }
+    }
+
+    fn update_velocity_and_position(
+        &mut self,
+        imu_data: &ImuData,
+        receive_dt: Option<f32>,
+    ) -> Result<(), SkipReason> {
+        let dt_acc = if let Some(dt) = receive_dt {
+            dt
+        } else if self.state.last_acc_timestamp != 0 {
+            sensor_delta_time(
+                (imu_data.timestamp_acc, imu_data.timestamp_acc_us),
+                (
+                    self.state.last_acc_timestamp,
+                    self.state.last_acc_timestamp_us,
+                ),
+            )
+        } else {
+            MIN_DELTA_TIME
+        };
+        let first_update = self.state.last_acc_timestamp == 0;
+        self.state.last_acc_timestamp = imu_data.timestamp_acc;
+        self.state.last_acc_timestamp_us = imu_data.timestamp_acc_us;
+
+        if dt_acc > MAX_DELTA_TIME {
+            return Err(SkipReason::ExcessiveAccDelta { dt: dt_acc });
+        }
+
+        let acc_mg = self.acc_vector(imu_data);
+        let gyro_rate = self.state.angular_velocity.norm();
+        if let Some(gate) = self.motion_gate.as_mut()
+            && !gate.update(acc_mg.norm(), gyro_rate)
+        {
+            return Err(SkipReason::AwaitingMotion);
+        }
+
+        let acc_body = acc_mg.map(units::mg_to_mps2);
+
+        let mut acc_world_no_gravity = if self.accel_gravity {
+            let gravity_body = self.track_gravity(acc_body, dt_acc);
+            self.state.orientation * (acc_body - gravity_body)
+        } else {
+            let gravity = Vector3::new(0.0, 0.0, STANDARD_GRAVITY);
+            self.state.orientation * acc_body - gravity
+        };
+
+        if let Some(filter) = self.acc_high_pass.as_mut() {
+            acc_world_no_gravity = filter.apply(acc_world_no_gravity, dt_acc);
+        }
+        // Nothing to differentiate against yet, and repeated timestamps
+        // would blow the difference up
+        self.state.jerk = if first_update || dt_acc < MIN_DELTA_TIME {
+            Vector3::zeros()
+        } else {
+            (acc_world_no_gravity - self.state.linear_acceleration) / dt_acc
+        };
+        self.state.linear_acceleration = acc_world_no_gravity;
+
+        self.position_estimator
+            .set_state(self.state.velocity, self.state.position);
+        let estimate = self.position_estimator.update(acc_world_no_gravity, dt_acc);
+        self.state.velocity = estimate.velocity;
+        self.state.position = estimate.position;
+        self.enforce_position_bound();
+        Ok(())
+    }
+
+    /// Gravity in the body frame, following slow attitude changes of the accel.
+    /// Falls back to the estimated orientation until a sample inside the
+    /// gravity window has been seen.
+    fn track_gravity(&mut self, acc_body: Vector3<f32>, dt: f32) -> Vector3<f32> {
+        let acc_g = acc_body.norm() / STANDARD_GRAVITY;
+        if gravity_consistency(acc_g, self.gravity_window) > 0.0 {
+            let gravity = self.gravity_body.get_or_insert(acc_body);
+            let alpha = dt / (GRAVITY_TRACKING_TIME_CONSTANT + dt);
+            *gravity += (acc_body - *gravity) * alpha;
+        }
+        self.gravity_body
+            .and_then(|gravity| gravity.try_normalize(f32::EPSILON))
+            .unwrap_or_else(|| -self.state.gravity_direction_body())
+            * STANDARD_GRAVITY
+    }
+
+    fn enforce_position_bound(&mut self) {
+        let Some((max_position, policy)) = self.position_bound else {
+            return;
+        };
+        let distance = self.state.position.norm();
+        if distance <= max_position {
+            return;
+        }
+
+        if !self.position_bound_warned {
+            warn!(self.logger, "Position exceeds sanity bound, estimate is diverging"; "distance" => distance, "max_position" => max_position, "policy" => ?policy);
+            self.position_bound_warned = true;
+        }
+
+        match policy {
+            PositionBoundPolicy::Clamp => self.state.position *= max_position / distance,
+            PositionBoundPolicy::Reset => {
+                self.state.position = Vector3::zeros();
+                self.state.velocity = Vector3::zeros();
+            }
+        }
+    }
+}
+
+fn unit_scale() -> Vector3<f32> {
+    Vector3::repeat(1.0)
+}
+
+fn wrap_degrees(angle: f32) -> f32 {
+    let wrapped = angle.rem_euclid(360.0);
+    // rem_euclid can round up to exactly 360.0 for tiny negative inputs, and
+    // keeps the sign of -0.0
+    if wrapped >= 360.0 || wrapped == 0.0 {
+        0.0
+    } else {
+        wrapped
+    }
+}
+
+/// Seconds between two `(ms, us)` sensor timestamps, from the microsecond
+/// ones when both samples carry them, otherwise quantized to milliseconds.
+fn sensor_delta_time(current: (u32, Option<u64>), last: (u32, Option<u64>)) -> f32 {
+    match (current.1, last.1) {
+        (Some(current_us), Some(last_us)) => current_us.saturating_sub(last_us) as f32 / 1e6,
+        _ => current.0.saturating_sub(last.0) as f32 / 1000.0,
+    }
+}
+
+/// Reads a JSON file whose top-level `version` must equal `expected`.
+fn read_versioned_json<T: serde::de::DeserializeOwned>(
+    path: &Path,
+    expected: u32,
+    kind: &str,
+) -> std::io::Result<T> {
+    let json = std::fs::read_to_string(path)?;
+    let version = serde_json::from_str::<serde_json::Value>(&json)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?
+        .get("version")
+        .and_then(serde_json::Value::as_u64);
+    if version != Some(expected.into()) {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!(
+                "unsupported {} version {:?}, expected {}",
+                kind, version, expected
+            ),
+        ));
+    }
+
+    serde_json::from_str(&json).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::position::PositionEstimate;
+    use crate::test_support::capturing_logger;
+    use approx::assert_relative_eq;
+    use common::slog::{Discard, Logger, o};
+    use common::trajectory::SinusoidalTranslation;
+    use std::sync::{Arc, Mutex};
+
+    fn create_test_logger() -> Logger {
+        Logger::root(Discard, o!())
+    }
+
+    fn create_test_imu_data(
+        x_acc: f32,
+        y_acc: f32,
+        z_acc: f32,
+        x_gyro: i32,
+        y_gyro: i32,
+        z_gyro: i32,
+        timestamp: u32,
+    ) -> ImuData {
+        ImuData {
+            x_acc,
+            y_acc,
+            z_acc,
+            timestamp_acc: timestamp,
+            x_gyro,
+            y_gyro,
+            z_gyro,
+            timestamp_gyro: timestamp,
+            x_mag: 0.0,
+            y_mag: 0.0,
+            z_mag: 0.0,
+            timestamp_mag: timestamp,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_mount_rotation_reports_vehicle_frame() {
+        // Sensor pitched 30deg against the vehicle, which yaws at 90deg/s on level ground
+        let mount = UnitQuaternion::from_euler_angles(0.0, 30f32.to_radians(), 0.0);
+        let acc = mount.inverse_transform_vector(&Vector3::new(0.0, 0.0, 1000.0));
+        let gyro = mount.inverse_transform_vector(&Vector3::new(0.0, 0.0, 90_000.0));
+        let sample = |timestamp| {
+            create_test_imu_data(
+                acc.x,
+                acc.y,
+                acc.z,
+                gyro.x.round() as i32,
+                gyro.y.round() as i32,
+                gyro.z.round() as i32,
+                timestamp,
+            )
+        };
+
+        let mut processor = MotionProcessor::new(create_test_logger());
+        processor.set_mount_rotation(mount);
+        processor.set_acc_deadband(0.0);
+        // The accel correction would pull the yaw back
+        processor.set_gyro_only(true);
+        for i in 0..=100 {
+            processor.process(&sample(1000 + i * 10));
+        }
+
+        // One second of yaw, level and at rest in the vehicle frame
+        let (roll, pitch, yaw) = processor.state.orientation.euler_angles();
+        assert_relative_eq!(roll, 0.0, epsilon = 1e-2);
+        assert_relative_eq!(pitch, 0.0, epsilon = 1e-2);
+        assert_relative_eq!(yaw, std::f32::consts::FRAC_PI_2, epsilon = 1e-2);
+        assert!(processor.state.linear_acceleration.norm() < 0.05);
+        assert!(processor.state.position.norm() < 0.01);
+
+        // Without it the sensor frame is reported, tilted by about the mount
+        let mut unmounted = MotionProcessor::new(create_test_logger());
+        for i in 0..=100 {
+            unmounted.process(&sample(1000 + i * 10));
+        }
+        let tilt = unmounted
+            .state
+            .orientation
+            .transform_vector(&Vector3::z())
+            .angle(&Vector3::z());
+        assert!(tilt.to_degrees() > 20.0, "tilt {}", tilt.to_degrees());
+    }
+
+    #[test]
+    fn test_default_motion_state() {
+        let state = MotionState::default();
+        assert_eq!(state.position, Vector3::zeros());
+        assert_eq!(state.velocity, Vector3::zeros());
+        assert_eq!(state.orientation, UnitQuaternion::identity());
+        assert_eq!(state.last_acc_timestamp, 0);
+        assert_eq!(state.last_gyro_timestamp, 0);
+    }
+
+    #[test]
+    fn test_gravity_direction_body_follows_tilt() {
+        let mut state = MotionState::default();
+        assert_relative_eq!(state.gravity_direction_body(), -Vector3::z());
+
+        // Rolled 90deg about X, the body Y axis now points up
+        state.orientation =
+            UnitQuaternion::from_euler_angles(std::f32::consts::FRAC_PI_2, 0.0, 0.0);
+        assert_relative_eq!(
+            state.gravity_direction_body(),
+            Vector3::new(0.0, -1.0, 0.0),
+            epsilon = 1e-6
+        );
+
+        // Pitched 30deg, down leans towards the body X axis
+        state.orientation = UnitQuaternion::from_euler_angles(0.0, 30f32.to_radians(), 0.0);
+        assert_relative_eq!(
+            state.gravity_direction_body(),
+            Vector3::new(0.5, 0.0, -(3f32.sqrt()) / 2.0),
+            epsilon = 1e-6
+        );
+    }
+
+    #[test]
+    fn test_acceleration_integration() {
+        let logger = create_test_logger();
+        let mut processor = MotionProcessor::new(logger);
+
+        processor.set_velocity_decay(1.0);
+
+        let imu_data = create_test_imu_data(1000.0, 0.0, 1000.0, 0, 0, 0, 1000);
+
+        let total_time_steps = 100;
+        let dt_ms = 10;
+        let dt_s = dt_ms as f32 / 1000.0;
+
+        for i in 0..total_time_steps {
+            let mut data = imu_data;
+            let current_timestamp = 1000 + i * dt_ms;
+            data.timestamp_acc = current_timestamp;
+            data.timestamp_gyro = current_timestamp;
+            processor.process(&data);
+        }
+
+        let expected_velocity_x = 9.81 * (total_time_steps as f32 * dt_s);
+
+        let expected_position_x = 0.5 * 9.81 * (total_time_steps as f32 * dt_s).powi(2);
+
+        println!(
+            "Final Velocity: {:?}, Final Position: {:?}",
+            processor.state.velocity, processor.state.position
+        );
+
+        assert_relative_eq!(
+            processor.state.velocity.x,
+            expected_velocity_x,
+            epsilon = 0.1
+        );
+        assert_relative_eq!(
+            processor.state.position.x,
+            expected_position_x,
+            epsilon = 0.1
+        );
+
+        assert_relative_eq!(processor.state.velocity.y, 0.0, epsilon = 0.01);
+        assert_relative_eq!(processor.state.position.y, 0.0, epsilon = 0.01);
+
+        assert_relative_eq!(processor.state.velocity.z, 0.0, epsilon = 0.1);
+        assert_relative_eq!(processor.state.position.z, 0.0, epsilon = 0.1);
+    }
+
+    #[test]
+    fn test_complementary_filter() {
+        let logger = create_test_logger();
+        let mut processor = MotionProcessor::new(logger);
+
+        processor.set_orientation_filter(Box::new(ComplementaryFilter::new(0.5, 0.5)));
+
+        let imu_data = create_test_imu_data(0.0, 0.0, 1000.0, 10000, 0, 0, 1000);
+
+        for i in 0..10 {
+            let mut data = imu_data;
+            data.timestamp_acc = 1000 + i * 10;
+            data.timestamp_gyro = 1000 + i * 10;
+            processor.process(&data);
+        }
+
+        let (roll, _, _) = processor.state.orientation.euler_angles();
+
+        assert!(roll > 0.0);
+        assert!(roll < 0.17);
+    }
+
+    #[test]
+    fn test_angular_velocity_is_bias_corrected() {
+        let logger = create_test_logger();
+        let mut processor = MotionProcessor::new(logger);
+        processor.gyro_bias = Vector3::new(100.0, -200.0, 0.0);
+
+        let imu_data = create_test_imu_data(0.0, 0.0, 1000.0, 9100, -200, 45000, 1000);
+        for i in 0..5 {
+            let mut data = imu_data;
+            data.timestamp_acc = 1000 + i * 10;
+            data.timestamp_gyro = 1000 + i * 10;
+            processor.process(&data);
+        }
+
+        let expected = Vector3::new(9.0, 0.0, 45.0).map(f32::to_radians);
+        assert_relative_eq!(processor.state.angular_velocity, expected, epsilon = 1e-6);
+    }
+
+    #[test]
+    fn test_linear_acceleration_of_stationary_tilted_device() {
+        let logger = create_test_logger();
+        let mut processor = MotionProcessor::new(logger);
+        let tilt = UnitQuaternion::from_euler_angles(0.3, -0.2, 0.0);
+        processor.state.orientation = tilt;
+
+        let acc_body_mg = tilt.inverse() * Vector3::new(0.0, 0.0, 1000.0);
+        let imu_data =
+            create_test_imu_data(acc_body_mg.x, acc_body_mg.y, acc_body_mg.z, 0, 0, 0, 1000);
+        processor.process(&imu_data);
+
+        assert_relative_eq!(
+            processor.state.linear_acceleration,
+            Vector3::zeros(),
+            epsilon = 0.05
+        );
+    }
+
+    #[test]
+    fn test_jerk_spikes_at_acceleration_step() {
+        let mut processor = MotionProcessor::new(create_test_logger());
+        let mut jerks = Vec::new();
+        for i in 0..10 {
+            // Level and at rest, then a sudden 0.5g push along X
+            let x_acc = if i < 5 { 0.0 } else { 500.0 };
+            processor.process(&create_test_imu_data(
+                x_acc,
+                0.0,
+                1000.0,
+                0,
+                0,
+                0,
+                1000 + i * 10,
+            ));
+            jerks.push(processor.state.jerk);
+        }
+
+        assert!(jerks[..5].iter().all(|j| j.norm() < 1e-3), "{:?}", jerks);
+        // 0.5g within one 10ms sample
+        assert_relative_eq!(jerks[5].x, units::mg_to_mps2(500.0) / 0.01, epsilon = 1e-2);
+        assert!(jerks[6..].iter().all(|j| j.norm() < 1e-3), "{:?}", jerks);
+    }
+
+    #[test]
+    fn test_jerk_guards_against_repeated_timestamps() {
+        let mut processor = MotionProcessor::new(create_test_logger());
+        processor.process(&create_test_imu_data(0.0, 0.0, 1000.0, 0, 0, 0, 1000));
+        processor.process(&create_test_imu_data(500.0, 0.0, 1000.0, 0, 0, 0, 1000));
+
+        assert!(processor.state.jerk.iter().all(|j| j.is_finite()));
+        assert_eq!(processor.state.jerk, Vector3::zeros());
+    }
+
+    #[test]
+    fn test_accel_units_yield_same_linear_acceleration() {
+        let run = |accel_units: AccelUnits, scale: f32| {
+            let mut processor = MotionProcessor::new(create_test_logger());
+            processor.set_accel_units(accel_units);
+            let imu_data = create_test_imu_data(500.0 * scale, 0.0, 1000.0 * scale, 0, 0, 0, 1000);
+            processor.process(&imu_data);
+            processor.state.linear_acceleration
+        };
+
+        let reference = run(AccelUnits::Mg, 1.0);
+        assert_relative_eq!(reference.x, units::mg_to_mps2(500.0), epsilon = 1e-4);
+        assert_relative_eq!(
+            run(AccelUnits::Mps2, STANDARD_GRAVITY / 1000.0),
+            reference,
+            epsilon = 1e-4
+        );
+        assert_relative_eq!(run(AccelUnits::G, 0.001), reference, epsilon = 1e-4);
+    }
+
+    #[test]
+    fn test_watchdog_warns_about_frozen_accel() {
+        let (logger, captured) = capturing_logger();
+        let mut processor = MotionProcessor::new(logger);
+        processor.set_sensor_watchdog(Some(SensorWatchdog::new(100)));
+
+        for i in 0..20 {
+            let mut data = create_test_imu_data(0.0, 0.0, 1000.0, 1000, 0, 0, 1000);
+            data.timestamp_gyro = 1000 + i * 10;
+            data.timestamp_mag = 0;
+            processor.process(&data);
+        }
+
+        let messages = captured.messages();
+        let warnings: Vec<_> = messages
+            .iter()
+            .filter(|m| m.contains("stopped advancing"))
+            .collect();
+        assert_eq!(warnings, ["Accel timestamp stopped advancing"]);
+    }
+
+    #[test]
+    fn test_orientation_confidence_tracks_accel_magnitude() {
+        let confidence_after = |z_acc: f32, gyro_only: bool| {
+            let mut processor = MotionProcessor::new(create_test_logger());
+            processor.set_gyro_only(gyro_only);
+            for i in 0..10 {
+                processor.process(&create_test_imu_data(
+                    0.0,
+                    0.0,
+                    z_acc,
+                    0,
+                    0,
+                    100,
+                    1000 + i * 10,
+                ));
+            }
+            processor.state.orientation_confidence
+        };
+
+        // Gravity only
+        assert_relative_eq!(confidence_after(1000.0, false), 1.0, epsilon = 1e-3);
+        // Halfway to the edge of the default 950-1050mg window
+        assert_relative_eq!(confidence_after(1025.0, false), 0.5, epsilon = 1e-3);
+        assert_relative_eq!(confidence_after(975.0, false), 0.5, epsilon = 1e-3);
+        // Large acceleration, the accel no longer corrects the gyro
+        assert_eq!(confidence_after(1500.0, false), 0.0);
+        assert_eq!(confidence_after(200.0, false), 0.0);
+        // The accel never corrects in gyro-only mode
+        assert_eq!(confidence_after(1000.0, true), 0.0);
+    }
+
+    #[test]
+    fn test_gravity_gate_holds_for_every_accel_unit() {
+        // Raw readings of 1g in each unit
+        let units = [
+            (AccelUnits::Mg, 1000.0),
+            (AccelUnits::Mps2, STANDARD_GRAVITY),
+            (AccelUnits::G, 1.0),
+        ];
+        let roll_after = |units: AccelUnits, one_g: f32, magnitude_g: f32| {
+            let mut processor = MotionProcessor::new(create_test_logger());
+            processor.set_accel_units(units);
+            processor.set_gravity_tolerance(0.1);
+            // Tilted 10deg about X, slow yaw so updates are not skipped
+            let tilt = 10f32.to_radians();
+            let magnitude = one_g * magnitude_g;
+            for i in 0..50 {
+                processor.process(&create_test_imu_data(
+                    0.0,
+                    magnitude * tilt.sin(),
+                    magnitude * tilt.cos(),
+                    0,
+                    0,
+                    100,
+                    1000 + i * 10,
+                ));
+            }
+            let (roll, _, _) = processor.state.orientation.euler_angles();
+            (roll.abs(), processor.state.orientation_confidence)
+        };
+
+        for (units, one_g) in units {
+            let (roll, confidence) = roll_after(units, one_g, 1.0);
+            assert!(roll > 0.05, "{:?}: gravity should be trusted", units);
+            assert_relative_eq!(confidence, 1.0, epsilon = 1e-3);
+
+            let (roll, confidence) = roll_after(units, one_g, 1.05);
+            assert!(roll > 0.05, "{:?}: within the tolerance", units);
+            assert_relative_eq!(confidence, 0.5, epsilon = 1e-3);
+
+            let (roll, confidence) = roll_after(units, one_g, 1.2);
+            assert!(roll < 1e-4, "{:?}: outside the tolerance", units);
+            assert_eq!(confidence, 0.0);
+        }
+    }
+
+    #[test]
+    fn test_gravity_window_controls_accel_trust() {
+        let tilt_after = |window_mg: Option<(f32, f32)>, y_acc: f32, z_acc: f32| {
+            let mut processor = MotionProcessor::new(create_test_logger());
+            if let Some((lower, upper)) = window_mg {
+                processor.set_gravity_window(lower, upper);
+            }
+            // Slow yaw so orientation updates are not skipped as too small
+            for i in 0..50 {
+                processor.process(&create_test_imu_data(
+                    0.0,
+                    y_acc,
+                    z_acc,
+                    0,
+                    0,
+                    100,
+                    1000 + i * 10,
+                ));
+            }
+            let (roll, _, _) = processor.state.orientation.euler_angles();
+            roll.abs()
+        };
+
+        // Tilted 10deg while accelerating to 1.1g, outside the default window
+        let (y_mild, z_mild) = (
+            1100.0 * 10f32.to_radians().sin(),
+            1100.0 * 10f32.to_radians().cos(),
+        );
+        assert_relative_eq!(tilt_after(None, y_mild, z_mild), 0.0, epsilon = 1e-4);
+        assert!(tilt_after(Some((900.0, 1200.0)), y_mild, z_mild) > 0.05);
+
+        // Tilted 10deg at 1.03g, trusted by default but not by a narrow window
+        let (y_near, z_near) = (
+            1030.0 * 10f32.to_radians().sin(),
+            1030.0 * 10f32.to_radians().cos(),
+        );
+        assert!(tilt_after(None, y_near, z_near) > 0.05);
+        assert_relative_eq!(
+            tilt_after(Some((990.0, 1010.0)), y_near, z_near),
+            0.0,
+            epsilon = 1e-4
+        );
+    }
+
+    #[test]
+    fn test_drift_monitor_warns_about_biased_stationary_data() {
+        let run = |x_acc: f32| {
+            let (logger, captured) = capturing_logger();
+            let mut processor = MotionProcessor::new(logger);
+            processor.set_velocity_decay(1.0);
+            processor.set_gyro_only(true);
+            processor.set_drift_monitor(Some(DriftMonitor::new(0.05)));
+
+            // Resting level, 2s at 100Hz
+            for i in 0..200 {
+                processor.process(&create_test_imu_data(
+                    x_acc,
+                    0.0,
+                    1000.0,
+                    0,
+                    0,
+                    0,
+                    1000 + i * 10,
+                ));
+            }
+            let messages = captured.messages();
+            messages
+                .iter()
+                .filter(|m| m.contains("drifting while stationary"))
+                .count()
+        };
+
+        assert_eq!(run(0.0), 0);
+        // A 20mg accel bias on X integrates to 0.4m/s within those 2s
+        assert_eq!(run(20.0), 1);
+    }
+
+    #[test]
+    fn test_motion_gate_pins_position_until_motion() {
+        let run = |gated: bool| {
+            let mut processor = MotionProcessor::new(create_test_logger());
+            processor.set_velocity_decay(1.0);
+            processor.set_motion_gate(gated);
+
+            // Idle with a 20mg accel bias on X for 1s at 100Hz
+            let mut idle_position = Vector3::zeros();
+            for i in 0..100 {
+                let outcome = processor.process(&create_test_imu_data(
+                    20.0,
+                    0.0,
+                    1000.0,
+                    0,
+                    0,
+                    0,
+                    1000 + i * 10,
+                ));
+                assert_eq!(outcome.position_updated, !gated);
+                idle_position = processor.state.position;
+            }
+
+            // Then a push along X
+            let outcome =
+                processor.process(&create_test_imu_data(300.0, 0.0, 1000.0, 0, 0, 0, 2000));
+            assert!(outcome.position_updated);
+            (idle_position, processor.state.velocity.x)
+        };
+
+        let (idle_position, velocity) = run(true);
+        assert_eq!(idle_position, Vector3::zeros());
+        assert!(velocity > 0.0);
+
+        let (idle_position, _) = run(false);
+        assert!(idle_position.x > 0.01, "The bias drifts without the gate");
+    }
+
+    #[test]
+    fn test_drift_rate_reports_gyro_bias_at_rest() {
+        let (logger, captured) = capturing_logger();
+        let mut processor = MotionProcessor::new(logger);
+        processor.set_gyro_only(true);
+        processor.set_drift_rate_estimator(Some(DriftRateEstimator::new(1000)));
+
+        // Resting level with an uncorrected 2deg/s yaw bias, 3s at 100Hz
+        for i in 0..300 {
+            processor.process(&create_test_imu_data(
+                0.0,
+                0.0,
+                1000.0,
+                0,
+                0,
+                2000,
+                1000 + i * 10,
+            ));
+        }
+
+        let records = captured.records();
+        let rates: Vec<f32> = records
+            .iter()
+            .filter(|(msg, _)| msg == "Drift rate while stationary")
+            .map(|(_, kv)| {
+                let (_, rate) = kv
+                    .iter()
+                    .find(|(key, _)| key == "orientation_deg_per_s")
+                    .unwrap();
+                rate.parse().unwrap()
+            })
+            .collect();
+        assert_eq!(rates.len(), 2, "{:?}", rates);
+        for rate in rates {
+            assert!((rate - 2.0).abs() < 0.05, "{}", rate);
+        }
+    }
+
+    #[test]
+    fn test_verbose_motion_logs_filter_internals() {
+        let (logger, captured) = capturing_logger();
+        let mut processor = MotionProcessor::new(logger);
+
+        let imu_data = create_test_imu_data(0.0, 0.0, 1000.0, 10000, 0, 0, 1000);
+        processor.process(&imu_data);
+        assert!(captured.records().is_empty(), "Quiet unless enabled");
+
+        processor.set_verbose_motion(true);
+        let mut data = imu_data;
+        data.timestamp_gyro = 1010;
+        processor.process(&data);
+
+        let records = captured.records();
+        let (_, keys) = records
+            .iter()
+            .find(|(msg, _)| msg == "Orientation update")
+            .expect("Verbose mode should log the orientation update");
+        for key in [
+            "dt_gyro",
+            "gyro_rate",
+            "acc_magnitude",
+            "correction_angle",
+            "gyro_weight",
+            "acc_weight",
+        ] {
+            assert!(keys.iter().any(|(k, _)| k == key), "Missing key {}", key);
+        }
+    }
+
+    fn create_test_mag_data(x_mag: f32, y_mag: f32, z_mag: f32) -> ImuData {
+        ImuData {
+            x_mag,
+            y_mag,
+            z_mag,
+            ..create_test_imu_data(0.0, 0.0, 1000.0, 0, 0, 0, 1000)
+        }
+    }
+
+    #[test]
+    fn test_heading_wraps_yaw() {
+        let logger = create_test_logger();
+        let mut processor = MotionProcessor::new(logger);
+
+        processor.state.orientation =
+            UnitQuaternion::from_euler_angles(0.0, 0.0, 90f32.to_radians());
+        assert_relative_eq!(processor.heading(), 90.0, epsilon = 1e-3);
+
+        processor.state.orientation =
+            UnitQuaternion::from_euler_angles(0.0, 0.0, -90f32.to_radians());
+        assert_relative_eq!(processor.heading(), 270.0, epsilon = 1e-3);
+
+        processor.state.orientation = UnitQuaternion::identity();
+        assert_relative_eq!(processor.heading(), 0.0, epsilon = 1e-3);
+    }
+
+    #[test]
+    fn test_tilt_compensated_heading_level() {
+        let logger = create_test_logger();
+        let processor = MotionProcessor::new(logger);
+
+        let cases = [
+            ((300.0, 0.0, -400.0), 0.0),
+            ((0.0, -300.0, -400.0), 90.0),
+            ((-300.0, 0.0, -400.0), 180.0),
+            ((0.0, 300.0, -400.0), 270.0),
+        ];
+
+        for ((x, y, z), expected) in cases {
+            let heading =
+                processor.tilt_compensated_heading_from_mag(&create_test_mag_data(x, y, z));
+            assert_relative_eq!(heading, expected, epsilon = 1e-3);
+        }
+    }
+
+    #[test]
+    fn test_mag_heading_turns_like_fused_heading() {
+        let mut processor = MotionProcessor::new(create_test_logger());
+        let north = Vector3::new(300.0, 0.0, -400.0);
+
+        for yaw in [30f32, 135.0, 250.0] {
+            let orientation = UnitQuaternion::from_euler_angles(
+                10f32.to_radians(),
+                -5f32.to_radians(),
+                yaw.to_radians(),
+            );
+            processor.state.orientation = orientation;
+            let mag_body = orientation.inverse() * north;
+
+            let mag_heading = processor.tilt_compensated_heading_from_mag(&create_test_mag_data(
+                mag_body.x, mag_body.y, mag_body.z,
+            ));
+            assert_relative_eq!(processor.heading(), yaw, epsilon = 1e-2);
+            assert_relative_eq!(mag_heading, yaw, epsilon = 1e-2);
+        }
+    }
+
+    #[test]
+    fn test_tilt_compensated_heading_tilted() {
+        let logger = create_test_logger();
+        let mut processor = MotionProcessor::new(logger);
+
+        let tilt = UnitQuaternion::from_euler_angles(30f32.to_radians(), -20f32.to_radians(), 0.0);
+        processor.state.orientation = tilt;
+
+        // Field pointing 45 degrees off +X in the level frame, dipping downward
+        let bearing = 45f32.to_radians();
+        let mag_world = Vector3::new(bearing.cos() * 300.0, -bearing.sin() * 300.0, -400.0);
+        let mag_body = tilt.inverse() * mag_world;
+
+        let imu_data = create_test_mag_data(mag_body.x, mag_body.y, mag_body.z);
+        let before = processor.state.clone();
+        let heading = processor.tilt_compensated_heading_from_mag(&imu_data);
+
+        assert_relative_eq!(heading, 45.0, epsilon = 1e-2);
+        assert_eq!(processor.state.orientation, before.orientation);
+    }
+
+    #[test]
+    fn test_free_fall_event() {
+        let logger = create_test_logger();
+        let mut processor = MotionProcessor::new(logger);
+
+        for i in 0..10 {
+            processor.process(&create_test_imu_data(
+                0.0,
+                0.0,
+                1000.0,
+                0,
+                0,
+                0,
+                1000 + i * 10,
+            ));
+        }
+        assert!(processor.take_events().is_empty());
+
+        // 200ms of near-zero g
+        for i in 0..20 {
+            processor.process(&create_test_imu_data(
+                10.0,
+                -5.0,
+                20.0,
+                0,
+                0,
+                0,
+                1100 + i * 10,
+            ));
+        }
+
+        let events = processor.take_events();
+        assert_eq!(
+            events.len(),
+            1,
+            "Free fall should be reported once per episode"
+        );
+        match events[0] {
+            MotionEvent::FreeFall {
+                timestamp,
+                duration_ms,
+            } => {
+                assert_eq!(timestamp, 1100);
+                assert!(duration_ms >= FREE_FALL_MIN_DURATION_MS);
+            }
+            other => panic!("Expected free fall event, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_short_near_zero_g_is_not_free_fall() {
+        let logger = create_test_logger();
+        let mut processor = MotionProcessor::new(logger);
+
+        for i in 0..3 {
+            processor.process(&create_test_imu_data(
+                0.0,
+                0.0,
+                10.0,
+                0,
+                0,
+                0,
+                1000 + i * 10,
+            ));
+        }
+        processor.process(&create_test_imu_data(0.0, 0.0, 1000.0, 0, 0, 0, 1030));
+
+        assert!(processor.take_events().is_empty());
+    }
+
+    #[test]
+    fn test_high_impact_event() {
+        let logger = create_test_logger();
+        let mut processor = MotionProcessor::new(logger);
+
+        processor.process(&create_test_imu_data(0.0, 0.0, 1000.0, 0, 0, 0, 1000));
+        processor.process(&create_test_imu_data(3500.0, 0.0, 1000.0, 0, 0, 0, 1010));
+        processor.process(&create_test_imu_data(3800.0, 0.0, 1000.0, 0, 0, 0, 1020));
+        processor.process(&create_test_imu_data(0.0, 0.0, 1000.0, 0, 0, 0, 1030));
+
+        let events = processor.take_events();
+        assert_eq!(events.len(), 1, "Spike should be reported once");
+        match events[0] {
+            MotionEvent::HighImpact {
+                timestamp,
+                magnitude_mg,
+            } => {
+                assert_eq!(timestamp, 1010);
+                assert!(magnitude_mg > HIGH_G_THRESHOLD_MG);
+            }
+            other => panic!("Expected high impact event, got {:?}", other),
+        }
+        assert!(processor.take_events().is_empty());
+    }
+
+    #[test]
+    fn test_step_detection_is_optional() {
+        let logger = create_test_logger();
+        let mut processor = MotionProcessor::new(logger);
+        assert_eq!(processor.step_count(), None);
+
+        processor.set_step_detector(Some(StepDetector::new(150.0, 250)));
+        for i in 0..500 {
+            let t = i as f32 / 100.0;
+            let z_acc = 1000.0 + 400.0 * (2.0 * std::f32::consts::PI * 2.0 * t).sin();
+            processor.process(&create_test_imu_data(
+                0.0,
+                0.0,
+                z_acc,
+                0,
+                0,
+                0,
+                1000 + i * 10,
+            ));
+        }
+
+        assert_eq!(processor.step_count(), Some(10));
+    }
+
+    #[test]
+    fn test_tilt_angles_from_accel() {
+        let cases = [
+            (0.0f32, 0.0f32),
+            (30.0, 0.0),
+            (0.0, -45.0),
+            (-20.0, 60.0),
+            (10.0, 170.0),
+        ];
+
+        let processor = MotionProcessor::new(create_test_logger());
+        for (pitch_deg, roll_deg) in cases {
+            let orientation = UnitQuaternion::from_euler_angles(
+                roll_deg.to_radians(),
+                pitch_deg.to_radians(),
+                0.0,
+            );
+            let acc = orientation.inverse() * Vector3::new(0.0, 0.0, 1000.0);
+            let imu_data = create_test_imu_data(acc.x, acc.y, acc.z, 0, 0, 0, 1000);
+
+            let (pitch, roll) = processor.tilt_angles_from_accel(&imu_data);
+            assert_relative_eq!(pitch.to_degrees(), pitch_deg, epsilon = 1e-2);
+            assert_relative_eq!(roll.to_degrees(), roll_deg, epsilon = 1e-2);
+        }
+    }
+
+    #[test]
+    fn test_tilt_angles_from_zero_accel() {
+        let imu_data = create_test_imu_data(0.0, 0.0, 0.0, 0, 0, 0, 1000);
+        let processor = MotionProcessor::new(create_test_logger());
+        assert_eq!(processor.tilt_angles_from_accel(&imu_data), (0.0, 0.0));
+    }
+
+    #[test]
+    fn test_tilt_angles_from_accel_use_calibrated_mg() {
+        // 0.5mg along Y is still "down" once the g reading is converted to mg
+        let mut processor = MotionProcessor::new(create_test_logger());
+        processor.set_accel_units(AccelUnits::G);
+        let imu_data = create_test_imu_data(0.0, 0.0005, 0.0, 0, 0, 0, 1000);
+        let (pitch, roll) = processor.tilt_angles_from_accel(&imu_data);
+        assert_relative_eq!(pitch, 0.0, epsilon = 1e-5);
+        assert_relative_eq!(roll.to_degrees(), 90.0, epsilon = 1e-3);
+
+        // The bias is removed before the angles are taken
+        let mut processor = MotionProcessor::new(create_test_logger());
+        processor.set_calibration(&Calibration {
+            acc_bias: Vector3::new(0.0, 1000.0, 0.0),
+            ..processor.calibration()
+        });
+        let imu_data = create_test_imu_data(0.0, 1000.0, 1000.0, 0, 0, 0, 1000);
+        let (pitch, roll) = processor.tilt_angles_from_accel(&imu_data);
+        assert_relative_eq!(pitch, 0.0, epsilon = 1e-5);
+        assert_relative_eq!(roll, 0.0, epsilon = 1e-5);
+    }
+
+    #[test]
+    fn test_acc_deadband_suppresses_noise_drift() {
+        let noise = [0.5, 2.5, 1.0, 3.5, 0.0, 2.0, 3.0, 1.5];
+
+        let run = |deadband: f32| {
+            let mut processor = MotionProcessor::new(create_test_logger());
+            processor.set_velocity_decay(1.0);
+            processor.set_acc_deadband(deadband);
+
+            for i in 0..200 {
+                let x_acc = noise[i % noise.len()];
+                processor.process(&create_test_imu_data(
+                    x_acc,
+                    0.0,
+                    1000.0,
+                    0,
+                    0,
+                    0,
+                    1000 + i as u32 * 10,
+                ));
+            }
+            processor.state.velocity.x
+        };
+
+        let drift_default = run(0.01);
+        let drift_raised = run(0.05);
+
+        assert!(
+            drift_default > 0.01,
+            "Default deadband should let noise drift"
+        );
+        assert_eq!(
+            drift_raised, 0.0,
+            "Raised deadband should suppress noise entirely"
+        );
+    }
+
+    #[test]
+    fn test_acc_deadband_keeps_real_motion() {
+        let mut processor = MotionProcessor::new(create_test_logger());
+        processor.set_velocity_decay(1.0);
+        processor.set_acc_deadband(0.05);
+
+        for i in 0..100 {
+            processor.process(&create_test_imu_data(
+                1000.0,
+                0.0,
+                1000.0,
+                0,
+                0,
+                0,
+                1000 + i * 10,
+            ));
+        }
+
+        assert_relative_eq!(processor.state.velocity.x, 9.81, epsilon = 0.1);
+    }
+
+    #[test]
+    fn test_acc_high_pass_reduces_bias_drift() {
+        let run = |cutoff_hz: Option<f32>| {
+            let mut processor = MotionProcessor::new(create_test_logger());
+            processor.set_velocity_decay(1.0);
+            processor.set_acc_high_pass_cutoff(cutoff_hz);
+
+            // 20mg constant bias on X
+            for i in 0..200 {
+                processor.process(&create_test_imu_data(
+                    20.0,
+                    0.0,
+                    1000.0,
+                    0,
+                    0,
+                    0,
+                    1000 + i * 10,
+                ));
+            }
+            processor.state.velocity.x.abs()
+        };
+
+        let drift_unfiltered = run(None);
+        let drift_filtered = run(Some(0.5));
+
+        assert!(drift_unfiltered > 0.3);
+        assert!(
+            drift_filtered < drift_unfiltered * 0.25,
+            "High-pass drift {} should be well below unfiltered drift {}",
+            drift_filtered,
+            drift_unfiltered
+        );
+    }
+
+    #[test]
+    fn test_process_outcome_updated() {
+        let mut processor = MotionProcessor::new(create_test_logger());
+
+        processor.process(&create_test_imu_data(0.0, 0.0, 1000.0, 10000, 0, 0, 1000));
+        let outcome = processor.process(&create_test_imu_data(0.0, 0.0, 1000.0, 10000, 0, 0, 1010));
+
+        assert!(outcome.orientation_updated);
+        assert!(outcome.position_updated);
+        assert_eq!(outcome.skipped_reason, None);
+    }
+
+    #[test]
+    fn test_process_outcome_small_angle() {
+        let mut processor = MotionProcessor::new(create_test_logger());
+
+        let outcome = processor.process(&create_test_imu_data(0.0, 0.0, 1000.0, 0, 0, 0, 1000));
+
+        assert!(!outcome.orientation_updated);
+        assert!(outcome.position_updated);
+        assert!(matches!(
+            outcome.skipped_reason,
+            Some(SkipReason::SmallAngle { .. })
+        ));
+    }
+
+    #[test]
+    fn test_process_outcome_excessive_gyro_delta() {
+        let mut processor = MotionProcessor::new(create_test_logger());
+
+        processor.process(&create_test_imu_data(0.0, 0.0, 1000.0, 10000, 0, 0, 1000));
+        let mut data = create_test_imu_data(0.0, 0.0, 1000.0, 10000, 0, 0, 1010);
+        data.timestamp_gyro = 1500;
+        let outcome = processor.process(&data);
+
+        assert!(!outcome.orientation_updated);
+        assert!(outcome.position_updated);
+        assert_eq!(
+            outcome.skipped_reason,
+            Some(SkipReason::ExcessiveGyroDelta { dt: 0.5 })
+        );
+    }
+
+    #[test]
+    fn test_process_outcome_excessive_acc_delta() {
+        let mut processor = MotionProcessor::new(create_test_logger());
+
+        processor.process(&create_test_imu_data(0.0, 0.0, 1000.0, 10000, 0, 0, 1000));
+        let mut data = create_test_imu_data(0.0, 0.0, 1000.0, 10000, 0, 0, 1010);
+        data.timestamp_acc = 1500;
+        let outcome = processor.process(&data);
+
+        assert!(outcome.orientation_updated);
+        assert!(!outcome.position_updated);
+        assert_eq!(
+            outcome.skipped_reason,
+            Some(SkipReason::ExcessiveAccDelta { dt: 0.5 })
+        );
+    }
+
+    #[test]
+    fn test_complementary_cutoff_weights() {
+        let mut processor = MotionProcessor::new(create_test_logger());
+        processor.set_complementary_cutoff_hz(0.5, 500.0);
+
+        let tau = 1.0 / (2.0 * std::f32::consts::PI * 0.5);
+        // A level, slowly turning sample at exactly 1g gets the full accel weight
+        processor.process(&create_test_imu_data(0.0, 0.0, 1000.0, 0, 0, 10_000, 1000));
+        processor.process(&create_test_imu_data(0.0, 0.0, 1000.0, 0, 0, 10_000, 1002));
+        let diagnostics = processor.orientation_filter.diagnostics().unwrap();
+        assert_relative_eq!(diagnostics.gyro_weight, tau / (tau + 0.002), epsilon = 1e-6);
+        assert_relative_eq!(
+            diagnostics.gyro_weight + diagnostics.acc_weight,
+            1.0,
+            epsilon = 1e-6
+        );
+    }
+
+    #[test]
+    fn test_complementary_cutoff_consistent_across_rates() {
+        const CUTOFF_HZ: f32 = 0.5;
+        const DRIFT_MDPS: i32 = 5000;
+
+        let steady_state_error = |sample_rate_hz: u32| {
+            let mut processor = MotionProcessor::new(create_test_logger());
+            processor.set_complementary_cutoff_hz(CUTOFF_HZ, sample_rate_hz as f32);
+
+            let dt_ms = 1000 / sample_rate_hz;
+            for i in 0..(5 * sample_rate_hz) {
+                let timestamp = 1000 + i * dt_ms;
+                processor.process(&create_test_imu_data(
+                    0.0, 0.0, 1000.0, DRIFT_MDPS, 0, 0, timestamp,
+                ));
+            }
+
+            let (roll, _, _) = processor.state.orientation.euler_angles();
+            roll
+        };
+
+        let error_slow = steady_state_error(100);
+        let error_fast = steady_state_error(500);
+
+        // Drift is attenuated to roughly drift_rate * tau regardless of rate
+        let tau = 1.0 / (2.0 * std::f32::consts::PI * CUTOFF_HZ);
+        let expected = units::mdps_to_radps(DRIFT_MDPS as f32) * tau;
+        assert_relative_eq!(error_slow, expected, max_relative = 0.1);
+        assert_relative_eq!(error_fast, expected, max_relative = 0.1);
+        assert_relative_eq!(error_slow, error_fast, max_relative = 0.1);
+    }
+
+    #[test]
+    fn test_orientation_stays_unit_norm_over_many_updates() {
+        let mut processor = MotionProcessor::new(create_test_logger());
+        processor.set_gyro_only(true);
+
+        for i in 0..50_000u32 {
+            let phase = i as f32 * 0.01;
+            processor.process(&create_test_imu_data(
+                0.0,
+                0.0,
+                1000.0,
+                (90_000.0 * phase.sin()) as i32,
+                (45_000.0 * phase.cos()) as i32,
+                120_000,
+                1000 + i * 5,
+            ));
+            let norm = processor.state.orientation.norm();
+            assert!(
+                (norm - 1.0).abs() < 1e-5,
+                "Norm {} after {} updates",
+                norm,
+                i
+            );
+        }
+        assert!(processor.max_norm_deviation < 1e-5);
+
+        // A denormalized orientation is corrected on the next update
+        processor.state.orientation =
+            UnitQuaternion::new_unchecked(processor.state.orientation.into_inner() * 1.01);
+        processor.process(&create_test_imu_data(
+            0.0, 0.0, 1000.0, 0, 0, 120_000, 251_000,
+        ));
+        assert_relative_eq!(processor.state.orientation.norm(), 1.0, epsilon = 1e-6);
+        assert_relative_eq!(processor.max_norm_deviation, 0.01, epsilon = 1e-3);
+    }
+
+    #[test]
+    fn test_smoothed_orientation_lags_and_jitters_less() {
+        let run = |smoothing: Option<f32>| {
+            let mut processor = MotionProcessor::new(create_test_logger());
+            processor.set_gyro_only(true);
+            processor.set_orientation_smoothing(smoothing);
+
+            let mut raw = Vec::new();
+            let mut smoothed = Vec::new();
+            for i in 0..200u32 {
+                // 45deg/s with +-200deg/s of alternating noise
+                let noise = if i % 2 == 0 { 200_000 } else { -200_000 };
+                processor.process(&create_test_imu_data(
+                    0.0,
+                    0.0,
+                    1000.0,
+                    0,
+                    0,
+                    45_000 + noise,
+                    1000 + i * 10,
+                ));
+                raw.push(processor.state.orientation);
+                smoothed.push(processor.state.smoothed_orientation());
+            }
+            (raw, smoothed)
+        };
+        let jitter = |track: &[UnitQuaternion<f32>]| -> f32 {
+            track
+                .windows(2)
+                .map(|pair| pair[0].angle_to(&pair[1]))
+                .sum()
+        };
+
+        let (unsmoothed_raw, unsmoothed_display) = run(None);
+        assert_eq!(unsmoothed_display, unsmoothed_raw);
+
+        let (raw, smoothed) = run(Some(0.2));
+        assert_eq!(
+            raw, unsmoothed_raw,
+            "Smoothing must not touch the raw estimate"
+        );
+        assert!(
+            jitter(&smoothed) < jitter(&raw) / 2.0,
+            "smoothed {} vs raw {}",
+            jitter(&smoothed),
+            jitter(&raw)
+        );
+
+        let yaw = |q: &UnitQuaternion<f32>| q.euler_angles().2;
+        assert!(
+            yaw(smoothed.last().unwrap()) < yaw(raw.last().unwrap()),
+            "Smoothed orientation should trail the rotation"
+        );
+    }
+
+    #[test]
+    fn test_gyro_only_ignores_accel() {
+        let initial = UnitQuaternion::from_euler_angles(30f32.to_radians(), 0.0, 0.0);
+        let z_rate_mdps = 90_000;
+
+        let run = |gyro_only: bool| {
+            let mut processor = MotionProcessor::new(create_test_logger());
+            processor.set_gyro_only(gyro_only);
+            processor.state.orientation = initial;
+
+            for i in 0..10 {
+                processor.process(&create_test_imu_data(
+                    0.0,
+                    0.0,
+                    1000.0,
+                    0,
+                    0,
+                    z_rate_mdps,
+                    1000 + i * 10,
+                ));
+            }
+            processor.state.orientation
+        };
+
+        // First sample integrates over MIN_DELTA_TIME, then nine 10ms steps
+        let total_time = MIN_DELTA_TIME + 9.0 * 0.01;
+        let expected = initial
+            * UnitQuaternion::from_axis_angle(
+                &Vector3::z_axis(),
+                units::mdps_to_radps(z_rate_mdps as f32) * total_time,
+            );
+
+        let gyro_only = run(true);
+        assert_relative_eq!(gyro_only.angle_to(&expected), 0.0, epsilon = 1e-4);
+
+        let fused = run(false);
+        assert!(
+            fused.angle_to(&expected) > 1e-3,
+            "Accel correction should pull the fused orientation toward level"
+        );
+    }
+
+    fn create_mag_sphere(center: Vector3<f32>, radii: Vector3<f32>) -> Vec<ImuData> {
+        let mut samples = Vec::new();
+        for i in 0..36 {
+            let azimuth = (i as f32 * 10.0).to_radians();
+            for j in -9..=9 {
+                let elevation = (j as f32 * 10.0).to_radians();
+                let direction = Vector3::new(
+                    elevation.cos() * azimuth.cos(),
+                    elevation.cos() * azimuth.sin(),
+                    elevation.sin(),
+                );
+                let mag = center + radii.component_mul(&direction);
+                samples.push(create_test_mag_data(mag.x, mag.y, mag.z));
+            }
+        }
+        samples
+    }
+
+    #[test]
+    fn test_calibrate_mag_hard_iron() {
+        let mut processor = MotionProcessor::new(create_test_logger());
+        let offset = Vector3::new(120.0, -80.0, 35.0);
+
+        let samples = create_mag_sphere(offset, Vector3::repeat(400.0));
+        processor.calibrate_mag_hard_iron(&samples);
+
+        assert_relative_eq!(processor.mag_bias, offset, epsilon = 1e-2);
+    }
+
+    #[test]
+    fn test_mag_bias_applied_to_heading() {
+        let mut processor = MotionProcessor::new(create_test_logger());
+        let offset = Vector3::new(120.0, -80.0, 35.0);
+        processor.set_mag_bias(offset);
+
+        // North along -Y after removing the offset -> 90 degrees
+        let raw = Vector3::new(0.0, -300.0, -400.0) + offset;
+        let heading =
+            processor.tilt_compensated_heading_from_mag(&create_test_mag_data(raw.x, raw.y, raw.z));
+
+        assert_relative_eq!(heading, 90.0, epsilon = 1e-3);
+    }
+
+    #[test]
+    fn test_calibrate_mag_hard_iron_empty() {
+        let mut processor = MotionProcessor::new(create_test_logger());
+        processor.set_mag_bias(Vector3::new(1.0, 2.0, 3.0));
+
+        processor.calibrate_mag_hard_iron(&[]);
+
+        assert_eq!(processor.mag_bias, Vector3::new(1.0, 2.0, 3.0));
+    }
+
+    #[test]
+    fn test_calibrate_mag_soft_iron() {
+        let mut processor = MotionProcessor::new(create_test_logger());
+        let offset = Vector3::new(120.0, -80.0, 35.0);
+
+        let samples = create_mag_sphere(offset, Vector3::new(500.0, 300.0, 400.0));
+        processor.calibrate_mag_hard_iron(&samples);
+        processor.calibrate_mag_soft_iron(&samples);
+
+        let norms: Vec<f32> = samples
+            .iter()
+            .map(|s| processor.mag_vector(s).norm())
+            .collect();
+        let min = norms.iter().cloned().fold(f32::MAX, f32::min);
+        let max = norms.iter().cloned().fold(f32::MIN, f32::max);
+
+        assert_relative_eq!(min, 400.0, max_relative = 1e-3);
+        assert_relative_eq!(max, 400.0, max_relative = 1e-3);
+    }
+
+    #[test]
+    fn test_calibrate_mag_soft_iron_degenerate() {
+        let mut processor = MotionProcessor::new(create_test_logger());
+
+        let samples = [
+            create_test_mag_data(100.0, 0.0, 0.0),
+            create_test_mag_data(-100.0, 0.0, 0.0),
+        ];
+        processor.calibrate_mag_soft_iron(&samples);
+
+        assert_eq!(processor.mag_scale, Vector3::repeat(1.0));
+    }
+
+    #[test]
+    fn test_receive_clock_with_frozen_timestamps() {
+        let run = |use_receive_clock: bool| {
+            let mut processor = MotionProcessor::new(create_test_logger());
+            processor.set_velocity_decay(1.0);
+            processor.set_use_receive_clock(use_receive_clock);
+
+            for _ in 0..10 {
+                processor.process(&create_test_imu_data(1000.0, 0.0, 1000.0, 0, 0, 0, 1000));
+                std::thread::sleep(std::time::Duration::from_millis(10));
+            }
+            processor.state.velocity.x
+        };
+
+        // Only the first sample integrates (MIN_DELTA_TIME); the rest have zero dt
+        assert_relative_eq!(run(false), 9.81 * MIN_DELTA_TIME, epsilon = 1e-4);
+
+        // ~9 arrivals 10ms apart plus the initial MIN_DELTA_TIME at 1g
+        let velocity = run(true);
+        assert!(
+            velocity > 0.5,
+            "Receive clock should advance integration, got {}",
+            velocity
+        );
+        assert!(velocity < 9.81 * 10.0 * MAX_DELTA_TIME);
+    }
+
+    #[test]
+    fn test_orientation_only_skips_position() {
+        let mut processor = MotionProcessor::new(create_test_logger());
+        processor.set_orientation_only(true);
+
+        for i in 0..10 {
+            let outcome = processor.process(&create_test_imu_data(
+                1000.0,
+                0.0,
+                1000.0,
+                0,
+                0,
+                90_000,
+                1000 + i * 10,
+            ));
+            assert!(!outcome.position_updated);
+            assert!(outcome.skipped_reason.is_none());
+        }
+
+        assert_eq!(processor.state.velocity, Vector3::zeros());
+        assert_eq!(processor.state.position, Vector3::zeros());
+        assert!(processor.state.orientation.angle() > 0.0);
+    }
+
+    #[test]
+    fn test_sample_gap_policies() {
+        let run = |policy: Option<SampleGapPolicy>| {
+            let (logger, captured) = capturing_logger();
+            let mut processor = MotionProcessor::new(logger);
+            processor.set_velocity_decay(1.0);
+            processor.set_max_sample_gap(
+                policy.map(|_| 0.5),
+                policy.unwrap_or(SampleGapPolicy::Reset),
+            );
+
+            // 0.5g along X for 0.5s, then the stream stalls for 2s
+            for i in 0..50 {
+                processor.process(&create_test_imu_data(
+                    500.0,
+                    0.0,
+                    1000.0,
+                    0,
+                    0,
+                    0,
+                    1000 + i * 10,
+                ));
+            }
+            assert!(processor.state.velocity.norm() > 2.0);
+            processor.state.orientation = UnitQuaternion::from_euler_angles(0.3, 0.0, 1.0);
+
+            let outcome = processor.process(&create_test_imu_data(0.0, 0.0, 1000.0, 0, 0, 0, 3500));
+            let position_updated = outcome.position_updated;
+            let warnings = captured
+                .messages()
+                .iter()
+                .filter(|m| m.contains("Gap in sample stream"))
+                .count();
+            (processor.state.clone(), position_updated, warnings)
+        };
+
+        let (state, position_updated, warnings) = run(None);
+        assert!(!position_updated, "Stale state is only skipped by default");
+        assert!(state.velocity.norm() > 2.0);
+        assert_eq!(warnings, 0);
+
+        let (state, position_updated, warnings) = run(Some(SampleGapPolicy::Reset));
+        assert!(position_updated, "The sample after the gap starts afresh");
+        assert!(state.velocity.norm() < 1e-2);
+        assert!(state.position.norm() < 1e-2);
+        assert_eq!(warnings, 1);
+        let (roll, _, _) = state.orientation.euler_angles();
+        assert_relative_eq!(roll, 0.3, epsilon = 1e-5);
+
+        let (state, _, _) = run(Some(SampleGapPolicy::ResetAndLevel));
+        assert!(state.velocity.norm() < 1e-2);
+        let (roll, pitch, yaw) = state.orientation.euler_angles();
+        assert_relative_eq!(roll, 0.0, epsilon = 1e-5);
+        assert_relative_eq!(pitch, 0.0, epsilon = 1e-5);
+        assert_relative_eq!(yaw, 1.0, epsilon = 1e-5);
+    }
+
+    #[test]
+    fn test_position_bound_policies() {
+        let run = |policy: PositionBoundPolicy| {
+            let (logger, captured) = capturing_logger();
+            let mut processor = MotionProcessor::new(logger);
+            processor.set_velocity_decay(1.0);
+            processor.set_position_bound(Some(1.0), policy);
+
+            // 1g along X for 2s reaches ~19.6m without the bound
+            let mut positions = Vec::new();
+            for i in 0..200 {
+                processor.process(&create_test_imu_data(
+                    1000.0,
+                    0.0,
+                    1000.0,
+                    0,
+                    0,
+                    0,
+                    1000 + i * 10,
+                ));
+                positions.push(processor.state.position.norm());
+            }
+
+            let warnings = captured
+                .messages()
+                .iter()
+                .filter(|m| m.contains("sanity bound"))
+                .count();
+            (positions, processor.state.velocity.norm(), warnings)
+        };
+
+        let (positions, velocity, warnings) = run(PositionBoundPolicy::Clamp);
+        assert_eq!(warnings, 1, "Warning should be logged exactly once");
+        assert!(positions.iter().all(|&p| p <= 1.0 + 1e-5));
+        assert_relative_eq!(*positions.last().unwrap(), 1.0, epsilon = 1e-5);
+        assert!(velocity > 1.0, "Clamping keeps the velocity");
+
+        let (positions, _, warnings) = run(PositionBoundPolicy::Reset);
+        assert_eq!(warnings, 1, "Warning should be logged exactly once");
+        assert!(positions.iter().all(|&p| p <= 1.0));
+        assert!(
+            positions.contains(&0.0),
+            "Position should restart from zero after tripping the bound"
+        );
+    }
+
+    #[test]
+    fn test_history_keeps_only_recent_states() {
+        let mut processor = MotionProcessor::new(create_test_logger());
+        processor.set_history_capacity(4);
+
+        for i in 0..10 {
+            processor.process(&create_test_imu_data(
+                0.0,
+                0.0,
+                1000.0,
+                0,
+                0,
+                0,
+                1000 + i * 10,
+            ));
+        }
+
+        let timestamps: Vec<u32> = processor
+            .recent_states()
+            .map(|state| state.last_acc_timestamp)
+            .collect();
+        assert_eq!(timestamps, vec![1060, 1070, 1080, 1090]);
+
+        let path = std::env::temp_dir().join("test_imu_recent_states.json");
+        processor.save_recent_states(&path).unwrap();
+        let saved: Vec<MotionState> =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(
+            saved.iter().collect::<Vec<_>>(),
+            processor.recent_states().collect::<Vec<_>>()
+        );
+        std::fs::remove_file(&path).ok();
+
+        processor.set_history_capacity(2);
+        assert_eq!(processor.recent_states().count(), 2);
+        assert_eq!(
+            processor.recent_states().next().unwrap().last_acc_timestamp,
+            1080
+        );
+    }
+
+    #[test]
+    fn test_state_snapshot_round_trip() {
+        let path = std::env::temp_dir().join("test_imu_motion_state.json");
+        let samples: Vec<ImuData> = (0..20)
+            .map(|i| create_test_imu_data(200.0, -100.0, 1000.0, 5_000, 0, 30_000, 1000 + i * 10))
+            .collect();
+
+        let mut original = MotionProcessor::new(create_test_logger());
+        original.set_accel_gravity(true);
+        original.set_calibration(&Calibration {
+            gyro_bias: Vector3::new(100.0, -50.0, 20.0),
+            acc_scale: Vector3::new(1.01, 0.99, 1.0),
+            ..original.calibration()
+        });
+        for sample in &samples[..10] {
+            original.process(sample);
+        }
+        original.save_state(&path).expect("Failed to save state");
+
+        let mut restored = MotionProcessor::new(create_test_logger());
+        restored.set_accel_gravity(true);
+        restored.load_state(&path).expect("Failed to load state");
+        assert_eq!(restored.state, original.state);
+        assert_eq!(restored.calibration(), original.calibration());
+        assert!(original.gravity_body.is_some());
+        assert_eq!(restored.gravity_body, original.gravity_body);
+
+        for sample in &samples[10..] {
+            original.process(sample);
+            restored.process(sample);
+        }
+        assert_eq!(restored.state, original.state);
+
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_microsecond_timestamps_resolve_sub_millisecond_dt() {
+        // 2kHz, so consecutive samples often share a millisecond timestamp
+        let sample = |us: u64| ImuData {
+            timestamp_acc_us: Some(us),
+            timestamp_gyro_us: Some(us),
+            ..create_test_imu_data(0.0, 0.0, 1000.0, 0, 0, 90_000, (us / 1000) as u32)
+        };
+        let first = sample(1_000_000);
+        let second = sample(1_000_500);
+        assert_eq!(first.timestamp_gyro, second.timestamp_gyro);
+
+        assert_relative_eq!(
+            sensor_delta_time(
+                (second.timestamp_gyro, second.timestamp_gyro_us),
+                (first.timestamp_gyro, first.timestamp_gyro_us)
+            ),
+            0.0005
+        );
+        // Without microseconds on both sides dt falls back to milliseconds
+        assert_eq!(
+            sensor_delta_time(
+                (second.timestamp_gyro, None),
+                (first.timestamp_gyro, Some(1))
+            ),
+            0.0
+        );
+
+        let mut processor = MotionProcessor::new(create_test_logger());
+        processor.set_gyro_only(true);
+        processor.process(&first);
+        let yaw_before = processor.state.orientation.euler_angles().2;
+        let outcome = processor.process(&second);
+        assert!(outcome.orientation_updated, "{:?}", outcome.skipped_reason);
+
+        let yaw = processor.state.orientation.euler_angles().2 - yaw_before;
+        assert_relative_eq!(yaw, 90f32.to_radians() * 0.0005, epsilon = 1e-6);
+    }
+
+    #[test]
+    fn test_six_position_calibration_restores_1g() {
+        // Gain errors per axis and an offset, as a real accelerometer has
+        let gain = Vector3::new(1.05, 0.97, 1.02);
+        let offset = Vector3::new(10.0, -5.0, 20.0);
+        let measure = |gravity_mg: Vector3<f32>, timestamp: u32| {
+            let raw = gravity_mg.component_mul(&gain) + offset;
+            create_test_imu_data(raw.x, raw.y, raw.z, 0, 0, 0, timestamp)
+        };
+        let positions: Vec<[Vec<ImuData>; 2]> = (0..3)
+            .map(|axis| {
+                let up = Vector3::ith(axis, 1000.0);
+                [
+                    (0..10).map(|i| measure(up, i)).collect(),
+                    (0..10).map(|i| measure(-up, i)).collect(),
+                ]
+            })
+            .collect();
+
+        let mut processor = MotionProcessor::new(create_test_logger());
+        let tilted = measure(Vector3::new(1.0, 1.0, 1.0).normalize() * 1000.0, 0);
+        let uncalibrated = processor.acc_vector(&tilted).norm();
+        assert!((uncalibrated - 1000.0).abs() > 20.0);
+
+        processor.calibrate_acc_scale([
+            [&positions[0][0], &positions[0][1]],
+            [&positions[1][0], &positions[1][1]],
+            [&positions[2][0], &positions[2][1]],
+        ]);
+        assert_relative_eq!(processor.acc_bias, offset, epsilon = 1e-3);
+        assert_relative_eq!(processor.acc_scale, gain.map(|g| 1.0 / g), epsilon = 1e-5);
+        assert_relative_eq!(processor.acc_vector(&tilted).norm(), 1000.0, epsilon = 1e-2);
+
+        // Incomplete input keeps the calibration
+        processor.calibrate_acc_scale([[&[], &positions[0][1]], [&[], &[]], [&[], &[]]]);
+        assert_relative_eq!(processor.acc_scale, gain.map(|g| 1.0 / g), epsilon = 1e-5);
+
+        // A single run through all positions, turning in between
+        let run: Vec<ImuData> = positions
+            .iter()
+            .flatten()
+            .flat_map(|position| position.iter().copied().chain([tilted]))
+            .collect();
+        let mut processor = MotionProcessor::new(create_test_logger());
+        processor.calibrate_acc_scale_from_run(&run);
+        assert_relative_eq!(processor.acc_bias, offset, epsilon = 1e-3);
+        assert_relative_eq!(processor.acc_scale, gain.map(|g| 1.0 / g), epsilon = 1e-5);
+    }
+
+    #[test]
+    fn test_gyro_scale_calibration_recovers_turn_angle() {
+        // A full turn about Z at 90deg/s, read 10% too high by the gyro
+        let turn: Vec<ImuData> = (0..=400)
+            .map(|i| create_test_imu_data(0.0, 0.0, 1000.0, 0, 0, 99_000, 1000 + i * 10))
+            .collect();
+        let quarter_turn_yaw = |processor: &mut MotionProcessor| {
+            processor.set_gyro_only(true);
+            for sample in &turn[..=100] {
+                processor.process(sample);
+            }
+            processor.state.orientation.euler_angles().2.to_degrees()
+        };
+
+        let mut uncalibrated = MotionProcessor::new(create_test_logger());
+        assert!((quarter_turn_yaw(&mut uncalibrated) - 99.0).abs() < 0.5);
+
+        let mut processor = MotionProcessor::new(create_test_logger());
+        processor.calibrate_gyro_scale(&turn, Axis::Z, 360.0);
+        assert_relative_eq!(
+            processor.gyro_scale,
+            Vector3::new(1.0, 1.0, 1.0 / 1.1),
+            epsilon = 1e-5
+        );
+        assert!(
+            (quarter_turn_yaw(&mut processor) - 90.0).abs() < 0.5,
+            "yaw {}",
+            processor.state.orientation.euler_angles().2.to_degrees()
+        );
+
+        // Standing still says nothing about the scale
+        processor.calibrate_gyro_scale(&turn[..1], Axis::Z, 360.0);
+        assert_relative_eq!(processor.gyro_scale.z, 1.0 / 1.1, epsilon = 1e-5);
+    }
+
+    #[test]
+    fn test_calibration_file_round_trip() {
+        let path = std::env::temp_dir().join("test_imu_calibration.json");
+        let mut calibrated = MotionProcessor::new(create_test_logger());
+        calibrated.set_calibration(&Calibration {
+            acc_bias: Vector3::new(20.0, -10.0, 5.0),
+            acc_scale: Vector3::repeat(1.0),
+            gyro_bias: Vector3::new(100.0, -200.0, 0.0),
+            gyro_scale: Vector3::repeat(1.0),
+            mag_bias: Vector3::new(30.0, 0.0, -15.0),
+            mag_scale: Vector3::new(1.1, 0.9, 1.0),
+        });
+        calibrated
+            .save_calibration(&path)
+            .expect("Failed to save calibration");
+
+        let mut restored = MotionProcessor::new(create_test_logger());
+        restored
+            .load_calibration(&path)
+            .expect("Failed to load calibration");
+        assert_eq!(restored.calibration(), calibrated.calibration());
+
+        // The loaded gyro bias is subtracted from the readings
+        let sample = create_test_imu_data(20.0, -10.0, 1005.0, 9100, -200, 45000, 1000);
+        restored.process(&sample);
+        let expected = Vector3::new(9.0, 0.0, 45.0).map(f32::to_radians);
+        assert_relative_eq!(restored.state.angular_velocity, expected, epsilon = 1e-6);
+        assert_relative_eq!(restored.acc_vector(&sample), Vector3::new(0.0, 0.0, 1000.0));
+
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_calibration_without_scales_defaults_to_unit() {
+        let calibration: Calibration = serde_json::from_str(
+            r#"{"acc_bias": [1.0, 2.0, 3.0], "gyro_bias": [0.0, 0.0, 0.0], "mag_bias": [0.0, 0.0, 0.0]}"#,
+        )
+        .expect("Scales should be optional");
+
+        assert_eq!(calibration.acc_scale, unit_scale());
+        assert_eq!(calibration.gyro_scale, unit_scale());
+        assert_eq!(calibration.mag_scale, unit_scale());
+    }
+
+    #[test]
+    fn test_state_snapshot_rejects_other_version() {
+        let path = std::env::temp_dir().join("test_imu_motion_state_version.json");
+        std::fs::write(&path, r#"{"version": 99, "state": {}}"#).unwrap();
+
+        let mut processor = MotionProcessor::new(create_test_logger());
+        processor.state.position = Vector3::new(1.0, 2.0, 3.0);
+        let err = processor
+            .load_state(&path)
+            .expect_err("Version 99 should be rejected");
+
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert_eq!(processor.state.position, Vector3::new(1.0, 2.0, 3.0));
+
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_accel_gravity_survives_gyro_drift() {
+        let run = |accel_gravity: bool| {
+            let mut processor = MotionProcessor::new(create_test_logger());
+            processor.set_velocity_decay(1.0);
+            processor.set_gyro_only(true);
+            processor.set_accel_gravity(accel_gravity);
+
+            // Stationary sensor with a 10dps gyro bias on X, tilting the estimate by 20deg
+            for i in 0..200 {
+                processor.process(&create_test_imu_data(
+                    0.0,
+                    0.0,
+                    1000.0,
+                    10_000,
+                    0,
+                    0,
+                    1000 + i * 10,
+                ));
+            }
+            processor
+        };
+
+        let hybrid = run(true);
+        let (roll, _, _) = hybrid.state.orientation.euler_angles();
+        assert_relative_eq!(roll.to_degrees(), 20.0, epsilon = 0.5);
+        assert_relative_eq!(hybrid.state.velocity.norm(), 0.0, epsilon = 1e-3);
+
+        let drifting = run(false);
+        assert!(
+            drifting.state.velocity.norm() > 1.0,
+            "Orientation-based gravity removal should leak into velocity"
+        );
+    }
+
+    #[test]
+    fn test_accel_gravity_ignores_samples_outside_gravity_window() {
+        let mut processor = MotionProcessor::new(create_test_logger());
+        processor.set_velocity_decay(1.0);
+        processor.set_accel_gravity(true);
+
+        // A zero reading first, then a second of 0.5g sustained forward
+        // acceleration, neither of which may become the gravity estimate
+        processor.process(&create_test_imu_data(0.0, 0.0, 0.0, 0, 0, 0, 1000));
+        for i in 1..=100 {
+            processor.process(&create_test_imu_data(
+                500.0,
+                0.0,
+                1000.0,
+                0,
+                0,
+                0,
+                1000 + i * 10,
+            ));
+        }
+
+        assert!(processor.state.velocity.iter().all(|v| v.is_finite()));
+        let gravity = processor.track_gravity(Vector3::zeros(), 0.01);
+        assert_relative_eq!(
+            gravity,
+            Vector3::new(0.0, 0.0, STANDARD_GRAVITY),
+            epsilon = 1e-3
+        );
+        assert!(
+            processor.state.velocity.x > 3.0,
+            "Sustained acceleration should reach velocity, got {}",
+            processor.state.velocity
+        );
+    }
+
+    #[test]
+    fn test_delegates_to_custom_orientation_filter() {
+        /// Gyro, accel and dt of one `update` call
+        type Call = (Vector3<f32>, Vector3<f32>, f32);
+
+        /// Ignores its inputs and reports a fixed orientation.
+        #[derive(Debug)]
+        struct FixedFilter {
+            calls: Arc<Mutex<Vec<Call>>>,
+        }
+
+        impl OrientationFilter for FixedFilter {
+            fn update(
+                &mut self,
+                gyro: Vector3<f32>,
+                accel: Vector3<f32>,
+                _mag: Option<Vector3<f32>>,
+                dt: f32,
+            ) -> UnitQuaternion<f32> {
+                self.calls.lock().unwrap().push((gyro, accel, dt));
+                UnitQuaternion::from_euler_angles(0.0, 0.0, 1.0)
+            }
+        }
+
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let mut processor = MotionProcessor::new(create_test_logger());
+        processor.set_orientation_filter(Box::new(FixedFilter {
+            calls: calls.clone(),
+        }));
+        // Settings of the built-in filter leave the custom one in place
+        processor.set_gravity_tolerance(0.1);
+        processor.set_acc_weight_curve(AccWeightCurve::Linear);
+        processor.set_gyro_only(true);
+
+        processor.process(&create_test_imu_data(0.0, 0.0, 1000.0, 0, 0, 90_000, 1000));
+        processor.process(&create_test_imu_data(0.0, 0.0, 1000.0, 0, 0, 90_000, 1010));
+
+        let calls = calls.lock().unwrap();
+        assert_eq!(calls.len(), 2);
+        let (gyro, accel, dt) = calls[1];
+        assert_relative_eq!(gyro.z, units::mdps_to_radps(90_000.0), epsilon = 1e-6);
+        assert_relative_eq!(accel.z, STANDARD_GRAVITY, epsilon = 1e-5);
+        assert_relative_eq!(dt, 0.01, epsilon = 1e-6);
+        assert_relative_eq!(processor.heading(), 1f32.to_degrees(), epsilon = 1e-3);
+    }
+
+    #[test]
+    fn test_forwards_world_acceleration_to_position_estimator() {
+        /// Gravity-free world acceleration and dt of one `update` call
+        type Call = (Vector3<f32>, f32);
+
+        /// Records its inputs and reports a fixed estimate.
+        #[derive(Debug)]
+        struct MockEstimator {
+            calls: Arc<Mutex<Vec<Call>>>,
+        }
+
+        impl PositionEstimator for MockEstimator {
+            fn update(&mut self, acc_world: Vector3<f32>, dt: f32) -> PositionEstimate {
+                self.calls.lock().unwrap().push((acc_world, dt));
+                PositionEstimate {
+                    velocity: Vector3::new(0.0, 1.0, 0.0),
+                    position: Vector3::new(0.0, 0.0, 2.0),
+                }
+            }
+        }
+
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let mut processor = MotionProcessor::new(create_test_logger());
+        processor.set_position_estimator(Box::new(MockEstimator {
+            calls: calls.clone(),
+        }));
+        // Settings of the built-in estimator leave the custom one in place
+        processor.set_acc_deadband(1.0);
+        processor.set_zero_altitude_prior(Some((0.1, 1.0)));
+
+        processor.process(&create_test_imu_data(500.0, 0.0, 1000.0, 0, 0, 0, 1000));
+        processor.process(&create_test_imu_data(500.0, 0.0, 1000.0, 0, 0, 0, 1020));
+
+        let calls = calls.lock().unwrap();
+        assert_eq!(calls.len(), 2);
+        let (acc_world, dt) = calls[1];
+        assert_relative_eq!(acc_world.x, units::mg_to_mps2(500.0), epsilon = 1e-3);
+        assert_relative_eq!(acc_world.z, 0.0, epsilon = 1e-3);
+        assert_relative_eq!(dt, 0.02, epsilon = 1e-6);
+
+        assert_eq!(processor.state.velocity, Vector3::new(0.0, 1.0, 0.0));
+        assert_eq!(processor.state.position, Vector3::new(0.0, 0.0, 2.0));
+    }
+
+    #[test]
+    fn test_position_tracks_sinusoidal_translation() {
+        let trajectory = SinusoidalTranslation::new(0.5, 0.5);
+        let mut processor = MotionProcessor::new(create_test_logger());
+        processor.set_velocity_decay(1.0);
+        processor.set_acc_deadband(0.0);
+        processor.set_gyro_only(true);
+
+        // Half a period at 500Hz, out to the far end of the swing
+        for i in 0..=500 {
+            let sample = trajectory.sample(i as f32 * 0.002, 1000 + i * 2);
+            processor.process(&sample);
+
+            let truth = Vector3::new(
+                sample.true_x_pos.unwrap(),
+                sample.true_y_pos.unwrap(),
+                sample.true_z_pos.unwrap(),
+            );
+            assert!(
+                (processor.state.position - truth).norm() < 0.02,
+                "Estimate {:?} strayed from {:?} at sample {}",
+                processor.state.position,
+                truth,
+                i
+            );
+        }
+        assert_relative_eq!(processor.state.position.x, 1.0, epsilon = 0.02);
     }
 }
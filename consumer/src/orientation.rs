@@ -0,0 +1,353 @@
+use common::clap;
+use common::units::STANDARD_GRAVITY;
+use nalgebra::{Quaternion, Unit, UnitQuaternion, Vector3};
+
+/// Accel magnitudes outside this window [g] are not trusted as gravity.
+pub const DEFAULT_GRAVITY_WINDOW: (f32, f32) = (0.95, 1.05);
+
+/// How the complementary filter weighs the accel by how close its magnitude
+/// is to 1g, as a measure of how little linear acceleration contaminates
+/// the gravity estimate. Outside the gravity window the weight is always 0.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AccWeightCurve {
+    /// Full weight anywhere inside the window
+    #[default]
+    Fixed,
+    /// Full weight at 1g, falling linearly to 0 at the window edges
+    Linear,
+    /// Full weight at 1g, falling as a bell curve with the window edges at
+    /// two standard deviations
+    Gaussian,
+}
+
+impl AccWeightCurve {
+    /// Fraction [0, 1] of the configured accel weight applied at `acc_g`.
+    pub fn factor(self, acc_g: f32, window: (f32, f32)) -> f32 {
+        let (lower, upper) = window;
+        if acc_g <= lower || acc_g >= upper {
+            return 0.0;
+        }
+        match self {
+            AccWeightCurve::Fixed => 1.0,
+            AccWeightCurve::Linear => gravity_consistency(acc_g, window),
+            AccWeightCurve::Gaussian => {
+                let sigma = if acc_g >= 1.0 {
+                    upper - 1.0
+                } else {
+                    1.0 - lower
+                } / 2.0;
+                (-0.5 * ((acc_g - 1.0) / sigma).powi(2)).exp()
+            }
+        }
+    }
+}
+
+/// 1 for an accel magnitude of exactly 1g [g], falling linearly to 0 at
+/// the edge of `window` on that side and staying 0 beyond it.
+pub fn gravity_consistency(acc_g: f32, window: (f32, f32)) -> f32 {
+    let deviation = acc_g - 1.0;
+    let half_width = if deviation >= 0.0 {
+        window.1 - 1.0
+    } else {
+        1.0 - window.0
+    };
+    (1.0 - deviation.abs() / half_width.max(f32::EPSILON)).clamp(0.0, 1.0)
+}
+
+/// Component layout of logged quaternions. The math always uses nalgebra's
+/// Hamilton quaternions, this only changes the output.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum QuaternionConvention {
+    /// Hamilton `[w, x, y, z]`, scalar first
+    #[default]
+    Hamilton,
+    /// JPL `[x, y, z, w]`, scalar last. JPL multiplication is flipped, so
+    /// the same rotation matrix comes from the conjugate: the vector part
+    /// is negated
+    Jpl,
+}
+
+impl QuaternionConvention {
+    /// Components of `q` in this convention's order and sign.
+    pub fn components(self, q: &UnitQuaternion<f32>) -> [f32; 4] {
+        match self {
+            QuaternionConvention::Hamilton => [q.w, q.i, q.j, q.k],
+            QuaternionConvention::Jpl => [-q.i, -q.j, -q.k, q.w],
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            QuaternionConvention::Hamilton => "quat",
+            QuaternionConvention::Jpl => "quat-jpl",
+        }
+    }
+}
+
+/// Internals of the last update, logged by `MotionProcessor` in verbose mode.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct FilterDiagnostics {
+    /// Angle between the gyro-integrated and the accel-implied orientation [rad]
+    pub correction_angle: f32,
+    /// Weight of the gyro-integrated orientation in the blend
+    pub gyro_weight: f32,
+    /// Weight of the accel-implied orientation after adaptation, 0 when the
+    /// accel was not trusted
+    pub acc_weight: f32,
+}
+
+/// Attitude estimator driven by `MotionProcessor`.
+///
+/// Inputs are bias-corrected body-frame readings in SI units: `gyro` [rad/s],
+/// `accel` [m/s^2] and `mag` [T], the latter `None` when the sample carries
+/// no magnetometer reading. `dt` [s] is already validated by the processor.
+pub trait OrientationFilter: std::fmt::Debug + Send + Sync {
+    fn update(
+        &mut self,
+        gyro: Vector3<f32>,
+        accel: Vector3<f32>,
+        mag: Option<Vector3<f32>>,
+        dt: f32,
+    ) -> UnitQuaternion<f32>;
+
+    /// Called with the processor's orientation before every update, so
+    /// external changes (e.g. a restored snapshot) carry over. Filters that
+    /// keep no orientation of their own can ignore it.
+    fn set_orientation(&mut self, _orientation: UnitQuaternion<f32>) {}
+
+    /// Internals of the last update for tuning, if the filter exposes any.
+    fn diagnostics(&self) -> Option<FilterDiagnostics> {
+        None
+    }
+}
+
+/// Blends the gyro-integrated orientation with the tilt implied by gravity
+/// while the accel reads close to 1g. Weights sum to one. The magnetometer
+/// isn't fused, so yaw drifts with the gyro.
+#[derive(Debug, Clone)]
+pub struct ComplementaryFilter {
+    orientation: UnitQuaternion<f32>,
+    gyro_weight: f32,
+    acc_weight: f32,
+    gravity_window: (f32, f32),
+    acc_weight_curve: AccWeightCurve,
+    diagnostics: FilterDiagnostics,
+}
+
+impl ComplementaryFilter {
+    pub fn new(gyro_weight: f32, acc_weight: f32) -> Self {
+        Self {
+            orientation: UnitQuaternion::identity(),
+            gyro_weight,
+            acc_weight,
+            gravity_window: DEFAULT_GRAVITY_WINDOW,
+            acc_weight_curve: AccWeightCurve::default(),
+            diagnostics: FilterDiagnostics::default(),
+        }
+    }
+
+    /// Blend of the gyro-integrated and the accel-implied orientation, the
+    /// weights should sum to one.
+    pub fn set_weights(&mut self, gyro_weight: f32, acc_weight: f32) {
+        self.gyro_weight = gyro_weight;
+        self.acc_weight = acc_weight;
+    }
+
+    /// Accel magnitudes [g] strictly between `lower` and `upper` are trusted
+    /// as gravity. Wider trusts the accel during more real acceleration.
+    pub fn set_gravity_window(&mut self, lower: f32, upper: f32) {
+        self.gravity_window = (lower, upper);
+    }
+
+    /// Scales the accel weight down as the magnitude leaves 1g, handing the
+    /// difference to the gyro so the weights still sum to one.
+    pub fn set_acc_weight_curve(&mut self, curve: AccWeightCurve) {
+        self.acc_weight_curve = curve;
+    }
+
+    fn integrate_gyro(&self, gyro: Vector3<f32>, dt: f32) -> UnitQuaternion<f32> {
+        const EPSILON: f32 = 1e-6;
+
+        let angle = gyro.norm() * dt;
+        if gyro.norm() <= EPSILON {
+            return self.orientation;
+        }
+
+        let axis_unit = Unit::new_normalize(gyro);
+        self.orientation * UnitQuaternion::from_axis_angle(&axis_unit, angle)
+    }
+}
+
+impl OrientationFilter for ComplementaryFilter {
+    fn update(
+        &mut self,
+        gyro: Vector3<f32>,
+        accel: Vector3<f32>,
+        _mag: Option<Vector3<f32>>,
+        dt: f32,
+    ) -> UnitQuaternion<f32> {
+        let gyro_orientation = self.integrate_gyro(gyro, dt);
+
+        let acc_g = accel.norm() / STANDARD_GRAVITY;
+        self.diagnostics = FilterDiagnostics {
+            correction_angle: 0.0,
+            gyro_weight: 1.0,
+            acc_weight: 0.0,
+        };
+        let acc_weight = self.acc_weight * self.acc_weight_curve.factor(acc_g, self.gravity_window);
+        let gyro_weight = self.gyro_weight + (self.acc_weight - acc_weight);
+        self.orientation = if acc_weight > 0.0 {
+            let gravity_unit = Vector3::z_axis();
+            let acc_norm_unit = Unit::new_normalize(accel);
+
+            let acc_quat = UnitQuaternion::rotation_between(&gravity_unit, &acc_norm_unit)
+                .unwrap_or(UnitQuaternion::identity());
+            self.diagnostics = FilterDiagnostics {
+                correction_angle: gyro_orientation.angle_to(&acc_quat),
+                gyro_weight,
+                acc_weight,
+            };
+
+            UnitQuaternion::from_quaternion(
+                Quaternion::new(
+                    gyro_weight * gyro_orientation.scalar() + acc_weight * acc_quat.scalar(),
+                    gyro_weight * gyro_orientation.vector().x + acc_weight * acc_quat.vector().x,
+                    gyro_weight * gyro_orientation.vector().y + acc_weight * acc_quat.vector().y,
+                    gyro_weight * gyro_orientation.vector().z + acc_weight * acc_quat.vector().z,
+                )
+                .normalize(),
+            )
+        } else {
+            gyro_orientation
+        };
+
+        self.orientation
+    }
+
+    fn set_orientation(&mut self, orientation: UnitQuaternion<f32>) {
+        self.orientation = orientation;
+    }
+
+    fn diagnostics(&self) -> Option<FilterDiagnostics> {
+        Some(self.diagnostics)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_jpl_components_describe_the_same_rotation() {
+        let half = std::f32::consts::FRAC_1_SQRT_2;
+        // A quarter turn about Z
+        let q = UnitQuaternion::from_euler_angles(0.0, 0.0, std::f32::consts::FRAC_PI_2);
+        assert_relative_eq!(
+            &QuaternionConvention::Hamilton.components(&q)[..],
+            &[half, 0.0, 0.0, half][..],
+            epsilon = 1e-6
+        );
+        assert_relative_eq!(
+            &QuaternionConvention::Jpl.components(&q)[..],
+            &[0.0, 0.0, -half, half][..],
+            epsilon = 1e-6
+        );
+
+        // The JPL rotation matrix of the emitted components matches nalgebra's
+        let q = UnitQuaternion::from_euler_angles(0.3, -0.5, 1.2);
+        let [x, y, z, w] = QuaternionConvention::Jpl.components(&q);
+        let v = Vector3::new(x, y, z);
+        let jpl = nalgebra::Matrix3::identity() * (2.0 * w * w - 1.0)
+            - v.cross_matrix() * (2.0 * w)
+            + v * v.transpose() * 2.0;
+        assert_relative_eq!(jpl, *q.to_rotation_matrix().matrix(), epsilon = 1e-6);
+    }
+
+    #[test]
+    fn test_complementary_filter_pulls_towards_gravity() {
+        let mut filter = ComplementaryFilter::new(0.9, 0.1);
+        let tilted = UnitQuaternion::from_euler_angles(0.3, 0.0, 0.0);
+        filter.set_orientation(tilted);
+
+        let level = Vector3::new(0.0, 0.0, STANDARD_GRAVITY);
+        let mut orientation = tilted;
+        for _ in 0..100 {
+            orientation = filter.update(Vector3::zeros(), level, None, 0.01);
+        }
+
+        assert_relative_eq!(orientation.angle(), 0.0, epsilon = 1e-3);
+    }
+
+    #[test]
+    fn test_complementary_filter_ignores_accel_outside_gravity_window() {
+        let mut filter = ComplementaryFilter::new(0.9, 0.1);
+        let tilted = UnitQuaternion::from_euler_angles(0.3, 0.0, 0.0);
+        filter.set_orientation(tilted);
+
+        let free_fall = Vector3::new(0.0, 0.0, 0.1);
+        let orientation = filter.update(Vector3::zeros(), free_fall, None, 0.01);
+
+        assert_relative_eq!(orientation.angle_to(&tilted), 0.0, epsilon = 1e-6);
+    }
+
+    #[test]
+    fn test_adaptive_acc_weight_drops_while_accelerating() {
+        let acc_weight = |curve: AccWeightCurve, acc_g: &[f32]| {
+            let mut filter = ComplementaryFilter::new(0.9, 0.1);
+            filter.set_acc_weight_curve(curve);
+            filter.set_gravity_window(0.9, 1.1);
+            acc_g
+                .iter()
+                .map(|g| {
+                    filter.update(
+                        Vector3::zeros(),
+                        Vector3::new(0.0, 0.0, g * STANDARD_GRAVITY),
+                        None,
+                        0.01,
+                    );
+                    let diagnostics = filter.diagnostics().unwrap();
+                    assert_relative_eq!(diagnostics.gyro_weight + diagnostics.acc_weight, 1.0);
+                    diagnostics.acc_weight
+                })
+                .collect::<Vec<_>>()
+        };
+        // Still, speeding up at 0.05g and 0.15g, then still again
+        let profile = [1.0, 1.05, 1.15, 1.0];
+
+        assert_eq!(
+            acc_weight(AccWeightCurve::Fixed, &profile),
+            [0.1, 0.1, 0.0, 0.1]
+        );
+        let linear = acc_weight(AccWeightCurve::Linear, &profile);
+        assert_relative_eq!(&linear[..], &[0.1, 0.05, 0.0, 0.1][..], epsilon = 1e-5);
+        let gaussian = acc_weight(AccWeightCurve::Gaussian, &profile);
+        assert_relative_eq!(gaussian[0], 0.1);
+        assert!(
+            gaussian[1] < 0.1 && gaussian[1] > linear[1],
+            "{:?}",
+            gaussian
+        );
+        assert_eq!(gaussian[2], 0.0);
+        assert_relative_eq!(gaussian[3], 0.1);
+    }
+
+    #[test]
+    fn test_gravity_window_bounds_are_configurable() {
+        // Level, accelerating at 0.1g along Z
+        let accel = Vector3::new(0.0, 0.0, 1.1 * STANDARD_GRAVITY);
+        let tilted = UnitQuaternion::from_euler_angles(0.3, 0.0, 0.0);
+
+        let update = |window: (f32, f32)| {
+            let mut filter = ComplementaryFilter::new(0.9, 0.1);
+            filter.set_gravity_window(window.0, window.1);
+            filter.set_orientation(tilted);
+            filter.update(Vector3::zeros(), accel, None, 0.01)
+        };
+
+        assert_relative_eq!(update(DEFAULT_GRAVITY_WINDOW).angle_to(&tilted), 0.0);
+        assert!(update((0.9, 1.2)).angle() < tilted.angle());
+    }
+}
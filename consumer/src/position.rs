@@ -0,0 +1,190 @@
+use nalgebra::{Matrix2, RowVector2, Vector2, Vector3};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PositionEstimate {
+    pub velocity: Vector3<f32>,
+    pub position: Vector3<f32>,
+}
+
+/// Velocity and position estimator driven by `MotionProcessor`.
+///
+/// `acc_world` is the world-frame acceleration with gravity already removed
+/// [m/s^2], `dt` [s] is already validated by the processor.
+pub trait PositionEstimator: std::fmt::Debug + Send + Sync {
+    fn update(&mut self, acc_world: Vector3<f32>, dt: f32) -> PositionEstimate;
+
+    /// Called with the processor's velocity and position before every update,
+    /// so external changes (e.g. the position bound) carry over. Estimators
+    /// that keep no such state of their own can ignore it.
+    fn set_state(&mut self, _velocity: Vector3<f32>, _position: Vector3<f32>) {}
+}
+
+/// Plain double integration with a per-axis deadband on the acceleration
+/// and a per-sample velocity decay to bound the drift.
+#[derive(Debug, Clone)]
+pub struct DeadReckoning {
+    velocity_decay: f32,
+    acc_deadband: f32,
+    velocity: Vector3<f32>,
+    position: Vector3<f32>,
+}
+
+impl DeadReckoning {
+    pub fn new(velocity_decay: f32, acc_deadband: f32) -> Self {
+        Self {
+            velocity_decay,
+            acc_deadband,
+            velocity: Vector3::zeros(),
+            position: Vector3::zeros(),
+        }
+    }
+}
+
+impl PositionEstimator for DeadReckoning {
+    fn update(&mut self, acc_world: Vector3<f32>, dt: f32) -> PositionEstimate {
+        let acc_deadband = self.acc_deadband;
+        let filtered_acc = acc_world.map(|a| if a.abs() < acc_deadband { 0.0 } else { a });
+
+        self.velocity += filtered_acc * dt;
+        self.velocity *= self.velocity_decay;
+        self.position += self.velocity * dt;
+
+        PositionEstimate {
+            velocity: self.velocity,
+            position: self.position,
+        }
+    }
+
+    fn set_state(&mut self, velocity: Vector3<f32>, position: Vector3<f32>) {
+        self.velocity = velocity;
+        self.position = position;
+    }
+}
+
+/// Dead reckoning in the horizontal plane with a 1D Kalman filter on the
+/// vertical axis that holds the altitude near zero.
+///
+/// The filter state is altitude and vertical velocity, predicted from the
+/// world-frame Z acceleration. There is no altitude sensor, so every update
+/// observes an altitude of zero: a prior for a device that stays at one
+/// height, not a measurement. It bounds the drift of naive double
+/// integration, but real climbs and descents are pulled back to zero too.
+/// `process_noise` is the acceleration noise variance [(m/s^2)^2],
+/// `prior_variance` the variance of the zero altitude [m^2]: raise it to
+/// follow real vertical motion longer at the cost of more drift.
+#[derive(Debug, Clone)]
+pub struct ZeroAltitudePrior {
+    horizontal: DeadReckoning,
+    state: Vector2<f32>,
+    covariance: Matrix2<f32>,
+    process_noise: f32,
+    prior_variance: f32,
+}
+
+impl ZeroAltitudePrior {
+    pub fn new(horizontal: DeadReckoning, process_noise: f32, prior_variance: f32) -> Self {
+        Self {
+            horizontal,
+            state: Vector2::zeros(),
+            covariance: Matrix2::identity(),
+            process_noise,
+            prior_variance,
+        }
+    }
+
+    fn predict(&mut self, acc_z: f32, dt: f32) {
+        let transition = Matrix2::new(1.0, dt, 0.0, 1.0);
+        let control = Vector2::new(0.5 * dt * dt, dt);
+        let noise = control * control.transpose() * self.process_noise;
+
+        self.state = transition * self.state + control * acc_z;
+        self.covariance = transition * self.covariance * transition.transpose() + noise;
+    }
+
+    fn correct(&mut self, altitude: f32) {
+        let observation = RowVector2::new(1.0, 0.0);
+        let innovation_variance =
+            (observation * self.covariance * observation.transpose())[0] + self.prior_variance;
+        let gain = self.covariance * observation.transpose() / innovation_variance;
+
+        self.state += gain * (altitude - self.state.x);
+        self.covariance = (Matrix2::identity() - gain * observation) * self.covariance;
+    }
+}
+
+impl PositionEstimator for ZeroAltitudePrior {
+    fn update(&mut self, acc_world: Vector3<f32>, dt: f32) -> PositionEstimate {
+        let mut estimate = self.horizontal.update(acc_world, dt);
+
+        self.predict(acc_world.z, dt);
+        self.correct(0.0);
+
+        estimate.position.z = self.state.x;
+        estimate.velocity.z = self.state.y;
+        estimate
+    }
+
+    fn set_state(&mut self, velocity: Vector3<f32>, position: Vector3<f32>) {
+        self.horizontal.set_state(velocity, position);
+        self.state = Vector2::new(position.z, velocity.z);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_dead_reckoning_integrates_twice() {
+        let mut estimator = DeadReckoning::new(1.0, 0.0);
+
+        let mut estimate = estimator.update(Vector3::zeros(), 0.01);
+        for _ in 0..100 {
+            estimate = estimator.update(Vector3::new(1.0, 0.0, 0.0), 0.01);
+        }
+
+        assert_relative_eq!(estimate.velocity.x, 1.0, epsilon = 1e-4);
+        assert_relative_eq!(estimate.position.x, 0.505, epsilon = 1e-3);
+    }
+
+    #[test]
+    fn test_dead_reckoning_deadband_and_decay() {
+        let mut estimator = DeadReckoning::new(0.5, 0.1);
+        estimator.set_state(Vector3::new(2.0, 0.0, 0.0), Vector3::zeros());
+
+        let estimate = estimator.update(Vector3::new(0.05, 0.0, 0.0), 1.0);
+
+        assert_eq!(estimate.velocity, Vector3::new(1.0, 0.0, 0.0));
+        assert_eq!(estimate.position, Vector3::new(1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_zero_altitude_prior_drifts_less_than_integration() {
+        let mut naive = DeadReckoning::new(1.0, 0.0);
+        let mut kalman = ZeroAltitudePrior::new(DeadReckoning::new(1.0, 0.0), 0.1, 1.0);
+
+        // Stationary sensor with a small residual bias and noise on Z
+        let (mut naive_estimate, mut kalman_estimate) = (None, None);
+        for i in 0..1000 {
+            let noise = if i % 3 == 0 { 0.05 } else { -0.02 };
+            let acc_world = Vector3::new(0.0, 0.0, 0.02 + noise);
+            naive_estimate = Some(naive.update(acc_world, 0.01));
+            kalman_estimate = Some(kalman.update(acc_world, 0.01));
+        }
+
+        let naive_drift = naive_estimate.unwrap().position.z.abs();
+        let kalman_drift = kalman_estimate.unwrap().position.z.abs();
+        assert!(
+            naive_drift > 1.0,
+            "Naive integration should drift, got {}",
+            naive_drift
+        );
+        assert!(
+            kalman_drift < naive_drift / 10.0,
+            "Kalman drift {} should be well below naive drift {}",
+            kalman_drift,
+            naive_drift
+        );
+    }
+}
@@ -0,0 +1,105 @@
+use std::collections::HashMap;
+use std::time::Instant;
+
+#[derive(Debug, Clone)]
+struct Bucket {
+    tokens: f32,
+    last_refill: Instant,
+    suppressed: u64,
+}
+
+/// Token bucket per warning message, so a storm of identical warnings does
+/// not flood the log while distinct ones still get through.
+///
+/// Each message may burst up to `per_second` warnings and then refills at
+/// `per_second` tokens per second. Occurrences beyond that are counted and
+/// handed back once the message is admitted again, for a summary line.
+#[derive(Debug, Clone)]
+pub struct WarnLimiter {
+    per_second: u32,
+    buckets: HashMap<&'static str, Bucket>,
+}
+
+impl WarnLimiter {
+    /// `per_second` of 0 disables the limit.
+    pub fn new(per_second: u32) -> Self {
+        Self {
+            per_second,
+            buckets: HashMap::new(),
+        }
+    }
+
+    /// Whether a warning with `message` may be logged now. If so, returns
+    /// the occurrences suppressed since it was last admitted.
+    pub fn admit(&mut self, message: &'static str) -> Option<u64> {
+        self.admit_at(message, Instant::now())
+    }
+
+    fn admit_at(&mut self, message: &'static str, now: Instant) -> Option<u64> {
+        if self.per_second == 0 {
+            return Some(0);
+        }
+
+        let capacity = self.per_second as f32;
+        let bucket = self.buckets.entry(message).or_insert(Bucket {
+            tokens: capacity,
+            last_refill: now,
+            suppressed: 0,
+        });
+        let elapsed = now.duration_since(bucket.last_refill).as_secs_f32();
+        bucket.tokens = (bucket.tokens + elapsed * capacity).min(capacity);
+        bucket.last_refill = now;
+
+        if bucket.tokens < 1.0 {
+            bucket.suppressed += 1;
+            return None;
+        }
+        bucket.tokens -= 1.0;
+        Some(std::mem::take(&mut bucket.suppressed))
+    }
+
+    /// Drains the occurrences suppressed since each message was last admitted.
+    pub fn take_suppressed(&mut self) -> Vec<(&'static str, u64)> {
+        self.buckets
+            .iter_mut()
+            .filter(|(_, bucket)| bucket.suppressed > 0)
+            .map(|(message, bucket)| (*message, std::mem::take(&mut bucket.suppressed)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_bursts_then_refills() {
+        let mut limiter = WarnLimiter::new(5);
+        let start = Instant::now();
+
+        for _ in 0..5 {
+            assert_eq!(limiter.admit_at("storm", start), Some(0));
+        }
+        for _ in 0..10 {
+            assert_eq!(limiter.admit_at("storm", start), None);
+        }
+        assert_eq!(limiter.admit_at("other", start), Some(0));
+
+        // One token back after 200ms, reporting what was dropped meanwhile
+        let later = start + Duration::from_millis(200);
+        assert_eq!(limiter.admit_at("storm", later), Some(10));
+        assert_eq!(limiter.admit_at("storm", later), None);
+
+        assert_eq!(limiter.take_suppressed(), [("storm", 1)]);
+        assert!(limiter.take_suppressed().is_empty());
+    }
+
+    #[test]
+    fn test_zero_rate_admits_everything() {
+        let mut limiter = WarnLimiter::new(0);
+        for _ in 0..100 {
+            assert_eq!(limiter.admit("storm"), Some(0));
+        }
+    }
+}
@@ -0,0 +1,121 @@
+const SMOOTHING_ALPHA: f32 = 0.3;
+const BASELINE_ALPHA: f32 = 0.02;
+
+/// Pedometer detecting peaks of the low-pass filtered accel magnitude.
+///
+/// A step is counted at a local maximum rising more than `threshold_mg` above
+/// a slowly tracking baseline, at least `min_step_interval_ms` after the
+/// previous step.
+#[derive(Debug, Clone)]
+pub struct StepDetector {
+    threshold_mg: f32,
+    min_step_interval_ms: u32,
+    step_count: u32,
+    smoothed: Option<f32>,
+    previous: Option<f32>,
+    baseline: f32,
+    rising: bool,
+    last_step_timestamp: Option<u32>,
+}
+
+impl StepDetector {
+    pub fn new(threshold_mg: f32, min_step_interval_ms: u32) -> Self {
+        Self {
+            threshold_mg,
+            min_step_interval_ms,
+            step_count: 0,
+            smoothed: None,
+            previous: None,
+            baseline: 0.0,
+            rising: false,
+            last_step_timestamp: None,
+        }
+    }
+
+    pub fn step_count(&self) -> u32 {
+        self.step_count
+    }
+
+    /// Feeds one accel magnitude sample, returns true if a step was counted.
+    pub fn update(&mut self, acc_magnitude: f32, timestamp: u32) -> bool {
+        let smoothed = match self.smoothed {
+            Some(s) => s + SMOOTHING_ALPHA * (acc_magnitude - s),
+            None => {
+                self.baseline = acc_magnitude;
+                acc_magnitude
+            }
+        };
+        self.smoothed = Some(smoothed);
+        self.baseline += BASELINE_ALPHA * (smoothed - self.baseline);
+
+        let Some(previous) = self.previous.replace(smoothed) else {
+            return false;
+        };
+
+        let mut step = false;
+        if smoothed < previous && self.rising {
+            let refractory_passed = self
+                .last_step_timestamp
+                .is_none_or(|last| timestamp.saturating_sub(last) >= self.min_step_interval_ms);
+
+            if previous - self.baseline > self.threshold_mg && refractory_passed {
+                self.step_count += 1;
+                self.last_step_timestamp = Some(timestamp);
+                step = true;
+            }
+        }
+        self.rising = smoothed > previous;
+
+        step
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed_sine(
+        detector: &mut StepDetector,
+        frequency_hz: f32,
+        amplitude_mg: f32,
+        duration_s: f32,
+    ) -> u32 {
+        const SAMPLE_RATE_HZ: f32 = 100.0;
+        let samples = (duration_s * SAMPLE_RATE_HZ) as u32;
+
+        for i in 0..samples {
+            let t = i as f32 / SAMPLE_RATE_HZ;
+            let magnitude =
+                1000.0 + amplitude_mg * (2.0 * std::f32::consts::PI * frequency_hz * t).sin();
+            detector.update(magnitude, 1000 + i * 10);
+        }
+
+        detector.step_count()
+    }
+
+    #[test]
+    fn test_counts_periodic_peaks() {
+        let mut detector = StepDetector::new(150.0, 250);
+
+        // 2 Hz cadence for 5 seconds -> 10 peaks
+        let steps = feed_sine(&mut detector, 2.0, 400.0, 5.0);
+        assert_eq!(steps, 10);
+    }
+
+    #[test]
+    fn test_ignores_peaks_below_threshold() {
+        let mut detector = StepDetector::new(150.0, 250);
+
+        let steps = feed_sine(&mut detector, 2.0, 50.0, 5.0);
+        assert_eq!(steps, 0);
+    }
+
+    #[test]
+    fn test_refractory_period_limits_cadence() {
+        let mut detector = StepDetector::new(150.0, 400);
+
+        // 4 Hz peaks every 250ms, but only one step per 400ms allowed -> every other peak
+        let steps = feed_sine(&mut detector, 4.0, 400.0, 5.0);
+        assert_eq!(steps, 10);
+    }
+}
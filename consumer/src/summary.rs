@@ -0,0 +1,86 @@
+use crate::motion::MotionState;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TrajectorySummary {
+    pub samples: u64,
+    /// Integrated speed over the whole run [m]
+    pub distance: f32,
+    pub max_speed: f32, // m/s
+    /// Roll, pitch and yaw of the last state [rad]
+    pub final_euler: (f32, f32, f32),
+    /// Frames skipped because they failed to decode
+    pub decode_errors: u64,
+    /// Orientation or position updates skipped for an excessive time delta
+    pub skipped_updates: u64,
+}
+
+/// Recap of a run for scripted use, built from the state after every
+/// processed sample.
+#[derive(Debug, Clone, Default)]
+pub struct TrajectoryTracker {
+    samples: u64,
+    distance: f32,
+    max_speed: f32,
+    last: Option<(u64, f32)>, // us, m/s
+    final_euler: (f32, f32, f32),
+    skipped_updates: u64,
+}
+
+impl TrajectoryTracker {
+    /// Notes the state after the sample taken at `time_us`. The distance
+    /// integrates the speed with the trapezoidal rule, timestamps that do
+    /// not advance add nothing.
+    pub fn record(&mut self, time_us: u64, state: &MotionState, skipped_update: bool) {
+        let speed = state.velocity.norm();
+        if let Some((last_us, last_speed)) = self.last
+            && time_us > last_us
+        {
+            let dt = (time_us - last_us) as f32 * 1e-6;
+            self.distance += 0.5 * (last_speed + speed) * dt;
+        }
+        self.last = Some((time_us, speed));
+        self.samples += 1;
+        self.max_speed = self.max_speed.max(speed);
+        self.final_euler = state.orientation.euler_angles();
+        self.skipped_updates += u64::from(skipped_update);
+    }
+
+    pub fn summary(&self, decode_errors: u64) -> TrajectorySummary {
+        TrajectorySummary {
+            samples: self.samples,
+            distance: self.distance,
+            max_speed: self.max_speed,
+            final_euler: self.final_euler,
+            decode_errors,
+            skipped_updates: self.skipped_updates,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nalgebra::{UnitQuaternion, Vector3};
+
+    #[test]
+    fn test_distance_integrates_speed() {
+        let mut tracker = TrajectoryTracker::default();
+        let mut state = MotionState::default();
+
+        // Speeding up from 0 to 2m/s over 1s, then holding it for 1s
+        for (time_us, speed) in [(0, 0.0), (500_000, 1.0), (1_000_000, 2.0), (2_000_000, 2.0)] {
+            state.velocity = Vector3::new(0.0, speed, 0.0);
+            tracker.record(time_us, &state, false);
+        }
+        state.orientation = UnitQuaternion::from_euler_angles(0.0, 0.0, 1.0);
+        // Repeated timestamp, counted but not integrated
+        tracker.record(2_000_000, &state, true);
+
+        let summary = tracker.summary(3);
+        assert_eq!(summary.samples, 5);
+        assert!((summary.distance - 3.0).abs() < 1e-6, "{:?}", summary);
+        assert_eq!(summary.max_speed, 2.0);
+        assert!((summary.final_euler.2 - 1.0).abs() < 1e-6);
+        assert_eq!((summary.decode_errors, summary.skipped_updates), (3, 1));
+    }
+}
@@ -0,0 +1,198 @@
+use common::framing::{Endianness, FRAME_MAGIC};
+use common::slog::{Logger, error, info, warn};
+#[cfg(unix)]
+use common::transport;
+use common::transport::{Listener, ServerStream};
+use std::io;
+use std::path::Path;
+#[cfg(unix)]
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::broadcast;
+
+const TEE_QUEUE_CAPACITY: usize = 256; // frames
+
+/// Re-publishes received frames unchanged on a socket of its own, so further
+/// consumers can be chained behind this one.
+///
+/// Downstream consumers receive every frame forwarded after they connected.
+/// One that falls behind loses the oldest frames instead of stalling the
+/// processing here, the framing stays intact either way. The socket file is
+/// removed once the last clone is dropped.
+#[derive(Debug, Clone)]
+pub struct Tee {
+    sender: broadcast::Sender<Arc<[u8]>>,
+    endianness: Endianness,
+    frame_magic: bool,
+    #[cfg(unix)]
+    _socket: Arc<SocketFile>,
+}
+
+/// Removes the bound socket path when dropped.
+#[cfg(unix)]
+#[derive(Debug)]
+struct SocketFile(PathBuf);
+
+#[cfg(unix)]
+impl Drop for SocketFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+impl Tee {
+    /// Binds `path` and accepts downstream consumers in the background.
+    /// Frames are prefixed with their length in `endianness`, which should
+    /// match the upstream stream so downstream consumers can share settings.
+    pub fn bind(path: &Path, endianness: Endianness, logger: Logger) -> io::Result<Self> {
+        #[cfg(unix)]
+        if path.exists() && !transport::is_live(path) {
+            warn!(logger, "Removing stale tee socket"; "path" => %path.display());
+            std::fs::remove_file(path)?;
+        }
+
+        let listener = Listener::bind(path)?;
+        info!(logger, "Forwarding frames to tee socket"; "path" => %path.display());
+        let (sender, _) = broadcast::channel(TEE_QUEUE_CAPACITY);
+        tokio::spawn(accept_downstream(listener, sender.clone(), logger));
+
+        Ok(Self {
+            sender,
+            endianness,
+            frame_magic: false,
+            #[cfg(unix)]
+            _socket: Arc::new(SocketFile(path.to_path_buf())),
+        })
+    }
+
+    /// Puts `FRAME_MAGIC` in front of every frame, for downstream consumers
+    /// on protocol version 2.
+    pub fn set_frame_magic(&mut self, frame_magic: bool) {
+        self.frame_magic = frame_magic;
+    }
+
+    /// Forwards one frame body as received, with its length prefix.
+    pub fn forward(&self, body: &[u8]) {
+        if self.sender.receiver_count() == 0 {
+            return;
+        }
+
+        let mut frame = Vec::with_capacity(FRAME_MAGIC.len() + 4 + body.len());
+        if self.frame_magic {
+            frame.extend_from_slice(&FRAME_MAGIC);
+        }
+        frame.extend_from_slice(&self.endianness.encode_len(body.len() as u32));
+        frame.extend_from_slice(body);
+        // Fails only if the last downstream consumer just left
+        let _ = self.sender.send(frame.into());
+    }
+}
+
+async fn accept_downstream(
+    mut listener: Listener,
+    sender: broadcast::Sender<Arc<[u8]>>,
+    logger: Logger,
+) {
+    loop {
+        match listener.accept().await {
+            Ok(stream) => {
+                let receiver = sender.subscribe();
+                info!(logger, "Tee consumer connected"; "downstream" => sender.receiver_count());
+                tokio::spawn(write_downstream(stream, receiver, logger.clone()));
+            }
+            Err(e) => {
+                error!(logger, "Failed to accept tee consumer"; "error" => %e);
+                return;
+            }
+        }
+    }
+}
+
+async fn write_downstream(
+    mut stream: ServerStream,
+    mut receiver: broadcast::Receiver<Arc<[u8]>>,
+    logger: Logger,
+) {
+    loop {
+        match receiver.recv().await {
+            Ok(frame) => {
+                if let Err(e) = stream.write_all(&frame).await {
+                    info!(logger, "Tee consumer disconnected"; "error" => %e);
+                    return;
+                }
+            }
+            Err(broadcast::error::RecvError::Lagged(dropped)) => {
+                warn!(logger, "Tee consumer falling behind, dropped frames"; "frames" => dropped);
+            }
+            Err(broadcast::error::RecvError::Closed) => return,
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::consumer::Consumer;
+    use common::codec::{FrameReader, encode_frame};
+    use common::proto::ImuData;
+    use common::slog::{Discard, o};
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_downstream_receives_frames_verbatim() {
+        let path = PathBuf::from("/tmp/test_imu_tee");
+        let logger = Logger::root(Discard, o!());
+        let tee = Tee::bind(&path, Endianness::Big, logger.clone()).unwrap();
+
+        let mut downstream = transport::connect(&path).await.unwrap();
+        while tee.sender.receiver_count() == 0 {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+
+        // Samples, an empty frame and garbage all pass through unchanged
+        let mut upstream = Vec::new();
+        for i in 0..5 {
+            let sample = ImuData {
+                x_acc: i as f32,
+                z_acc: 1000.0,
+                timestamp_acc: 100 + i * 10,
+                timestamp_gyro: 100 + i * 10,
+                ..Default::default()
+            };
+            encode_frame(&sample, &mut upstream);
+        }
+        upstream.extend_from_slice(&0u32.to_be_bytes());
+        upstream.extend_from_slice(&3u32.to_be_bytes());
+        upstream.extend_from_slice(b"\xff\xff\xff");
+
+        let (mut publisher_end, consumer_end) = transport::memory::pair();
+        publisher_end.write_all(&upstream).await.unwrap();
+        drop(publisher_end);
+
+        let mut consumer = Consumer::new(PathBuf::from("/tmp/unused"), 5, logger);
+        consumer.set_tee(Some(tee.clone()));
+        consumer.run_single(consumer_end).await.unwrap();
+
+        let mut received = vec![0u8; upstream.len()];
+        tokio::time::timeout(
+            Duration::from_secs(5),
+            tokio::io::AsyncReadExt::read_exact(&mut downstream, &mut received),
+        )
+        .await
+        .expect("Tee did not forward every frame")
+        .unwrap();
+        assert_eq!(received, upstream);
+
+        let mut reader = FrameReader::default();
+        let first = reader.read(&mut &received[..]).await.unwrap().unwrap();
+        assert_eq!(first.timestamp_acc, 100);
+
+        // The socket outlives the consumer's clone but not the last one
+        drop(consumer);
+        assert!(path.exists());
+        drop(tee);
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,53 @@
+use common::slog::{Drain, KV, Key, Logger, Never, OwnedKVList, Record, Serializer, o};
+use std::sync::{Arc, Mutex};
+
+/// Message and key-value pairs of a captured record.
+pub type CapturedRecord = (String, Vec<(String, String)>);
+
+/// Records logged through a `capturing_logger`, in order.
+#[derive(Clone, Default)]
+pub struct Captured(Arc<Mutex<Vec<CapturedRecord>>>);
+
+impl Captured {
+    /// Messages of the records logged so far.
+    pub fn messages(&self) -> Vec<String> {
+        let records = self.0.lock().unwrap();
+        records.iter().map(|(message, _)| message.clone()).collect()
+    }
+
+    /// Messages and key-value pairs of the records logged so far.
+    pub fn records(&self) -> Vec<CapturedRecord> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+/// Collects the message and key-value pairs of all records logged through it.
+struct CaptureDrain(Captured);
+
+struct KeyCollector(Vec<(String, String)>);
+
+impl Serializer for KeyCollector {
+    fn emit_arguments(&mut self, key: Key, value: &std::fmt::Arguments) -> common::slog::Result {
+        self.0.push((key.to_string(), value.to_string()));
+        Ok(())
+    }
+}
+
+impl Drain for CaptureDrain {
+    type Ok = ();
+    type Err = Never;
+
+    fn log(&self, record: &Record, _: &OwnedKVList) -> Result<(), Never> {
+        let mut keys = KeyCollector(Vec::new());
+        record.kv().serialize(record, &mut keys).unwrap();
+        let mut records = (self.0).0.lock().unwrap();
+        records.push((record.msg().to_string(), keys.0));
+        Ok(())
+    }
+}
+
+/// A logger whose records can be inspected through the returned handle.
+pub fn capturing_logger() -> (Logger, Captured) {
+    let captured = Captured::default();
+    (Logger::root(CaptureDrain(captured.clone()), o!()), captured)
+}
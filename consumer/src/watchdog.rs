@@ -0,0 +1,114 @@
+use common::proto::ImuData;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sensor {
+    Accel,
+    Gyro,
+    Mag,
+}
+
+const SENSORS: [Sensor; 3] = [Sensor::Accel, Sensor::Gyro, Sensor::Mag];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SensorStatus {
+    /// Timestamp has not advanced for `stale_ms` while other sensors did
+    Stale { sensor: Sensor, stale_ms: u32 },
+    /// Timestamp advanced again after being reported stale
+    Recovered { sensor: Sensor },
+}
+
+/// Detects a sensor block whose timestamp froze while the others keep going.
+///
+/// Staleness is measured on the sample clock: a sensor is stale once the
+/// newest timestamp of any sensor is more than `timeout_ms` ahead of its own.
+/// Sensors that never reported a timestamp are not monitored.
+#[derive(Debug, Clone)]
+pub struct SensorWatchdog {
+    timeout_ms: u32,
+    last_timestamps: [u32; 3],
+    stale: [bool; 3],
+}
+
+impl SensorWatchdog {
+    pub fn new(timeout_ms: u32) -> Self {
+        Self {
+            timeout_ms,
+            last_timestamps: [0; 3],
+            stale: [false; 3],
+        }
+    }
+
+    /// Feeds one sample, returns the sensors that went stale or recovered.
+    pub fn update(&mut self, imu_data: &ImuData) -> Vec<SensorStatus> {
+        let timestamps = [
+            imu_data.timestamp_acc,
+            imu_data.timestamp_gyro,
+            imu_data.timestamp_mag,
+        ];
+        let now = timestamps.into_iter().max().unwrap_or(0);
+
+        let mut changes = Vec::new();
+        for (i, sensor) in SENSORS.into_iter().enumerate() {
+            if timestamps[i] > self.last_timestamps[i] {
+                self.last_timestamps[i] = timestamps[i];
+                if std::mem::take(&mut self.stale[i]) {
+                    changes.push(SensorStatus::Recovered { sensor });
+                }
+                continue;
+            }
+
+            let stale_ms = now.saturating_sub(self.last_timestamps[i]);
+            if self.last_timestamps[i] != 0 && !self.stale[i] && stale_ms > self.timeout_ms {
+                self.stale[i] = true;
+                changes.push(SensorStatus::Stale { sensor, stale_ms });
+            }
+        }
+        changes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(timestamp_acc: u32, timestamp_gyro: u32) -> ImuData {
+        ImuData {
+            timestamp_acc,
+            timestamp_gyro,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_reports_stale_sensor_once_and_recovery() {
+        let mut watchdog = SensorWatchdog::new(50);
+
+        for t in (100..=150).step_by(10) {
+            assert!(watchdog.update(&sample(100, t)).is_empty());
+        }
+        assert_eq!(
+            watchdog.update(&sample(100, 160)),
+            [SensorStatus::Stale {
+                sensor: Sensor::Accel,
+                stale_ms: 60
+            }]
+        );
+        assert!(watchdog.update(&sample(100, 170)).is_empty());
+
+        assert_eq!(
+            watchdog.update(&sample(180, 180)),
+            [SensorStatus::Recovered {
+                sensor: Sensor::Accel
+            }]
+        );
+    }
+
+    #[test]
+    fn test_ignores_sensor_that_never_reported() {
+        let mut watchdog = SensorWatchdog::new(50);
+
+        for t in (100..1000).step_by(10) {
+            assert!(watchdog.update(&sample(t, t)).is_empty());
+        }
+    }
+}
@@ -0,0 +1,221 @@
+//! Runs the built publisher and consumer binaries against each other over a
+//! real socket, covering CLI parsing and wiring that unit tests bypass.
+#![cfg(unix)]
+
+use common::codec::encode_frame_with_format;
+use common::framing::{Endianness, WireFormat};
+use common::proto::ImuData;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Output, Stdio};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+const SAMPLE_COUNT: u32 = 200;
+const SAMPLE_SPACING_MS: u32 = 2;
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(10);
+const RUN_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// The publisher binary is built by another package, next to the consumer.
+/// Cargo does not rebuild it for the consumer's tests, so it is built once
+/// per test run, in the consumer's profile and target directory, to never
+/// test a stale binary.
+fn publisher_binary() -> PathBuf {
+    static BUILD: OnceLock<()> = OnceLock::new();
+
+    let consumer = PathBuf::from(env!("CARGO_BIN_EXE_consumer"));
+    let profile_dir = consumer.parent().expect("Consumer binary has no directory");
+    BUILD.get_or_init(|| {
+        let profile = match profile_dir.file_name().and_then(|name| name.to_str()) {
+            Some("debug") | None => "dev",
+            Some(name) => name,
+        };
+        let target_dir = profile_dir
+            .parent()
+            .expect("Profile directory has no target directory");
+        let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+        let status = Command::new(cargo)
+            .args(["build", "-p", "publisher", "--profile", profile])
+            .arg("--target-dir")
+            .arg(target_dir)
+            .status()
+            .expect("Failed to run cargo build");
+        assert!(status.success(), "Building the publisher failed");
+    });
+    profile_dir.join(format!("publisher{}", std::env::consts::EXE_SUFFIX))
+}
+
+/// Stationary, level sensor samples as length-prefixed frames in `format`.
+fn write_recording(path: &Path, format: WireFormat) {
+    let mut bytes = Vec::new();
+    for i in 0..SAMPLE_COUNT {
+        let timestamp = 1000 + i * SAMPLE_SPACING_MS;
+        let sample = ImuData {
+            z_acc: 1000.0,
+            timestamp_acc: timestamp,
+            timestamp_gyro: timestamp,
+            x_mag: 200.0,
+            z_mag: -400.0,
+            timestamp_mag: timestamp,
+            ..Default::default()
+        };
+        encode_frame_with_format(&sample, &mut bytes, Endianness::Big, format);
+    }
+    std::fs::write(path, bytes).expect("Failed to write recording");
+}
+
+/// Waits for the child to exit, killing it after `timeout`.
+fn wait_with_timeout(mut child: Child, timeout: Duration, name: &str) -> Output {
+    let start = Instant::now();
+    while child.try_wait().expect("Failed to poll child").is_none() {
+        if start.elapsed() > timeout {
+            child.kill().ok();
+            panic!("{} did not exit within {:?}", name, timeout);
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    }
+    child.wait_with_output().expect("Failed to collect output")
+}
+
+/// Components of the last `Pos: [x,y,z]m` log line.
+fn last_position(log: &str) -> Option<[f32; 3]> {
+    let line = log.lines().rev().find(|line| line.contains("Pos: ["))?;
+    let start = line.find("Pos: [")? + "Pos: [".len();
+    let end = start + line[start..].find(']')?;
+    let values: Vec<f32> = line[start..end]
+        .split(',')
+        .map(|v| v.parse().ok())
+        .collect::<Option<_>>()?;
+    values.try_into().ok()
+}
+
+/// Replays the stationary recording, encoded in `format`, through the
+/// publisher binary with `publisher_args` into the consumer binary with
+/// `consumer_args`, returning the consumer log once both exited successfully.
+fn run_recording(
+    tag: &str,
+    format: WireFormat,
+    publisher_args: &[&str],
+    consumer_args: &[&str],
+) -> String {
+    let pid = std::process::id();
+    let socket_path = PathBuf::from(format!("/tmp/test_imu_e2e_{}_{}.sock", tag, pid));
+    let recording = PathBuf::from(format!("/tmp/test_imu_e2e_{}_{}.frames", tag, pid));
+    write_recording(&recording, format);
+
+    let publisher = Command::new(publisher_binary())
+        .arg("--socket-path")
+        .arg(&socket_path)
+        .arg("--replay")
+        .arg(&recording)
+        .args(publisher_args)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Failed to start publisher");
+
+    let start = Instant::now();
+    while !socket_path.exists() {
+        assert!(
+            start.elapsed() < STARTUP_TIMEOUT,
+            "Publisher never created its socket"
+        );
+        std::thread::sleep(Duration::from_millis(20));
+    }
+
+    let consumer = Command::new(env!("CARGO_BIN_EXE_consumer"))
+        .arg("--socket-path")
+        .arg(&socket_path)
+        .args(consumer_args)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Failed to start consumer");
+
+    let consumer = wait_with_timeout(consumer, RUN_TIMEOUT, "consumer");
+    let publisher = wait_with_timeout(publisher, RUN_TIMEOUT, "publisher");
+    std::fs::remove_file(&socket_path).ok();
+    std::fs::remove_file(&recording).ok();
+
+    let consumer_log = String::from_utf8_lossy(&consumer.stdout).into_owned()
+        + &String::from_utf8_lossy(&consumer.stderr);
+    assert!(
+        consumer.status.success(),
+        "Consumer failed:\n{}",
+        consumer_log
+    );
+    assert!(
+        publisher.status.success(),
+        "Publisher failed:\n{}",
+        String::from_utf8_lossy(&publisher.stderr)
+    );
+    consumer_log
+}
+
+#[test]
+fn test_publisher_and_consumer_binaries_interoperate() {
+    let consumer_log = run_recording("plain", WireFormat::Protobuf, &[], &[]);
+
+    let reports = consumer_log.matches("Pos: [").count();
+    assert_eq!(reports, SAMPLE_COUNT as usize, "Log:\n{}", consumer_log);
+    let position = last_position(&consumer_log).expect("Unparsable position report");
+    assert!(
+        position.iter().all(|p| p.abs() < 0.1),
+        "A level, stationary sensor should stay near the origin, got {:?}",
+        position
+    );
+}
+
+/// The consumer treats a timestamp that does not advance as a zero-length
+/// step, so neither anomaly may move the estimate or end the run.
+#[cfg(debug_assertions)]
+#[test]
+fn test_consumer_rides_out_injected_timestamp_anomalies() {
+    let consumer_log = run_recording(
+        "anomalies",
+        WireFormat::Protobuf,
+        &[
+            "--inject-anomaly",
+            "50:backward-timestamp",
+            "--inject-anomaly",
+            "120:duplicate",
+        ],
+        &[],
+    );
+
+    let reports = consumer_log.matches("Pos: [").count();
+    assert_eq!(reports, SAMPLE_COUNT as usize, "Log:\n{}", consumer_log);
+    let position = last_position(&consumer_log).expect("Unparsable position report");
+    assert!(
+        position.iter().all(|p| p.abs() < 0.1),
+        "Timestamp anomalies should not move a stationary sensor, got {:?}",
+        position
+    );
+}
+
+#[test]
+fn test_binaries_run_on_single_threaded_runtime() {
+    let consumer_log = run_recording(
+        "single_thread",
+        WireFormat::Protobuf,
+        &["--single-thread"],
+        &["--single-thread"],
+    );
+
+    let reports = consumer_log.matches("Pos: [").count();
+    assert_eq!(reports, SAMPLE_COUNT as usize, "Log:\n{}", consumer_log);
+    assert!(consumer_log.contains("Single thread: true"));
+}
+
+#[test]
+fn test_binaries_exchange_packed_frames() {
+    let format = ["--format", "packed"];
+    let consumer_log = run_recording("packed", WireFormat::Packed, &format, &format);
+
+    let reports = consumer_log.matches("Pos: [").count();
+    assert_eq!(reports, SAMPLE_COUNT as usize, "Log:\n{}", consumer_log);
+    assert!(
+        !consumer_log.contains("Failed to decode"),
+        "Log:\n{}",
+        consumer_log
+    );
+}
@@ -0,0 +1,179 @@
+use super::source::DataSource;
+
+use common::clap;
+use common::proto::ImuData;
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+const BACKWARD_STEP_MS: u32 = 50;
+
+/// Defect injected into the stream, for exercising the consumer's
+/// robustness through the whole pipeline.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Anomaly {
+    /// NaN accel and mag readings
+    Nan,
+    /// Every timestamp 50ms before the previous sample's
+    BackwardTimestamp,
+    /// The previous sample again, unchanged
+    Duplicate,
+}
+
+/// `anomaly` replaces the generated sample at `index`, counted from 0.
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize)]
+pub struct AnomalySpec {
+    pub index: u64,
+    pub anomaly: Anomaly,
+}
+
+/// Parses `<index>:<anomaly>`, e.g. `100:nan`.
+pub fn parse_anomaly_spec(s: &str) -> Result<AnomalySpec, String> {
+    let (index, anomaly) = s
+        .split_once(':')
+        .ok_or_else(|| format!("{} is not <index>:<anomaly>", s))?;
+    Ok(AnomalySpec {
+        index: index
+            .parse()
+            .map_err(|_| format!("{} is not a sample index", index))?,
+        anomaly: <Anomaly as clap::ValueEnum>::from_str(anomaly, false)?,
+    })
+}
+
+/// Passes the samples of another source through, replacing those at the
+/// configured indices with deterministic anomalies.
+///
+/// Indices count the samples produced by the wrapped source, before any
+/// decimation. A duplicate or backward timestamp at index 0 has no previous
+/// sample to refer to and leaves the sample alone.
+pub struct AnomalyInjector {
+    source: Box<dyn DataSource>,
+    anomalies: BTreeMap<u64, Anomaly>,
+    index: u64,
+    previous: Option<ImuData>,
+}
+
+impl AnomalyInjector {
+    pub fn new(source: Box<dyn DataSource>, specs: &[AnomalySpec]) -> Self {
+        AnomalyInjector {
+            source,
+            anomalies: specs
+                .iter()
+                .map(|spec| (spec.index, spec.anomaly))
+                .collect(),
+            index: 0,
+            previous: None,
+        }
+    }
+
+    fn inject(anomaly: Anomaly, sample: ImuData, previous: Option<ImuData>) -> ImuData {
+        match (anomaly, previous) {
+            (Anomaly::Nan, _) => ImuData {
+                x_acc: f32::NAN,
+                y_acc: f32::NAN,
+                z_acc: f32::NAN,
+                x_mag: f32::NAN,
+                y_mag: f32::NAN,
+                z_mag: f32::NAN,
+                ..sample
+            },
+            (Anomaly::BackwardTimestamp, Some(previous)) => {
+                let back = |ms: u32| ms.saturating_sub(BACKWARD_STEP_MS);
+                let back_us = |us: Option<u64>| {
+                    us.map(|us| us.saturating_sub(u64::from(BACKWARD_STEP_MS) * 1000))
+                };
+                ImuData {
+                    timestamp_acc: back(previous.timestamp_acc),
+                    timestamp_gyro: back(previous.timestamp_gyro),
+                    timestamp_mag: back(previous.timestamp_mag),
+                    timestamp_acc_us: back_us(previous.timestamp_acc_us),
+                    timestamp_gyro_us: back_us(previous.timestamp_gyro_us),
+                    timestamp_mag_us: back_us(previous.timestamp_mag_us),
+                    ..sample
+                }
+            }
+            (Anomaly::Duplicate, Some(previous)) => previous,
+            (_, None) => sample,
+        }
+    }
+}
+
+impl DataSource for AnomalyInjector {
+    fn next_delay(&mut self) -> Option<Duration> {
+        self.source.next_delay()
+    }
+
+    fn blocking(&self) -> bool {
+        self.source.blocking()
+    }
+
+    fn next_sample(&mut self) -> Option<ImuData> {
+        let mut sample = self.source.next_sample()?;
+        if let Some(&anomaly) = self.anomalies.get(&self.index) {
+            sample = Self::inject(anomaly, sample, self.previous);
+        }
+        self.index += 1;
+        self.previous = Some(sample);
+        Some(sample)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::replay::ReplaySource;
+
+    fn create_samples(count: u32) -> Vec<ImuData> {
+        (0..count)
+            .map(|i| ImuData {
+                x_acc: i as f32,
+                z_acc: 1000.0,
+                timestamp_acc: 1000 + i * 10,
+                timestamp_gyro: 1000 + i * 10,
+                timestamp_mag: 1000 + i * 10,
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_anomaly_spec() {
+        assert_eq!(
+            parse_anomaly_spec("100:backward-timestamp"),
+            Ok(AnomalySpec {
+                index: 100,
+                anomaly: Anomaly::BackwardTimestamp
+            })
+        );
+        assert!(parse_anomaly_spec("100").is_err());
+        assert!(parse_anomaly_spec("-1:nan").is_err());
+        assert!(parse_anomaly_spec("5:bit-flip").is_err());
+    }
+
+    #[test]
+    fn test_anomalies_replace_samples_at_their_index() {
+        let specs = [
+            parse_anomaly_spec("2:nan").unwrap(),
+            parse_anomaly_spec("4:backward-timestamp").unwrap(),
+            parse_anomaly_spec("6:duplicate").unwrap(),
+            parse_anomaly_spec("0:duplicate").unwrap(),
+        ];
+        let source = ReplaySource::new(create_samples(8), 1.0);
+        let mut injector = AnomalyInjector::new(Box::new(source), &specs);
+        let emitted: Vec<ImuData> = std::iter::from_fn(|| injector.next_sample()).collect();
+        let samples = create_samples(8);
+
+        assert_eq!(emitted.len(), 8);
+        assert_eq!(emitted[0], samples[0]);
+        assert!(emitted[2].x_acc.is_nan() && emitted[2].z_mag.is_nan());
+        assert_eq!(emitted[2].timestamp_acc, samples[2].timestamp_acc);
+        assert_eq!(emitted[4].x_acc, samples[4].x_acc);
+        assert_eq!(emitted[4].timestamp_acc, samples[3].timestamp_acc - 50);
+        assert_eq!(emitted[6], emitted[5]);
+        // Everything else passes through untouched
+        for i in [1, 3, 5, 7] {
+            assert_eq!(emitted[i], samples[i]);
+        }
+    }
+}
@@ -0,0 +1,42 @@
+use std::fmt;
+use std::time::Duration;
+
+/// Outcome of `Publisher::bench`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct BenchReport {
+    pub duration: Duration,
+    /// Frames written and flushed without error
+    pub sent: u64,
+    /// Frames the consumer decoded
+    pub received: u64,
+    /// Failed writes or flushes
+    pub errors: u64,
+    pub mean_write_latency: Duration,
+    pub max_write_latency: Duration,
+}
+
+impl BenchReport {
+    /// Sustained send rate, counting only frames the consumer received [Hz]
+    pub fn rate_hz(&self) -> f64 {
+        if self.duration.is_zero() {
+            return 0.0;
+        }
+        self.received as f64 / self.duration.as_secs_f64()
+    }
+}
+
+impl fmt::Display for BenchReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Achieved {:.0} Hz over {:.2}s | sent: {} | received: {} | write latency mean: {:.1}us max: {:.1}us | errors: {}",
+            self.rate_hz(),
+            self.duration.as_secs_f64(),
+            self.sent,
+            self.received,
+            self.mean_write_latency.as_secs_f64() * 1e6,
+            self.max_write_latency.as_secs_f64() * 1e6,
+            self.errors
+        )
+    }
+}
@@ -0,0 +1,146 @@
+use common::clap;
+use common::proto::ImuData;
+
+use tokio::sync::{broadcast, mpsc};
+
+/// What the generator does when the writer falls behind and the queue is full.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BackpressurePolicy {
+    /// Wait for the writer to free a slot, slowing down generation
+    Block,
+    /// Keep generating and discard the oldest queued samples
+    DropOldest,
+}
+
+pub enum Received {
+    Sample(ImuData),
+    /// Number of samples discarded since the last received one
+    Dropped(u64),
+}
+
+pub enum SampleSender {
+    Block(mpsc::Sender<ImuData>),
+    DropOldest(broadcast::Sender<ImuData>),
+}
+
+pub enum SampleReceiver {
+    Block(mpsc::Receiver<ImuData>),
+    DropOldest(broadcast::Receiver<ImuData>),
+}
+
+/// Bounded queue between the sample generator and the socket writer.
+pub fn sample_channel(
+    capacity: usize,
+    policy: BackpressurePolicy,
+) -> (SampleSender, SampleReceiver) {
+    match policy {
+        BackpressurePolicy::Block => {
+            let (tx, rx) = mpsc::channel(capacity);
+            (SampleSender::Block(tx), SampleReceiver::Block(rx))
+        }
+        BackpressurePolicy::DropOldest => {
+            // broadcast overwrites the oldest value once a receiver lags behind
+            let (tx, rx) = broadcast::channel(capacity);
+            (SampleSender::DropOldest(tx), SampleReceiver::DropOldest(rx))
+        }
+    }
+}
+
+impl SampleSender {
+    /// Queues a sample, returns false once the receiver is gone.
+    pub async fn send(&self, data: ImuData) -> bool {
+        match self {
+            SampleSender::Block(tx) => tx.send(data).await.is_ok(),
+            SampleSender::DropOldest(tx) => tx.send(data).is_ok(),
+        }
+    }
+}
+
+impl SampleReceiver {
+    /// Next queued sample or drop notice, `None` once the sender is gone.
+    pub async fn recv(&mut self) -> Option<Received> {
+        match self {
+            SampleReceiver::Block(rx) => rx.recv().await.map(Received::Sample),
+            SampleReceiver::DropOldest(rx) => match rx.recv().await {
+                Ok(data) => Some(Received::Sample(data)),
+                Err(broadcast::error::RecvError::Lagged(dropped)) => {
+                    Some(Received::Dropped(dropped))
+                }
+                Err(broadcast::error::RecvError::Closed) => None,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn sample(timestamp: u32) -> ImuData {
+        ImuData {
+            timestamp_acc: timestamp,
+            ..Default::default()
+        }
+    }
+
+    async fn recv_timestamp(rx: &mut SampleReceiver) -> u32 {
+        match rx.recv().await {
+            Some(Received::Sample(data)) => data.timestamp_acc,
+            Some(Received::Dropped(n)) => panic!("Unexpected drop of {} samples", n),
+            None => panic!("Channel closed unexpectedly"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_block_policy_waits_for_writer() {
+        let (tx, mut rx) = sample_channel(2, BackpressurePolicy::Block);
+
+        assert!(tx.send(sample(1)).await);
+        assert!(tx.send(sample(2)).await);
+
+        let blocked = tokio::time::timeout(Duration::from_millis(50), tx.send(sample(3))).await;
+        assert!(blocked.is_err(), "Send into a full queue should block");
+
+        assert_eq!(recv_timestamp(&mut rx).await, 1);
+        assert!(
+            tx.send(sample(3)).await,
+            "Freed slot should accept the sample"
+        );
+        assert_eq!(recv_timestamp(&mut rx).await, 2);
+        assert_eq!(recv_timestamp(&mut rx).await, 3);
+    }
+
+    #[tokio::test]
+    async fn test_drop_oldest_policy_keeps_newest() {
+        let (tx, mut rx) = sample_channel(2, BackpressurePolicy::DropOldest);
+
+        for timestamp in 1..=5 {
+            let sent = tokio::time::timeout(Duration::from_millis(50), tx.send(sample(timestamp)))
+                .await
+                .expect("Send should never block");
+            assert!(sent);
+        }
+
+        match rx.recv().await {
+            Some(Received::Dropped(dropped)) => assert_eq!(dropped, 3),
+            _ => panic!("Expected drop notice"),
+        }
+        assert_eq!(recv_timestamp(&mut rx).await, 4);
+        assert_eq!(recv_timestamp(&mut rx).await, 5);
+    }
+
+    #[tokio::test]
+    async fn test_closed_channel() {
+        for policy in [BackpressurePolicy::Block, BackpressurePolicy::DropOldest] {
+            let (tx, mut rx) = sample_channel(2, policy);
+            drop(tx);
+            assert!(rx.recv().await.is_none());
+
+            let (tx, rx) = sample_channel(2, policy);
+            drop(rx);
+            assert!(!tx.send(sample(1)).await);
+        }
+    }
+}
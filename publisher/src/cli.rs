@@ -1,19 +1,162 @@
+use common::capabilities::Capabilities;
 use common::clap;
 use common::cli_defaults::*;
+use common::framing::{Compression, Endianness, WireFormat};
 use common::logging::LogLevel;
 use common::slog;
 
-#[derive(clap::Parser, Debug)]
+use crate::channel::BackpressurePolicy;
+use crate::clock::TimestampBase;
+use crate::imu_emulator::EmulatorProfile;
+
+#[derive(clap::Parser, Debug, serde::Serialize)]
 #[command(version, about, long_about = None)]
 pub struct PublisherArgs {
+    /// Print the effective configuration as JSON and exit
+    #[arg(long)]
+    #[serde(skip)]
+    pub show_config: bool,
+
+    /// Print a JSON banner of the supported features before starting
+    #[arg(long)]
+    #[serde(skip)]
+    pub print_capabilities: bool,
+
     #[arg(short, long, default_value = DEFAULT_SOCKET_PATH, value_parser = clap::value_parser!(std::path::PathBuf))]
     pub socket_path: std::path::PathBuf,
 
     #[arg(short, long, value_enum, default_value_t = DEFAULT_LOG_LEVEL, value_parser = clap::value_parser!(LogLevel))]
     pub log_level: LogLevel,
 
-    #[arg(short, long, default_value = DEFAULT_FREQUENCY, value_parser = clap::value_parser!(u32).range(1..=1000))]
+    /// Sample rate, 0 publishes as fast as possible for benchmarking [Hz]
+    #[arg(short, long, default_value = DEFAULT_FREQUENCY, value_parser = clap::value_parser!(u32).range(0..=1000))]
     pub frequency: u32,
+
+    /// Generate at --frequency but send only every Nth sample, saving bandwidth
+    #[arg(long, default_value = DEFAULT_DECIMATE, value_parser = clap::value_parser!(u32).range(1..))]
+    pub decimate: u32,
+
+    /// Motion produced by the emulator, ignored with --replay
+    #[arg(long, value_enum, default_value_t = EmulatorProfile::Random)]
+    pub profile: EmulatorProfile,
+
+    /// Accel update rate of the random and consistent profiles, samples in between repeat the last reading [Hz]
+    #[arg(long, default_value_t = DEFAULT_ACC_RATE, value_parser = clap::value_parser!(u32).range(1..))]
+    pub acc_rate: u32,
+
+    /// Gyro update rate of the random and consistent profiles [Hz]
+    #[arg(long, default_value_t = DEFAULT_GYRO_RATE, value_parser = clap::value_parser!(u32).range(1..))]
+    pub gyro_rate: u32,
+
+    /// Mag update rate of the random and consistent profiles [Hz]
+    #[arg(long, default_value_t = DEFAULT_MAG_RATE, value_parser = clap::value_parser!(u32).range(1..))]
+    pub mag_rate: u32,
+
+    /// Origin of the emulated timestamps, process keeps them small for easy dt math
+    #[arg(long, value_enum, default_value_t = TimestampBase::Epoch)]
+    pub timestamp_base: TimestampBase,
+
+    /// Replay length-prefixed frames recorded from the wire instead of emulating
+    #[arg(long, value_parser = clap::value_parser!(std::path::PathBuf))]
+    pub replay: Option<std::path::PathBuf>,
+
+    /// Read comma-separated samples from a serial device instead of emulating, needs the serial feature
+    #[arg(long, conflicts_with = "replay", value_parser = clap::value_parser!(std::path::PathBuf))]
+    pub serial: Option<std::path::PathBuf>,
+
+    /// Baud rate of the --serial device [bit/s]
+    #[arg(long, default_value = DEFAULT_BAUD, value_parser = clap::value_parser!(u32))]
+    pub baud: u32,
+
+    /// Print the decoded frames of a recording as a table and exit
+    #[arg(long, value_parser = clap::value_parser!(std::path::PathBuf))]
+    pub inspect: Option<std::path::PathBuf>,
+
+    /// Playback speed factor of --replay, e.g. 2.0 is twice as fast
+    #[arg(long, default_value = DEFAULT_REPLAY_SPEED, value_parser = parse_replay_speed)]
+    pub replay_speed: f32,
+
+    /// Capacity of the queue between sample generation and the socket writer
+    #[arg(long, default_value_t = DEFAULT_QUEUE_CAPACITY, value_parser = clap::value_parser!(u32).range(1..))]
+    pub queue_capacity: u32,
+
+    /// What to do when a slow consumer fills the queue
+    #[arg(long, value_enum, default_value_t = BackpressurePolicy::Block)]
+    pub backpressure: BackpressurePolicy,
+
+    /// Flush the socket only every N messages, fewer syscalls at high rates
+    #[arg(long, default_value_t = DEFAULT_FLUSH_EVERY, value_parser = clap::value_parser!(u32).range(1..))]
+    pub flush_every: u32,
+
+    /// Longest a written sample waits for a coalesced flush, bounds the latency --flush-every adds [ms]
+    #[arg(long, default_value_t = DEFAULT_MAX_FLUSH_LATENCY, value_parser = clap::value_parser!(u32).range(1..))]
+    pub max_flush_latency: u32,
+
+    /// Give up on a socket write or flush a stuck consumer does not drain, 0 waits forever [ms]
+    #[arg(long, default_value_t = DEFAULT_WRITE_TIMEOUT, value_parser = clap::value_parser!(u32))]
+    pub write_timeout_ms: u32,
+
+    /// Exit with an error after this many consecutive failed accepts, so a wedged listener becomes visible, 0 retries forever
+    #[arg(long, default_value = DEFAULT_ACCEPT_RETRIES, value_parser = clap::value_parser!(u32))]
+    pub accept_retries: u32,
+
+    /// Byte order of the frame length prefix, also of recordings read by --replay and --inspect
+    #[arg(long, value_enum, default_value_t = DEFAULT_LENGTH_ENDIANNESS)]
+    pub length_endianness: Endianness,
+
+    /// Encoding of the frame bodies, must match the consumer and any recording read by --replay and --inspect. packed is a fixed 48-byte struct without the optional fields
+    #[arg(long, value_enum, default_value_t = DEFAULT_WIRE_FORMAT)]
+    pub format: WireFormat,
+
+    /// Wire protocol version, must match the consumer and any recording read by --replay and --inspect. 2 puts a marker in front of every frame so the consumer can find the next one after corrupt data
+    #[arg(long, default_value = DEFAULT_PROTOCOL_VERSION, value_parser = clap::value_parser!(u32).range(1..=i64::from(common::capabilities::PROTOCOL_VERSION)))]
+    pub protocol_version: u32,
+
+    /// Compression of frame bodies, offered to each consumer on connect. Consumers without it get plain frames. lz4 needs the compression feature
+    #[arg(long, value_enum, default_value_t = DEFAULT_COMPRESSION)]
+    pub compression: Compression,
+
+    /// Smallest frame body compressed with --compression [bytes], smaller ones are sent plain. A single sample stays below the default, so lower it for compression to take effect
+    #[arg(long, default_value = DEFAULT_COMPRESS_THRESHOLD)]
+    pub compress_threshold: u32,
+
+    /// Permissions of the socket file in octal, e.g. 660 for owner and group only, the umask applies if not set
+    #[arg(long, value_parser = parse_socket_mode)]
+    pub socket_mode: Option<u32>,
+
+    /// Publish as fast as possible to a consumer in this process for this long, print the achieved rate and exit [s]
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub bench: Option<u32>,
+
+    /// Stamp frames with the wall-clock send time so a consumer on the same host can measure latency
+    #[arg(long)]
+    pub stamp_send_time: bool,
+
+    /// Run on a single-threaded runtime, less scheduling jitter at moderate rates but no parallelism
+    #[arg(long)]
+    pub single_thread: bool,
+
+    /// Replace the generated sample at an index with an anomaly, e.g. 100:nan, repeatable. Debug builds only
+    #[cfg(debug_assertions)]
+    #[arg(long, value_parser = crate::anomaly::parse_anomaly_spec)]
+    pub inject_anomaly: Vec<crate::anomaly::AnomalySpec>,
+}
+
+fn parse_replay_speed(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(speed) if speed.is_finite() && speed > 0.0 => Ok(speed),
+        Ok(_) => Err("replay speed must be a positive number".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn parse_socket_mode(s: &str) -> Result<u32, String> {
+    let digits = s.strip_prefix("0o").unwrap_or(s);
+    match u32::from_str_radix(digits, 8) {
+        Ok(mode) if mode <= 0o777 => Ok(mode),
+        Ok(_) => Err("socket mode must be at most 777".to_string()),
+        Err(_) => Err(format!("{} is not an octal mode", s)),
+    }
 }
 
 impl PublisherArgs {
@@ -21,9 +164,152 @@ impl PublisherArgs {
         <Self as clap::Parser>::parse()
     }
 
+    pub fn to_json(&self) -> String {
+        // Plain data with string keys, serialization cannot fail
+        serde_json::to_string_pretty(self).unwrap()
+    }
+
+    pub fn capabilities() -> Capabilities {
+        let mut features = vec![
+            "emulator",
+            "replay",
+            "inspect",
+            "backpressure",
+            "flush-coalescing",
+            "write-timeout",
+            "timestamp-base",
+            "decimation",
+            "socket-mode",
+            "send-time",
+            "bench",
+            "single-thread",
+            "accept-retries",
+            "frame-magic",
+            "sensor-rates",
+        ];
+        if cfg!(all(unix, feature = "serial")) {
+            features.push("serial");
+        }
+        if cfg!(debug_assertions) {
+            features.push("anomaly-injection");
+        }
+        Capabilities::new("publisher", env!("CARGO_PKG_VERSION"), features)
+    }
+
+    pub fn capabilities_json() -> String {
+        serde_json::to_string(&Self::capabilities()).unwrap()
+    }
+
     pub fn print(&self, logger: &slog::Logger) {
         slog::info!(logger, "Log level: {:?}", self.log_level);
         slog::info!(logger, "Socket path: {:?}", self.socket_path);
         slog::info!(logger, "Frequency: {:?}Hz", self.frequency);
+        slog::info!(logger, "Decimate: {:?}", self.decimate);
+        slog::info!(logger, "Emulator profile: {:?}", self.profile);
+        slog::info!(
+            logger,
+            "Sensor rates: acc {:?}Hz, gyro {:?}Hz, mag {:?}Hz",
+            self.acc_rate,
+            self.gyro_rate,
+            self.mag_rate
+        );
+        slog::info!(logger, "Timestamp base: {:?}", self.timestamp_base);
+        slog::info!(logger, "Replay: {:?}", self.replay);
+        slog::info!(logger, "Replay speed: {:?}x", self.replay_speed);
+        slog::info!(logger, "Serial: {:?}", self.serial);
+        slog::info!(logger, "Baud: {:?}", self.baud);
+        slog::info!(logger, "Queue capacity: {:?}", self.queue_capacity);
+        slog::info!(logger, "Backpressure: {:?}", self.backpressure);
+        slog::info!(logger, "Flush every: {:?}", self.flush_every);
+        slog::info!(logger, "Max flush latency: {:?}ms", self.max_flush_latency);
+        slog::info!(logger, "Write timeout: {:?}ms", self.write_timeout_ms);
+        slog::info!(logger, "Accept retries: {:?}", self.accept_retries);
+        slog::info!(logger, "Length endianness: {:?}", self.length_endianness);
+        slog::info!(logger, "Format: {:?}", self.format);
+        slog::info!(logger, "Protocol version: {:?}", self.protocol_version);
+        slog::info!(logger, "Compression: {:?}", self.compression);
+        slog::info!(logger, "Compress threshold: {:?}B", self.compress_threshold);
+        slog::info!(
+            logger,
+            "Socket mode: {:?}",
+            self.socket_mode.map(|mode| format!("{:o}", mode))
+        );
+        slog::info!(logger, "Stamp send time: {:?}", self.stamp_send_time);
+        slog::info!(logger, "Bench: {:?}s", self.bench);
+        slog::info!(logger, "Single thread: {:?}", self.single_thread);
+        #[cfg(debug_assertions)]
+        slog::info!(logger, "Injected anomalies: {:?}", self.inject_anomaly);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn test_show_config_json_contains_every_field() {
+        let args = <PublisherArgs as clap::Parser>::try_parse_from(["publisher", "--show-config"])
+            .unwrap();
+        assert!(args.show_config);
+
+        let json: serde_json::Value = serde_json::from_str(&args.to_json()).unwrap();
+        let object = json
+            .as_object()
+            .expect("Config should serialize to a JSON object");
+
+        for arg in PublisherArgs::command().get_arguments() {
+            let id = arg.get_id().as_str();
+            if matches!(
+                id,
+                "help" | "version" | "show_config" | "print_capabilities"
+            ) {
+                continue;
+            }
+            assert!(
+                object.contains_key(id),
+                "Missing field '{}' in {}",
+                id,
+                json
+            );
+        }
+
+        assert_eq!(object["socket_path"], DEFAULT_SOCKET_PATH);
+        assert_eq!(object["log_level"], "info");
+    }
+
+    #[test]
+    fn test_capabilities_banner_lists_expected_keys() {
+        let json: serde_json::Value =
+            serde_json::from_str(&PublisherArgs::capabilities_json()).unwrap();
+        for key in [
+            "binary",
+            "version",
+            "protocol_version",
+            "transports",
+            "length_endianness",
+            "wire_formats",
+            "compression",
+            "features",
+        ] {
+            assert!(json.get(key).is_some(), "Missing key '{}' in {}", key, json);
+        }
+
+        assert_eq!(json["binary"], "publisher");
+        assert_eq!(
+            json["protocol_version"],
+            common::capabilities::PROTOCOL_VERSION
+        );
+        assert!(!json["features"].as_array().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_socket_mode_accepts_octal_only() {
+        assert_eq!(parse_socket_mode("660"), Ok(0o660));
+        assert_eq!(parse_socket_mode("0o600"), Ok(0o600));
+        assert_eq!(parse_socket_mode("0755"), Ok(0o755));
+        assert!(parse_socket_mode("689").is_err());
+        assert!(parse_socket_mode("1777").is_err());
+        assert!(parse_socket_mode("rw-").is_err());
     }
 }
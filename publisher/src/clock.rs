@@ -0,0 +1,91 @@
+use common::clap;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+/// Origin of the emulator timestamps.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TimestampBase {
+    /// Milliseconds since the Unix epoch, truncated to u32
+    #[default]
+    Epoch,
+    /// Milliseconds since the publisher started, far from u32 overflow
+    Process,
+}
+
+/// Time source of the emulator, in milliseconds.
+pub trait Clock: Send {
+    fn now_millis(&self) -> u64;
+
+    /// Same clock in microseconds, for sub-millisecond timestamps. Clocks
+    /// without finer resolution scale their milliseconds.
+    fn now_micros(&self) -> u64 {
+        self.now_millis() * 1000
+    }
+}
+
+/// Monotonic clock anchored at the wall-clock time of its creation, or at
+/// zero with `TimestampBase::Process`.
+///
+/// Epoch-based values look like Unix epoch millis but never jump backwards
+/// on NTP adjustments, since they advance with `Instant`.
+pub struct MonotonicClock {
+    start: Instant,
+    start_epoch_micros: u64,
+}
+
+impl MonotonicClock {
+    pub fn new() -> Self {
+        Self::with_base(TimestampBase::Epoch)
+    }
+
+    pub fn with_base(base: TimestampBase) -> Self {
+        let start_epoch_micros = match base {
+            TimestampBase::Epoch => SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_micros() as u64)
+                .unwrap_or(0),
+            TimestampBase::Process => 0,
+        };
+        MonotonicClock {
+            start: Instant::now(),
+            start_epoch_micros,
+        }
+    }
+}
+
+impl Clock for MonotonicClock {
+    fn now_millis(&self) -> u64 {
+        self.now_micros() / 1000
+    }
+
+    fn now_micros(&self) -> u64 {
+        self.start_epoch_micros + self.start.elapsed().as_micros() as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread::sleep;
+    use std::time::Duration;
+
+    #[test]
+    fn test_monotonic_clock_advances() {
+        let clock = MonotonicClock::new();
+        let first = clock.now_millis();
+
+        sleep(Duration::from_millis(10));
+        let second = clock.now_millis();
+
+        assert!(second >= first + 10);
+    }
+
+    #[test]
+    fn test_process_base_starts_near_zero() {
+        let epoch = MonotonicClock::new();
+        let process = MonotonicClock::with_base(TimestampBase::Process);
+
+        assert!(process.now_millis() < 1000);
+        assert!(epoch.now_millis() > u32::MAX as u64);
+    }
+}
@@ -0,0 +1,81 @@
+use std::fmt;
+use std::io;
+
+/// Failure modes of `Publisher`, converted to `io::Error` at the binary's edge.
+#[derive(Debug)]
+pub enum PublisherError {
+    /// Preparing, cleaning up or binding the socket path failed
+    SocketSetup(io::Error),
+    /// Accepting a consumer failed more often than the retries allow
+    Accept(io::Error),
+    /// Writing a frame to the consumer failed
+    Write(io::Error),
+    /// Too many consecutive writes failed, the consumer is gone
+    BrokenPipe,
+}
+
+impl PublisherError {
+    pub fn kind(&self) -> io::ErrorKind {
+        match self {
+            PublisherError::SocketSetup(e)
+            | PublisherError::Accept(e)
+            | PublisherError::Write(e) => e.kind(),
+            PublisherError::BrokenPipe => io::ErrorKind::BrokenPipe,
+        }
+    }
+}
+
+impl fmt::Display for PublisherError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PublisherError::SocketSetup(e) => write!(f, "socket setup failed: {}", e),
+            PublisherError::Accept(e) => write!(f, "accept failed: {}", e),
+            PublisherError::Write(e) => write!(f, "write failed: {}", e),
+            PublisherError::BrokenPipe => write!(f, "connection broken"),
+        }
+    }
+}
+
+impl std::error::Error for PublisherError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PublisherError::SocketSetup(e)
+            | PublisherError::Accept(e)
+            | PublisherError::Write(e) => Some(e),
+            PublisherError::BrokenPipe => None,
+        }
+    }
+}
+
+impl From<PublisherError> for io::Error {
+    fn from(e: PublisherError) -> Self {
+        io::Error::new(e.kind(), e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_io_error_kinds() {
+        let cases = [
+            (
+                PublisherError::SocketSetup(io::ErrorKind::PermissionDenied.into()),
+                io::ErrorKind::PermissionDenied,
+            ),
+            (
+                PublisherError::Write(io::ErrorKind::TimedOut.into()),
+                io::ErrorKind::TimedOut,
+            ),
+            (PublisherError::BrokenPipe, io::ErrorKind::BrokenPipe),
+        ];
+
+        for (err, kind) in cases {
+            let message = err.to_string();
+            let io_err = io::Error::from(err);
+            assert_eq!(io_err.kind(), kind);
+            assert_eq!(io_err.to_string(), message);
+        }
+    }
+}
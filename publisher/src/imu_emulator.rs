@@ -1,7 +1,14 @@
+use super::clock::{Clock, MonotonicClock};
+use super::source::DataSource;
+
+use common::clap;
+use common::cli_defaults::{DEFAULT_ACC_RATE, DEFAULT_GYRO_RATE, DEFAULT_MAG_RATE};
+use common::nalgebra::{UnitQuaternion, Vector3};
 use common::proto::ImuData;
+use common::trajectory::{ConstantRotation, MAG_FIELD_MGAUSS, SinusoidalTranslation};
 use rand::prelude::*;
+use rand::rngs::StdRng;
 use rand_distr::{Distribution, Normal};
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 const ACC_MAX_CHANGE: f32 = 100.0; // mg per update
 const GYRO_MAX_CHANGE: i32 = 500; // mDeg/s per update
@@ -14,13 +21,76 @@ const MAG_NOISE_STD_DEV: f32 = 5.0; // mGauss
 // Low-pass filter coefficient for sensor data smoothing
 const ALPHA: f32 = 0.7; // 0 < ALPHA < 1, higher = more filtering
 
+const TRANSLATION_AMPLITUDE: f32 = 0.5; // m
+const TRANSLATION_FREQUENCY: f32 = 0.5; // Hz
+const ROTATION_RATE: f32 = 45.0; // deg/s about Y
+
+/// Kind of motion the emulator produces.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum EmulatorProfile {
+    /// Noisy readings wandering between random targets
+    Random,
+    /// Noise-free sinusoidal motion along X without rotation, with the
+    /// ground-truth position and orientation in every sample
+    Translation,
+    /// Noise-free pitching at a constant rate in place, gravity and the earth
+    /// field rotate into the body frame with the ground-truth orientation
+    Rotation,
+    /// Gyro wandering between random targets like `random`, rotating the
+    /// device in place: accel and mag follow from the orientation integrated
+    /// from the gyro readings, which is also the ground truth
+    Consistent,
+}
+
+/// When a sensor of the random profiles takes its next reading, every
+/// `interval_us` on average with each interval jittered by up to a quarter.
+struct SensorSchedule {
+    interval_us: u64,
+    due_us: Option<u64>,
+}
+
+impl SensorSchedule {
+    fn new(rate_hz: u32) -> Self {
+        Self {
+            interval_us: 1_000_000 / u64::from(rate_hz.max(1)),
+            due_us: None,
+        }
+    }
+
+    /// Whether a reading is due at `now_us`, scheduling the next one if so.
+    fn is_due(&mut self, now_us: u64, rng: &mut StdRng) -> bool {
+        if self.due_us.is_some_and(|due| now_us < due) {
+            return false;
+        }
+
+        let jitter = self.interval_us / 4;
+        let interval = rng.random_range(self.interval_us - jitter..=self.interval_us + jitter);
+        // Keep to the schedule, without bursts to catch up on readings
+        // missed while samples were generated more slowly
+        let base = self.due_us.map_or(now_us, |due| {
+            due.max(now_us.saturating_sub(self.interval_us))
+        });
+        self.due_us = Some(base + interval);
+        true
+    }
+}
+
 pub struct ImuEmulator {
     data: ImuData,
-    next_target_change: SystemTime,
-    rng: ThreadRng,
+    profile: EmulatorProfile,
+    start: Option<u64>, // ms
+    clock: Box<dyn Clock>,
+    next_target_change: u64, // ms
+    rng: StdRng,
     acc_target: (f32, f32, f32),
     gyro_target: (i32, i32, i32),
     mag_target: (f32, f32, f32),
+    orientation: UnitQuaternion<f32>, // body to world
+    orientation_us: Option<u64>,
+    acc_schedule: SensorSchedule,
+    gyro_schedule: SensorSchedule,
+    mag_schedule: SensorSchedule,
     acc_noise: Normal<f32>,
     gyro_noise: Normal<f32>,
     mag_noise: Normal<f32>,
@@ -28,13 +98,25 @@ pub struct ImuEmulator {
 
 impl ImuEmulator {
     pub fn new() -> Self {
+        Self::with_clock(Box::new(MonotonicClock::new()))
+    }
+
+    pub fn with_clock(clock: Box<dyn Clock>) -> Self {
         ImuEmulator {
             data: common::proto::ImuData::default(),
+            profile: EmulatorProfile::Random,
+            start: None,
+            clock,
             acc_target: (0.0, 0.0, 0.0),
             gyro_target: (0, 0, 0),
             mag_target: (0.0, 0.0, 0.0),
-            next_target_change: UNIX_EPOCH,
-            rng: rand::rng(),
+            orientation: UnitQuaternion::identity(),
+            orientation_us: None,
+            acc_schedule: SensorSchedule::new(DEFAULT_ACC_RATE),
+            gyro_schedule: SensorSchedule::new(DEFAULT_GYRO_RATE),
+            mag_schedule: SensorSchedule::new(DEFAULT_MAG_RATE),
+            next_target_change: 0,
+            rng: StdRng::from_os_rng(),
             // *_STD_DEV are constant and finite, so unwrap is "safe"
             acc_noise: Normal::new(0.0, ACC_NOISE_STD_DEV).unwrap(),
             gyro_noise: Normal::new(0.0, GYRO_NOISE_STD_DEV).unwrap(),
@@ -42,22 +124,83 @@ impl ImuEmulator {
         }
     }
 
+    pub fn set_profile(&mut self, profile: EmulatorProfile) {
+        self.profile = profile;
+    }
+
+    /// Update rates of the random profiles [Hz]. A sensor cannot update
+    /// more often than samples are generated, in between a sample repeats
+    /// its last reading and timestamp.
+    pub fn set_sensor_rates(&mut self, acc: u32, gyro: u32, mag: u32) {
+        self.acc_schedule = SensorSchedule::new(acc);
+        self.gyro_schedule = SensorSchedule::new(gyro);
+        self.mag_schedule = SensorSchedule::new(mag);
+    }
+
     pub fn generate_data(&mut self) -> ImuData {
-        let now = SystemTime::now();
+        let now_us = self.clock.now_micros();
+        let now = now_us / 1000;
+
+        if matches!(
+            self.profile,
+            EmulatorProfile::Translation | EmulatorProfile::Rotation
+        ) {
+            let start = *self.start.get_or_insert(now_us);
+            let t = (now_us - start) as f32 / 1e6;
+            let timestamp = self.get_timestamp(now);
+            self.data = match self.profile {
+                EmulatorProfile::Translation => {
+                    SinusoidalTranslation::new(TRANSLATION_AMPLITUDE, TRANSLATION_FREQUENCY)
+                        .sample(t, timestamp)
+                }
+                _ => ConstantRotation::new(Vector3::new(0.0, ROTATION_RATE, 0.0))
+                    .sample(t, timestamp),
+            };
+            self.data.timestamp_acc_us = Some(now_us);
+            self.data.timestamp_gyro_us = Some(now_us);
+            self.data.timestamp_mag_us = Some(now_us);
+            return self.data;
+        }
 
         if now >= self.next_target_change {
             self.update_targets();
-            self.next_target_change =
-                now + Duration::from_millis(self.rng.random_range(1000..3000));
+            self.next_target_change = now + self.rng.random_range(1000..3000);
         }
 
-        self.update_accelerometer(now);
-        self.update_gyroscope(now);
-        self.update_magnetometer(now);
+        let consistent = self.profile == EmulatorProfile::Consistent;
+        if consistent {
+            self.update_orientation(now_us);
+        }
+
+        self.update_accelerometer(now_us);
+        self.update_gyroscope(now_us);
+        self.update_magnetometer(now_us);
+
+        if consistent {
+            self.data.true_x_pos = Some(0.0);
+            self.data.true_y_pos = Some(0.0);
+            self.data.true_z_pos = Some(0.0);
+            self.data.true_qw = Some(self.orientation.w);
+            self.data.true_qx = Some(self.orientation.i);
+            self.data.true_qy = Some(self.orientation.j);
+            self.data.true_qz = Some(self.orientation.k);
+        }
 
         self.data
     }
 
+    /// Turns the orientation by the last gyro reading, held since the
+    /// previous call, up to `now_us`.
+    fn update_orientation(&mut self, now_us: u64) {
+        let Some(last_us) = self.orientation_us.replace(now_us) else {
+            return;
+        };
+        let dt = now_us.saturating_sub(last_us) as f32 / 1e6;
+        let rate = Vector3::new(self.data.x_gyro, self.data.y_gyro, self.data.z_gyro)
+            .map(|mdps| (mdps as f32 / 1000.0).to_radians());
+        self.orientation *= UnitQuaternion::from_scaled_axis(rate * dt);
+    }
+
     fn update_targets(&mut self) {
         self.acc_target = (
             self.rng.random_range(-300.0..300.0),
@@ -78,50 +221,40 @@ impl ImuEmulator {
         );
     }
 
-    fn get_timestamp(&self, now: SystemTime) -> u32 {
-        now.duration_since(UNIX_EPOCH)
-            .unwrap_or(Duration::from_secs(0))
-            .as_millis() as u32
+    fn get_timestamp(&self, now: u64) -> u32 {
+        now as u32
     }
 
-    fn should_update_sensor(
-        &mut self,
-        now: SystemTime,
-        last_timestamp: u32,
-        jitter_range: std::ops::Range<u64>,
-    ) -> bool {
-        let last_time = UNIX_EPOCH + Duration::from_millis(last_timestamp as u64);
-        let elapsed = now
-            .duration_since(last_time)
-            .unwrap_or(Duration::from_millis(0))
-            .as_millis() as u64;
-
-        elapsed >= self.rng.random_range(jitter_range.start..jitter_range.end)
-    }
-
-    fn update_accelerometer(&mut self, now: SystemTime) {
-        // Update every ~1ms on average with some jitter
-        if !self.should_update_sensor(now, self.data.timestamp_acc, 0..2) {
+    fn update_accelerometer(&mut self, now_us: u64) {
+        if !self.acc_schedule.is_due(now_us, &mut self.rng) {
             return;
         }
 
-        self.data.x_acc =
-            self.move_toward_target_float(self.data.x_acc, self.acc_target.0, ACC_MAX_CHANGE);
-        self.data.y_acc =
-            self.move_toward_target_float(self.data.y_acc, self.acc_target.1, ACC_MAX_CHANGE);
-        self.data.z_acc =
-            self.move_toward_target_float(self.data.z_acc, self.acc_target.2, ACC_MAX_CHANGE);
+        if self.profile == EmulatorProfile::Consistent {
+            // The reaction to gravity, up in the world frame
+            let acc = self
+                .orientation
+                .inverse_transform_vector(&Vector3::new(0.0, 0.0, 1000.0));
+            (self.data.x_acc, self.data.y_acc, self.data.z_acc) = (acc.x, acc.y, acc.z);
+        } else {
+            self.data.x_acc =
+                self.move_toward_target_float(self.data.x_acc, self.acc_target.0, ACC_MAX_CHANGE);
+            self.data.y_acc =
+                self.move_toward_target_float(self.data.y_acc, self.acc_target.1, ACC_MAX_CHANGE);
+            self.data.z_acc =
+                self.move_toward_target_float(self.data.z_acc, self.acc_target.2, ACC_MAX_CHANGE);
+        }
 
         self.data.x_acc += self.acc_noise.sample(&mut self.rng);
         self.data.y_acc += self.acc_noise.sample(&mut self.rng);
         self.data.z_acc += self.acc_noise.sample(&mut self.rng);
 
-        self.data.timestamp_acc = self.get_timestamp(now)
+        self.data.timestamp_acc = self.get_timestamp(now_us / 1000);
+        self.data.timestamp_acc_us = Some(now_us);
     }
 
-    fn update_gyroscope(&mut self, now: SystemTime) {
-        // Update every ~1.25ms on average with some jitter
-        if !self.should_update_sensor(now, self.data.timestamp_gyro, 1..2) {
+    fn update_gyroscope(&mut self, now_us: u64) {
+        if !self.gyro_schedule.is_due(now_us, &mut self.rng) {
             return;
         }
 
@@ -136,27 +269,36 @@ impl ImuEmulator {
         self.data.y_gyro += self.gyro_noise.sample(&mut self.rng) as i32;
         self.data.z_gyro += self.gyro_noise.sample(&mut self.rng) as i32;
 
-        self.data.timestamp_gyro = self.get_timestamp(now);
+        self.data.timestamp_gyro = self.get_timestamp(now_us / 1000);
+        self.data.timestamp_gyro_us = Some(now_us);
     }
 
-    fn update_magnetometer(&mut self, now: SystemTime) {
-        // Update every ~2ms on average with some jitter
-        if !self.should_update_sensor(now, self.data.timestamp_mag, 1..3) {
+    fn update_magnetometer(&mut self, now_us: u64) {
+        if !self.mag_schedule.is_due(now_us, &mut self.rng) {
             return;
         }
 
-        self.data.x_mag =
-            self.move_toward_target_float(self.data.x_mag, self.mag_target.0, MAG_MAX_CHANGE);
-        self.data.y_mag =
-            self.move_toward_target_float(self.data.y_mag, self.mag_target.1, MAG_MAX_CHANGE);
-        self.data.z_mag =
-            self.move_toward_target_float(self.data.z_mag, self.mag_target.2, MAG_MAX_CHANGE);
+        if self.profile == EmulatorProfile::Consistent {
+            let (x, y, z) = MAG_FIELD_MGAUSS;
+            let mag = self
+                .orientation
+                .inverse_transform_vector(&Vector3::new(x, y, z));
+            (self.data.x_mag, self.data.y_mag, self.data.z_mag) = (mag.x, mag.y, mag.z);
+        } else {
+            self.data.x_mag =
+                self.move_toward_target_float(self.data.x_mag, self.mag_target.0, MAG_MAX_CHANGE);
+            self.data.y_mag =
+                self.move_toward_target_float(self.data.y_mag, self.mag_target.1, MAG_MAX_CHANGE);
+            self.data.z_mag =
+                self.move_toward_target_float(self.data.z_mag, self.mag_target.2, MAG_MAX_CHANGE);
+        }
 
         self.data.x_mag += self.mag_noise.sample(&mut self.rng);
         self.data.y_mag += self.mag_noise.sample(&mut self.rng);
         self.data.z_mag += self.mag_noise.sample(&mut self.rng);
 
-        self.data.timestamp_mag = self.get_timestamp(now);
+        self.data.timestamp_mag = self.get_timestamp(now_us / 1000);
+        self.data.timestamp_mag_us = Some(now_us);
     }
 
     fn move_toward_target_float(&self, current: f32, target: f32, max_change: f32) -> f32 {
@@ -189,9 +331,18 @@ impl ImuEmulator {
     }
 }
 
+impl DataSource for ImuEmulator {
+    fn next_sample(&mut self) -> Option<ImuData> {
+        Some(self.generate_data())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::clock::TimestampBase;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicU64, Ordering};
     use std::thread::sleep;
     use std::time::Duration;
 
@@ -229,6 +380,215 @@ mod tests {
         assert!(emulator.data.timestamp_mag > initial_ts_mag);
     }
 
+    struct FakeClock(Arc<AtomicU64>);
+
+    impl Clock for FakeClock {
+        fn now_millis(&self) -> u64 {
+            self.0.load(Ordering::Relaxed)
+        }
+    }
+
+    struct FakeMicroClock(Arc<AtomicU64>);
+
+    impl Clock for FakeMicroClock {
+        fn now_millis(&self) -> u64 {
+            self.now_micros() / 1000
+        }
+
+        fn now_micros(&self) -> u64 {
+            self.0.load(Ordering::Relaxed)
+        }
+    }
+
+    #[test]
+    fn test_microsecond_timestamps_resolve_high_rates() {
+        let now = Arc::new(AtomicU64::new(1_000_000));
+        let mut emulator = ImuEmulator::with_clock(Box::new(FakeMicroClock(now.clone())));
+
+        // 4kHz generation, finer than the millisecond timestamps
+        let mut deltas = Vec::new();
+        let mut last = emulator.generate_data().timestamp_acc_us.unwrap();
+        for step in 1..=400 {
+            now.store(1_000_000 + step * 250, Ordering::Relaxed);
+            let data = emulator.generate_data();
+            let us = data.timestamp_acc_us.unwrap();
+            assert_eq!(data.timestamp_acc as u64, us / 1000);
+            if us != last {
+                deltas.push(us - last);
+                last = us;
+            }
+        }
+
+        assert!(
+            deltas.iter().any(|delta| delta % 1000 != 0),
+            "Expected sub-millisecond intervals, got {:?}",
+            deltas
+        );
+    }
+
+    #[test]
+    fn test_timestamps_follow_injected_clock() {
+        let now = Arc::new(AtomicU64::new(1000));
+        let mut emulator = ImuEmulator::with_clock(Box::new(FakeClock(now.clone())));
+
+        let data = emulator.generate_data();
+        assert_eq!(data.timestamp_acc, 1000);
+        assert_eq!(data.timestamp_gyro, 1000);
+        assert_eq!(data.timestamp_mag, 1000);
+
+        // Every sensor updates at least once per 3ms
+        for expected in [1005, 1010, 1013] {
+            now.store(expected, Ordering::Relaxed);
+            let data = emulator.generate_data();
+            assert_eq!(data.timestamp_acc, expected as u32);
+            assert_eq!(data.timestamp_gyro, expected as u32);
+            assert_eq!(data.timestamp_mag, expected as u32);
+        }
+
+        // Frozen clock never moves timestamps forward
+        let frozen = emulator.generate_data();
+        assert_eq!(frozen.timestamp_acc, 1013);
+        assert_eq!(frozen.timestamp_gyro, 1013);
+        assert_eq!(frozen.timestamp_mag, 1013);
+    }
+
+    #[test]
+    fn test_process_timestamp_base_starts_small() {
+        let clock = MonotonicClock::with_base(TimestampBase::Process);
+        let mut emulator = ImuEmulator::with_clock(Box::new(clock));
+
+        let data = emulator.generate_data();
+        assert!(data.timestamp_acc < 1000, "Got {}", data.timestamp_acc);
+    }
+
+    #[test]
+    fn test_translation_profile_follows_trajectory() {
+        let now = Arc::new(AtomicU64::new(5000));
+        let mut emulator = ImuEmulator::with_clock(Box::new(FakeClock(now.clone())));
+        emulator.set_profile(EmulatorProfile::Translation);
+        let trajectory = SinusoidalTranslation::new(TRANSLATION_AMPLITUDE, TRANSLATION_FREQUENCY);
+
+        let stamped = |sample: ImuData, us: u64| ImuData {
+            timestamp_acc_us: Some(us),
+            timestamp_gyro_us: Some(us),
+            timestamp_mag_us: Some(us),
+            ..sample
+        };
+
+        let first = emulator.generate_data();
+        assert_eq!(first, stamped(trajectory.sample(0.0, 5000), 5_000_000));
+        assert_eq!(first.true_x_pos, Some(0.0));
+
+        now.store(5500, Ordering::Relaxed);
+        let data = emulator.generate_data();
+        assert_eq!(data, stamped(trajectory.sample(0.5, 5500), 5_500_000));
+        assert!((data.true_x_pos.unwrap() - TRANSLATION_AMPLITUDE).abs() < 1e-6);
+        assert_eq!(data.x_gyro, 0);
+    }
+
+    #[test]
+    fn test_rotation_profile_moves_gravity_from_z_to_x() {
+        let now = Arc::new(AtomicU64::new(0));
+        let mut emulator = ImuEmulator::with_clock(Box::new(FakeClock(now.clone())));
+        emulator.set_profile(EmulatorProfile::Rotation);
+
+        let level = emulator.generate_data();
+        assert!((level.z_acc - 1000.0).abs() < 1e-2);
+        assert!(level.x_acc.abs() < 1e-2);
+
+        // 90deg at 45deg/s about +Y leaves body -X pointing up
+        now.store(2000, Ordering::Relaxed);
+        let turned = emulator.generate_data();
+        assert!(
+            (turned.x_acc + 1000.0).abs() < 1e-2,
+            "x_acc {}",
+            turned.x_acc
+        );
+        assert!(turned.z_acc.abs() < 1e-2, "z_acc {}", turned.z_acc);
+        assert_eq!(turned.y_gyro, 45_000);
+    }
+
+    #[test]
+    fn test_consistent_profile_follows_integrated_gyro() {
+        let now = Arc::new(AtomicU64::new(1_000_000));
+        let mut emulator = ImuEmulator::with_clock(Box::new(FakeMicroClock(now.clone())));
+        emulator.set_profile(EmulatorProfile::Consistent);
+        // About 23deg/s once the gyro has ramped up, kept for the whole run
+        emulator.gyro_target = (20_000, -10_000, 5_000);
+        emulator.next_target_change = u64::MAX;
+
+        // Integrate the gyro as a consumer would, from level
+        let mut orientation = UnitQuaternion::identity();
+        let mut previous = emulator.generate_data();
+        let gravity = |q: &UnitQuaternion<f32>| q.inverse_transform_vector(&Vector3::z());
+        for step in 1..=5000 {
+            now.store(1_000_000 + step * 1000, Ordering::Relaxed);
+            let data = emulator.generate_data();
+            let rate = Vector3::new(previous.x_gyro, previous.y_gyro, previous.z_gyro)
+                .map(|mdps| (mdps as f32 / 1000.0).to_radians());
+            orientation *= UnitQuaternion::from_scaled_axis(rate * 1e-3);
+            previous = data;
+
+            let acc = Vector3::new(data.x_acc, data.y_acc, data.z_acc);
+            let (x, y, z) = MAG_FIELD_MGAUSS;
+            let expected_mag = orientation.inverse_transform_vector(&Vector3::new(x, y, z));
+            let mag = Vector3::new(data.x_mag, data.y_mag, data.z_mag);
+            // Within noise, about 1deg for the weaker mag, and the staleness
+            // of a reading up to 2.5ms old
+            assert!(
+                acc.angle(&gravity(&orientation)).to_degrees() < 1.0,
+                "step {}: accel {:?}",
+                step,
+                acc
+            );
+            assert!(
+                mag.angle(&expected_mag).to_degrees() < 5.0,
+                "step {}: mag {:?}",
+                step,
+                mag
+            );
+        }
+
+        let truth = UnitQuaternion::from_quaternion(common::nalgebra::Quaternion::new(
+            previous.true_qw.unwrap(),
+            previous.true_qx.unwrap(),
+            previous.true_qy.unwrap(),
+            previous.true_qz.unwrap(),
+        ));
+        assert!(truth.angle_to(&orientation).to_degrees() < 0.5);
+        assert!(
+            gravity(&orientation).angle(&Vector3::z()).to_degrees() > 30.0,
+            "Expected the device to turn well away from level"
+        );
+    }
+
+    #[test]
+    fn test_sensor_rates_set_update_counts() {
+        let now = Arc::new(AtomicU64::new(1_000_000));
+        let mut emulator = ImuEmulator::with_clock(Box::new(FakeMicroClock(now.clone())));
+        emulator.set_sensor_rates(100, 1000, 50);
+        // The counts depend on the interval jitter, keep it reproducible
+        emulator.rng = StdRng::seed_from_u64(1);
+
+        // 2s generated at 10kHz
+        let (mut acc, mut gyro, mut mag) = (0, 0, 0);
+        let mut last = emulator.generate_data();
+        for step in 1..=20_000 {
+            now.store(1_000_000 + step * 100, Ordering::Relaxed);
+            let data = emulator.generate_data();
+            acc += usize::from(data.timestamp_acc_us != last.timestamp_acc_us);
+            gyro += usize::from(data.timestamp_gyro_us != last.timestamp_gyro_us);
+            mag += usize::from(data.timestamp_mag_us != last.timestamp_mag_us);
+            last = data;
+        }
+
+        assert!((190..=210).contains(&acc), "acc {}", acc);
+        assert!((1900..=2100).contains(&gyro), "gyro {}", gyro);
+        assert!((95..=105).contains(&mag), "mag {}", mag);
+        let ratio = acc as f32 / mag as f32;
+        assert!((ratio - 2.0).abs() < 0.2, "acc/mag {}", ratio);
+    }
+
     #[test]
     fn test_move_toward_target_float() {
         let emulator = ImuEmulator::new();
@@ -0,0 +1,134 @@
+use common::proto::ImuData;
+
+use std::fmt::Write;
+
+/// Renders decoded frames as a table with per-sensor timestamps and magnitudes.
+pub fn render_frames(samples: &[ImuData]) -> String {
+    let mut out = String::new();
+
+    // Writing into a String cannot fail
+    let _ = writeln!(
+        out,
+        "{:>6} | {:>10} {:>9} {:>9} {:>9} {:>9} | {:>10} {:>8} {:>8} {:>8} {:>9} | {:>10} {:>8} {:>8} {:>8} {:>8}",
+        "frame",
+        "t_acc[ms]",
+        "x[mg]",
+        "y[mg]",
+        "z[mg]",
+        "|acc|",
+        "t_gyro[ms]",
+        "x[mdps]",
+        "y[mdps]",
+        "z[mdps]",
+        "|gyro|",
+        "t_mag[ms]",
+        "x[mG]",
+        "y[mG]",
+        "z[mG]",
+        "|mag|"
+    );
+
+    for (index, data) in samples.iter().enumerate() {
+        let acc = (data.x_acc.powi(2) + data.y_acc.powi(2) + data.z_acc.powi(2)).sqrt();
+        let gyro = ((data.x_gyro as f32).powi(2)
+            + (data.y_gyro as f32).powi(2)
+            + (data.z_gyro as f32).powi(2))
+        .sqrt();
+        let mag = (data.x_mag.powi(2) + data.y_mag.powi(2) + data.z_mag.powi(2)).sqrt();
+
+        let _ = writeln!(
+            out,
+            "{:>6} | {:>10} {:>9.2} {:>9.2} {:>9.2} {:>9.2} | {:>10} {:>8} {:>8} {:>8} {:>9.1} | {:>10} {:>8.2} {:>8.2} {:>8.2} {:>8.2}",
+            index,
+            data.timestamp_acc,
+            data.x_acc,
+            data.y_acc,
+            data.z_acc,
+            acc,
+            data.timestamp_gyro,
+            data.x_gyro,
+            data.y_gyro,
+            data.z_gyro,
+            gyro,
+            data.timestamp_mag,
+            data.x_mag,
+            data.y_mag,
+            data.z_mag,
+            mag
+        );
+    }
+
+    let _ = writeln!(out, "{} frames", samples.len());
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::replay::ReplaySource;
+    use common::codec::{FrameReader, encode_frame};
+    use std::fs;
+    use std::path::PathBuf;
+
+    #[tokio::test]
+    async fn test_inspect_recorded_frames() {
+        let samples = [
+            ImuData {
+                x_acc: 300.0,
+                y_acc: 0.0,
+                z_acc: 400.0,
+                timestamp_acc: 1000,
+                x_gyro: 3000,
+                y_gyro: -4000,
+                z_gyro: 0,
+                timestamp_gyro: 1001,
+                x_mag: 0.0,
+                y_mag: 6.0,
+                z_mag: 8.0,
+                timestamp_mag: 1002,
+                ..Default::default()
+            },
+            ImuData {
+                z_acc: 1000.0,
+                timestamp_acc: 1010,
+                timestamp_gyro: 1011,
+                timestamp_mag: 1012,
+                ..Default::default()
+            },
+        ];
+
+        let path = PathBuf::from("/tmp/test_imu_inspect.frames");
+        let mut bytes = Vec::new();
+        for sample in &samples {
+            encode_frame(sample, &mut bytes);
+        }
+        fs::write(&path, bytes).expect("Failed to write frame file");
+
+        let decoded = ReplaySource::read_frames(&path, FrameReader::default())
+            .await
+            .expect("Failed to read frame file");
+        let table = render_frames(&decoded);
+        let lines: Vec<&str> = table.lines().collect();
+
+        assert_eq!(lines.len(), 4, "Header, two frames and a footer");
+        assert!(lines[0].contains("|acc|"));
+
+        let first: Vec<&str> = lines[1].split_whitespace().collect();
+        assert_eq!(
+            first,
+            [
+                "0", "|", "1000", "300.00", "0.00", "400.00", "500.00", "|", "1001", "3000",
+                "-4000", "0", "5000.0", "|", "1002", "0.00", "6.00", "8.00", "10.00"
+            ]
+        );
+
+        let second: Vec<&str> = lines[2].split_whitespace().collect();
+        assert_eq!(second[2], "1010");
+        assert_eq!(second[6], "1000.00");
+        assert_eq!(second[8], "1011");
+        assert_eq!(second[14], "1012");
+
+        assert_eq!(lines[3], "2 frames");
+        fs::remove_file(&path).expect("Failed to clean up frame file");
+    }
+}
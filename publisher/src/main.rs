@@ -1,14 +1,116 @@
+#[cfg(debug_assertions)]
+mod anomaly;
+mod bench;
+mod channel;
 mod cli;
+mod clock;
+mod error;
 mod imu_emulator;
+mod inspect;
 mod publisher;
+mod replay;
+mod serial;
+mod source;
 
-#[tokio::main]
-async fn main() -> std::io::Result<()> {
+fn main() -> std::io::Result<()> {
     let args = cli::PublisherArgs::parse();
+    // One thread avoids cross-thread wakeups and their jitter, at the cost
+    // of parallelism
+    let mut runtime = if args.single_thread {
+        tokio::runtime::Builder::new_current_thread()
+    } else {
+        tokio::runtime::Builder::new_multi_thread()
+    };
+    let runtime = runtime.enable_all().build()?;
+    let result = runtime.block_on(run(args));
+    // A read from a silent serial device may still be pending, don't wait for it
+    runtime.shutdown_background();
+    result
+}
+
+async fn run(args: cli::PublisherArgs) -> std::io::Result<()> {
+    if args.show_config {
+        println!("{}", args.to_json());
+        return Ok(());
+    }
+    let recording = recording_reader(&args);
+    if let Some(path) = &args.inspect {
+        print!(
+            "{}",
+            inspect::render_frames(&replay::ReplaySource::read_frames(path, recording).await?)
+        );
+        return Ok(());
+    }
+    if args.print_capabilities {
+        println!("{}", cli::PublisherArgs::capabilities_json());
+    }
+
     let logger = common::logging::setup_logger(args.log_level.clone());
     cli::PublisherArgs::print(&args, &logger);
 
-    publisher::Publisher::new(args.socket_path, args.frequency, logger.clone())
-        .run()
-        .await
+    let mut publisher = publisher::Publisher::new(args.socket_path, args.frequency, logger.clone());
+
+    publisher.set_decimation(args.decimate);
+    publisher.set_socket_mode(args.socket_mode);
+    publisher.set_stamp_send_time(args.stamp_send_time);
+    publisher.set_backpressure(args.queue_capacity as usize, args.backpressure);
+    publisher.set_length_endianness(args.length_endianness);
+    if args.stamp_send_time && args.format == common::framing::WireFormat::Packed {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "--stamp-send-time needs --format protobuf, packed frames carry no send time",
+        ));
+    }
+    publisher.set_wire_format(args.format);
+    publisher.set_frame_magic(args.protocol_version >= 2);
+    publisher.set_compression(args.compression, args.compress_threshold as usize);
+    publisher.set_flush_coalescing(
+        args.flush_every,
+        std::time::Duration::from_millis(args.max_flush_latency.into()),
+    );
+    publisher.set_write_timeout(
+        (args.write_timeout_ms > 0)
+            .then(|| std::time::Duration::from_millis(args.write_timeout_ms.into())),
+    );
+    publisher.set_accept_retries((args.accept_retries > 0).then_some(args.accept_retries));
+
+    let source: Box<dyn source::DataSource> = if let Some(path) = &args.replay {
+        Box::new(replay::ReplaySource::from_file(path, args.replay_speed, recording).await?)
+    } else if let Some(path) = &args.serial {
+        Box::new(serial::SerialSource::open(path, args.baud, logger.clone())?)
+    } else {
+        let clock = clock::MonotonicClock::with_base(args.timestamp_base);
+        let mut emulator = imu_emulator::ImuEmulator::with_clock(Box::new(clock));
+        emulator.set_profile(args.profile);
+        emulator.set_sensor_rates(args.acc_rate, args.gyro_rate, args.mag_rate);
+        Box::new(emulator)
+    };
+    #[cfg(debug_assertions)]
+    let source: Box<dyn source::DataSource> = if args.inject_anomaly.is_empty() {
+        source
+    } else {
+        Box::new(anomaly::AnomalyInjector::new(source, &args.inject_anomaly))
+    };
+    publisher.set_source(source);
+
+    if let Some(seconds) = args.bench {
+        let report = publisher
+            .bench(std::time::Duration::from_secs(seconds.into()))
+            .await?;
+        common::slog::info!(logger, "Benchmark finished"; "rate_hz" => report.rate_hz(), "errors" => report.errors);
+        println!("{}", report);
+        return Ok(());
+    }
+
+    Ok(publisher.run().await?)
+}
+
+/// Reads recordings framed and encoded like the stream this publisher sends.
+fn recording_reader(args: &cli::PublisherArgs) -> common::codec::FrameReader {
+    let mut frames = common::codec::FrameReader::default();
+    frames.set_endianness(args.length_endianness);
+    frames.set_wire_format(args.format);
+    frames.set_frame_magic(args.protocol_version >= 2);
+    frames.set_compression(args.compression);
+    frames
 }
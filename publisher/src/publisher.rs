@@ -1,21 +1,68 @@
+use super::bench::BenchReport;
+use super::channel::{self, BackpressurePolicy, Received, SampleSender};
+use super::error::PublisherError;
 use super::imu_emulator;
+use super::source::DataSource;
 
-use common::prost::Message;
+use common::cli_defaults::{
+    DEFAULT_FLUSH_EVERY, DEFAULT_MAX_FLUSH_LATENCY, DEFAULT_QUEUE_CAPACITY, DEFAULT_WRITE_TIMEOUT,
+};
+use common::codec::{FrameReader, compress_frame, encode_frame_with_format};
+use common::framing::{Compression, Endianness, FRAME_MAGIC, WireFormat};
+use common::handshake;
 use common::slog::{Logger, debug, error, info, warn};
+use common::transport::{Listener, ServerStream};
+use common::wallclock::unix_time_us;
 
-use tokio::io::AsyncWriteExt;
-use tokio::net::{UnixListener, UnixStream};
-use tokio::time::{Duration, interval};
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt, BufReader, BufWriter};
+use tokio::sync::Mutex;
+use tokio::time::{Duration, Instant, interval, timeout, timeout_at};
 
+#[cfg(unix)]
 use std::fs;
 use std::io;
 use std::path::PathBuf;
+use std::sync::Arc;
+
+/// How long a consumer may take to answer the compression offer.
+const COMPRESSION_REPLY_TIMEOUT: Duration = Duration::from_secs(1);
 
 pub struct Publisher {
     socket_path: PathBuf,
     frequency_hz: u32,
+    decimation: u32,
     logger: Logger,
-    emulator: imu_emulator::ImuEmulator,
+    source: Arc<Mutex<Box<dyn DataSource>>>,
+    queue_capacity: usize,
+    backpressure: BackpressurePolicy,
+    encode_buffer: Vec<u8>,
+    /// Bytes of the frame in `encode_buffer` already written when its write
+    /// was interrupted, 0 if it went out whole
+    partial_frame: usize,
+    length_endianness: Endianness,
+    wire_format: WireFormat,
+    frame_magic: bool,
+    compression: Compression,
+    /// Compression agreed on with the current consumer
+    stream_compression: Compression,
+    compress_threshold: usize,
+    flush_every: u32,
+    max_flush_latency: Duration,
+    write_timeout: Option<Duration>,
+    socket_mode: Option<u32>,
+    stamp_send_time: bool,
+    accept_retries: Option<u32>,
+}
+
+/// Source of consumer connections, the socket listener outside of tests.
+trait Accept {
+    fn accept(&mut self) -> impl Future<Output = io::Result<ServerStream>>;
+}
+
+impl Accept for Listener {
+    fn accept(&mut self) -> impl Future<Output = io::Result<ServerStream>> {
+        Listener::accept(self)
+    }
 }
 
 impl Publisher {
@@ -23,34 +70,151 @@ impl Publisher {
         Publisher {
             socket_path,
             frequency_hz,
+            decimation: 1,
             logger,
-            emulator: imu_emulator::ImuEmulator::new(),
+            source: Arc::new(Mutex::new(Box::new(imu_emulator::ImuEmulator::new()))),
+            queue_capacity: DEFAULT_QUEUE_CAPACITY as usize,
+            backpressure: BackpressurePolicy::Block,
+            encode_buffer: Vec::new(),
+            partial_frame: 0,
+            length_endianness: Endianness::default(),
+            wire_format: WireFormat::default(),
+            frame_magic: false,
+            compression: Compression::None,
+            stream_compression: Compression::None,
+            compress_threshold: 0,
+            flush_every: DEFAULT_FLUSH_EVERY,
+            max_flush_latency: Duration::from_millis(DEFAULT_MAX_FLUSH_LATENCY as u64),
+            write_timeout: Some(Duration::from_millis(DEFAULT_WRITE_TIMEOUT as u64)),
+            socket_mode: None,
+            stamp_send_time: false,
+            accept_retries: None,
         }
     }
 
-    fn io_error<E: std::fmt::Display>(kind: io::ErrorKind, e: E) -> io::Error {
-        io::Error::new(kind, e.to_string())
+    /// Replaces the default emulator as the producer of published samples.
+    pub fn set_source(&mut self, source: Box<dyn DataSource>) {
+        self.source = Arc::new(Mutex::new(source));
+    }
+
+    /// Sends only every `decimation`th generated sample, the source still
+    /// runs at the full frequency. 1 sends every sample.
+    pub fn set_decimation(&mut self, decimation: u32) {
+        self.decimation = decimation.max(1);
+    }
+
+    /// Sizes the queue between generator and socket writer and chooses what
+    /// happens when a slow consumer fills it.
+    pub fn set_backpressure(&mut self, queue_capacity: usize, policy: BackpressurePolicy) {
+        self.queue_capacity = queue_capacity;
+        self.backpressure = policy;
+    }
+
+    /// Byte order of the frame length prefix, must match the consumer.
+    pub fn set_length_endianness(&mut self, endianness: Endianness) {
+        self.length_endianness = endianness;
+    }
+
+    /// Encoding of the frame bodies, must match the consumer.
+    pub fn set_wire_format(&mut self, format: WireFormat) {
+        self.wire_format = format;
+    }
+
+    /// Puts `FRAME_MAGIC` in front of every frame, protocol version 2.
+    pub fn set_frame_magic(&mut self, frame_magic: bool) {
+        self.frame_magic = frame_magic;
+    }
+
+    /// Offers compression of frame bodies of at least `threshold` bytes to
+    /// every consumer in the handshake, see `negotiate`. Consumers without
+    /// support for it get plain frames, as do smaller bodies and those that
+    /// would not shrink.
+    pub fn set_compression(&mut self, compression: Compression, threshold: usize) {
+        self.compression = compression;
+        self.compress_threshold = threshold;
+    }
+
+    /// Flushes the stream only after `flush_every` messages, or once the
+    /// oldest unflushed one is `max_latency` old. Fewer flushes mean fewer
+    /// syscalls at high rates, but samples reach the consumer up to
+    /// `max_latency` late. 1 flushes every message.
+    pub fn set_flush_coalescing(&mut self, flush_every: u32, max_latency: Duration) {
+        self.flush_every = flush_every.max(1);
+        self.max_flush_latency = max_latency;
+    }
+
+    /// Longest a single write or flush may wait for the consumer to drain
+    /// the socket before it counts as a failed send. None waits forever.
+    pub fn set_write_timeout(&mut self, write_timeout: Option<Duration>) {
+        self.write_timeout = write_timeout;
+    }
+
+    /// Permission bits of the socket file, set right after binding. None
+    /// leaves them to the umask. Named pipes have no file mode and ignore it.
+    pub fn set_socket_mode(&mut self, mode: Option<u32>) {
+        self.socket_mode = mode;
+    }
+
+    /// Stamps every frame with the wall-clock time it is written, letting a
+    /// consumer on the same host measure the transport latency.
+    pub fn set_stamp_send_time(&mut self, stamp: bool) {
+        self.stamp_send_time = stamp;
     }
 
-    async fn ensure_socket_path(&self) -> io::Result<()> {
+    /// Consecutive failed accepts retried before `run` gives up with an
+    /// error. None retries forever.
+    pub fn set_accept_retries(&mut self, retries: Option<u32>) {
+        self.accept_retries = retries;
+    }
+
+    /// Named pipes vanish with their server, only socket files need this.
+    #[cfg(unix)]
+    async fn ensure_socket_path(&self) -> Result<(), PublisherError> {
         let path = self.socket_path.as_path();
 
-        // Clean up existing socket if needed
+        // Clean up existing socket if needed, unless someone is still listening on it
         if path.exists() {
-            warn!(self.logger, "Socket file already exists. Removing it.");
+            if common::transport::is_live(path) {
+                error!(
+                    self.logger,
+                    "Socket {} is in use by another publisher. Stop it or use a different --socket-path",
+                    path.display()
+                );
+                return Err(PublisherError::SocketSetup(io::Error::new(
+                    io::ErrorKind::AddrInUse,
+                    format!("Socket {} is in use", path.display()),
+                )));
+            }
+
+            warn!(
+                self.logger,
+                "Stale socket file already exists. Removing it."
+            );
             fs::remove_file(path).map_err(|e| {
                 error!(self.logger, "Failed to remove existing socket: {}", e);
-                e
+                PublisherError::SocketSetup(e)
             })?;
         }
 
         // Ensure parent directory exists
         if let Some(parent) = path.parent() {
+            if parent.is_file() {
+                error!(
+                    self.logger,
+                    "Socket parent {} is a file, not a directory. Use a different --socket-path",
+                    parent.display()
+                );
+                return Err(PublisherError::SocketSetup(io::Error::new(
+                    io::ErrorKind::NotADirectory,
+                    format!("Socket parent {} is not a directory", parent.display()),
+                )));
+            }
+
             if !parent.exists() {
                 info!(self.logger, "Creating parent directories");
                 fs::create_dir_all(parent).map_err(|e| {
                     error!(self.logger, "Failed to create directories: {}", e);
-                    e
+                    PublisherError::SocketSetup(self.permission_hint(e, parent))
                 })?;
             }
         }
@@ -58,7 +222,24 @@ impl Publisher {
         Ok(())
     }
 
-    async fn setup_socket(&self) -> io::Result<UnixListener> {
+    fn permission_hint(&self, e: io::Error, dir: &std::path::Path) -> io::Error {
+        if e.kind() != io::ErrorKind::PermissionDenied {
+            return e;
+        }
+
+        error!(
+            self.logger,
+            "No write permission in {}. Use a different --socket-path, e.g. under /tmp",
+            dir.display()
+        );
+        io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("Permission denied in {}: {}", dir.display(), e),
+        )
+    }
+
+    async fn setup_socket(&self) -> Result<Listener, PublisherError> {
+        #[cfg(unix)]
         self.ensure_socket_path().await?;
 
         info!(
@@ -67,22 +248,50 @@ impl Publisher {
             self.socket_path.display()
         );
 
-        match UnixListener::bind(&self.socket_path) {
+        match Listener::bind(&self.socket_path) {
             Ok(listener) => {
                 info!(self.logger, "Socket created successfully");
+                self.apply_socket_mode()?;
                 Ok(listener)
             }
             Err(e) => {
                 error!(self.logger, "Failed to create socket: {}", e);
-                Err(e)
+                let dir = self.socket_path.parent().unwrap_or(&self.socket_path);
+                Err(PublisherError::SocketSetup(self.permission_hint(e, dir)))
             }
         }
     }
 
-    async fn wait_for_consumer(&self, listener: &UnixListener) -> io::Result<UnixStream> {
+    #[cfg(unix)]
+    fn apply_socket_mode(&self) -> Result<(), PublisherError> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let Some(mode) = self.socket_mode else {
+            return Ok(());
+        };
+        fs::set_permissions(&self.socket_path, fs::Permissions::from_mode(mode)).map_err(|e| {
+            error!(self.logger, "Failed to set socket permissions: {}", e);
+            PublisherError::SocketSetup(e)
+        })?;
+        info!(self.logger, "Socket permissions set to {:o}", mode);
+        Ok(())
+    }
+
+    #[cfg(not(unix))]
+    fn apply_socket_mode(&self) -> Result<(), PublisherError> {
+        if self.socket_mode.is_some() {
+            warn!(
+                self.logger,
+                "Named pipes have no file mode, ignoring --socket-mode"
+            );
+        }
+        Ok(())
+    }
+
+    async fn wait_for_consumer(&self, listener: &mut impl Accept) -> io::Result<ServerStream> {
         info!(self.logger, "Waiting for consumer to connect...");
         match listener.accept().await {
-            Ok((stream, _addr)) => {
+            Ok(stream) => {
                 info!(self.logger, "Consumer connected");
                 Ok(stream)
             }
@@ -93,43 +302,279 @@ impl Publisher {
         }
     }
 
-    async fn send_message(
-        &self,
-        stream: &mut UnixStream,
+    /// Writes `data` as one frame. A write that times out part way leaves the
+    /// rest of the frame pending, it goes out first on the next call so the
+    /// consumer never sees a new frame start inside an unfinished one.
+    async fn send_message<S: AsyncWrite + Unpin>(
+        &mut self,
+        stream: &mut S,
         data: &common::proto::ImuData,
-    ) -> io::Result<()> {
-        let mut buf = Vec::with_capacity(data.encoded_len());
-        data.encode(&mut buf)
-            .map_err(|e| Self::io_error(io::ErrorKind::Other, format!("Encoding error: {}", e)))?;
+    ) -> Result<(), PublisherError> {
+        if self.partial_frame > 0 {
+            debug!(self.logger, "Finishing interrupted frame"; "sent" => self.partial_frame, "len" => self.encode_buffer.len());
+            self.write_frame(stream).await?;
+        }
 
-        let len_bytes = (buf.len() as u32).to_be_bytes();
-        stream.write_all(&len_bytes).await?;
-        stream.write_all(&buf).await?;
-        stream.flush().await?;
+        // Reuse one buffer for prefix and body, it stops growing after the largest message
+        let buf = &mut self.encode_buffer;
+        buf.clear();
+        if self.frame_magic {
+            buf.extend_from_slice(&FRAME_MAGIC);
+        }
+        let prefix_at = buf.len();
+        if self.stamp_send_time {
+            let stamped = common::proto::ImuData {
+                send_time_us: Some(unix_time_us()),
+                ..*data
+            };
+            encode_frame_with_format(&stamped, buf, self.length_endianness, self.wire_format);
+        } else {
+            encode_frame_with_format(data, buf, self.length_endianness, self.wire_format);
+        }
+        compress_frame(
+            buf,
+            prefix_at,
+            self.length_endianness,
+            self.stream_compression,
+            self.compress_threshold,
+        );
 
-        Ok(())
+        self.write_frame(stream).await
+    }
+
+    /// Writes what is left of the frame in `encode_buffer`, keeping count of
+    /// the bytes the stream took in case the write is interrupted.
+    async fn write_frame<S: AsyncWrite + Unpin>(
+        &mut self,
+        stream: &mut S,
+    ) -> Result<(), PublisherError> {
+        let frame = &self.encode_buffer;
+        let mut sent = self.partial_frame;
+        let write = async {
+            while sent < frame.len() {
+                match stream.write(&frame[sent..]).await? {
+                    0 => return Err(io::ErrorKind::WriteZero.into()),
+                    n => sent += n,
+                }
+            }
+            Ok(())
+        };
+        let result = with_write_timeout(self.write_timeout, write).await;
+        // A frame that never started can be dropped, one that did must be finished
+        self.partial_frame = if sent < frame.len() { sent } else { 0 };
+        result
+    }
+
+    /// A new connection starts with a fresh frame, the consumer of the
+    /// old one has no use for the rest of an interrupted one.
+    fn discard_partial_frame(&mut self) {
+        if self.partial_frame > 0 {
+            warn!(self.logger, "Discarding the rest of a frame interrupted on the previous connection"; "sent" => self.partial_frame, "len" => self.encode_buffer.len());
+            self.partial_frame = 0;
+        }
+    }
+
+    /// Flushes what `send_message` buffered and resets the coalescing state,
+    /// whether or not the flush succeeds.
+    async fn flush_pending<S: AsyncWrite + Unpin>(
+        stream: &mut S,
+        pending: &mut u32,
+        flush_deadline: &mut Option<Instant>,
+        write_timeout: Option<Duration>,
+    ) -> Result<(), PublisherError> {
+        *pending = 0;
+        *flush_deadline = None;
+        with_write_timeout(write_timeout, stream.flush()).await
+    }
+
+    async fn generate_samples(
+        source: Arc<Mutex<Box<dyn DataSource>>>,
+        frequency_hz: u32,
+        decimation: u32,
+        tx: SampleSender,
+        logger: Logger,
+    ) {
+        let mut source = source.lock_owned().await;
+        let blocking = source.blocking();
+
+        // A frequency of 0 means no rate limit at all
+        let mut interval_timer = (frequency_hz > 0).then(|| {
+            interval(Duration::from_micros(
+                (1_000_000.0 / frequency_hz as f64) as u64,
+            ))
+        });
+
+        // Samples still to drop before the next one is sent
+        let mut to_skip = 0;
+        loop {
+            match (source.next_delay(), interval_timer.as_mut()) {
+                (Some(delay), _) => tokio::time::sleep(delay).await,
+                (None, Some(interval_timer)) => {
+                    interval_timer.tick().await;
+                }
+                // Never-blocking sends (drop-oldest) would otherwise starve the writer
+                (None, None) => tokio::task::yield_now().await,
+            }
+
+            let next = if blocking {
+                // The read moves to the blocking pool together with the
+                // source, an abort on disconnect leaves it there instead of
+                // waiting on it
+                match tokio::task::spawn_blocking(move || {
+                    let sample = source.next_sample();
+                    (source, sample)
+                })
+                .await
+                {
+                    Ok((returned, sample)) => {
+                        source = returned;
+                        sample
+                    }
+                    Err(e) => {
+                        error!(logger, "Data source failed"; "error" => %e);
+                        return;
+                    }
+                }
+            } else {
+                source.next_sample()
+            };
+            let Some(imu_data) = next else {
+                info!(logger, "Data source exhausted");
+                return;
+            };
+            debug!(logger, "Generated IMU data: {:?}", &imu_data);
+
+            if to_skip > 0 {
+                to_skip -= 1;
+                continue;
+            }
+            to_skip = decimation - 1;
+            if !tx.send(imu_data).await {
+                return;
+            }
+        }
+    }
+
+    /// Offers the configured compression to a freshly connected consumer and
+    /// sends the following `publish_data` frames with what it chose. Streams
+    /// published without this handshake get plain frames.
+    pub async fn negotiate<S: AsyncRead + AsyncWrite + Unpin>(
+        &mut self,
+        stream: &mut S,
+    ) -> Result<Compression, PublisherError> {
+        let chosen =
+            handshake::offer_compression(stream, self.compression, COMPRESSION_REPLY_TIMEOUT)
+                .await
+                .map_err(PublisherError::Write)?;
+        if chosen != self.compression {
+            warn!(self.logger, "Consumer doesn't support the compression, sending plain frames"; "offered" => ?self.compression);
+        }
+        self.stream_compression = chosen;
+        Ok(chosen)
     }
 
-    async fn publish_data(&mut self, mut stream: UnixStream) -> io::Result<()> {
+    /// Publishes over an already connected stream until the source is
+    /// exhausted or the consumer goes away. `run` calls this for every
+    /// accepted connection, in-process setups can pass any writer, e.g. one
+    /// end of `transport::memory::pair`.
+    pub async fn publish_data<S: AsyncWrite + Unpin>(
+        &mut self,
+        stream: S,
+    ) -> Result<(), PublisherError> {
+        if self.frequency_hz == 0 {
+            info!(
+                self.logger,
+                "Rate limit disabled, publishing as fast as possible"
+            );
+        }
+        self.discard_partial_frame();
         info!(
             self.logger,
-            "Starting to publish data at {} Hz", self.frequency_hz
+            "Starting to publish data at {} Hz", self.frequency_hz;
+            "queue_capacity" => self.queue_capacity, "backpressure" => ?self.backpressure
         );
 
-        let interval_duration =
-            Duration::from_micros((1_000_000.0 / self.frequency_hz as f64) as u64);
-        let mut interval_timer = interval(interval_duration);
+        // Generation runs on its own task so socket latency doesn't skew its cadence
+        let (tx, rx) = channel::sample_channel(self.queue_capacity, self.backpressure);
+        let generator = tokio::spawn(Self::generate_samples(
+            self.source.clone(),
+            self.frequency_hz,
+            self.decimation,
+            tx,
+            self.logger.clone(),
+        ));
+
+        let result = self.write_samples(stream, rx).await;
+        self.stream_compression = Compression::None;
+        generator.abort();
+        // Wait for the generator to stop before a new connection reuses the
+        // source. A pending blocking read keeps it locked until it returns
+        let _ = generator.await;
+        result
+    }
 
+    async fn write_samples<S: AsyncWrite + Unpin>(
+        &mut self,
+        stream: S,
+        mut rx: channel::SampleReceiver,
+    ) -> Result<(), PublisherError> {
         let mut consecutive_errors = 0;
         const MAX_CONSECUTIVE_ERRORS: u32 = 5;
 
+        let mut stream = BufWriter::new(stream);
+        let mut pending = 0;
+        let mut flush_deadline = None;
+
         loop {
-            interval_timer.tick().await;
+            let received = match flush_deadline {
+                Some(deadline) => timeout_at(deadline, rx.recv()).await.ok(),
+                None => Some(rx.recv().await),
+            };
 
-            let imu_data = self.emulator.generate_data();
-            debug!(self.logger, "Generated IMU data: {:?}", &imu_data);
+            let result = match received {
+                Some(Some(Received::Sample(imu_data))) => {
+                    pending += 1;
+                    flush_deadline.get_or_insert_with(|| Instant::now() + self.max_flush_latency);
+                    match self.send_message(&mut stream, &imu_data).await {
+                        Ok(()) if pending >= self.flush_every => {
+                            Self::flush_pending(
+                                &mut stream,
+                                &mut pending,
+                                &mut flush_deadline,
+                                self.write_timeout,
+                            )
+                            .await
+                        }
+                        result => result,
+                    }
+                }
+                Some(Some(Received::Dropped(dropped))) => {
+                    warn!(self.logger, "Consumer too slow, dropped oldest samples"; "dropped" => dropped);
+                    continue;
+                }
+                // Source exhausted, hand over whatever is still buffered
+                Some(None) => {
+                    return Self::flush_pending(
+                        &mut stream,
+                        &mut pending,
+                        &mut flush_deadline,
+                        self.write_timeout,
+                    )
+                    .await;
+                }
+                // Nothing new within the latency bound
+                None => {
+                    Self::flush_pending(
+                        &mut stream,
+                        &mut pending,
+                        &mut flush_deadline,
+                        self.write_timeout,
+                    )
+                    .await
+                }
+            };
 
-            match self.send_message(&mut stream, &imu_data).await {
+            match result {
                 Ok(_) => {
                     consecutive_errors = 0;
                 }
@@ -142,10 +587,7 @@ impl Publisher {
                             self.logger,
                             "Too many consecutive errors, stopping publisher"
                         );
-                        return Err(Self::io_error(
-                            io::ErrorKind::BrokenPipe,
-                            "Connection broken",
-                        ));
+                        return Err(PublisherError::BrokenPipe);
                     }
 
                     tokio::time::sleep(Duration::from_millis(100)).await;
@@ -154,34 +596,139 @@ impl Publisher {
         }
     }
 
-    pub async fn run(&mut self) -> io::Result<()> {
-        let listener = self.setup_socket().await?;
+    /// Publishes as fast as the source and socket allow for `duration`, to a
+    /// consumer in this process that only decodes the frames, and reports
+    /// the sustained rate. Every frame is written and flushed on its own, so
+    /// the write latency covers the whole way into the socket.
+    pub async fn bench(&mut self, duration: Duration) -> Result<BenchReport, PublisherError> {
+        const MAX_CONSECUTIVE_ERRORS: u32 = 5;
+
+        let mut listener = self.setup_socket().await?;
+        let socket_path = self.socket_path.clone();
+        let mut frame_reader = FrameReader::default();
+        frame_reader.set_endianness(self.length_endianness);
+        frame_reader.set_wire_format(self.wire_format);
+        frame_reader.set_frame_magic(self.frame_magic);
+        frame_reader.set_compression(self.compression);
+        let drain = tokio::spawn(async move {
+            let stream = common::transport::connect(&socket_path).await?;
+            let (_, stream) = handshake::answer_offer(stream, frame_reader.compression()).await?;
+            let mut stream = BufReader::new(stream);
+            let mut received = 0u64;
+            while frame_reader.read(&mut stream).await?.is_some() {
+                received += 1;
+            }
+            io::Result::Ok(received)
+        });
+        let mut stream = self
+            .wait_for_consumer(&mut listener)
+            .await
+            .map_err(PublisherError::SocketSetup)?;
+        self.negotiate(&mut stream).await?;
+
+        info!(self.logger, "Benchmarking send rate"; "duration" => ?duration);
+        let source = self.source.clone();
+        let mut source = source.lock().await;
+        let mut report = BenchReport::default();
+        let mut total_latency = Duration::ZERO;
+        let mut consecutive_errors = 0;
+        let start = Instant::now();
+        while start.elapsed() < duration {
+            let Some(imu_data) = source.next_sample() else {
+                info!(self.logger, "Data source exhausted, ending benchmark early");
+                break;
+            };
+
+            let write_start = Instant::now();
+            let result = match self.send_message(&mut stream, &imu_data).await {
+                Ok(()) => with_write_timeout(self.write_timeout, stream.flush()).await,
+                result => result,
+            };
+            let latency = write_start.elapsed();
+            match result {
+                Ok(()) => {
+                    consecutive_errors = 0;
+                    report.sent += 1;
+                    total_latency += latency;
+                    report.max_write_latency = report.max_write_latency.max(latency);
+                }
+                Err(e) => {
+                    warn!(self.logger, "Benchmark write failed: {}", e);
+                    report.errors += 1;
+                    consecutive_errors += 1;
+                    if consecutive_errors >= MAX_CONSECUTIVE_ERRORS {
+                        break;
+                    }
+                }
+            }
+        }
+        report.duration = start.elapsed();
+        if report.sent > 0 {
+            report.mean_write_latency = total_latency.div_f64(report.sent as f64);
+        }
+
+        // Closing the stream lets the consumer see the end
+        drop(stream);
+        report.received = drain
+            .await
+            .map_err(|e| PublisherError::Write(io::Error::other(e)))?
+            .map_err(PublisherError::Write)?;
+        #[cfg(unix)]
+        let _ = fs::remove_file(&self.socket_path);
+
+        Ok(report)
+    }
 
+    pub async fn run(&mut self) -> Result<(), PublisherError> {
+        let mut listener = self.setup_socket().await?;
+        self.serve(&mut listener).await
+    }
+
+    async fn serve(&mut self, listener: &mut impl Accept) -> Result<(), PublisherError> {
+        let mut failed_accepts = 0;
         loop {
-            let stream = match self.wait_for_consumer(&listener).await {
-                Ok(stream) => stream,
+            let mut stream = match self.wait_for_consumer(listener).await {
+                Ok(stream) => {
+                    failed_accepts = 0;
+                    stream
+                }
                 Err(e) => {
-                    error!(self.logger, "Failed to accept connection: {}", e);
+                    failed_accepts += 1;
+                    if self
+                        .accept_retries
+                        .is_some_and(|retries| failed_accepts > retries)
+                    {
+                        error!(
+                            self.logger,
+                            "Giving up after {} failed accepts", failed_accepts
+                        );
+                        return Err(PublisherError::Accept(e));
+                    }
                     tokio::time::sleep(Duration::from_millis(100)).await;
                     continue;
                 }
             };
 
+            if let Err(e) = self.negotiate(&mut stream).await {
+                warn!(self.logger, "Handshake failed, waiting for new connection"; "error" => %e);
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                continue;
+            }
+
             match self.publish_data(stream).await {
                 Ok(_) => {
                     info!(self.logger, "Publisher finished normally");
                     break;
                 }
+                Err(PublisherError::BrokenPipe) => {
+                    info!(
+                        self.logger,
+                        "Consumer disconnected, waiting for new connection"
+                    );
+                }
                 Err(e) => {
-                    if e.kind() == io::ErrorKind::BrokenPipe {
-                        info!(
-                            self.logger,
-                            "Consumer disconnected, waiting for new connection"
-                        );
-                    } else {
-                        error!(self.logger, "Publisher error: {}", e);
-                        return Err(e);
-                    }
+                    error!(self.logger, "Publisher error: {}", e);
+                    return Err(e);
                 }
             }
 
@@ -192,33 +739,44 @@ impl Publisher {
     }
 }
 
-#[cfg(test)]
+/// Runs a socket write, turning a consumer that does not drain the socket
+/// within `write_timeout` into a `TimedOut` write error.
+///
+/// Timing out a write is frame-safe because `write_frame` keeps count of
+/// the bytes sent. Timing out a flush is frame-safe only because the stream
+/// is a `BufWriter` holding whole frames: it keeps its progress when the
+/// flush is dropped, and the next flush resumes mid-frame instead of
+/// starting the next one.
+async fn with_write_timeout(
+    write_timeout: Option<Duration>,
+    write: impl std::future::Future<Output = io::Result<()>>,
+) -> Result<(), PublisherError> {
+    let result = match write_timeout {
+        Some(write_timeout) => timeout(write_timeout, write).await.unwrap_or_else(|_| {
+            Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!(
+                    "Consumer did not drain the socket within {:?}",
+                    write_timeout
+                ),
+            ))
+        }),
+        None => write.await,
+    };
+    result.map_err(PublisherError::Write)
+}
+
+#[cfg(all(test, unix))]
 mod tests {
     use super::*;
+    use common::codec::{FrameReader, read_frame};
     use common::slog::o;
     use std::time::Duration;
-    use tokio::io::AsyncReadExt;
     use tokio::net::UnixStream;
 
     async fn read_imu_message(stream: &mut UnixStream) -> io::Result<common::proto::ImuData> {
-        let mut len_buf = [0u8; 4];
-        stream.read_exact(&mut len_buf).await.map_err(|e| {
-            io::Error::new(e.kind(), format!("Failed to read message length: {}", e))
-        })?;
-
-        let msg_len = u32::from_be_bytes(len_buf);
-        let mut msg_buf = vec![0u8; msg_len as usize];
-
-        stream
-            .read_exact(&mut msg_buf)
-            .await
-            .map_err(|e| io::Error::new(e.kind(), format!("Failed to read message data: {}", e)))?;
-
-        common::proto::ImuData::decode(&msg_buf[..]).map_err(|e| {
-            io::Error::new(
-                io::ErrorKind::InvalidData,
-                format!("Failed to decode message: {}", e),
-            )
+        read_frame(stream).await?.ok_or_else(|| {
+            io::Error::new(io::ErrorKind::UnexpectedEof, "Publisher closed the stream")
         })
     }
 
@@ -391,4 +949,818 @@ mod tests {
 
         cleanup_socket(socket_path);
     }
+
+    async fn measure_replay_span(test_name: &str, speed: f32) -> Duration {
+        use crate::replay::ReplaySource;
+
+        const SAMPLE_SPACING_MS: u32 = 40;
+        const SAMPLE_COUNT: u32 = 6;
+
+        let socket_path = setup_socket_path(test_name);
+        let samples: Vec<_> = (0..SAMPLE_COUNT)
+            .map(|i| common::proto::ImuData {
+                timestamp_acc: 1000 + i * SAMPLE_SPACING_MS,
+                ..Default::default()
+            })
+            .collect();
+
+        std::thread::spawn({
+            let socket_path = socket_path.clone();
+            move || {
+                let rt = tokio::runtime::Runtime::new().expect("Failed to create tokio runtime");
+                rt.block_on(async {
+                    let mut publisher = Publisher::new(socket_path, 500, create_logger());
+                    publisher.set_source(Box::new(ReplaySource::new(samples, speed)));
+                    let _ = publisher.run().await;
+                });
+            }
+        });
+
+        let mut stream = connect_to_publisher(&socket_path, 200)
+            .await
+            .expect("Failed to connect to publisher");
+
+        let first = read_imu_message(&mut stream)
+            .await
+            .expect("Failed to read first replayed message");
+        assert_eq!(first.timestamp_acc, 1000);
+        let start = std::time::Instant::now();
+
+        for i in 1..SAMPLE_COUNT {
+            let data = read_imu_message(&mut stream)
+                .await
+                .expect("Failed to read replayed message");
+            assert_eq!(
+                data.timestamp_acc,
+                1000 + i * SAMPLE_SPACING_MS,
+                "Recorded timestamps should be sent unchanged"
+            );
+        }
+
+        let span = start.elapsed();
+        cleanup_socket(socket_path);
+        span
+    }
+
+    #[tokio::test]
+    async fn test_replay_speed_scales_send_spacing() {
+        let normal = measure_replay_span("replay_normal", 1.0).await;
+        let fast = measure_replay_span("replay_fast", 2.0).await;
+
+        let ratio = normal.as_secs_f32() / fast.as_secs_f32();
+        assert!(
+            (1.6..2.4).contains(&ratio),
+            "Expected 2x faster replay, got {:?} vs {:?}",
+            normal,
+            fast
+        );
+    }
+
+    #[tokio::test]
+    async fn test_socket_parent_is_file() {
+        let parent = PathBuf::from("/tmp/test_imu_parent_is_file");
+        fs::write(&parent, b"not a directory").expect("Failed to create parent file");
+
+        let publisher = Publisher::new(parent.join("imu.sock"), 500, create_logger());
+        let err = publisher
+            .setup_socket()
+            .await
+            .expect_err("Binding under a file should fail");
+
+        assert_eq!(err.kind(), io::ErrorKind::NotADirectory);
+        fs::remove_file(&parent).expect("Failed to clean up parent file");
+    }
+
+    #[tokio::test]
+    async fn test_socket_parent_read_only() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let parent = PathBuf::from("/tmp/test_imu_read_only_dir");
+        let _ = fs::remove_dir_all(&parent);
+        fs::create_dir(&parent).expect("Failed to create read-only dir");
+        fs::set_permissions(&parent, fs::Permissions::from_mode(0o555))
+            .expect("Failed to make dir read-only");
+
+        // Privileged users bypass directory permissions, nothing to test there
+        let probe = parent.join("probe");
+        if fs::write(&probe, b"").is_ok() {
+            let _ = fs::remove_file(&probe);
+            let _ = fs::remove_dir_all(&parent);
+            return;
+        }
+
+        for socket_path in [parent.join("imu.sock"), parent.join("nested/imu.sock")] {
+            let publisher = Publisher::new(socket_path, 500, create_logger());
+            let err = publisher
+                .setup_socket()
+                .await
+                .expect_err("Binding in a read-only dir should fail");
+
+            assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+            assert!(err.to_string().contains(&parent.display().to_string()));
+        }
+
+        fs::set_permissions(&parent, fs::Permissions::from_mode(0o755)).unwrap();
+        let _ = fs::remove_dir_all(&parent);
+    }
+
+    #[tokio::test]
+    async fn test_refuses_live_socket() {
+        let socket_path = setup_socket_path("live_socket");
+        let _live = std::os::unix::net::UnixListener::bind(&socket_path)
+            .expect("Failed to bind live listener");
+
+        let publisher = Publisher::new(socket_path.clone(), 500, create_logger());
+        let err = publisher
+            .setup_socket()
+            .await
+            .expect_err("A live socket must not be hijacked");
+
+        assert_eq!(err.kind(), io::ErrorKind::AddrInUse);
+        assert!(
+            socket_path.exists(),
+            "Live socket file must be left in place"
+        );
+        cleanup_socket(socket_path);
+    }
+
+    #[tokio::test]
+    async fn test_removes_stale_socket() {
+        let socket_path = setup_socket_path("stale_socket");
+        drop(
+            std::os::unix::net::UnixListener::bind(&socket_path)
+                .expect("Failed to bind stale listener"),
+        );
+        assert!(socket_path.exists());
+
+        let publisher = Publisher::new(socket_path.clone(), 500, create_logger());
+        publisher
+            .setup_socket()
+            .await
+            .expect("Stale socket should be replaced");
+
+        cleanup_socket(socket_path);
+    }
+
+    #[tokio::test]
+    async fn test_encode_buffer_stops_growing() {
+        let mut publisher = Publisher::new(PathBuf::from("/tmp/unused"), 500, create_logger());
+        let (mut writer, mut reader) = UnixStream::pair().expect("Failed to create stream pair");
+
+        // Negative varints and large timestamps give the longest sensor encoding
+        let largest = common::proto::ImuData {
+            x_acc: -1.0,
+            y_acc: -1.0,
+            z_acc: -1.0,
+            timestamp_acc: u32::MAX,
+            x_gyro: -1,
+            y_gyro: -1,
+            z_gyro: -1,
+            timestamp_gyro: u32::MAX,
+            x_mag: -1.0,
+            y_mag: -1.0,
+            z_mag: -1.0,
+            timestamp_mag: u32::MAX,
+            ..Default::default()
+        };
+        publisher
+            .send_message(&mut writer, &largest)
+            .await
+            .expect("Failed to send warmup message");
+        assert_eq!(read_imu_message(&mut reader).await.unwrap(), largest);
+        let warm_capacity = publisher.encode_buffer.capacity();
+
+        for i in 0..100 {
+            let data = common::proto::ImuData {
+                z_acc: 1000.0,
+                timestamp_acc: i,
+                x_gyro: -(i as i32),
+                ..Default::default()
+            };
+            publisher
+                .send_message(&mut writer, &data)
+                .await
+                .expect("Failed to send message");
+            assert_eq!(read_imu_message(&mut reader).await.unwrap(), data);
+            assert_eq!(publisher.encode_buffer.capacity(), warm_capacity);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_unbounded_frequency_outpaces_rate_limit() {
+        async fn count_messages(test_name: &str, frequency_hz: u32) -> usize {
+            let socket_path = setup_socket_path(test_name);
+            spawn_publisher(socket_path.clone(), frequency_hz, create_logger(), false);
+
+            let mut stream = connect_to_publisher(&socket_path, 200)
+                .await
+                .expect("Failed to connect to publisher");
+
+            let mut count = 0;
+            let deadline = tokio::time::Instant::now() + Duration::from_millis(200);
+            while tokio::time::Instant::now() < deadline {
+                read_imu_message(&mut stream)
+                    .await
+                    .expect("Failed to read IMU message");
+                count += 1;
+            }
+
+            cleanup_socket(socket_path);
+            count
+        }
+
+        let limited = count_messages("rate_limited", 100).await;
+        let unbounded = count_messages("unbounded", 0).await;
+
+        assert!(
+            unbounded > limited * 10,
+            "Unbounded mode should be much faster: {} vs {} messages",
+            unbounded,
+            limited
+        );
+    }
+
+    #[tokio::test]
+    async fn test_little_endian_length_prefix() {
+        let mut publisher = Publisher::new(PathBuf::from("/tmp/unused"), 500, create_logger());
+        publisher.set_length_endianness(Endianness::Little);
+        let (mut writer, mut reader) = UnixStream::pair().expect("Failed to create stream pair");
+
+        let data = common::proto::ImuData {
+            z_acc: 1000.0,
+            timestamp_acc: 1234,
+            ..Default::default()
+        };
+        publisher
+            .send_message(&mut writer, &data)
+            .await
+            .expect("Failed to send message");
+
+        let mut frame_reader = FrameReader::default();
+        frame_reader.set_endianness(Endianness::Little);
+        let received = frame_reader.read(&mut reader).await.unwrap();
+        assert_eq!(received, Some(data));
+    }
+
+    /// Takes bytes up to a limit the test raises, then stalls.
+    struct ThrottledWriter {
+        written: Vec<u8>,
+        limit: usize,
+    }
+
+    impl AsyncWrite for ThrottledWriter {
+        fn poll_write(
+            mut self: std::pin::Pin<&mut Self>,
+            _: &mut std::task::Context<'_>,
+            buf: &[u8],
+        ) -> std::task::Poll<io::Result<usize>> {
+            let room = self.limit.saturating_sub(self.written.len()).min(buf.len());
+            if room == 0 {
+                return std::task::Poll::Pending;
+            }
+            self.written.extend_from_slice(&buf[..room]);
+            std::task::Poll::Ready(Ok(room))
+        }
+
+        fn poll_flush(
+            self: std::pin::Pin<&mut Self>,
+            _: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<io::Result<()>> {
+            std::task::Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(
+            self: std::pin::Pin<&mut Self>,
+            _: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<io::Result<()>> {
+            std::task::Poll::Ready(Ok(()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_interrupted_frame_is_finished_before_the_next() {
+        let mut publisher = Publisher::new(PathBuf::from("/tmp/unused"), 500, create_logger());
+        publisher.set_write_timeout(Some(Duration::from_millis(20)));
+        let sample = |timestamp| common::proto::ImuData {
+            z_acc: 1000.0,
+            timestamp_acc: timestamp,
+            ..Default::default()
+        };
+        let mut first_frame = Vec::new();
+        common::codec::encode_frame(&sample(1), &mut first_frame);
+
+        // The second frame stalls after its length prefix and two body bytes
+        let mut writer = ThrottledWriter {
+            written: Vec::new(),
+            limit: first_frame.len() + 6,
+        };
+        publisher
+            .send_message(&mut writer, &sample(1))
+            .await
+            .unwrap();
+        let err = publisher
+            .send_message(&mut writer, &sample(2))
+            .await
+            .expect_err("The stalled write should time out");
+        assert_eq!(io::Error::from(err).kind(), io::ErrorKind::TimedOut);
+
+        writer.limit = usize::MAX;
+        publisher
+            .send_message(&mut writer, &sample(3))
+            .await
+            .unwrap();
+        let mut reader = &writer.written[..];
+        let mut frame_reader = FrameReader::default();
+        for timestamp in [1, 2, 3] {
+            let received = frame_reader.read(&mut reader).await.unwrap();
+            assert_eq!(received, Some(sample(timestamp)));
+        }
+        assert_eq!(frame_reader.read(&mut reader).await.unwrap(), None);
+
+        // A new connection starts with a whole frame instead of the old rest
+        let mut writer = ThrottledWriter {
+            written: Vec::new(),
+            limit: 3,
+        };
+        assert!(
+            publisher
+                .send_message(&mut writer, &sample(4))
+                .await
+                .is_err()
+        );
+        publisher.discard_partial_frame();
+        let mut writer = ThrottledWriter {
+            written: Vec::new(),
+            limit: usize::MAX,
+        };
+        publisher
+            .send_message(&mut writer, &sample(5))
+            .await
+            .unwrap();
+        let received = read_frame(&mut &writer.written[..]).await.unwrap();
+        assert_eq!(received, Some(sample(5)));
+    }
+
+    #[tokio::test]
+    async fn test_closed_consumer_errors_by_variant() {
+        let mut publisher = Publisher::new(PathBuf::from("/tmp/unused"), 500, create_logger());
+        let (mut writer, reader) = UnixStream::pair().expect("Failed to create stream pair");
+        drop(reader);
+
+        let data = common::proto::ImuData::default();
+        let err = publisher
+            .send_message(&mut writer, &data)
+            .await
+            .expect_err("Writing to a closed consumer should fail");
+        assert!(matches!(err, PublisherError::Write(_)));
+
+        let (tx, rx) = channel::sample_channel(8, BackpressurePolicy::Block);
+        for _ in 0..5 {
+            assert!(tx.send(data).await);
+        }
+        let err = publisher
+            .write_samples(writer, rx)
+            .await
+            .expect_err("Repeated write failures should end the connection");
+        assert!(matches!(err, PublisherError::BrokenPipe));
+        assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+    }
+
+    /// Publishes from a line device, lets the consumer read one sample and
+    /// leave, then asserts the publisher returns once writes fail even
+    /// though the device has gone silent.
+    async fn assert_returns_on_disconnect_with_silent_device(
+        source: crate::serial::SerialSource,
+        mut device: impl std::io::Write,
+    ) {
+        let line = b"1000,0,0,1000,0,0,0,200,0,-400\n";
+        let (server, mut client) = common::transport::memory::pair();
+        let publisher_handle = tokio::spawn(async move {
+            let mut publisher = Publisher::new(PathBuf::from("/tmp/unused"), 0, create_logger());
+            publisher.set_source(Box::new(source));
+            publisher.publish_data(server).await
+        });
+
+        device.write_all(line).unwrap();
+        read_frame(&mut client)
+            .await
+            .expect("Failed to read frame")
+            .expect("Publisher ended early");
+        drop(client);
+        // Enough lines for the write failures to end the connection, then silence
+        for _ in 0..10 {
+            device.write_all(line).unwrap();
+        }
+
+        let result = tokio::time::timeout(Duration::from_secs(3), publisher_handle)
+            .await
+            .expect("Publisher hung on the silent device")
+            .unwrap();
+        assert!(matches!(result, Err(PublisherError::BrokenPipe)));
+        // Closing the device ends the read still pending on the blocking pool
+        drop(device);
+    }
+
+    #[tokio::test]
+    async fn test_disconnect_returns_while_line_device_is_silent() {
+        let (device, reader) = std::os::unix::net::UnixStream::pair().unwrap();
+        let source = crate::serial::SerialSource::new(reader, create_logger());
+        assert_returns_on_disconnect_with_silent_device(source, device).await;
+    }
+
+    #[cfg(feature = "serial")]
+    #[tokio::test]
+    async fn test_disconnect_returns_while_pty_is_silent() {
+        use std::os::unix::io::FromRawFd;
+
+        // SAFETY: plain libc calls on a fresh pseudo-terminal pair
+        let (master, slave_path) = unsafe {
+            let fd = libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY);
+            assert!(fd >= 0, "No pseudo-terminal available");
+            assert_eq!(libc::grantpt(fd), 0);
+            assert_eq!(libc::unlockpt(fd), 0);
+            let name = std::ffi::CStr::from_ptr(libc::ptsname(fd));
+            let path = PathBuf::from(name.to_str().unwrap());
+            (std::fs::File::from_raw_fd(fd), path)
+        };
+        let source =
+            crate::serial::SerialSource::open(&slave_path, 115200, create_logger()).unwrap();
+        assert_returns_on_disconnect_with_silent_device(source, master).await;
+    }
+
+    #[tokio::test]
+    async fn test_consumer_processes_published_stream_in_memory() {
+        const SAMPLES: u64 = 200;
+
+        let mut emulator = crate::imu_emulator::ImuEmulator::new();
+        let samples = (0..SAMPLES)
+            .map(|_| emulator.next_sample().expect("Emulator ran dry"))
+            .collect();
+        let mut publisher = Publisher::new(PathBuf::from("/tmp/unused"), 0, create_logger());
+        publisher.set_source(Box::new(crate::replay::ReplaySource::new(samples, 1000.0)));
+        let mut consumer =
+            consumer::consumer::Consumer::new(PathBuf::from("/tmp/unused"), 5, create_logger());
+
+        let (server, client) = common::transport::memory::pair();
+        let (published, consumed) = tokio::time::timeout(Duration::from_secs(5), async {
+            tokio::join!(publisher.publish_data(server), consumer.run_single(client))
+        })
+        .await
+        .expect("Stream did not finish");
+        published.expect("Publisher should end cleanly once the source is exhausted");
+        consumed.expect("Consumer should process the stream cleanly");
+
+        let report = consumer.status_report();
+        assert_eq!(report.samples, SAMPLES);
+        assert!(report.position.iter().all(|p| p.is_finite()));
+        assert!(report.speed.is_finite());
+    }
+
+    #[cfg(feature = "compression")]
+    #[tokio::test]
+    async fn test_compressed_stream_reads_back() {
+        let (mut server, client) = common::transport::memory::pair();
+        let publisher_handle = tokio::spawn(async move {
+            let mut publisher = Publisher::new(PathBuf::from("/tmp/unused"), 0, create_logger());
+            publisher.set_compression(Compression::Lz4, 0);
+            assert_eq!(publisher.negotiate(&mut server).await?, Compression::Lz4);
+            publisher.publish_data(server).await
+        });
+
+        let (chosen, mut client) = handshake::answer_offer(client, Compression::Lz4)
+            .await
+            .unwrap();
+        assert_eq!(chosen, Compression::Lz4);
+        let mut frame_reader = FrameReader::default();
+        frame_reader.set_compression(Compression::Lz4);
+        let mut last_timestamp = 0;
+        for _ in 0..50 {
+            let data = frame_reader
+                .read(&mut client)
+                .await
+                .expect("Failed to read frame")
+                .expect("Publisher ended early");
+            assert!(data.timestamp_acc >= last_timestamp);
+            last_timestamp = data.timestamp_acc;
+        }
+
+        publisher_handle.abort();
+    }
+
+    #[cfg(feature = "compression")]
+    #[tokio::test]
+    async fn test_consumer_without_compression_gets_plain_frames() {
+        const SAMPLES: u64 = 100;
+
+        let mut emulator = crate::imu_emulator::ImuEmulator::new();
+        let samples = (0..SAMPLES)
+            .map(|_| emulator.next_sample().expect("Emulator ran dry"))
+            .collect();
+        let mut publisher = Publisher::new(PathBuf::from("/tmp/unused"), 0, create_logger());
+        publisher.set_source(Box::new(crate::replay::ReplaySource::new(samples, 1000.0)));
+        publisher.set_compression(Compression::Lz4, 0);
+        let mut consumer =
+            consumer::consumer::Consumer::new(PathBuf::from("/tmp/unused"), 5, create_logger());
+
+        let (mut server, client) = common::transport::memory::pair();
+        let (published, consumed) = tokio::time::timeout(Duration::from_secs(5), async {
+            tokio::join!(
+                async {
+                    assert_eq!(publisher.negotiate(&mut server).await?, Compression::None);
+                    publisher.publish_data(server).await
+                },
+                async {
+                    let client = consumer.negotiate(client).await?;
+                    consumer.run_single(client).await
+                }
+            )
+        })
+        .await
+        .expect("Stream did not finish");
+        published.expect("Publisher should end cleanly once the source is exhausted");
+        consumed.expect("Consumer should read the plain frames");
+
+        assert_eq!(consumer.status_report().samples, SAMPLES);
+    }
+
+    /// Counts the flushes reaching the wrapped writer.
+    struct FlushCounter<W> {
+        inner: W,
+        flushes: Arc<std::sync::atomic::AtomicUsize>,
+    }
+
+    impl<W: AsyncWrite + Unpin> AsyncWrite for FlushCounter<W> {
+        fn poll_write(
+            mut self: std::pin::Pin<&mut Self>,
+            cx: &mut std::task::Context<'_>,
+            buf: &[u8],
+        ) -> std::task::Poll<io::Result<usize>> {
+            std::pin::Pin::new(&mut self.inner).poll_write(cx, buf)
+        }
+
+        fn poll_flush(
+            mut self: std::pin::Pin<&mut Self>,
+            cx: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<io::Result<()>> {
+            self.flushes
+                .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            std::pin::Pin::new(&mut self.inner).poll_flush(cx)
+        }
+
+        fn poll_shutdown(
+            mut self: std::pin::Pin<&mut Self>,
+            cx: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<io::Result<()>> {
+            std::pin::Pin::new(&mut self.inner).poll_shutdown(cx)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_flush_coalescing_reduces_flushes() {
+        let (server, mut client) = common::transport::memory::pair();
+        let flushes = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let writer = FlushCounter {
+            inner: server,
+            flushes: flushes.clone(),
+        };
+
+        let mut publisher = Publisher::new(PathBuf::from("/tmp/unused"), 500, create_logger());
+        publisher.set_flush_coalescing(10, Duration::from_secs(10));
+        let (tx, rx) = channel::sample_channel(128, BackpressurePolicy::Block);
+        for i in 1..=100 {
+            let data = common::proto::ImuData {
+                timestamp_acc: i,
+                ..Default::default()
+            };
+            assert!(tx.send(data).await);
+        }
+        drop(tx);
+        publisher
+            .write_samples(writer, rx)
+            .await
+            .expect("Coalesced writes should succeed");
+
+        for i in 1..=100 {
+            let data = read_frame(&mut client).await.unwrap().unwrap();
+            assert_eq!(data.timestamp_acc, i);
+        }
+        // One per 10 messages plus the final flush on shutdown
+        assert_eq!(flushes.load(std::sync::atomic::Ordering::Relaxed), 11);
+    }
+
+    #[tokio::test]
+    async fn test_flush_coalescing_bounds_latency() {
+        let (server, mut client) = common::transport::memory::pair();
+        let mut publisher = Publisher::new(PathBuf::from("/tmp/unused"), 500, create_logger());
+        publisher.set_flush_coalescing(1000, Duration::from_millis(20));
+
+        let (tx, rx) = channel::sample_channel(8, BackpressurePolicy::Block);
+        let writer = tokio::spawn(async move { publisher.write_samples(server, rx).await });
+        let data = common::proto::ImuData {
+            timestamp_acc: 42,
+            ..Default::default()
+        };
+        assert!(tx.send(data).await);
+
+        // Far fewer than 1000 messages, the latency bound pushes it out
+        let received = tokio::time::timeout(Duration::from_secs(1), read_frame(&mut client))
+            .await
+            .expect("Buffered sample was never flushed")
+            .unwrap();
+        assert_eq!(received, Some(data));
+
+        drop(tx);
+        writer.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_timed_out_flush_resumes_mid_frame() {
+        let mut publisher = Publisher::new(PathBuf::from("/tmp/unused"), 500, create_logger());
+        let sample = |timestamp| common::proto::ImuData {
+            z_acc: 1000.0,
+            timestamp_acc: timestamp,
+            ..Default::default()
+        };
+        let mut first_frame = Vec::new();
+        common::codec::encode_frame(&sample(1), &mut first_frame);
+
+        // Both frames fit the buffer, the flush stalls inside the second one
+        let mut stream = BufWriter::new(ThrottledWriter {
+            written: Vec::new(),
+            limit: first_frame.len() + 6,
+        });
+        for timestamp in [1, 2] {
+            publisher
+                .send_message(&mut stream, &sample(timestamp))
+                .await
+                .unwrap();
+        }
+        let (mut pending, mut flush_deadline) = (2, None);
+        let err = Publisher::flush_pending(
+            &mut stream,
+            &mut pending,
+            &mut flush_deadline,
+            Some(Duration::from_millis(20)),
+        )
+        .await
+        .expect_err("The stalled flush should time out");
+        assert_eq!(io::Error::from(err).kind(), io::ErrorKind::TimedOut);
+
+        stream.get_mut().limit = usize::MAX;
+        publisher
+            .send_message(&mut stream, &sample(3))
+            .await
+            .unwrap();
+        Publisher::flush_pending(&mut stream, &mut pending, &mut flush_deadline, None)
+            .await
+            .unwrap();
+        let mut reader = &stream.get_ref().written[..];
+        let mut frame_reader = FrameReader::default();
+        for timestamp in [1, 2, 3] {
+            let received = frame_reader.read(&mut reader).await.unwrap();
+            assert_eq!(received, Some(sample(timestamp)));
+        }
+        assert_eq!(frame_reader.read(&mut reader).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_socket_mode_applied_after_bind() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let socket_path = setup_socket_path("socket_mode");
+        let mut publisher = Publisher::new(socket_path.clone(), 100, create_logger());
+        publisher.set_socket_mode(Some(0o640));
+
+        let _listener = publisher.setup_socket().await.unwrap();
+        let mode = fs::metadata(&socket_path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o640, "Got {:o}", mode);
+
+        cleanup_socket(socket_path);
+    }
+
+    #[tokio::test]
+    async fn test_stuck_consumer_times_out_instead_of_hanging() {
+        let mut publisher = Publisher::new(PathBuf::from("/tmp/unused"), 0, create_logger());
+        publisher.set_write_timeout(Some(Duration::from_millis(20)));
+        // Kept open but never read, the socket buffer fills and writes stall
+        let (writer, _reader) = UnixStream::pair().expect("Failed to create stream pair");
+
+        let err = tokio::time::timeout(Duration::from_secs(10), publisher.publish_data(writer))
+            .await
+            .expect("Publisher hung on a stuck consumer")
+            .expect_err("A stuck consumer should end the connection");
+        assert!(matches!(err, PublisherError::BrokenPipe));
+    }
+
+    #[tokio::test]
+    async fn test_stamps_send_time_when_enabled() {
+        for stamp in [false, true] {
+            let (server, mut client) = common::transport::memory::pair();
+            let mut publisher = Publisher::new(PathBuf::from("/tmp/unused"), 0, create_logger());
+            publisher.set_stamp_send_time(stamp);
+            let before = unix_time_us();
+            let publisher_handle =
+                tokio::spawn(async move { publisher.publish_data(server).await });
+
+            let data = read_frame(&mut client)
+                .await
+                .expect("Failed to read frame")
+                .expect("Publisher ended early");
+            let after = unix_time_us();
+            match data.send_time_us {
+                Some(sent) => {
+                    assert!(stamp);
+                    assert!(
+                        (before..=after).contains(&sent),
+                        "{} not in {}..={}",
+                        sent,
+                        before,
+                        after
+                    );
+                }
+                None => assert!(!stamp),
+            }
+
+            publisher_handle.abort();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_bench_reports_rate_and_write_latency() {
+        let socket_path = setup_socket_path("bench");
+        let mut publisher = Publisher::new(socket_path.clone(), 0, create_logger());
+
+        let report = publisher.bench(Duration::from_millis(200)).await.unwrap();
+
+        assert!(report.duration >= Duration::from_millis(200));
+        assert!(report.sent > 0);
+        assert_eq!(report.received, report.sent);
+        assert_eq!(report.errors, 0);
+        assert!(report.rate_hz() > 100.0, "{}", report);
+        assert!(report.max_write_latency > Duration::ZERO);
+        assert!(report.mean_write_latency <= report.max_write_latency);
+        assert!(!socket_path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_decimation_sends_every_nth_sample() {
+        use crate::replay::ReplaySource;
+
+        const GENERATED: u32 = 100;
+        const DECIMATION: u32 = 5;
+
+        let samples = (1..=GENERATED)
+            .map(|timestamp| common::proto::ImuData {
+                timestamp_acc: timestamp,
+                ..Default::default()
+            })
+            .collect();
+        let (server, mut client) = common::transport::memory::pair();
+        let mut publisher = Publisher::new(PathBuf::from("/tmp/unused"), 0, create_logger());
+        publisher.set_source(Box::new(ReplaySource::new(samples, 1000.0)));
+        publisher.set_decimation(DECIMATION);
+        let publisher_handle = tokio::spawn(async move { publisher.publish_data(server).await });
+
+        let mut received = Vec::new();
+        while let Some(data) = read_frame(&mut client).await.expect("Failed to read frame") {
+            received.push(data.timestamp_acc);
+            if received.len() == (GENERATED / DECIMATION) as usize {
+                break;
+            }
+        }
+        publisher_handle
+            .await
+            .unwrap()
+            .expect("Publishing should succeed");
+
+        assert_eq!(received.len(), (GENERATED / DECIMATION) as usize);
+        assert!(received.windows(2).all(|pair| pair[1] > pair[0]));
+        // Timestamps are those of the generated samples that were kept
+        assert_eq!(received[..3], [1, 6, 11]);
+    }
+
+    struct FailingListener {
+        attempts: u32,
+    }
+
+    impl Accept for FailingListener {
+        async fn accept(&mut self) -> io::Result<ServerStream> {
+            self.attempts += 1;
+            Err(io::Error::other("injected accept failure"))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_gives_up_after_accept_retries() {
+        let mut publisher = Publisher::new(PathBuf::from("/tmp/unused"), 100, create_logger());
+        publisher.set_accept_retries(Some(3));
+        let mut listener = FailingListener { attempts: 0 };
+
+        let result = tokio::time::timeout(Duration::from_secs(5), publisher.serve(&mut listener))
+            .await
+            .expect("Publisher kept retrying");
+        assert!(matches!(result, Err(PublisherError::Accept(_))));
+        // The first attempt and three retries
+        assert_eq!(listener.attempts, 4);
+    }
 }
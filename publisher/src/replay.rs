@@ -0,0 +1,180 @@
+use super::source::DataSource;
+
+use common::codec::FrameReader;
+use common::proto::ImuData;
+
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+/// Replays length-prefixed frames recorded from the wire, e.g. by the
+/// consumer's tee.
+///
+/// Samples are paced by the difference of their recorded timestamps divided
+/// by `speed`, while the timestamps themselves are sent unchanged.
+pub struct ReplaySource {
+    samples: Vec<ImuData>,
+    next_index: usize,
+    speed: f32,
+}
+
+impl ReplaySource {
+    pub fn new(samples: Vec<ImuData>, speed: f32) -> Self {
+        ReplaySource {
+            samples,
+            next_index: 0,
+            speed,
+        }
+    }
+
+    pub async fn from_file(path: &Path, speed: f32, frames: FrameReader) -> io::Result<Self> {
+        Ok(Self::new(Self::read_frames(path, frames).await?, speed))
+    }
+
+    /// Reads all frames of a recording, framed and encoded as `frames`
+    /// expects them.
+    pub async fn read_frames(path: &Path, frames: FrameReader) -> io::Result<Vec<ImuData>> {
+        Self::decode_frames(&fs::read(path)?, frames).await
+    }
+
+    async fn decode_frames(mut bytes: &[u8], mut frames: FrameReader) -> io::Result<Vec<ImuData>> {
+        let mut samples = Vec::new();
+        while let Some(sample) = frames.read(&mut bytes).await? {
+            samples.push(sample);
+        }
+        Ok(samples)
+    }
+
+    fn sample_time(data: &ImuData) -> u32 {
+        data.timestamp_acc
+            .max(data.timestamp_gyro)
+            .max(data.timestamp_mag)
+    }
+}
+
+impl DataSource for ReplaySource {
+    fn next_delay(&mut self) -> Option<Duration> {
+        let delay_ms = match self.next_index {
+            0 => 0,
+            i if i < self.samples.len() => Self::sample_time(&self.samples[i])
+                .saturating_sub(Self::sample_time(&self.samples[i - 1])),
+            _ => 0,
+        };
+
+        Some(Duration::from_secs_f32(
+            delay_ms as f32 / 1000.0 / self.speed,
+        ))
+    }
+
+    fn next_sample(&mut self) -> Option<ImuData> {
+        let sample = self.samples.get(self.next_index).copied()?;
+        self.next_index += 1;
+        Some(sample)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::codec::{encode_frame, encode_frame_with_format};
+    use common::framing::{Endianness, FRAME_MAGIC, WireFormat};
+
+    fn create_samples(timestamps: &[u32]) -> Vec<ImuData> {
+        timestamps
+            .iter()
+            .map(|&timestamp| ImuData {
+                timestamp_acc: timestamp,
+                timestamp_gyro: timestamp,
+                timestamp_mag: timestamp,
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    fn encode_frames(samples: &[ImuData]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for sample in samples {
+            encode_frame(sample, &mut bytes);
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_delay_scales_with_speed() {
+        for (speed, expected_ms) in [(1.0, 20), (2.0, 10), (0.5, 40)] {
+            let mut source = ReplaySource::new(create_samples(&[1000, 1020, 1040]), speed);
+
+            assert_eq!(source.next_delay(), Some(Duration::ZERO));
+            assert_eq!(source.next_sample().unwrap().timestamp_acc, 1000);
+
+            for timestamp in [1020, 1040] {
+                let delay = source.next_delay().unwrap();
+                assert!((delay.as_secs_f32() * 1000.0 - expected_ms as f32).abs() < 1e-3);
+                assert_eq!(source.next_sample().unwrap().timestamp_acc, timestamp);
+            }
+
+            assert!(source.next_sample().is_none());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_decode_frames() {
+        let samples = create_samples(&[100, 200, 300]);
+        let decoded = ReplaySource::decode_frames(&encode_frames(&samples), FrameReader::default())
+            .await
+            .unwrap();
+        assert_eq!(decoded, samples);
+    }
+
+    #[tokio::test]
+    async fn test_decode_truncated_frame() {
+        let mut bytes = encode_frames(&create_samples(&[100, 200]));
+        bytes.truncate(bytes.len() - 1);
+
+        let err = ReplaySource::decode_frames(&bytes, FrameReader::default())
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[tokio::test]
+    async fn test_decode_frames_in_configured_wire_format() {
+        let samples = create_samples(&[100, 200]);
+        let mut bytes = Vec::new();
+        for sample in &samples {
+            encode_frame_with_format(sample, &mut bytes, Endianness::Little, WireFormat::Packed);
+        }
+
+        let mut frames = FrameReader::default();
+        frames.set_endianness(Endianness::Little);
+        frames.set_wire_format(WireFormat::Packed);
+        let decoded = ReplaySource::decode_frames(&bytes, frames).await.unwrap();
+        assert_eq!(decoded, samples);
+    }
+
+    #[tokio::test]
+    async fn test_replays_protocol_v2_recording() {
+        // What the tee writes with protocol version 2, including garbage
+        // between frames that the marker lets the reader skip
+        let samples = create_samples(&[100, 200, 300]);
+        let mut bytes = Vec::new();
+        for sample in &samples {
+            bytes.extend_from_slice(&FRAME_MAGIC);
+            encode_frame(sample, &mut bytes);
+            bytes.extend_from_slice(&[0x00, 0xFF]);
+        }
+        let path = std::env::temp_dir().join("test_imu_replay_v2.frames");
+        fs::write(&path, bytes).unwrap();
+
+        let mut frames = FrameReader::default();
+        frames.set_frame_magic(true);
+        let mut source = ReplaySource::from_file(&path, 1.0, frames).await.unwrap();
+        for sample in &samples {
+            source.next_delay();
+            assert_eq!(source.next_sample().as_ref(), Some(sample));
+        }
+        assert!(source.next_sample().is_none());
+        fs::remove_file(&path).unwrap();
+    }
+}
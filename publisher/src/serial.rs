@@ -0,0 +1,290 @@
+use super::source::DataSource;
+
+use common::proto::ImuData;
+use common::slog::{Logger, error, info, warn};
+
+use std::io::{self, BufRead, BufReader, Read};
+use std::time::Duration;
+
+const FIELD_COUNT: usize = 10;
+
+/// Reads samples from a sensor speaking a line-based protocol, e.g. a
+/// microcontroller on a serial port.
+///
+/// Every line holds comma-separated fields in the units of `ImuData`:
+/// `timestamp,x_acc,y_acc,z_acc,x_gyro,y_gyro,z_gyro,x_mag,y_mag,z_mag`
+/// with the timestamp [ms] applied to all three sensors. Malformed lines
+/// are skipped with a warning. The device paces the stream, so samples are
+/// forwarded as soon as they arrive instead of at the publisher frequency.
+pub struct SerialSource {
+    reader: Box<dyn BufRead + Send>,
+    logger: Logger,
+    line: Vec<u8>,
+}
+
+impl SerialSource {
+    pub fn new(reader: impl Read + Send + 'static, logger: Logger) -> Self {
+        SerialSource {
+            reader: Box::new(BufReader::new(reader)),
+            logger,
+            line: Vec::new(),
+        }
+    }
+
+    /// Opens a serial device in raw mode at `baud`.
+    pub fn open(path: &std::path::Path, baud: u32, logger: Logger) -> io::Result<Self> {
+        let device = device::open_raw(path, baud)?;
+        info!(
+            logger,
+            "Reading serial device {} at {} baud",
+            path.display(),
+            baud
+        );
+        Ok(Self::new(device, logger))
+    }
+
+    /// Parses one protocol line, `None` if it is malformed.
+    pub fn parse_line(line: &str) -> Option<ImuData> {
+        let fields: Vec<&str> = line.trim().split(',').map(str::trim).collect();
+        if fields.len() != FIELD_COUNT {
+            return None;
+        }
+
+        let timestamp = fields[0].parse().ok()?;
+        let float = |i: usize| fields[i].parse::<f32>().ok().filter(|v| v.is_finite());
+        let int = |i: usize| fields[i].parse::<i32>().ok();
+
+        Some(ImuData {
+            x_acc: float(1)?,
+            y_acc: float(2)?,
+            z_acc: float(3)?,
+            timestamp_acc: timestamp,
+            x_gyro: int(4)?,
+            y_gyro: int(5)?,
+            z_gyro: int(6)?,
+            timestamp_gyro: timestamp,
+            x_mag: float(7)?,
+            y_mag: float(8)?,
+            z_mag: float(9)?,
+            timestamp_mag: timestamp,
+            ..Default::default()
+        })
+    }
+}
+
+impl DataSource for SerialSource {
+    fn next_delay(&mut self) -> Option<Duration> {
+        Some(Duration::ZERO)
+    }
+
+    fn blocking(&self) -> bool {
+        true
+    }
+
+    fn next_sample(&mut self) -> Option<ImuData> {
+        loop {
+            // Partial reads accumulate until the line is complete
+            self.line.clear();
+            match self.reader.read_until(b'\n', &mut self.line) {
+                Ok(0) => {
+                    info!(self.logger, "Serial device closed");
+                    return None;
+                }
+                Ok(_) => {}
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    error!(self.logger, "Failed to read serial device: {}", e);
+                    return None;
+                }
+            }
+
+            let line = String::from_utf8_lossy(&self.line);
+            if line.trim().is_empty() {
+                continue;
+            }
+            match Self::parse_line(&line) {
+                Some(imu_data) => return Some(imu_data),
+                None => warn!(self.logger, "Skipping malformed serial line"; "line" => line.trim()),
+            }
+        }
+    }
+}
+
+#[cfg(all(unix, feature = "serial"))]
+mod device {
+    use std::fs::{File, OpenOptions};
+    use std::io;
+    use std::os::unix::fs::OpenOptionsExt;
+    use std::os::unix::io::AsRawFd;
+    use std::path::Path;
+
+    fn speed(baud: u32) -> io::Result<libc::speed_t> {
+        Ok(match baud {
+            9600 => libc::B9600,
+            19200 => libc::B19200,
+            38400 => libc::B38400,
+            57600 => libc::B57600,
+            115200 => libc::B115200,
+            230400 => libc::B230400,
+            460800 => libc::B460800,
+            921600 => libc::B921600,
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("Unsupported baud rate {}", baud),
+                ));
+            }
+        })
+    }
+
+    /// Opens the device without making it the controlling terminal and
+    /// switches it to raw 8N1 at `baud`.
+    pub fn open_raw(path: &Path, baud: u32) -> io::Result<File> {
+        let speed = speed(baud)?;
+        let file = OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_NOCTTY)
+            .open(path)?;
+
+        let fd = file.as_raw_fd();
+        // SAFETY: fd is open for the lifetime of `file`, termios is plain data
+        unsafe {
+            let mut termios: libc::termios = std::mem::zeroed();
+            if libc::tcgetattr(fd, &mut termios) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            libc::cfmakeraw(&mut termios);
+            termios.c_cflag |= libc::CLOCAL | libc::CREAD;
+            if libc::cfsetispeed(&mut termios, speed) != 0
+                || libc::cfsetospeed(&mut termios, speed) != 0
+                || libc::tcsetattr(fd, libc::TCSANOW, &termios) != 0
+            {
+                return Err(io::Error::last_os_error());
+            }
+        }
+
+        Ok(file)
+    }
+}
+
+#[cfg(not(all(unix, feature = "serial")))]
+mod device {
+    use std::fs::File;
+    use std::io;
+    use std::path::Path;
+
+    pub fn open_raw(path: &Path, _baud: u32) -> io::Result<File> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!(
+                "Cannot open {}, this build has no serial support. Rebuild with --features serial on a Unix host",
+                path.display()
+            ),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::slog::o;
+
+    /// Hands out at most `chunk` bytes per read, like a slow serial line.
+    struct TrickleReader {
+        bytes: Vec<u8>,
+        position: usize,
+        chunk: usize,
+    }
+
+    impl Read for TrickleReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let end = (self.position + self.chunk.min(buf.len())).min(self.bytes.len());
+            let n = end - self.position;
+            buf[..n].copy_from_slice(&self.bytes[self.position..end]);
+            self.position = end;
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn test_lines_become_samples_across_partial_reads() {
+        let stream = "1000,1.5,-2,1000,100,-200,300,200,0,-400\n\
+                      garbage\n\
+                      \n\
+                      1010,1,2,3,not-an-int,5,6,7,8,9\n\
+                      1020, 0.0, 0.0, 998.5, 0, 0, 0, 201.0, 1.0, -399.0\r\n\
+                      1030,1,2,3";
+        let reader = TrickleReader {
+            bytes: stream.as_bytes().to_vec(),
+            position: 0,
+            chunk: 3,
+        };
+        let logger = Logger::root(common::slog::Discard, o!());
+        let mut source = SerialSource::new(reader, logger);
+
+        let first = source.next_sample().unwrap();
+        assert_eq!(
+            first,
+            ImuData {
+                x_acc: 1.5,
+                y_acc: -2.0,
+                z_acc: 1000.0,
+                timestamp_acc: 1000,
+                x_gyro: 100,
+                y_gyro: -200,
+                z_gyro: 300,
+                timestamp_gyro: 1000,
+                x_mag: 200.0,
+                y_mag: 0.0,
+                z_mag: -400.0,
+                timestamp_mag: 1000,
+                ..Default::default()
+            }
+        );
+
+        // Malformed and empty lines are skipped
+        let second = source.next_sample().unwrap();
+        assert_eq!(second.timestamp_acc, 1020);
+        assert_eq!(second.z_acc, 998.5);
+        assert_eq!(second.z_mag, -399.0);
+
+        // A truncated line is still malformed, then the device is done
+        assert_eq!(source.next_sample(), None);
+    }
+
+    #[test]
+    fn test_open_reports_missing_device() {
+        let logger = Logger::root(common::slog::Discard, o!());
+        let result =
+            SerialSource::open(std::path::Path::new("/nonexistent/ttyIMU"), 115200, logger);
+        assert!(result.is_err());
+    }
+
+    #[cfg(all(unix, feature = "serial"))]
+    #[test]
+    fn test_reads_lines_from_pty() {
+        use std::io::Write;
+        use std::os::unix::io::FromRawFd;
+
+        // SAFETY: plain libc calls on a fresh pseudo-terminal pair
+        let (mut master, slave_path) = unsafe {
+            let fd = libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY);
+            assert!(fd >= 0, "No pseudo-terminal available");
+            assert_eq!(libc::grantpt(fd), 0);
+            assert_eq!(libc::unlockpt(fd), 0);
+            let name = std::ffi::CStr::from_ptr(libc::ptsname(fd));
+            let path = std::path::PathBuf::from(name.to_str().unwrap());
+            (std::fs::File::from_raw_fd(fd), path)
+        };
+
+        let logger = Logger::root(common::slog::Discard, o!());
+        let mut source = SerialSource::open(&slave_path, 115200, logger).unwrap();
+        master
+            .write_all(b"1000,0,0,1000,0,0,90000,200,0,-400\n")
+            .unwrap();
+
+        let sample = source.next_sample().unwrap();
+        assert_eq!(sample.timestamp_gyro, 1000);
+        assert_eq!(sample.z_gyro, 90_000);
+    }
+}
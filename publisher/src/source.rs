@@ -0,0 +1,20 @@
+use common::proto::ImuData;
+use std::time::Duration;
+
+/// Producer of the samples sent by the publisher.
+pub trait DataSource: Send {
+    /// Time to wait before the next sample, `None` follows the publisher frequency.
+    fn next_delay(&mut self) -> Option<Duration> {
+        None
+    }
+
+    /// Produces the next sample, `None` once the source is exhausted.
+    fn next_sample(&mut self) -> Option<ImuData>;
+
+    /// Whether `next_sample` waits on I/O that may never complete, e.g. a
+    /// silent device. The publisher then calls it on the blocking pool, so
+    /// it stalls neither the runtime nor a disconnect.
+    fn blocking(&self) -> bool {
+        false
+    }
+}